@@ -1,21 +1,53 @@
-use std::fs;
+use std::{env, fs};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::process::Command;
 use std::sync::Mutex;
 use tggen::Generate;
 
+/// Spec shipped with the crate
+const BUNDLED_SPEC: &str = "./telegram-bot-api-spec/api.json";
+
+/// Overlay applied to the bundled spec
+const BUNDLED_OVERLAY: &str = "./overlay.json";
+
 fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=generate/");
     println!("cargo:rerun-if-changed=telegram-bot-api-spec/");
+    println!("cargo:rerun-if-changed=overlay.json");
     let mtx = Mutex::new(());
     let guard = mtx.lock().unwrap();
-    let json = fs::read_to_string("./telegram-bot-api-spec/api.json")?;
 
-    let gen = Generate::new(json)?;
+    // With codegen-at-build the code is generated into OUT_DIR, optionally from a spec
+    // and overlay chosen by the user, instead of into src
+    let at_build = env::var_os("CARGO_FEATURE_CODEGEN_AT_BUILD").is_some();
+    let (spec, overlay, out_dir) = if at_build {
+        println!("cargo:rerun-if-env-changed=BOTAPI_SPEC");
+        println!("cargo:rerun-if-env-changed=BOTAPI_OVERLAY");
+        let spec = env::var("BOTAPI_SPEC").unwrap_or_else(|_| BUNDLED_SPEC.to_owned());
+        let overlay = env::var("BOTAPI_OVERLAY").ok();
+        if let Some(ref overlay) = overlay {
+            println!("cargo:rerun-if-changed={}", overlay);
+        }
+        println!("cargo:rerun-if-changed={}", spec);
+        (spec, overlay, env::var("OUT_DIR")?)
+    } else {
+        (BUNDLED_SPEC.to_owned(), None, "./src".to_owned())
+    };
+    let json = fs::read_to_string(&spec)
+        .with_context(|| format!("failed to read bot api spec {}", spec))?;
+
+    let overlay = match overlay {
+        Some(overlay) => Some(
+            fs::read_to_string(&overlay)
+                .with_context(|| format!("failed to read overlay {}", overlay))?,
+        ),
+        None => fs::read_to_string(BUNDLED_OVERLAY).ok(),
+    };
+
+    let gen = Generate::with_overlay(json, overlay)?;
     let types = gen.generate_types()?;
     let methods = gen.generate_methods()?;
-    let out_dir = "./src";
     let methods_path = out_dir.to_owned() + "/gen_methods.rs";
     let types_path = out_dir.to_owned() + "/gen_types.rs";
     println!("cargo:rustc-env=BOT_GEN_DIR={}", out_dir);
@@ -12,7 +12,7 @@ async fn main() -> Result<()> {
         let update = bot.get_updates(Some(offset), None, None, None).await?;
         offset = update
             .iter()
-            .map(|u| u.get_update_id())
+            .map(|u| u.get_update_id().get())
             .max()
             .unwrap_or_default()
             + 1;
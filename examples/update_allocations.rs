@@ -0,0 +1,102 @@
+//! Count the allocations made fetching a batch of 100 large updates with get_updates,
+//! which deserializes them straight from the response body, and by parsing the result
+//! through an intermediate serde_json::Value. Run with --features test-util
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Result;
+use botapi::gen_types::Update;
+use botapi::testing::TestBot;
+use serde_json::json;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const BATCH: i64 = 100;
+const ROUNDS: usize = 20;
+
+fn update(id: i64) -> serde_json::Value {
+    json!({
+        "update_id": id,
+        "message": {
+            "message_id": id,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup", "title": "High traffic"},
+            "from": {"id": id, "is_bot": false, "first_name": "User", "username": "user"},
+            "text": "lorem ipsum ".repeat(200),
+            "entities": (0..20)
+                .map(|i| json!({"type": "bold", "offset": i * 10, "length": 5}))
+                .collect::<Vec<_>>(),
+        }
+    })
+}
+
+/// Run a fetch ROUNDS times, returning the average allocations and bytes allocated
+async fn measure<F, Fut>(fetch: F) -> Result<(usize, usize)>
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = Result<usize>>,
+{
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = BYTES.load(Ordering::Relaxed);
+    for _ in 0..ROUNDS {
+        assert_eq!(fetch().await?, BATCH as usize);
+    }
+    Ok((
+        (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ROUNDS,
+        (BYTES.load(Ordering::Relaxed) - bytes) / ROUNDS,
+    ))
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let updates = (1..=BATCH).map(update).collect::<Vec<_>>();
+    let mut builder = TestBot::builder();
+    for _ in 0..ROUNDS * 2 {
+        builder = builder.respond("getUpdates", &updates);
+    }
+    let test = builder.build().await?;
+    let bot = test.get_bot();
+
+    let (value_allocs, value_bytes) = measure(|| async {
+        let resp = bot.post_empty("getUpdates").await?;
+        let updates: Vec<Update> = serde_json::from_value(resp.result.unwrap_or_default())?;
+        Ok(updates.len())
+    })
+    .await?;
+    let (typed_allocs, typed_bytes) =
+        measure(|| async { Ok(bot.get_updates(None, None, None, None).await?.len()) }).await?;
+
+    println!(
+        "serde_json::Value: {} allocations, {} bytes",
+        value_allocs, value_bytes
+    );
+    println!(
+        "get_updates:       {} allocations, {} bytes",
+        typed_allocs, typed_bytes
+    );
+    assert!(
+        typed_allocs < value_allocs,
+        "get_updates should allocate less than parsing through a Value"
+    );
+    assert!(typed_bytes < value_bytes);
+    assert_eq!(test.sent("getUpdates").len(), ROUNDS * 2);
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::schema::Spec;
+use crate::{ARRAY_OF, INPUT_FILE};
+
+/// Example payloads captured from the bot api, embedded so the generated crate can test
+/// deserializing them without access to this crate's source tree
+static FIXTURES: &[(&str, &str)] = &[
+    (
+        "message_photo",
+        include_str!("../fixtures/message_photo.json"),
+    ),
+    (
+        "message_sticker",
+        include_str!("../fixtures/message_sticker.json"),
+    ),
+    (
+        "update_callback_query",
+        include_str!("../fixtures/update_callback_query.json"),
+    ),
+    (
+        "update_chat_member_banned",
+        include_str!("../fixtures/update_chat_member_banned.json"),
+    ),
+    (
+        "update_message_text",
+        include_str!("../fixtures/update_message_text.json"),
+    ),
+    ("user_get_me", include_str!("../fixtures/user_get_me.json")),
+];
+
+/// A real payload and the spec type it should deserialize as
+#[derive(Deserialize, Debug)]
+pub(crate) struct Fixture {
+    #[serde(skip)]
+    pub(crate) name: String,
+    #[serde(rename = "type")]
+    pub(crate) tg_type: String,
+    pub(crate) payload: Value,
+}
+
+impl Fixture {
+    /// Parse every embedded fixture
+    pub(crate) fn all() -> Result<Vec<Fixture>> {
+        FIXTURES
+            .iter()
+            .map(|(name, json)| {
+                let mut fixture: Fixture = serde_json::from_str(json)
+                    .map_err(|e| anyhow!("invalid fixture {name}: {e}"))?;
+                fixture.name = (*name).to_owned();
+                Ok(fixture)
+            })
+            .collect()
+    }
+
+    /// Check that the payload only uses fields from the spec and includes every
+    /// required field, so a failure in the generated tests points at the generator
+    /// rather than the fixture
+    #[cfg(test)]
+    pub(crate) fn check(&self, spec: &Spec) -> Result<()> {
+        check_value(spec, &self.name, &self.tg_type, &self.payload)
+    }
+}
+
+/// Build the smallest payload of a spec type, with empty values for required fields and
+/// the documented value for fixed fields. The first subtype is used for types with
+/// subtypes. None if the type requires a file upload
+pub(crate) fn example(spec: &Spec, tg_type: &str) -> Option<Value> {
+    example_value(spec, tg_type, 0)
+}
+
+fn example_value(spec: &Spec, tg_type: &str, depth: usize) -> Option<Value> {
+    if depth > 8 {
+        return None;
+    }
+    if tg_type.starts_with(ARRAY_OF) {
+        return Some(Value::Array(Vec::new()));
+    }
+    match tg_type {
+        "Integer" => return Some(Value::from(0)),
+        "Float" => return Some(Value::from(0.0)),
+        "String" => return Some(Value::from("")),
+        "Boolean" => return Some(Value::from(false)),
+        "True" => return Some(Value::from(true)),
+        _ => (),
+    }
+    let t = spec.get_type(tg_type)?;
+    if let Some(ref subtypes) = t.subtypes {
+        return subtypes
+            .iter()
+            .find_map(|s| example_value(spec, s, depth + 1));
+    }
+    let mut object = serde_json::Map::new();
+    for field in t.fields.iter().flatten().filter(|f| f.required) {
+        if field.types.iter().any(|t| t == INPUT_FILE) {
+            return None;
+        }
+        let value = match field.fixed_value() {
+            Some(value) if field.types.iter().any(|t| t == "String") => Value::from(value),
+            _ => field
+                .types
+                .iter()
+                .find_map(|t| example_value(spec, t, depth + 1))?,
+        };
+        object.insert(field.name.clone(), value);
+    }
+    Some(Value::Object(object))
+}
+
+/// Check a json value against a spec type name, recursing into arrays and objects.
+/// Primitive types are not checked
+#[cfg(test)]
+fn check_value(spec: &Spec, path: &str, tg_type: &str, value: &Value) -> Result<()> {
+    if let Some(inner) = tg_type.strip_prefix(ARRAY_OF) {
+        let items = value
+            .as_array()
+            .ok_or_else(|| anyhow!("{path}: expected an array of {inner}"))?;
+        return items
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, v)| check_value(spec, &format!("{path}[{i}]"), inner, v));
+    }
+    let Some(t) = spec.get_type(tg_type) else {
+        return Ok(());
+    };
+    if let Some(ref subtypes) = t.subtypes {
+        return subtypes
+            .iter()
+            .find(|s| check_value(spec, path, s, value).is_ok())
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("{path}: no subtype of {tg_type} matches"));
+    }
+    let object = value
+        .as_object()
+        .ok_or_else(|| anyhow!("{path}: expected an object for {tg_type}"))?;
+    let fields = t.fields.as_deref().unwrap_or_default();
+    if let Some(missing) = fields
+        .iter()
+        .find(|f| f.required && !object.contains_key(&f.name))
+    {
+        return Err(anyhow!("{path}: missing required field {}", missing.name));
+    }
+    for (key, value) in object {
+        let path = format!("{path}.{key}");
+        let field = fields
+            .iter()
+            .find(|f| &f.name == key)
+            .ok_or_else(|| anyhow!("{path}: not a field of {tg_type}"))?;
+        if !field
+            .types
+            .iter()
+            .any(|t| check_value(spec, &path, t, value).is_ok())
+        {
+            return Err(anyhow!(
+                "{path}: does not match {}",
+                field.types.join(" or ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn examples() {
+        let spec = Spec::parse(
+            r#"{
+            "types": {
+                "MessageOriginUser": {"name": "MessageOriginUser", "href": "", "fields": [
+                    {"name": "type", "types": ["String"], "required": true,
+                        "description": "Type of the message origin, always \"user\""},
+                    {"name": "date", "types": ["Integer"], "required": true},
+                    {"name": "sender_user", "types": ["User"], "required": true}
+                ]},
+                "User": {"name": "User", "href": "", "fields": [
+                    {"name": "id", "types": ["Integer"], "required": true},
+                    {"name": "username", "types": ["String"], "required": false}
+                ]}
+            },
+            "methods": {}
+        }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            example(&spec, "MessageOriginUser"),
+            Some(serde_json::json!({"type": "user", "date": 0, "sender_user": {"id": 0}}))
+        );
+    }
+
+    #[test]
+    fn fixtures_match_spec() {
+        let json = std::fs::read_to_string("../telegram-bot-api-spec/api.json").unwrap();
+        let spec: Spec = serde_json::from_str(&json).unwrap();
+        for fixture in Fixture::all().unwrap() {
+            fixture.check(&spec).unwrap();
+        }
+    }
+}
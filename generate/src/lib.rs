@@ -4,14 +4,18 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+use crate::overlay::Overlay;
 use crate::schema::Spec;
 use anyhow::Result;
 use schema::ApxFeedbackArcSet;
 
+mod fixtures;
 mod methods;
 #[allow(dead_code)]
 pub(crate) mod naming;
+mod overlay;
 pub(crate) mod schema;
+mod tgbotapi;
 mod types;
 pub(crate) mod util;
 
@@ -31,7 +35,21 @@ pub(crate) static UPDATE: &str = "Update";
 
 impl Generate {
     pub fn new<T: AsRef<str>>(json: T) -> Result<Generate> {
-        let spec: Spec = serde_json::from_str(json.as_ref())?;
+        Self::with_overlay(json, None::<&str>)
+    }
+
+    /// Create a generator applying local overrides from an overlay json file on top of
+    /// the spec. The format is described in the README under "Overriding generated code"
+    pub fn with_overlay<T, O>(json: T, overlay: Option<O>) -> Result<Generate>
+    where
+        T: AsRef<str>,
+        O: AsRef<str>,
+    {
+        let mut spec = Spec::parse(json.as_ref())?;
+        spec.validate()?;
+        if let Some(overlay) = overlay {
+            spec.apply_overlay(Overlay::parse(overlay.as_ref())?)?;
+        }
         let mut fas = ApxFeedbackArcSet::new(&spec);
         let arcs = fas.run()?;
         arcs.iter().for_each(|(parent, child)| {
@@ -7,6 +7,11 @@ use quote::{format_ident, quote, ToTokens, __private::TokenStream};
 use std::rc::Rc;
 use std::sync::Arc;
 
+/// Methods whose result is deserialized straight from the response body with
+/// Bot::post_typed, skipping the intermediate serde_json::Value. Worth it for large
+/// results that are fetched often
+const TYPED_RESULTS: &[&str] = &["getUpdates"];
+
 /// Generator for telegram api methods. This hould be run after GenerateTypes
 pub(crate) struct GenerateMethods<'a> {
     spec: Arc<Spec>,
@@ -30,6 +35,8 @@ impl<'a> GenerateMethods<'a> {
         let choosetype = ChooseType::new(Arc::clone(&self.spec), move |opts| {
             if is_chatid(opts.types) {
                 "ChatHandle".to_owned()
+            } else if let Some(id) = id_type(None, opts.name, opts.types) {
+                id.to_owned()
             } else if opts.types.len() > 1 {
                 this.get_multitype_by_vec(opts.types).unwrap().to_owned()
             } else {
@@ -58,7 +65,9 @@ impl<'a> GenerateMethods<'a> {
             .unwrap_or_default()
             .iter()
             .map(|f| {
-                if is_inputfile(f) {
+                if let Some(t) = typed_str_field(method, f) {
+                    is_optional(f, t).to_token_stream()
+                } else if is_inputfile(f) {
                     let q = quote! { FileData };
                     is_optional(f, q).to_token_stream()
                 } else if is_str_field(f) {
@@ -99,7 +108,9 @@ impl<'a> GenerateMethods<'a> {
             .iter()
             .filter(|m| m.required)
             .map(|f| {
-                let v = if is_inputfile(f) {
+                let v = if let Some(t) = typed_str_field(method, f) {
+                    is_optional(f, t).to_token_stream()
+                } else if is_inputfile(f) {
                     let q = quote! { FileData };
                     is_optional(f, q).to_token_stream()
                 } else if is_str_field(f) {
@@ -144,17 +155,61 @@ impl<'a> GenerateMethods<'a> {
             false,
             true,
         )?;
-        let name = get_method_name(method);
+        let name = self.spec.method_name(method);
         let name = format_ident!("{}", name);
+        let formatted = self.generate_formatted_setters(method, &returntype);
         let res = quote! {
+            #formatted
+
+            /// Override Bot level settings for this request
+            pub fn with_options(mut self, options: RequestOptions) -> Self {
+                self.options = Some(options);
+                self
+            }
+
             pub async fn build(self) -> BotResult<#returntype> {
-                self.bot.#name( #( self.#typenames ),* ).await
+                match self.options {
+                    Some(options) => options.scope(self.bot.#name( #( self.#typenames ),* )).await,
+                    None => self.bot.#name( #( self.#typenames ),* ).await,
+                }
             }
         };
 
         Ok(res)
     }
 
+    /// Generate text_with and caption_with for methods taking text or a caption along with
+    /// its entities. These fill both from a TextBuilder and send the request, since the
+    /// builder only borrows its parameters
+    fn generate_formatted_setters(&self, method: &Method, returntype: &TokenStream) -> TokenStream {
+        let fields = method.fields.as_deref().unwrap_or_default();
+        let has = |name: &str| fields.iter().any(|f| f.name == name);
+        [("text", "entities"), ("caption", "caption_entities")]
+            .into_iter()
+            .filter(|(text, entities)| has(text) && has(entities))
+            .map(|(text, entities)| {
+                let with = format_ident!("{}_with", text);
+                let comment = format!(
+                    "Set the {} and its entities from a TextBuilder and send the request",
+                    text
+                )
+                .comment();
+                let text = format_ident!("{}", text);
+                let entities = format_ident!("{}", entities);
+                quote! {
+                    #comment
+                    pub async fn #with<F>(self, build: F) -> BotResult<#returntype>
+                    where
+                        F: FnOnce(TextBuilder) -> TextBuilder
+                    {
+                        let (text, entities) = build(TextBuilder::new()).build();
+                        self.#text(&text).#entities(&entities).build().await
+                    }
+                }
+            })
+            .collect()
+    }
+
     fn generate_call_builder_impl(&self, method: &Method) -> TokenStream {
         let name = get_type_name_str(&method.name);
         let name = format_ident!("Call{}", name);
@@ -174,7 +229,9 @@ impl<'a> GenerateMethods<'a> {
             });
         let types = method.fields.as_deref().unwrap_or_default().iter();
         let fields = names.zip(types).map(|((fieldname, getter), f)| {
-            let fieldtype = if is_inputfile(f) {
+            let fieldtype = if let Some(t) = typed_str_field(method, f) {
+                t
+            } else if is_inputfile(f) {
                 quote! { FileData }
             } else if is_str_field(f) {
                 quote! { &'a str }
@@ -287,6 +344,7 @@ impl<'a> GenerateMethods<'a> {
         quote! {
             pub struct #name <'a #generic>{
                 bot: &'a Bot,
+                options: Option<RequestOptions>,
                 #( #names: #types ),*
             }
 
@@ -305,8 +363,8 @@ impl<'a> GenerateMethods<'a> {
             .iter()
             .filter(|f| is_json(f))
             .map(|field| {
-                let name = get_field_name(field);
-                let value = format_ident!("{}", name);
+                let name = &field.name;
+                let value = format_ident!("{}", get_field_name(field));
                 if field.required {
                     quote! {
                         let form = form.text(#name, self.#value);
@@ -329,8 +387,8 @@ impl<'a> GenerateMethods<'a> {
             .iter()
             .filter(|f| !is_json(f) && !is_chatid(&f.types))
             .map(|field| {
-                let name = get_field_name(field);
-                let value = format_ident!("{}", name);
+                let name = &field.name;
+                let value = format_ident!("{}", get_field_name(field));
                 if field.required {
                     quote! {
                         let form = form.text(#name, self.#value.to_string());
@@ -353,8 +411,8 @@ impl<'a> GenerateMethods<'a> {
             .iter()
             .filter(|f| is_chatid(&f.types))
             .map(|field| {
-                let name = get_field_name(field);
-                let value = format_ident!("{}", name);
+                let name = &field.name;
+                let value = format_ident!("{}", get_field_name(field));
                 if field.required {
                     quote! {
                         let v: ChatHandle = self.#value.into();
@@ -374,7 +432,9 @@ impl<'a> GenerateMethods<'a> {
 
         let lifetime = !(method.fields.as_ref().map_or(0, |f| f.len()) == 0
             || method.fields.as_ref().map_or(false, |f| {
-                f.iter().all(|f| no_lifetime(f) || is_chatid(&f.types))
+                f.iter().all(|f| {
+                    no_lifetime(f) || is_chatid(&f.types) || typed_str_field(method, f).is_some()
+                })
             }));
 
         let g = method
@@ -413,6 +473,8 @@ impl<'a> GenerateMethods<'a> {
             quote! { <#lifetime V> }
         };
 
+        let chat_key = self.generate_chat_key(method);
+
         quote! {
             impl #generic #structname #generic_struct {
                 #[allow(clippy::let_and_return, dead_code)]
@@ -423,6 +485,33 @@ impl<'a> GenerateMethods<'a> {
                     form
                 }
             }
+
+            impl #generic ChatKey for #structname #generic_struct {
+                fn chat_key(&self) -> Option<String> {
+                    #chat_key
+                }
+            }
+        }
+    }
+
+    /// Read the chat_id field of a method's Opts struct for use as a throttle key,
+    /// without serializing the rest of the parameters
+    fn generate_chat_key(&self, method: &Method) -> TokenStream {
+        let Some(field) = method
+            .fields
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .find(|f| f.name == "chat_id")
+        else {
+            return quote! { None };
+        };
+        let value = format_ident!("{}", get_field_name(field));
+        match (is_chatid(&field.types), field.required) {
+            (true, true) => quote! { format_chat_key(&self.#value) },
+            (true, false) => quote! { self.#value.as_ref().and_then(format_chat_key) },
+            (false, true) => quote! { Some(self.#value.to_string()) },
+            (false, false) => quote! { self.#value.as_ref().map(|v| v.to_string()) },
         }
     }
 
@@ -445,7 +534,9 @@ impl<'a> GenerateMethods<'a> {
                 }
             });
             let types = fields.iter().map(|f| {
-                if is_json(f) || is_inputfile(f) {
+                if let Some(t) = typed_str_field(method, f) {
+                    is_optional(f, t)
+                } else if is_json(f) || is_inputfile(f) {
                     let res = quote! {
                         String
                     };
@@ -463,7 +554,9 @@ impl<'a> GenerateMethods<'a> {
                 }
             });
 
-            let lifetime = !fields.iter().all(|f| no_lifetime(f) || is_chatid(&f.types));
+            let lifetime = !fields.iter().all(|f| {
+                no_lifetime(f) || is_chatid(&f.types) || typed_str_field(method, f).is_some()
+            });
             let generic = if method
                 .fields
                 .as_ref()
@@ -567,6 +660,28 @@ impl<'a> GenerateMethods<'a> {
             } else {
                 quote!()
             };
+            let cached = self.cached_upload_fields(method);
+            let endpoint = method.name.as_str();
+            let prepare = cached.iter().map(|field| {
+                let name = field.name.as_str();
+                let typename = format_ident!("{}", name);
+                let key = format_ident!("{}_upload", name);
+                if field.required {
+                    quote! {
+                        let (#typename, #key) = self.prepare_upload(#endpoint, #name, #typename);
+                    }
+                } else {
+                    quote! {
+                        let (#typename, #key) = match #typename {
+                            Some(#typename) => {
+                                let (#typename, #key) = self.prepare_upload(#endpoint, #name, #typename);
+                                (Some(#typename), #key)
+                            }
+                            None => (None, UploadKey::default())
+                        };
+                    }
+                }
+            });
             let blocks = fieldlist.iter().map(|field| {
                 let name = field.name.as_str();
                 let typename = format_ident!("{}", name);
@@ -589,6 +704,7 @@ impl<'a> GenerateMethods<'a> {
                 }
             });
             quote! {
+                #( #prepare )*
                 #res
                 #( #blocks )*
             }
@@ -597,6 +713,33 @@ impl<'a> GenerateMethods<'a> {
         }
     }
 
+    /// Get the uploaded files of a method that can be reused through the upload cache.
+    /// Only methods returning a Message report the file_id of what they uploaded, and
+    /// thumbnails are never reported
+    fn cached_upload_fields<'b>(&self, method: &'b Method) -> Vec<&'b Field> {
+        if method.returns != ["Message"] {
+            return Vec::new();
+        }
+        method
+            .fields
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .filter(|f| is_inputfile(f) && f.name != "thumbnail")
+            .collect()
+    }
+
+    /// Remember the file_ids of files uploaded by a method in the upload cache
+    fn generate_upload_finish(&self, method: &Method) -> TokenStream {
+        let keys = self
+            .cached_upload_fields(method)
+            .into_iter()
+            .map(|f| format_ident!("{}_upload", f.name));
+        quote! {
+            #( self.finish_upload(#keys, &resp); )*
+        }
+    }
+
     /// Choose what post method to call based on whether we are uploading multipart/form-data
     /// or if we have a method with no parameters (which breaks serde for some reason)
     fn generate_post(&self, method: &Method) -> TokenStream {
@@ -609,22 +752,36 @@ impl<'a> GenerateMethods<'a> {
             .fold(false, |b, f| if is_inputfile(f) { true } else { b });
         if method.fields.as_deref().unwrap_or_default().is_empty() {
             quote! {
-                self.post_empty(#endpoint).await?
+                self.post_empty(#endpoint).await
             }
         } else if inputfile {
             quote! {
-                self.post_data(#endpoint, form, data).await?
+                self.post_data(#endpoint, &form, data).await
+            }
+        } else {
+            quote! {
+                self.post(#endpoint, &form).await
+            }
+        }
+    }
+
+    /// Generate a redacted summary of a method call for attaching to errors
+    fn generate_context(&self, method: &Method) -> TokenStream {
+        let endpoint = &method.name;
+        if method.fields.as_deref().unwrap_or_default().is_empty() {
+            quote! {
+                RequestContext::new(#endpoint, &())
             }
         } else {
             quote! {
-                self.post(#endpoint, form).await?
+                RequestContext::new(#endpoint, &form)
             }
         }
     }
 
     /// Generate an api method
     fn generate_method(&self, method: &Method) -> Result<TokenStream> {
-        let name = get_method_name(method);
+        let name = self.spec.method_name(method);
         let fn_name = format_ident!("{}", name);
         let returntype = self.choose_type.get().unwrap().choose_type(
             method.returns.as_slice(),
@@ -639,6 +796,9 @@ impl<'a> GenerateMethods<'a> {
         let instantiate = self.instantiate_urlencoding_struct(method)?;
         let file_handler = self.generate_file_handler(method);
         let post = self.generate_post(method);
+        let context = self.generate_context(method);
+        let entities_source = self.generate_entities_source(method);
+        let upload_finish = self.generate_upload_finish(method);
         let comment = method.description.concat().comment();
         let generic = if method
             .fields
@@ -651,19 +811,70 @@ impl<'a> GenerateMethods<'a> {
             quote! { , V: Into<ChatHandle> + Serialize }
         };
 
+        let defaults = self.generate_defaults(method);
+        let truncate = self.generate_truncation(method);
+        let (fit_caption, spill_caption) = self.generate_caption_overflow(method);
+        let validate = self.generate_validation(method, false);
+        let validate = if validate.is_empty() {
+            validate
+        } else {
+            quote! {
+                if cfg!(feature = "validate") {
+                    #validate
+                }
+            }
+        };
+
+        if let Some(body) = self.spec.overlay.method_body(&method.name) {
+            return Ok(quote! {
+                #[allow(clippy::too_many_arguments)]
+                #comment
+                pub async fn #fn_name <'a #generic> (&self, #( #typenames: #types ),*) -> BotResult<#returntype>{
+                    #body
+                }
+            });
+        }
+
+        let (post, result) = if TYPED_RESULTS.contains(&method.name.as_str()) {
+            let endpoint = &method.name;
+            (
+                quote! {
+                    let (resp, res) = self.post_typed::<_, #returntype>(#endpoint, &form).await
+                },
+                quote! { let resp = res.unwrap_or_default(); },
+            )
+        } else {
+            (
+                quote! { let resp = #post },
+                quote! {
+                    let res = resp.result.unwrap_or_default();
+                    let resp = serde_json::from_value(res)?;
+                },
+            )
+        };
+
         let res = quote! {
             #[allow(clippy::too_many_arguments)]
             #comment
             pub async fn #fn_name <'a #generic> (&self, #( #typenames: #types ),*) -> BotResult<#returntype>{
+                #defaults
+                #truncate
+                #fit_caption
+                #validate
                 #file_handler
                 #instantiate
-                let resp = #post;
+                #post.map_err(|e| e.with_context(#context))?;
                 if resp.ok {
-                    let res = resp.result.unwrap_or_default();
-                    let resp = serde_json::from_value(res)?;
+                    #result
+                    #upload_finish
+                    #spill_caption
                     Ok(resp)
                 } else {
-                    Err(ApiError::from_response(resp))
+                    let err = ApiError::from_response(resp)
+                        .with_context(#context)
+                        #entities_source;
+                    log::debug!("api error {}", err);
+                    Err(err)
                 }
             }
         };
@@ -671,8 +882,452 @@ impl<'a> GenerateMethods<'a> {
         Ok(res)
     }
 
+    /// Fall back to the bot's defaults for parse_mode, disable_notification, and
+    /// protect_content when a method is called without them. parse_mode is only set if
+    /// no entities are set either
+    fn generate_defaults(&self, method: &Method) -> TokenStream {
+        let fields = method.fields.as_deref().unwrap_or_default();
+        let flags = fields
+            .iter()
+            .filter(|f| f.name == "disable_notification" || f.name == "protect_content")
+            .map(|f| {
+                let name = format_ident!("{}", f.name);
+                let default = format_ident!("default_{}", f.name);
+                quote! {
+                    let #name = #name.or_else(|| self.#default());
+                }
+            });
+        let parse_mode = self.generate_default_parse_mode(fields);
+        quote! {
+            #( #flags )*
+            #parse_mode
+        }
+    }
+
+    fn generate_default_parse_mode(&self, fields: &[Field]) -> TokenStream {
+        if !fields.iter().any(|f| f.name == "parse_mode") {
+            return quote!();
+        }
+        match fields
+            .iter()
+            .find(|f| f.name == "entities" || f.name == "caption_entities")
+        {
+            Some(entities) => {
+                let entities = format_ident!("{}", entities.name);
+                quote! {
+                    let parse_mode = match parse_mode {
+                        None if #entities.is_none() => self.default_parse_mode(),
+                        parse_mode => parse_mode,
+                    };
+                }
+            }
+            None => quote! {
+                let parse_mode = parse_mode.or_else(|| self.default_parse_mode());
+            },
+        }
+    }
+
+    /// For methods sending formatted text or captions, attach the text to errors so
+    /// "can't parse entities" errors can show where formatting went wrong
+    fn generate_entities_source(&self, method: &Method) -> TokenStream {
+        let fields = method.fields.as_deref().unwrap_or_default();
+        if !fields.iter().any(|f| f.name == "parse_mode") {
+            return quote!();
+        }
+        match fields
+            .iter()
+            .find(|f| f.name == "text" || f.name == "caption")
+        {
+            Some(field) if field.required => {
+                let name = format_ident!("{}", field.name);
+                quote! { .with_entities_source(Some(#name)) }
+            }
+            Some(field) => {
+                let name = format_ident!("{}", field.name);
+                quote! { .with_entities_source(#name) }
+            }
+            None => quote!(),
+        }
+    }
+
+    /// For methods sending a caption, generate code shortening the caption according to
+    /// the bot's CaptionOverflow and sending any overflow after the media
+    fn generate_caption_overflow(&self, method: &Method) -> (TokenStream, TokenStream) {
+        let has_field = |name: &str| {
+            method
+                .fields
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .any(|f| f.name == name)
+        };
+        if !(has_field("caption") && has_field("caption_entities") && has_field("parse_mode")) {
+            return (quote!(), quote!());
+        }
+
+        let fit = quote! {
+            let fitted = self.fit_caption(caption, caption_entities, parse_mode.is_some());
+            let (caption, caption_entities) = match fitted {
+                Some(ref fitted) => (Some(fitted.caption.as_str()), Some(&fitted.entities)),
+                None => (caption, caption_entities),
+            };
+        };
+        let spill = if method.returns.len() == 1 && method.returns[0] == "Message" {
+            quote! {
+                if let Some(overflow) = fitted.and_then(|f| f.overflow) {
+                    self.spill_caption(&resp, overflow).await?;
+                }
+            }
+        } else {
+            quote!()
+        };
+        (fit, spill)
+    }
+
+    /// Generate checks for the lengths and ranges documented for a method's parameters.
+    /// If `params` is true the checks access fields of a Params struct, otherwise they
+    /// access the arguments of the generated method
+    fn generate_validation(&self, method: &Method, params: bool) -> TokenStream {
+        let fields = method.fields.as_deref().unwrap_or_default();
+        let endpoint = &method.name;
+        let access = |name: &str| {
+            let name = format_ident!("{}", name);
+            if params {
+                quote! { self.#name }
+            } else {
+                quote! { #name }
+            }
+        };
+        let checks = fields.iter().filter_map(|f| {
+            if typed_str_field(method, f).is_some() || id_type(None, &f.name, &f.types).is_some() {
+                return None;
+            }
+            let validation = get_validation(f)?;
+            let field = &f.name;
+            let v = access(&get_field_name(f));
+            let check = match validation {
+                Validation::Chars(min, max) | Validation::Bytes(min, max) => {
+                    let value = match (params, f.required) {
+                        (true, true) => quote! { Some(#v.as_str()) },
+                        (true, false) => quote! { #v.as_deref() },
+                        (false, true) => quote! { Some(#v) },
+                        (false, false) => quote! { #v },
+                    };
+                    let func = if let Validation::Bytes(..) = validation {
+                        quote! { check_bytes }
+                    } else {
+                        quote! { check_chars }
+                    };
+                    quote! { crate::validate::#func(#endpoint, #field, #value, #min, #max)?; }
+                }
+                Validation::Items(min, max) => {
+                    let value = match (params, f.required) {
+                        (_, true) => quote! { Some(#v.len()) },
+                        (true, false) => quote! { #v.as_ref().map(|v| v.len()) },
+                        (false, false) => quote! { #v.map(|v| v.len()) },
+                    };
+                    quote! { crate::validate::check_items(#endpoint, #field, #value, #min, #max)?; }
+                }
+                Validation::Range(min, max) => {
+                    let value = if f.required {
+                        quote! { Some(#v) }
+                    } else {
+                        quote! { #v }
+                    };
+                    quote! { crate::validate::check_range(#endpoint, #field, #value, #min, #max)?; }
+                }
+            };
+
+            // lengths documented "after entities parsing" can't be checked if markup is used
+            let parsed = f
+                .description
+                .as_deref()
+                .unwrap_or("")
+                .contains("after entities parsing");
+            let parse_mode = [format!("{}_parse_mode", f.name), "parse_mode".to_owned()]
+                .into_iter()
+                .find(|name| fields.iter().any(|f| &f.name == name));
+            let check = match parse_mode {
+                Some(parse_mode) if parsed => {
+                    let parse_mode = access(&parse_mode);
+                    quote! {
+                        if #parse_mode.is_none() {
+                            #check
+                        }
+                    }
+                }
+                _ => check,
+            };
+            Some(check)
+        });
+
+        let keyboards = fields
+            .iter()
+            .filter(|f| {
+                f.name == "reply_markup" && f.types.iter().any(|t| t == "InlineKeyboardMarkup")
+            })
+            .map(|f| {
+                let field = &f.name;
+                let v = access(&get_field_name(f));
+                let value = match (params, f.required) {
+                    (true, true) => quote! { Some(&#v) },
+                    (true, false) => quote! { #v.as_ref() },
+                    (false, true) => quote! { Some(#v) },
+                    (false, false) => quote! { #v },
+                };
+                quote! { crate::validate::check_keyboard(#endpoint, #field, #value)?; }
+            });
+
+        quote! {
+            #( #checks )*
+            #( #keyboards )*
+        }
+    }
+
+    /// Generate truncation of string arguments longer than their documented limit, done
+    /// only if the bot is built with truncate_overflow. Lengths counted after entities
+    /// parsing are left to caption overflow handling, since cutting them breaks entities
+    fn generate_truncation(&self, method: &Method) -> TokenStream {
+        let fields = method.fields.as_deref().unwrap_or_default();
+        let fits = fields.iter().filter_map(|f| {
+            if typed_str_field(method, f).is_some() || id_type(None, &f.name, &f.types).is_some() {
+                return None;
+            }
+            let Validation::Chars(_, max) = get_validation(f)? else {
+                return None;
+            };
+            if f.description
+                .as_deref()
+                .unwrap_or("")
+                .contains("after entities parsing")
+            {
+                return None;
+            }
+            let name = format_ident!("{}", get_field_name(f));
+            let fit = format_ident!("{}_fit", get_field_name(f));
+            let res = if f.required {
+                quote! {
+                    let #fit = self.fit_chars(Some(#name), #max);
+                    let #name = #fit.as_deref().unwrap_or(#name);
+                }
+            } else {
+                quote! {
+                    let #fit = self.fit_chars(#name, #max);
+                    let #name = #fit.as_deref().or(#name);
+                }
+            };
+            Some(res)
+        });
+        quote! { #( #fits )* }
+    }
+
+    /// Generate a method validating a Params struct
+    fn generate_params_validate(&self, method: &Method) -> TokenStream {
+        let checks = self.generate_validation(method, true);
+        quote! {
+            /// Check the lengths and ranges documented for this method's parameters
+            pub fn validate(&self) -> Result<(), crate::validate::ValidationError> {
+                #checks
+                Ok(())
+            }
+        }
+    }
+
+    /// Get the owned type used for a field in a method's Params struct
+    fn generate_params_type(&self, method: &Method, f: &Field) -> TokenStream {
+        let t = if let Some(t) = typed_str_field(method, f) {
+            t
+        } else if is_inputfile(f) {
+            quote! { FileRef }
+        } else if is_str_field(f) {
+            quote! { String }
+        } else if is_chatid(&f.types) {
+            quote! { ChatHandle }
+        } else {
+            self.choose_type
+                .get()
+                .unwrap()
+                .choose_type(&f.types, None, &f.name, false, true)
+                .unwrap()
+        };
+        is_optional(f, t)
+    }
+
+    /// Convert a field of a method's Params struct into the type expected by the method
+    fn generate_params_arg(&self, method: &Method, f: &Field) -> TokenStream {
+        let name = format_ident!("{}", get_field_name(f));
+        if typed_str_field(method, f).is_some() {
+            quote! { self.#name }
+        } else if is_inputfile(f) {
+            if f.required {
+                quote! { self.#name.into_file_data().await? }
+            } else {
+                quote! {
+                    match self.#name {
+                        Some(v) => Some(v.into_file_data().await?),
+                        None => None
+                    }
+                }
+            }
+        } else if is_str_field(f) {
+            if f.required {
+                quote! { &self.#name }
+            } else {
+                quote! { self.#name.as_deref() }
+            }
+        } else if is_chatid(&f.types)
+            || (is_primative(&[type_without_array(&f.types[0])]) && is_array(&f.types[0]) == 0)
+        {
+            quote! { self.#name }
+        } else if f.required {
+            quote! { &self.#name }
+        } else {
+            quote! { self.#name.as_ref() }
+        }
+    }
+
+    /// Generate an owned struct holding all parameters of a method, allowing calls to be
+    /// stored, serialized, and sent later
+    fn generate_params(&self, method: &Method) -> Result<TokenStream> {
+        let structname = get_type_name_str(&method.name);
+        let structname = format_ident!("{}Params", structname);
+        let fn_name = format_ident!("{}", self.spec.method_name(method));
+        let endpoint = &method.name;
+        let returntype = self.choose_type.get().unwrap().choose_type(
+            method.returns.as_slice(),
+            None,
+            &"",
+            false,
+            true,
+        )?;
+        let fields = method.fields.as_deref().unwrap_or_default();
+        let defs = fields.iter().map(|f| {
+            let name = format_ident!("{}", get_field_name(f));
+            let rename = &f.name;
+            let t = self.generate_params_type(method, f);
+            let comment = f.description.comment();
+            if f.required {
+                quote! {
+                    #comment
+                    #[serde(rename = #rename)]
+                    pub #name: #t
+                }
+            } else {
+                quote! {
+                    #comment
+                    #[serde(rename = #rename, skip_serializing_if = "Option::is_none", default)]
+                    pub #name: #t
+                }
+            }
+        });
+        let args = fields.iter().map(|f| self.generate_params_arg(method, f));
+        let validate = self.generate_params_validate(method);
+        let comment = format!(
+            "Owned parameters for {}. These can be stored or serialized and sent later using Bot::call",
+            method.name
+        )
+        .comment();
+
+        Ok(quote! {
+            #comment
+            #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+            pub struct #structname {
+                #( #defs ),*
+            }
+
+            impl #structname {
+                #validate
+            }
+
+            impl TelegramMethod for #structname {
+                type Response = #returntype;
+                const NAME: &'static str = #endpoint;
+
+                fn call(self, bot: &Bot) -> BoxFuture<'_, BotResult<Self::Response>> {
+                    Box::pin(async move {
+                        bot.#fn_name( #( #args ),* ).await
+                    })
+                }
+            }
+        })
+    }
+
+    /// Generate an object safe trait with one method per api method taking its Params
+    /// struct, implemented by Bot. Methods default to returning an error so fakes only
+    /// need to implement the calls they expect
+    fn generate_api_trait(&self) -> TokenStream {
+        let mut methods = self.spec.methods.values().collect::<Vec<_>>();
+        methods.sort_by_key(|m| &m.name);
+        let signatures = methods.iter().map(|method| {
+            let fn_name = format_ident!("{}", self.spec.method_name(method));
+            let structname = format_ident!("{}Params", get_type_name_str(&method.name));
+            let endpoint = &method.name;
+            let comment = method.description.concat().comment();
+            quote! {
+                #comment
+                fn #fn_name(
+                    &self,
+                    _params: #structname,
+                ) -> BoxFuture<'_, BotResult<<#structname as TelegramMethod>::Response>> {
+                    Box::pin(async { Err(anyhow::anyhow!("{} not implemented", #endpoint).into()) })
+                }
+            }
+        });
+        let impls = methods.iter().map(|method| {
+            let fn_name = format_ident!("{}", self.spec.method_name(method));
+            let structname = format_ident!("{}Params", get_type_name_str(&method.name));
+            quote! {
+                fn #fn_name(
+                    &self,
+                    params: #structname,
+                ) -> BoxFuture<'_, BotResult<<#structname as TelegramMethod>::Response>> {
+                    params.call(self)
+                }
+            }
+        });
+
+        quote! {
+            /// Every api method as an object safe trait, implemented by Bot. Application code
+            /// taking `&dyn TelegramApi` can be tested against a fake implementing only the
+            /// methods it uses, the rest return an error. Bot's inherent methods take
+            /// precedence over these when called directly on a Bot
+            pub trait TelegramApi: Send + Sync {
+                #( #signatures )*
+            }
+
+            impl TelegramApi for Bot {
+                #( #impls )*
+            }
+        }
+    }
+
+    /// Generate a method for executing any serialized Params struct by method name
+    fn generate_execute_serialized(&self) -> TokenStream {
+        let arms = self.spec.methods.values().map(|method| {
+            let endpoint = &method.name;
+            let structname = get_type_name_str(&method.name);
+            let structname = format_ident!("{}Params", structname);
+            quote! {
+                #endpoint => {
+                    let params: #structname = serde_json::from_value(request.get_params().clone())?;
+                    Ok(serde_json::to_value(self.call(params).await?)?)
+                }
+            }
+        });
+
+        quote! {
+            /// Execute a request serialized with SerializableRequest, returning the result as json
+            pub async fn execute_serialized(&self, request: &SerializableRequest) -> BotResult<serde_json::Value> {
+                match request.get_method() {
+                    #( #arms ),*
+                    method => Err(anyhow::anyhow!("unknown method {}", method).into())
+                }
+            }
+        }
+    }
+
     fn generate_builder_method(&self, method: &Method) -> Result<TokenStream> {
-        let name = get_method_name(method);
+        let name = self.spec.method_name(method);
         let fn_name = format_ident!("build_{}", name);
         let returntype = get_type_name_str(&method.name);
         let returntype = format_ident!("Call{}", returntype);
@@ -725,6 +1380,7 @@ impl<'a> GenerateMethods<'a> {
             pub fn #fn_name <'a #generic,  #( #types ),*> (&'a self, #( #typenames: #generics ),*) -> #returntype<'a #generic> {
                 #returntype {
                     bot: self,
+                    options: None,
                     #( #typenames #intos , )*
                     #( #nones ),*
                 }
@@ -740,10 +1396,14 @@ impl<'a> GenerateMethods<'a> {
            use reqwest::multipart::Form;
            use crate::bot::Part;
            use serde::{Deserialize, Serialize};
+           use futures_util::future::BoxFuture;
 
             use crate::{
-                bot::{Bot, Response, ApiError, BotResult},
+                bot::{Bot, Response, ApiError, BotResult, RequestContext, SerializableRequest, TelegramMethod, UploadKey},
                 gen_types::*,
+                options::RequestOptions,
+                entities::TextBuilder,
+                throttle::{format_chat_key, ChatKey},
             };
         }
     }
@@ -800,6 +1460,15 @@ impl<'a> GenerateMethods<'a> {
             .values()
             .map(|m| self.generate_into_form(m));
 
+        let params = self
+            .spec
+            .methods
+            .values()
+            .map(|m| self.generate_params(m).unwrap());
+
+        let execute = self.generate_execute_serialized();
+        let api = self.generate_api_trait();
+
         Ok(quote! {
             #gen_use
 
@@ -809,6 +1478,10 @@ impl<'a> GenerateMethods<'a> {
 
             #( #forms )*
 
+            #( #params )*
+
+            #api
+
             impl Bot {
                 #(
                     #methods
@@ -817,6 +1490,8 @@ impl<'a> GenerateMethods<'a> {
                 #(
                     #buildermethods
                 )*
+
+                #execute
             }
         })
     }
@@ -5,15 +5,56 @@ use crate::{
 
 use convert_case::{Case, Casing};
 
-/// Reserved words in rust as of 2022. Used to avoid generating identifiers that clash with
-/// keywords
+/// Strict and reserved keywords in rust as of the 2024 edition. Used to avoid generating
+/// identifiers that clash with keywords
 static RESERVED_WORDS: &[&str] = &[
     "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
     "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
     "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
-    "while", "async", "await", "dyn",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try", "gen",
 ];
 
+/// Names of methods generated on builders and params structs alongside one setter per
+/// field. Fields with these names are escaped like keywords so the setter doesn't clash
+static GENERATED_METHODS: &[&str] = &["build", "noskip"];
+
+/// Former names of fields renamed in past bot api versions, as (current, old). Old names
+/// are accepted as serde aliases so json stored by bots built against an older api still
+/// deserializes. Entries apply to every type with a field of the current name
+static FIELD_ALIASES: &[(&str, &str)] = &[
+    ("thumbnail", "thumb"),
+    ("thumbnail_url", "thumb_url"),
+    ("thumbnail_width", "thumb_width"),
+    ("thumbnail_height", "thumb_height"),
+    ("thumbnail_mime_type", "thumb_mime_type"),
+    ("can_manage_video_chats", "can_manage_voice_chats"),
+    ("video_chat_scheduled", "voice_chat_scheduled"),
+    ("video_chat_started", "voice_chat_started"),
+    ("video_chat_ended", "voice_chat_ended"),
+    (
+        "video_chat_participants_invited",
+        "voice_chat_participants_invited",
+    ),
+];
+
+/// Get the old names a field was known by in the spec
+pub(crate) fn get_field_aliases(f: &Field) -> impl Iterator<Item = &'static str> + '_ {
+    FIELD_ALIASES
+        .iter()
+        .filter(move |(name, _)| *name == f.name)
+        .map(|(_, alias)| *alias)
+}
+
+/// Escape a name that can't be used as an identifier as is with the prefix
+fn escape(name: String, prefix: &str) -> String {
+    if RESERVED_WORDS.contains(&name.as_str()) {
+        format!("{prefix}{name}")
+    } else {
+        name
+    }
+}
+
 /// Sanitize an identifier to conform to rust style and avoid using reserved words
 pub(crate) fn get_type_name_str<T>(t: &T) -> String
 where
@@ -21,12 +62,7 @@ where
 {
     let t = type_without_array(t);
     let t = type_mapper(&t);
-    let t = t.to_case(Case::UpperCamel);
-    if RESERVED_WORDS.contains(&t.as_str()) {
-        format!("Tg{t}")
-    } else {
-        t
-    }
+    escape(t.to_case(Case::UpperCamel), "Tg")
 }
 
 /// Sanitize a type to conform to rust style and avoid using reserved words
@@ -34,22 +70,85 @@ pub(crate) fn get_type_name(t: &Type) -> String {
     get_type_name_str(&t.name)
 }
 
-/// Sanitize a field name to conform to rust style and avoid using reserved words
+/// Sanitize a field name to conform to rust style and avoid using reserved words or the
+/// names of generated methods
 pub(crate) fn get_field_name(f: &Field) -> String {
     let f = f.name.to_case(Case::Snake);
-    if RESERVED_WORDS.contains(&f.as_str()) {
+    if GENERATED_METHODS.contains(&f.as_str()) {
         format!("tg_{f}")
     } else {
-        f
+        escape(f, "tg_")
     }
 }
 
 /// Santize an method name to conform to rust style and avoid using reserved words
 pub(crate) fn get_method_name(m: &Method) -> String {
-    let m = m.name.to_case(Case::Snake);
-    if RESERVED_WORDS.contains(&m.as_str()) {
-        format!("tg_{m}")
-    } else {
-        m
+    escape(m.name.to_case(Case::Snake), "tg_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::Spec;
+    use quote::format_ident;
+
+    fn field(name: &str) -> Field {
+        Field {
+            name: name.to_owned(),
+            types: vec!["String".to_owned()],
+            required: true,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn every_keyword_escaped() {
+        for word in RESERVED_WORDS {
+            let name = get_field_name(&field(word));
+            assert!(!RESERVED_WORDS.contains(&name.as_str()), "{word}");
+            let _ = format_ident!("{}", name);
+        }
+        assert_eq!(get_field_name(&field("type")), "tg_type");
+        assert_eq!(get_field_name(&field("build")), "tg_build");
+        assert_eq!(get_type_name_str(&"Self"), "TgSelf");
+    }
+
+    #[test]
+    fn aliases_name_spec_fields() {
+        let json = std::fs::read_to_string("../telegram-bot-api-spec/api.json").unwrap();
+        let spec: Spec = serde_json::from_str(&json).unwrap();
+        let fields = spec
+            .types
+            .values()
+            .filter_map(|t| t.fields.as_ref())
+            .flatten()
+            .collect::<Vec<_>>();
+        for (name, alias) in FIELD_ALIASES {
+            assert!(fields.iter().any(|f| f.name == *name), "{name}");
+            assert!(!fields.iter().any(|f| f.name == *alias), "{alias}");
+        }
+        let aliases = get_field_aliases(&field("thumbnail")).collect::<Vec<_>>();
+        assert_eq!(aliases, vec!["thumb"]);
+    }
+
+    #[test]
+    fn spec_names_are_identifiers() {
+        let json = std::fs::read_to_string("../telegram-bot-api-spec/api.json").unwrap();
+        let spec: Spec = serde_json::from_str(&json).unwrap();
+        let fields = spec
+            .types
+            .values()
+            .filter_map(|t| t.fields.as_ref())
+            .chain(spec.methods.values().filter_map(|m| m.fields.as_ref()))
+            .flatten();
+        let names = fields
+            .map(get_field_name)
+            .chain(spec.methods.values().map(get_method_name))
+            .chain(spec.types.values().map(get_type_name));
+        for name in names {
+            assert!(!RESERVED_WORDS.contains(&name.as_str()), "{name}");
+            assert!(!GENERATED_METHODS.contains(&name.as_str()), "{name}");
+            let _ = format_ident!("{}", name);
+        }
     }
 }
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use quote::__private::TokenStream;
+use serde::Deserialize;
+
+use crate::schema::Spec;
+
+/// Local overrides applied on top of the spec during generation, so fixes to the
+/// generated code survive regeneration. Loaded from a json file like
+///
+/// ```json
+/// {
+///     "types": {
+///         "Message": { "derives": ["Default"], "attributes": ["#[non_exhaustive]"] }
+///     },
+///     "methods": {
+///         "sendMessage": { "rename": "send_text" },
+///         "logOut": { "skip": true }
+///     }
+/// }
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Overlay {
+    #[serde(default)]
+    types: HashMap<String, TypeOverlay>,
+    #[serde(default)]
+    methods: HashMap<String, MethodOverlay>,
+}
+
+/// Overrides for a single type
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TypeOverlay {
+    /// Extra traits to derive
+    #[serde(default)]
+    derives: Vec<String>,
+    /// Extra attributes, like #[cfg(...)] or #[non_exhaustive]
+    #[serde(default)]
+    attributes: Vec<String>,
+    /// Rust source replacing the generated struct. It must keep the generated field
+    /// names since accessors are still generated
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// Overrides for a single method
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MethodOverlay {
+    /// Don't generate anything for this method
+    #[serde(default)]
+    skip: bool,
+    /// Name of the generated Bot method instead of the snake case method name
+    #[serde(default)]
+    rename: Option<String>,
+    /// Rust source replacing the body of the generated Bot method
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// Parse rust source from the overlay, naming the entry it came from in errors
+fn tokens(source: &str, path: &str) -> Result<TokenStream> {
+    TokenStream::from_str(source).map_err(|e| anyhow!("{}: invalid rust: {}", path, e))
+}
+
+impl Overlay {
+    /// Parse an overlay from json
+    pub(crate) fn parse(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("invalid overlay")
+    }
+
+    /// Check that every overridden type and method exists and that all rust source
+    /// parses
+    pub(crate) fn validate(&self, spec: &Spec) -> Result<()> {
+        for (name, t) in self.types.iter() {
+            let path = format!("overlay.types.{}", name);
+            if spec.get_type(name).is_none() {
+                return Err(anyhow!("{}: no such type", path));
+            }
+            for source in t
+                .derives
+                .iter()
+                .chain(t.attributes.iter())
+                .chain(t.body.iter())
+            {
+                tokens(source, &path)?;
+            }
+        }
+        for (name, m) in self.methods.iter() {
+            let path = format!("overlay.methods.{}", name);
+            if spec.get_method(name).is_none() {
+                return Err(anyhow!("{}: no such method", path));
+            }
+            for source in m.rename.iter().chain(m.body.iter()) {
+                tokens(source, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the names of methods that should not be generated
+    pub(crate) fn skipped_methods(&self) -> impl Iterator<Item = &'_ str> {
+        self.methods
+            .iter()
+            .filter(|(_, m)| m.skip)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Get the new name of a Bot method, if renamed
+    pub(crate) fn method_rename(&self, method: &str) -> Option<&'_ str> {
+        self.methods.get(method)?.rename.as_deref()
+    }
+
+    /// Get the replacement body of a Bot method, if any
+    pub(crate) fn method_body(&self, method: &str) -> Option<TokenStream> {
+        let body = self.methods.get(method)?.body.as_deref()?;
+        tokens(body, method).ok()
+    }
+
+    /// Get the extra derives and attributes for a type
+    pub(crate) fn type_attributes(&self, name: &str) -> TokenStream {
+        let Some(t) = self.types.get(name) else {
+            return TokenStream::new();
+        };
+        let derives = t
+            .derives
+            .iter()
+            .filter_map(|d| tokens(d, name).ok())
+            .collect::<Vec<_>>();
+        let attributes = t.attributes.iter().filter_map(|a| tokens(a, name).ok());
+        let derives = if derives.is_empty() {
+            TokenStream::new()
+        } else {
+            quote::quote! { #[derive( #( #derives ),* )] }
+        };
+        quote::quote! {
+            #derives
+            #( #attributes )*
+        }
+    }
+
+    /// Get the replacement source of a type's struct, if any
+    pub(crate) fn type_body(&self, name: &str) -> Option<TokenStream> {
+        let body = self.types.get(name)?.body.as_deref()?;
+        tokens(body, name).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_entries_rejected() {
+        let json = std::fs::read_to_string("../telegram-bot-api-spec/api.json").unwrap();
+        let spec = Spec::parse(&json).unwrap();
+        let overlay = Overlay::parse(r#"{"methods": {"sendMesage": {"skip": true}}}"#).unwrap();
+        let err = overlay.validate(&spec).unwrap_err().to_string();
+        assert_eq!(err, "overlay.methods.sendMesage: no such method");
+
+        let overlay = Overlay::parse(r#"{"types": {"Message": {"derives": ["Copy"]}}}"#).unwrap();
+        overlay.validate(&spec).unwrap();
+        assert!(overlay
+            .type_attributes("Message")
+            .to_string()
+            .contains("Copy"));
+        assert!(Overlay::parse(r#"{"methods": {"getMe": {"skipp": true}}}"#).is_err());
+    }
+}
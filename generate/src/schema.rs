@@ -8,12 +8,14 @@ use anyhow::Context;
 use anyhow::Result;
 use serde::Deserialize;
 
+use crate::naming::get_method_name;
+use crate::overlay::Overlay;
 use crate::util::is_primative;
 use crate::util::type_without_array;
 
 #[cfg(test)]
 mod tests {
-    use super::{ApxFeedbackArcSet, Spec, Type};
+    use super::{ApxFeedbackArcSet, Discriminant, Spec, Type};
     use std::collections::BTreeSet;
 
     #[test]
@@ -29,6 +31,72 @@ mod tests {
         let vs = spec.iter_types().collect::<BTreeSet<&Type>>().len() / 8;
         assert!(size < vs);
     }
+
+    #[test]
+    fn validate_reports_paths() {
+        let json = r#"{
+            "types": {
+                "Chat": {"name": "Chat", "href": "", "fields": [
+                    {"name": "photo", "types": ["ChatPhoto"], "required": false}
+                ]}
+            },
+            "methods": {
+                "getChat": {"name": "getChat", "href": "", "returns": ["Chat"]}
+            }
+        }"#;
+        let err = Spec::parse(json)
+            .unwrap()
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("types.Chat.fields.photo: unknown type ChatPhoto"));
+
+        let err =
+            Spec::parse(r#"{"types": {"Chat": {"name": "Chat"}}, "methods": {}}"#).unwrap_err();
+        assert_eq!(err.to_string(), "types.Chat");
+    }
+
+    #[test]
+    fn discriminants() {
+        let spec = Spec::parse(
+            r#"{
+            "types": {
+                "BotCommandScope": {"name": "BotCommandScope", "href": "",
+                    "subtypes": ["BotCommandScopeDefault", "BotCommandScopeChat"]},
+                "BotCommandScopeDefault": {"name": "BotCommandScopeDefault", "href": "", "fields": [
+                    {"name": "type", "types": ["String"], "required": true,
+                        "description": "Scope type, must be default"}
+                ]},
+                "BotCommandScopeChat": {"name": "BotCommandScopeChat", "href": "", "fields": [
+                    {"name": "type", "types": ["String"], "required": true,
+                        "description": "Scope type, must be chat"},
+                    {"name": "chat_id", "types": ["Integer", "String"], "required": true}
+                ]}
+            },
+            "methods": {}
+        }"#,
+        )
+        .unwrap();
+        let t = spec.get_type("BotCommandScope").unwrap();
+        assert_eq!(
+            spec.get_discriminant(t),
+            Some(Discriminant {
+                field: "type".to_owned(),
+                tags: vec![
+                    ("BotCommandScopeDefault".to_owned(), "default".to_owned()),
+                    ("BotCommandScopeChat".to_owned(), "chat".to_owned()),
+                ],
+            })
+        );
+        let chat = spec.get_type("BotCommandScopeChat").unwrap();
+        assert!(spec.get_discriminant(chat).is_none());
+    }
+
+    #[test]
+    fn upstream_spec_is_valid() {
+        let json = std::fs::read_to_string("../telegram-bot-api-spec/api.json").unwrap();
+        Spec::parse(&json).unwrap().validate().unwrap();
+    }
 }
 
 /// generate every proper subset of a given size of a set of types
@@ -237,12 +305,18 @@ impl<'a> FeedbackArcSet<'a> {
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 pub(crate) struct Spec {
+    #[serde(default)]
+    pub(crate) version: String,
+    #[serde(default)]
+    pub(crate) release_date: String,
     pub(crate) types: HashMap<String, Type>,
     pub(crate) methods: HashMap<String, Method>,
     #[serde(default)]
     boxed: RwLock<HashSet<String>>,
     #[serde(default)]
     min: usize,
+    #[serde(skip)]
+    pub(crate) overlay: Overlay,
 }
 
 /// Serde representation of a json method spec
@@ -280,6 +354,29 @@ pub(crate) struct Field {
     pub(crate) description: Option<String>,
 }
 
+/// The field telling the subtypes of a type apart
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Discriminant {
+    pub(crate) field: String,
+    /// The name of each subtype and the value of the field for it
+    pub(crate) tags: Vec<(String, String)>,
+}
+
+impl Field {
+    /// Get the only value a field can have, documented as `must be x` or `always "x"`
+    pub(crate) fn fixed_value(&self) -> Option<&'_ str> {
+        let description = self.description.as_deref()?;
+        let start = ["must be ", "always "]
+            .iter()
+            .find_map(|s| description.find(s).map(|i| i + s.len()))?;
+        let value = description[start..].trim_start_matches(['"', '\u{201c}']);
+        let end = value
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(value.len());
+        Some(&value[..end]).filter(|v| !v.is_empty())
+    }
+}
+
 impl Type {
     pub(crate) fn pretty_fields(&self) -> impl Iterator<Item = &Field> {
         self.fields
@@ -303,6 +400,192 @@ impl Type {
     }
 }
 
+/// Builtin type names the spec uses that are not defined as types
+static SCALAR_TYPES: &[&str] = &["Integer", "String", "Boolean", "Float", "True"];
+
+/// A problem found while validating a spec, along with where in the spec it is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SpecError {
+    path: String,
+    message: String,
+}
+
+impl std::fmt::Display for SpecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Spec {
+    /// Parse a spec from json, either in the PaulSonOfLars format or the tg-bot-api
+    /// format. Each type and method is parsed separately so that errors name the entry
+    /// that is malformed rather than just a line and column
+    pub(crate) fn parse(json: &str) -> Result<Self> {
+        let value: serde_json::Value =
+            serde_json::from_str(json).context("spec is not valid json")?;
+        if crate::tgbotapi::is_tgbotapi(&value) {
+            return crate::tgbotapi::parse(&value);
+        }
+        let string = |key: &str| -> Result<String> {
+            match value.get(key) {
+                None => Ok(String::new()),
+                Some(v) => v
+                    .as_str()
+                    .map(|v| v.to_owned())
+                    .ok_or_else(|| anyhow!("{}: expected a string", key)),
+            }
+        };
+        Ok(Self::new(
+            string("version")?,
+            string("release_date")?,
+            parse_entries(&value, "types")?,
+            parse_entries(&value, "methods")?,
+        ))
+    }
+
+    /// Create a spec from already normalized types and methods
+    pub(crate) fn new(
+        version: String,
+        release_date: String,
+        types: HashMap<String, Type>,
+        methods: HashMap<String, Method>,
+    ) -> Self {
+        Self {
+            version,
+            release_date,
+            types,
+            methods,
+            boxed: RwLock::new(HashSet::new()),
+            min: 0,
+            overlay: Overlay::default(),
+        }
+    }
+
+    /// Apply an overlay, removing skipped methods from the spec
+    pub(crate) fn apply_overlay(&mut self, overlay: Overlay) -> Result<()> {
+        overlay.validate(self)?;
+        for method in overlay.skipped_methods() {
+            self.methods.remove(method);
+        }
+        self.overlay = overlay;
+        Ok(())
+    }
+
+    /// Get the name of the Bot method generated for a method, taking renames from the
+    /// overlay into account
+    pub(crate) fn method_name(&self, method: &Method) -> String {
+        self.overlay
+            .method_rename(&method.name)
+            .map(|name| name.to_owned())
+            .unwrap_or_else(|| get_method_name(method))
+    }
+
+    /// Check that every type referenced by a field, return value, or subtype relation is
+    /// defined and that entries are named consistently. All problems are reported at once
+    pub(crate) fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        let mut types = self.types.iter().collect::<Vec<_>>();
+        types.sort_by_key(|(name, _)| *name);
+        for (key, t) in types {
+            if *key != t.name {
+                check_name(&mut errors, "types", key, &t.name);
+            }
+            for (i, f) in t.fields.iter().flatten().enumerate() {
+                self.check_types(
+                    &mut errors,
+                    format!("types.{}.fields.{}", key, field_path(f, i)),
+                    &f.types,
+                );
+            }
+            if let Some(ref subtypes) = t.subtypes {
+                self.check_types(&mut errors, format!("types.{}.subtypes", key), subtypes);
+            }
+            if let Some(ref subtype_of) = t.subtype_of {
+                self.check_types(&mut errors, format!("types.{}.subtype_of", key), subtype_of);
+            }
+        }
+
+        let mut methods = self.methods.iter().collect::<Vec<_>>();
+        methods.sort_by_key(|(name, _)| *name);
+        for (key, m) in methods {
+            if *key != m.name {
+                check_name(&mut errors, "methods", key, &m.name);
+            }
+            for (i, f) in m.fields.iter().flatten().enumerate() {
+                self.check_types(
+                    &mut errors,
+                    format!("methods.{}.fields.{}", key, field_path(f, i)),
+                    &f.types,
+                );
+            }
+            self.check_types(&mut errors, format!("methods.{}.returns", key), &m.returns);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            let report = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+            Err(anyhow!("invalid spec:\n{}", report.join("\n")))
+        }
+    }
+
+    /// Check that a list of types only references defined types
+    fn check_types(&self, errors: &mut Vec<SpecError>, path: String, types: &[String]) {
+        if types.is_empty() {
+            errors.push(SpecError {
+                path,
+                message: "no types listed".to_owned(),
+            });
+            return;
+        }
+        for t in types {
+            let name = type_without_array(t);
+            if !SCALAR_TYPES.contains(&name) && self.get_type(name).is_none() {
+                errors.push(SpecError {
+                    path: path.clone(),
+                    message: format!("unknown type {}", name),
+                });
+            }
+        }
+    }
+}
+
+/// Parse every entry of the "types" or "methods" object, adding the entry's name to
+/// deserialization errors
+fn parse_entries<T>(value: &serde_json::Value, key: &str) -> Result<HashMap<String, T>>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let entries = value
+        .get(key)
+        .ok_or_else(|| anyhow!("missing {}", key))?
+        .as_object()
+        .ok_or_else(|| anyhow!("{}: expected an object", key))?;
+    entries
+        .iter()
+        .map(|(name, entry)| {
+            let entry = T::deserialize(entry).with_context(|| format!("{}.{}", key, name))?;
+            Ok((name.clone(), entry))
+        })
+        .collect()
+}
+
+/// Name a field by its name if it has one, otherwise by its position
+fn field_path(f: &Field, index: usize) -> String {
+    if f.name.is_empty() {
+        index.to_string()
+    } else {
+        f.name.clone()
+    }
+}
+
+fn check_name(errors: &mut Vec<SpecError>, kind: &str, key: &str, name: &str) {
+    errors.push(SpecError {
+        path: format!("{}.{}", kind, key),
+        message: format!("entry is named {}", name),
+    });
+}
+
 #[allow(dead_code)]
 impl Spec {
     /// Gets a type from the spec by name, None if nonexistent
@@ -341,6 +624,41 @@ impl Spec {
         Ok(Some(types))
     }
 
+    /// Find the field telling the subtypes of a type apart, a required field that every
+    /// subtype has with a different fixed value. None for types without subtypes
+    pub(crate) fn get_discriminant(&self, t: &Type) -> Option<Discriminant> {
+        let subtypes = t
+            .subtypes
+            .as_ref()?
+            .iter()
+            .map(|s| self.get_type(s))
+            .collect::<Option<Vec<_>>>()?;
+        let first = subtypes.first()?;
+        first
+            .fields
+            .iter()
+            .flatten()
+            .filter(|f| f.required)
+            .find_map(|candidate| {
+                let tags = subtypes
+                    .iter()
+                    .map(|st| {
+                        let field = st
+                            .fields
+                            .iter()
+                            .flatten()
+                            .find(|f| f.required && f.name == candidate.name)?;
+                        Some((st.name.clone(), field.fixed_value()?.to_owned()))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let distinct = tags.iter().map(|(_, tag)| tag).collect::<HashSet<_>>();
+                (distinct.len() == tags.len()).then(|| Discriminant {
+                    field: candidate.name.clone(),
+                    tags,
+                })
+            })
+    }
+
     pub(crate) fn recursive_fields(&self, t: &Type) -> BTreeSet<String> {
         let mut set = BTreeSet::new();
         self.recursive_fields_priv(t, &mut set);
@@ -0,0 +1,261 @@
+//! Frontend for the schema published by <https://github.com/ark0f/tg-bot-api>. Types and
+//! methods are listed as arrays with structured type descriptions, these are normalized
+//! into the same [`Spec`] the PaulSonOfLars spec deserializes to
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::schema::{Field, Method, Spec, Type};
+use crate::ARRAY_OF;
+
+#[derive(Deserialize, Debug)]
+struct Version {
+    major: u64,
+    minor: u64,
+    #[serde(default)]
+    patch: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct Date {
+    year: u64,
+    month: u64,
+    day: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct Schema {
+    version: Version,
+    recent_changes: Option<Date>,
+    types: Vec<SchemaType>,
+    methods: Vec<SchemaMethod>,
+}
+
+/// A type description, like `{"type": "array", "array": {"type": "integer"}}`
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Kind {
+    Integer,
+    Float,
+    String,
+    Bool {
+        #[serde(default)]
+        default: Option<bool>,
+    },
+    Reference {
+        reference: String,
+    },
+    Array {
+        array: Box<Kind>,
+    },
+    AnyOf {
+        any_of: Vec<Kind>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct Property {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    required: bool,
+    #[serde(flatten)]
+    kind: Kind,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Body {
+    Object {
+        #[serde(default)]
+        properties: Vec<Property>,
+    },
+    AnyOf {
+        any_of: Vec<Kind>,
+    },
+    Unknown,
+}
+
+#[derive(Deserialize, Debug)]
+struct SchemaType {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    documentation_link: String,
+    #[serde(flatten)]
+    body: Body,
+}
+
+#[derive(Deserialize, Debug)]
+struct SchemaMethod {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    documentation_link: String,
+    #[serde(default)]
+    arguments: Vec<Property>,
+    return_type: Kind,
+}
+
+/// Check if a parsed json spec uses this format
+pub(crate) fn is_tgbotapi(value: &serde_json::Value) -> bool {
+    value.get("types").is_some_and(|t| t.is_array())
+}
+
+/// Convert a type description to the type names used by the PaulSonOfLars spec
+fn type_names(kind: &Kind) -> Vec<String> {
+    match kind {
+        Kind::Integer => vec!["Integer".to_owned()],
+        Kind::Float => vec!["Float".to_owned()],
+        Kind::String => vec!["String".to_owned()],
+        Kind::Bool {
+            default: Some(true),
+        } => vec!["True".to_owned()],
+        Kind::Bool { .. } => vec!["Boolean".to_owned()],
+        Kind::Reference { reference } => vec![reference.clone()],
+        Kind::Array { array } => type_names(array)
+            .into_iter()
+            .map(|t| format!("{}{}", ARRAY_OF, t))
+            .collect(),
+        Kind::AnyOf { any_of } => any_of.iter().flat_map(type_names).collect(),
+    }
+}
+
+fn paragraphs(description: &str) -> Vec<String> {
+    description
+        .split("\n\n")
+        .map(|p| p.trim().to_owned())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+fn field(property: Property) -> Field {
+    Field {
+        types: type_names(&property.kind),
+        name: property.name,
+        required: property.required,
+        description: Some(property.description),
+    }
+}
+
+/// Normalize a tg-bot-api schema into a Spec
+pub(crate) fn parse(value: &serde_json::Value) -> Result<Spec> {
+    let schema = Schema::deserialize(value).context("invalid tg-bot-api schema")?;
+    let version = format!(
+        "Bot API {}.{}{}",
+        schema.version.major,
+        schema.version.minor,
+        if schema.version.patch > 0 {
+            format!(".{}", schema.version.patch)
+        } else {
+            String::new()
+        }
+    );
+    let release_date = schema
+        .recent_changes
+        .map(|d| format!("{}-{:02}-{:02}", d.year, d.month, d.day))
+        .unwrap_or_default();
+
+    let mut subtype_of = HashMap::<String, Vec<String>>::new();
+    for t in schema.types.iter() {
+        if let Body::AnyOf { ref any_of } = t.body {
+            for subtype in any_of.iter().flat_map(type_names) {
+                subtype_of.entry(subtype).or_default().push(t.name.clone());
+            }
+        }
+    }
+
+    let types = schema
+        .types
+        .into_iter()
+        .map(|t| {
+            let (fields, subtypes) = match t.body {
+                Body::Object { properties } if properties.is_empty() => (None, None),
+                Body::Object { properties } => {
+                    (Some(properties.into_iter().map(field).collect()), None)
+                }
+                Body::AnyOf { any_of } => {
+                    (None, Some(any_of.iter().flat_map(type_names).collect()))
+                }
+                Body::Unknown => (None, None),
+            };
+            let parsed = Type {
+                subtype_of: subtype_of.remove(&t.name),
+                description: paragraphs(&t.description),
+                href: t.documentation_link,
+                name: t.name.clone(),
+                fields,
+                subtypes,
+            };
+            (t.name, parsed)
+        })
+        .collect();
+
+    let methods = schema
+        .methods
+        .into_iter()
+        .map(|m| {
+            let arguments = m.arguments.into_iter().map(field).collect::<Vec<_>>();
+            let parsed = Method {
+                name: m.name.clone(),
+                href: m.documentation_link,
+                description: paragraphs(&m.description),
+                returns: type_names(&m.return_type),
+                fields: if arguments.is_empty() {
+                    None
+                } else {
+                    Some(arguments)
+                },
+            };
+            (m.name, parsed)
+        })
+        .collect();
+
+    Ok(Spec::new(version, release_date, types, methods))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_schema() {
+        let json = r#"{
+            "version": {"major": 7, "minor": 1, "patch": 0},
+            "recent_changes": {"year": 2024, "month": 2, "day": 16},
+            "types": [
+                {"name": "User", "description": "A user", "kind": "object", "properties": [
+                    {"name": "id", "type": "integer", "required": true, "description": "Id"}
+                ]},
+                {"name": "Sender", "kind": "any_of", "any_of": [
+                    {"type": "reference", "reference": "User"}
+                ]}
+            ],
+            "methods": [
+                {"name": "getMe", "description": "Get the bot", "arguments": [],
+                 "return_type": {"type": "reference", "reference": "User"}},
+                {"name": "getIds", "arguments": [
+                    {"name": "chat_id", "required": true, "type": "any_of", "any_of": [
+                        {"type": "integer"}, {"type": "string"}
+                    ]}
+                ], "return_type": {"type": "array", "array": {"type": "integer"}}}
+            ]
+        }"#;
+        let value = serde_json::from_str(json).unwrap();
+        assert!(is_tgbotapi(&value));
+        let spec = parse(&value).unwrap();
+        spec.validate().unwrap();
+        assert_eq!(spec.version, "Bot API 7.1");
+        assert_eq!(spec.release_date, "2024-02-16");
+        let user = spec.get_type("User").unwrap();
+        assert_eq!(user.subtype_of, Some(vec!["Sender".to_owned()]));
+        let ids = spec.get_method("getIds").unwrap();
+        assert_eq!(ids.returns, ["Array of Integer"]);
+        assert_eq!(ids.fields.as_ref().unwrap()[0].types, ["Integer", "String"]);
+    }
+}
@@ -5,8 +5,9 @@ use itertools::Itertools;
 use lazy_static::lazy_static;
 use quote::{format_ident, quote, ToTokens, __private::TokenStream};
 
+use crate::fixtures::{example, Fixture};
 use crate::naming::*;
-use crate::schema::{Field, Spec};
+use crate::schema::{Discriminant, Field, Spec};
 use crate::util::*;
 use regex::{escape, Regex};
 use std::collections::{HashMap, HashSet};
@@ -14,6 +15,7 @@ use std::sync::Arc;
 
 lazy_static! {
     static ref REGEX_STATUS: Regex = Regex::new(r#""[a-z]+""#).unwrap();
+    static ref REGEX_QUOTED: Regex = Regex::new(r#""([^"\s]+)""#).unwrap();
 }
 
 /// Generator for the "types" source file
@@ -41,6 +43,8 @@ impl<'a> GenerateTypes<'a> {
                     "ChatHandle".to_owned()
                 } else if types.len() > 1 {
                     get_multitype_name_types(&name, types)
+                } else if let Some(id) = id_type(opts.parent, name, types) {
+                    id.to_owned()
                 } else if nested == 0 {
                     type_mapper(&mytype).to_owned()
                 } else {
@@ -109,7 +113,8 @@ impl<'a> GenerateTypes<'a> {
                     self.generate_enum_internally_tagged(statuses, &v.name, "status")
                         .unwrap()
                 } else {
-                    self.generate_enum_str(subtypes.as_slice(), &v.name)
+                    let discriminant = self.spec.get_discriminant(v);
+                    self.generate_enum_str(subtypes.as_slice(), &v.name, discriminant.as_ref())
                         .unwrap()
                 };
                 let name = format_ident!("{}", v.name);
@@ -136,17 +141,27 @@ impl<'a> GenerateTypes<'a> {
         let typeenums = self.generate_multitype_enums()?;
         let extra = self.generate_method_multitypes()?;
         let uses = self.generate_use()?;
-        let tests = self.generate_test();
+        let tests = self.generate_test()?;
         let chatid = self.generate_chat_enum();
+        let chataction = self.generate_chat_action_enum();
+        let strenums = self.generate_str_enums()?;
         let rhaihelpers = self.generate_rhai_helpers();
         let froms = self.generate_from_wrapper();
+        let version = self.generate_version();
+        let ids = self.generate_id_types();
+        let reduced = self.generate_reduced_types()?;
         let res = quote! {
             #uses
+            #version
+            #ids
             #chatid
+            #chataction
+            #strenums
             #( #traits )*
             #( #structs )*
             #( #impls )*
             #typeenums
+            #reduced
             #rhaihelpers
             #froms
             #extra
@@ -155,6 +170,212 @@ impl<'a> GenerateTypes<'a> {
         Ok(res)
     }
 
+    /// Generate constants for the version of the spec these bindings were generated from
+    fn generate_version(&self) -> TokenStream {
+        let version = self
+            .spec
+            .version
+            .strip_prefix("Bot API ")
+            .unwrap_or(&self.spec.version);
+        let release_date = &self.spec.release_date;
+        quote! {
+            /// Version of the telegram bot api these bindings were generated from
+            pub const TELEGRAM_BOT_API_VERSION: &str = #version;
+
+            /// Release date of TELEGRAM_BOT_API_VERSION
+            pub const TELEGRAM_BOT_API_RELEASE_DATE: &str = #release_date;
+        }
+    }
+
+    /// Generate conversions from full types to the reduced views of them returned in some
+    /// places, and accessors for enums over a full and reduced type
+    fn generate_reduced_types(&self) -> Result<TokenStream> {
+        let mut tokens = quote!();
+        for (full, reduced) in REDUCED_TYPES {
+            let full_t = self
+                .spec
+                .get_type(full)
+                .ok_or_else(|| anyhow!("reduced type source {} not found", full))?;
+            let reduced_t = self
+                .spec
+                .get_type(reduced)
+                .ok_or_else(|| anyhow!("reduced type {} not found", reduced))?;
+            let fields = reduced_t
+                .pretty_fields()
+                .map(|f| {
+                    let full_f = full_t
+                        .pretty_fields()
+                        .find(|v| v.name == f.name)
+                        .ok_or_else(|| {
+                            anyhow!("{} has no field {} of {}", full, f.name, reduced)
+                        })?;
+                    let reduced_type = self.choose_type.choose_type(
+                        &f.types,
+                        Some(reduced_t),
+                        &f.name,
+                        !f.required,
+                        false,
+                    )?;
+                    let full_type = self.choose_type.choose_type(
+                        &full_f.types,
+                        Some(full_t),
+                        &full_f.name,
+                        !full_f.required,
+                        false,
+                    )?;
+                    if reduced_type.to_string() != full_type.to_string() {
+                        return Err(anyhow!(
+                            "field {} differs between {} and {}",
+                            f.name,
+                            full,
+                            reduced
+                        ));
+                    }
+                    Ok(format_ident!("{}", get_field_name(f)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let full_name = format_ident!("{}", get_type_name(full_t));
+            let reduced_name = format_ident!("{}", get_type_name(reduced_t));
+            let comment =
+                format!("Drop the fields of {} not present in {}", full, reduced).comment();
+            tokens.extend(quote! {
+                impl From<#full_name> for #reduced_name {
+                    #comment
+                    fn from(value: #full_name) -> Self {
+                        Self {
+                            #( #fields: value.#fields ),*
+                        }
+                    }
+                }
+            });
+        }
+
+        for name in REDUCED_ENUMS {
+            let t = self
+                .spec
+                .get_type(name)
+                .ok_or_else(|| anyhow!("reduced enum {} not found", name))?;
+            if let Some(subtypes) = t.subtypes.as_ref() {
+                tokens.extend(self.generate_multitype_return_helpers(subtypes, &get_type_name(t)));
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Generate transparent newtypes for identifiers so they can't be confused with
+    /// other values of the same type
+    fn generate_id_types(&self) -> TokenStream {
+        let ids = ID_TYPES.iter().map(|(name, wrapped, suffix)| {
+            let name = format_ident!("{}", name);
+            let comment = format!("A {} returned by telegram", suffix).comment();
+            if *wrapped == "Integer" {
+                quote! {
+                    #comment
+                    #[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
+                    #[serde(transparent)]
+                    pub struct #name(i64);
+
+                    impl #name {
+                        /// Get the wrapped integer
+                        pub fn get(&self) -> i64 {
+                            self.0
+                        }
+                    }
+
+                    impl fmt::Display for #name {
+                        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                            write!(f, "{}", self.0)
+                        }
+                    }
+
+                    impl From<i64> for #name {
+                        fn from(value: i64) -> Self {
+                            Self(value)
+                        }
+                    }
+
+                    impl From<#name> for i64 {
+                        fn from(value: #name) -> Self {
+                            value.0
+                        }
+                    }
+                }
+            } else {
+                quote! {
+                    #comment
+                    #[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, Eq, PartialEq, Ord, PartialOrd)]
+                    #[serde(transparent)]
+                    pub struct #name(String);
+
+                    impl #name {
+                        /// Get this id as a string
+                        pub fn as_str(&self) -> &'_ str {
+                            &self.0
+                        }
+
+                        /// Get the wrapped string
+                        pub fn into_inner(self) -> String {
+                            self.0
+                        }
+                    }
+
+                    impl std::ops::Deref for #name {
+                        type Target = str;
+
+                        fn deref(&self) -> &Self::Target {
+                            &self.0
+                        }
+                    }
+
+                    impl AsRef<str> for #name {
+                        fn as_ref(&self) -> &str {
+                            &self.0
+                        }
+                    }
+
+                    impl fmt::Display for #name {
+                        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                            f.write_str(&self.0)
+                        }
+                    }
+
+                    impl From<String> for #name {
+                        fn from(value: String) -> Self {
+                            Self(value)
+                        }
+                    }
+
+                    impl From<&str> for #name {
+                        fn from(value: &str) -> Self {
+                            Self(value.to_owned())
+                        }
+                    }
+
+                    impl From<#name> for String {
+                        fn from(value: #name) -> Self {
+                            value.0
+                        }
+                    }
+                }
+            }
+        });
+        quote! {
+            #( #ids )*
+
+            impl From<FileId> for FileRef {
+                fn from(value: FileId) -> Self {
+                    Self::Id(value.0)
+                }
+            }
+
+            impl From<FileId> for FileData {
+                fn from(value: FileId) -> Self {
+                    Self::String(value.0)
+                }
+            }
+        }
+    }
+
     /// Generate use statements for this file
     fn generate_use(&self) -> Result<TokenStream> {
         Ok(quote! {
@@ -246,6 +467,177 @@ impl<'a> GenerateTypes<'a> {
         }
     }
 
+    /// Generates enum for the fixed set of actions accepted by sendChatAction
+    fn generate_chat_action_enum(&self) -> TokenStream {
+        quote! {
+            /// Type of action to broadcast with sendChatAction
+            #[derive(Serialize, Deserialize, Hash, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Default)]
+            #[serde(rename_all = "snake_case")]
+            pub enum ChatAction {
+                #[default]
+                Typing,
+                UploadPhoto,
+                RecordVideo,
+                UploadVideo,
+                RecordVoice,
+                UploadVoice,
+                UploadDocument,
+                ChooseSticker,
+                FindLocation,
+                RecordVideoNote,
+                UploadVideoNote
+            }
+
+            impl ChatAction {
+                /// Get the string representation of this action used by telegram
+                pub fn as_str(&self) -> &'static str {
+                    match self {
+                        Self::Typing => "typing",
+                        Self::UploadPhoto => "upload_photo",
+                        Self::RecordVideo => "record_video",
+                        Self::UploadVideo => "upload_video",
+                        Self::RecordVoice => "record_voice",
+                        Self::UploadVoice => "upload_voice",
+                        Self::UploadDocument => "upload_document",
+                        Self::ChooseSticker => "choose_sticker",
+                        Self::FindLocation => "find_location",
+                        Self::RecordVideoNote => "record_video_note",
+                        Self::UploadVideoNote => "upload_video_note"
+                    }
+                }
+            }
+
+            impl fmt::Display for ChatAction {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str(self.as_str())
+                }
+            }
+        }
+    }
+
+    /// Generate enums for string fields documented as one of a fixed set of values, along
+    /// with typed getters on the types using them
+    fn generate_str_enums(&self) -> Result<TokenStream> {
+        let mut tokens = quote!();
+        for (name, source, field, names) in STR_ENUMS {
+            let fields = match self.spec.get_type(source) {
+                Some(t) => t.fields.as_deref(),
+                None => self
+                    .spec
+                    .get_method(source)
+                    .ok_or_else(|| anyhow!("{} not found for {}", source, name))?
+                    .fields
+                    .as_deref(),
+            };
+            let description = fields
+                .unwrap_or_default()
+                .iter()
+                .find(|f| f.name == *field)
+                .and_then(|f| f.description.as_deref())
+                .ok_or_else(|| anyhow!("{}.{} not found for {}", source, field, name))?;
+            let values = REGEX_QUOTED
+                .captures_iter(description)
+                .filter_map(|c| c.get(1))
+                .map(|v| v.as_str())
+                .unique()
+                .collect_vec();
+            if values.is_empty() {
+                return Err(anyhow!("no values documented for {}.{}", source, field));
+            }
+            let variants = values
+                .iter()
+                .map(|v| {
+                    let variant = match names.iter().find(|(value, _)| value == v) {
+                        Some((_, variant)) => (*variant).to_owned(),
+                        None if v.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+                            v.to_case(Case::UpperCamel)
+                        }
+                        None => return Err(anyhow!("no variant name for {} in {}", v, name)),
+                    };
+                    Ok(format_ident!("{}", variant))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let enumname = format_ident!("{}", name);
+            let comment = format!("Possible values of {}.{}", source, field).comment();
+            let error = format!("unknown {} {{}}", name);
+            tokens.extend(quote! {
+                #comment
+                #[derive(Serialize, Deserialize, Hash, Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+                pub enum #enumname {
+                    #(
+                        #[serde(rename = #values)]
+                        #variants
+                    ),*
+                }
+
+                impl #enumname {
+                    /// Get the string representation used by telegram
+                    pub fn as_str(&self) -> &'static str {
+                        match self {
+                            #( Self::#variants => #values ),*
+                        }
+                    }
+                }
+
+                impl fmt::Display for #enumname {
+                    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str(self.as_str())
+                    }
+                }
+
+                impl std::str::FromStr for #enumname {
+                    type Err = anyhow::Error;
+
+                    fn from_str(s: &str) -> Result<Self> {
+                        match s {
+                            #( #values => Ok(Self::#variants), )*
+                            _ => Err(anyhow!(#error, s))
+                        }
+                    }
+                }
+            });
+        }
+
+        for (typename, field, getter, enumname) in STR_ENUM_GETTERS {
+            let t = self
+                .spec
+                .get_type(typename)
+                .ok_or_else(|| anyhow!("{} not found for {}", typename, getter))?;
+            let f = t
+                .pretty_fields()
+                .find(|f| f.name == *field)
+                .ok_or_else(|| anyhow!("{}.{} not found for {}", typename, field, getter))?;
+            if t.pretty_fields()
+                .any(|f| format!("get_{}", get_field_name(f)) == *getter)
+            {
+                return Err(anyhow!("{} conflicts with a field of {}", getter, typename));
+            }
+            let typename = format_ident!("{}", get_type_name(t));
+            let fieldgetter = format_ident!("get_{}", get_field_name(f));
+            let getter = format_ident!("{}", getter);
+            let enumname = format_ident!("{}", enumname);
+            let comment = format!(
+                "Get {} as a {}, None if telegram sent an unknown value",
+                field, enumname
+            )
+            .comment();
+            let body = if f.required {
+                quote! { self.#fieldgetter().parse().ok() }
+            } else {
+                quote! { self.#fieldgetter()?.parse().ok() }
+            };
+            tokens.extend(quote! {
+                impl #typename {
+                    #comment
+                    pub fn #getter(&self) -> Option<#enumname> {
+                        #body
+                    }
+                }
+            });
+        }
+        Ok(tokens)
+    }
+
     /// Generate a special helper type to treat "Update" as an enum
     fn generate_update_ext(&self, t: &Type) -> TokenStream {
         if t.name == UPDATE {
@@ -332,6 +724,17 @@ impl<'a> GenerateTypes<'a> {
                 })
                 .collect_vec();
 
+            let kinds = t
+                .pretty_fields()
+                .filter(|f| f.name != "update_id")
+                .map(|f| {
+                    let kind = get_type_name_str(&f.name);
+                    let variant = format_ident!("{}", kind);
+                    quote! {
+                        Self::#variant(_) => #kind
+                    }
+                });
+
             quote! {
                 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
                 pub enum UpdateExt {
@@ -341,6 +744,14 @@ impl<'a> GenerateTypes<'a> {
 
                 impl UpdateExt {
                     #( #methods )*
+
+                    /// Get the name of the kind of update, like "Message" or "CallbackQuery"
+                    pub fn get_kind(&self) -> &'static str {
+                        match self {
+                            #( #kinds, )*
+                            Self::Invalid => "Invalid"
+                        }
+                    }
                 }
             }
         } else {
@@ -388,6 +799,19 @@ impl<'a> GenerateTypes<'a> {
                     }
                 });
 
+            let setters = t
+                .pretty_fields()
+                .filter(|f| f.name != "update_id")
+                .map(|f| {
+                    let setname = format_ident!("set_{}", get_field_name(f));
+                    let extname = format_ident!("{}", get_type_name_str(&f.name));
+                    quote! {
+                        UpdateExt::#extname(thing) => {
+                            res.#setname(Some(thing));
+                        }
+                    }
+                });
+
             quote! {
                impl From<Update> for UpdateExt {
                    fn from(update: Update) -> Self {
@@ -396,6 +820,20 @@ impl<'a> GenerateTypes<'a> {
                         Self::Invalid
                    }
                }
+
+               impl From<(UpdateId, UpdateExt)> for Update {
+                   /// Convert back to an Update, UpdateExt doesn't keep the update_id so it
+                   /// has to be passed along
+                   fn from((update_id, update): (UpdateId, UpdateExt)) -> Self {
+                        let mut res = Update::default();
+                        res.set_update_id(update_id);
+                        match update {
+                            #( #setters )*
+                            UpdateExt::Invalid => ()
+                        }
+                        res
+                   }
+               }
             }
         } else {
             quote!()
@@ -529,16 +967,21 @@ impl<'a> GenerateTypes<'a> {
         } else {
             let res = if !is_inputfile_types(types) {
                 if let Some(name) = self.get_multitype_name_return(types) {
-                    let t = self.generate_enum_str(types, &name)?;
+                    let t = self.generate_enum_str(types, &name, None)?;
+                    let helpers = self.generate_multitype_return_helpers(types, &name);
                     if !is_json_types(types) {
                         let typeiter = types.iter().map(get_type_name_str);
                         let types = generate_fmt_display_enum(&name, typeiter);
                         quote! {
                             #t
                             #types
+                            #helpers
                         }
                     } else {
-                        t
+                        quote! {
+                            #t
+                            #helpers
+                        }
                     }
                 } else {
                     quote!()
@@ -551,6 +994,57 @@ impl<'a> GenerateTypes<'a> {
         }
     }
 
+    /// Generate accessors for method return types like "Message or True" so callers don't
+    /// need to match on the enum for the common case
+    fn generate_multitype_return_helpers(&self, types: &[String], name: &str) -> TokenStream {
+        let enumname = format_ident!("{}", name);
+        let methods = types.iter().map(|t| {
+            let variant = get_type_name_str(t);
+            let varianttype = type_mapper(&type_without_array(t)).to_owned();
+            let varianttype = format_ident!("{}", varianttype);
+            let variant_snake = variant.to_case(Case::Snake);
+            let variant = format_ident!("{}", variant);
+            if type_without_array(t) == "Boolean" {
+                quote! {
+                    /// Returns true if telegram returned `true` instead of an object
+                    pub fn is_true(&self) -> bool {
+                        matches!(self, Self::#variant(true))
+                    }
+                }
+            } else {
+                let into = format_ident!("into_{}", variant_snake);
+                let as_ref = format_ident!("as_{}", variant_snake);
+                let into_comment =
+                    format!("Consume this result, returning the {} if present", variant).comment();
+                let as_comment =
+                    format!("Returns a reference to the {} if present", variant).comment();
+                quote! {
+                    #into_comment
+                    pub fn #into(self) -> Option<#varianttype> {
+                        match self {
+                            Self::#variant(v) => Some(v),
+                            _ => None
+                        }
+                    }
+
+                    #as_comment
+                    pub fn #as_ref(&self) -> Option<&'_ #varianttype> {
+                        match self {
+                            Self::#variant(ref v) => Some(v),
+                            _ => None
+                        }
+                    }
+                }
+            }
+        });
+
+        quote! {
+            impl #enumname {
+                #( #methods )*
+            }
+        }
+    }
+
     /// If we can't chose a specific type when a field has multiple types, generate an enum with
     /// all types
     fn generate_multitype_enums(&self) -> Result<TokenStream> {
@@ -561,7 +1055,7 @@ impl<'a> GenerateTypes<'a> {
                 for field in fields {
                     if field.types.len() > 1 && !is_inputfile(field) {
                         if let Some(name) = self.get_multitype_name(field) {
-                            let t = self.generate_enum_str(&field.types, &name)?;
+                            let t = self.generate_enum_str(&field.types, &name, None)?;
                             tokens.extend(t);
 
                             if !is_json(field) {
@@ -580,7 +1074,7 @@ impl<'a> GenerateTypes<'a> {
                 for field in fields {
                     if field.types.len() > 1 && !is_inputfile(field) {
                         if let Some(name) = self.get_multitype_name(field) {
-                            let t = self.generate_enum_str(&field.types, &name)?;
+                            let t = self.generate_enum_str(&field.types, &name, None)?;
                             tokens.extend(t);
 
                             if !is_json(field) {
@@ -788,8 +1282,63 @@ impl<'a> GenerateTypes<'a> {
         Ok(e)
     }
 
-    /// Generate an enum with custom types
-    fn generate_enum_str<N, I>(&self, types: &[I], name: &N) -> Result<TokenStream>
+    /// Generate From conversions from each variant's type into an enum and TryFrom
+    /// conversions back out, returning the enum unchanged on mismatch. Types used by more
+    /// than one variant are skipped since the conversion would be ambiguous
+    fn generate_enum_conversions<I>(&self, types: &[I], name: &str) -> TokenStream
+    where
+        I: AsRef<str>,
+    {
+        let name = format_ident!("{}", name);
+        let variants = types
+            .iter()
+            .map(|v| {
+                (
+                    get_type_name_str(v),
+                    type_mapper(&type_without_array(v)).to_owned(),
+                )
+            })
+            .collect_vec();
+        let conversions = variants
+            .iter()
+            .filter(|(_, t)| variants.iter().filter(|(_, other)| other == t).count() == 1)
+            .map(|(variant, t)| {
+                let variant = format_ident!("{}", variant);
+                let t = format_ident!("{}", t);
+                quote! {
+                    impl From<#t> for #name {
+                        fn from(value: #t) -> Self {
+                            Self::#variant(value)
+                        }
+                    }
+
+                    impl TryFrom<#name> for #t {
+                        type Error = #name;
+
+                        #[allow(unreachable_patterns)]
+                        fn try_from(value: #name) -> std::result::Result<Self, Self::Error> {
+                            match value {
+                                #name::#variant(v) => Ok(v),
+                                other => Err(other),
+                            }
+                        }
+                    }
+                }
+            });
+        quote! {
+            #( #conversions )*
+        }
+    }
+
+    /// Generate an untagged enum over types. If a discriminant is given, deserializing
+    /// picks the variant by the value of the discriminant field instead of the first
+    /// variant that fits, since subtypes often have the same required fields
+    fn generate_enum_str<N, I>(
+        &self,
+        types: &[I],
+        name: &N,
+        discriminant: Option<&Discriminant>,
+    ) -> Result<TokenStream>
     where
         N: AsRef<str>,
         I: AsRef<str>,
@@ -831,9 +1380,21 @@ impl<'a> GenerateTypes<'a> {
             };
 
             //let enum_methods = self.generate_enum_methods()
+            let conversions = self.generate_enum_conversions(types, &name.to_string());
+
+            let (derive, deserialize) = match discriminant {
+                Some(discriminant) => (
+                    quote!(#[derive(Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]),
+                    self.generate_enum_deserialize(types, &name.to_string(), discriminant),
+                ),
+                None => (
+                    quote!(#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]),
+                    quote!(),
+                ),
+            };
 
             quote! {
-                #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+                #derive
                 #[serde(untagged)]
                 pub enum #name {
                     #(
@@ -841,6 +1402,8 @@ impl<'a> GenerateTypes<'a> {
                     ),*
                 }
                 #default
+                #conversions
+                #deserialize
             }
         } else {
             quote! {
@@ -851,6 +1414,86 @@ impl<'a> GenerateTypes<'a> {
         Ok(e)
     }
 
+    /// Generate Deserialize for an enum choosing the variant by a discriminant field,
+    /// through crate::tagged so map entries are only buffered until the discriminant is
+    /// found. Payloads without a known value, like arrays from non self-describing
+    /// formats, are matched against each variant in order like an untagged enum
+    fn generate_enum_deserialize<I>(
+        &self,
+        types: &[I],
+        name: &str,
+        discriminant: &Discriminant,
+    ) -> TokenStream
+    where
+        I: AsRef<str>,
+    {
+        let field = &discriminant.field;
+        let (tags, (tagged, tagged_types)): (Vec<_>, (Vec<_>, Vec<_>)) = types
+            .iter()
+            .filter_map(|t| {
+                let (_, tag) = discriminant
+                    .tags
+                    .iter()
+                    .find(|(subtype, _)| subtype == t.as_ref())?;
+                Some((
+                    tag,
+                    (
+                        format_ident!("{}", get_type_name_str(t)),
+                        format_ident!("{}", type_mapper(&type_without_array(t))),
+                    ),
+                ))
+            })
+            .unzip();
+        let variants = types
+            .iter()
+            .map(|t| format_ident!("{}", get_type_name_str(t)))
+            .collect_vec();
+        let variant_types = types
+            .iter()
+            .map(|t| format_ident!("{}", type_mapper(&type_without_array(t))));
+        let ident = format_ident!("{}", name);
+        quote! {
+            impl crate::tagged::Tagged for #ident {
+                const NAME: &'static str = #name;
+                const TAG: &'static str = #field;
+
+                fn is_tag(tag: &str) -> bool {
+                    [#( #tags ),*].contains(&tag)
+                }
+
+                fn deserialize_tag<'de, D>(tag: &str, deserializer: D) -> std::result::Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    match tag {
+                        #(
+                            #tags => #tagged_types::deserialize(deserializer).map(#ident::#tagged),
+                        )*
+                        _ => Err(serde::de::Error::unknown_variant(tag, &[#( #tags ),*])),
+                    }
+                }
+
+                fn deserialize_untagged(value: &serde_json::Value) -> Option<Self> {
+                    #(
+                        if let std::result::Result::Ok(v) = #variant_types::deserialize(value) {
+                            return Some(#ident::#variants(v));
+                        }
+                    )*
+                    None
+                }
+            }
+
+            impl<'de> Deserialize<'de> for #ident {
+                fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+                where
+                    D: serde::Deserializer<'de>,
+                {
+                    crate::tagged::deserialize(deserializer)
+                }
+            }
+        }
+    }
+
     fn generate_rhai_module(&self, t: &str) -> TokenStream {
         let name = format_ident!("{}", t);
         let modname = format_ident!("{}Module", t);
@@ -1085,6 +1728,55 @@ impl<'a> GenerateTypes<'a> {
                 String(String),
               }
 
+            /// Serializable reference to a file for use in owned method parameters. Raw bytes
+            /// can be sent but not serialized, use a path or file_id for requests that are stored
+            #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
+            #[serde(rename_all = "snake_case")]
+            pub enum FileRef {
+                /// A file_id or url already known to telegram
+                Id(String),
+                /// A local file, read when the request is sent
+                Path(std::path::PathBuf),
+                /// Raw file contents
+                #[serde(skip)]
+                Bytes(Vec<u8>)
+            }
+
+            impl FileRef {
+                /// Convert to FileData for uploading, reading the file if this is a path
+                pub async fn into_file_data(self) -> Result<FileData> {
+                    match self {
+                        Self::Id(id) => Ok(FileData::String(id)),
+                        Self::Path(path) => Ok(FileData::Bytes(tokio::fs::read(path).await?)),
+                        Self::Bytes(bytes) => Ok(FileData::Bytes(bytes)),
+                    }
+                }
+            }
+
+            impl Default for FileRef {
+                fn default() -> Self {
+                    Self::Id(String::new())
+                }
+            }
+
+            impl From<String> for FileRef {
+                fn from(value: String) -> Self {
+                    Self::Id(value)
+                }
+            }
+
+            impl From<std::path::PathBuf> for FileRef {
+                fn from(value: std::path::PathBuf) -> Self {
+                    Self::Path(value)
+                }
+            }
+
+            impl From<Vec<u8>> for FileRef {
+                fn from(value: Vec<u8>) -> Self {
+                    Self::Bytes(value)
+                }
+            }
+
             impl Default for #input_file {
                 fn default() -> Self {
                     #input_file::Bytes(FileBytes {
@@ -1979,7 +2671,62 @@ impl<'a> GenerateTypes<'a> {
         Ok(res)
     }
 
-    fn generate_test(&self) -> TokenStream {
+    /// Generate tests for the generated types: conformance tests parsing the payloads in
+    /// generate/fixtures, tagging tests for enums with a discriminant, and serialization
+    /// round trips for every struct. The tagging payloads are not taken from the spec,
+    /// they are synthesized by fixtures::example from the fields of each subtype
+    fn generate_test(&self) -> Result<TokenStream> {
+        let conformance = Fixture::all()?.into_iter().map(|fixture| {
+            let name = format_ident!("{}", get_type_name_str(&fixture.tg_type));
+            let test_name = format_ident!("conformance_{}", fixture.name);
+            let payload = fixture.payload.to_string();
+            quote! {
+                #[test]
+                fn #test_name() {
+                    let expected: serde_json::Value = serde_json::from_str(#payload).unwrap();
+                    let t: #name = serde_json::from_str(#payload).unwrap();
+                    let actual = serde_json::to_value(&t).unwrap();
+                    assert_subset(&expected, &actual, "");
+                }
+            }
+        });
+        let tagged = self
+            .spec
+            .types
+            .values()
+            .filter(|t| t.fields.as_ref().map(|f| f.len()).unwrap_or(0) == 0)
+            .filter_map(|t| {
+                let discriminant = self.spec.get_discriminant(t)?;
+                let name = format_ident!("{}", get_type_name(t));
+                let test_name = format_ident!("tagged_{}", t.name.to_case(Case::Snake));
+                let field = &discriminant.field;
+                let checks = discriminant
+                    .tags
+                    .iter()
+                    .filter_map(|(subtype, tag)| {
+                        let payload = example(&self.spec, subtype)?.to_string();
+                        let variant = format_ident!("{}", get_type_name_str(subtype));
+                        Some(quote! {
+                            let parsed: #name = serde_json::from_str(#payload).unwrap();
+                            assert!(
+                                matches!(parsed, #name::#variant(_)),
+                                "{} parsed as {:?}",
+                                #tag,
+                                parsed
+                            );
+                            let value = serde_json::to_value(&parsed).unwrap();
+                            assert_eq!(value[#field], #tag);
+                            assert_eq!(serde_json::from_value::<#name>(value).unwrap(), parsed);
+                        })
+                    })
+                    .collect_vec();
+                Some(quote! {
+                    #[test]
+                    fn #test_name() {
+                        #( #checks )*
+                    }
+                })
+            });
         let tests = self
             .spec
             .types
@@ -2025,20 +2772,44 @@ impl<'a> GenerateTypes<'a> {
                 }
             });
 
-        quote! {
+        Ok(quote! {
             #[cfg(test)]
             mod test {
                 use super::*;
                 use std::default::Default;
                 #( #tests )*
 
+                /// Check every value in a fixture survives a round trip through the
+                /// generated types. Extra fields serialized with default values are allowed
+                fn assert_subset(expected: &serde_json::Value, actual: &serde_json::Value, path: &str) {
+                    match (expected, actual) {
+                        (serde_json::Value::Object(e), serde_json::Value::Object(a)) => {
+                            for (k, v) in e {
+                                let path = format!("{}.{}", path, k);
+                                let a = a.get(k).unwrap_or_else(|| panic!("{} missing", path));
+                                assert_subset(v, a, &path);
+                            }
+                        }
+                        (serde_json::Value::Array(e), serde_json::Value::Array(a)) => {
+                            assert_eq!(e.len(), a.len(), "{} length", path);
+                            for (i, (e, a)) in e.iter().zip(a).enumerate() {
+                                assert_subset(e, a, &format!("{}[{}]", path, i));
+                            }
+                        }
+                        (e, a) => assert_eq!(e, a, "{}", path),
+                    }
+                }
+
+                #( #conformance )*
+                #( #tagged )*
+
                 #[test]
                 fn new_unbox() {
                     let v: BoxWrapper<Unbox<Message>> = BoxWrapper::new_unbox(Message::default());
                     let _: Message = v.into();
                 }
             }
-        }
+        })
     }
     /// Generate a struct based on a type name from the api spec
     fn generate_struct<T>(&self, type_name: T, name: T, serde_skip: bool) -> Result<TokenStream>
@@ -2056,16 +2827,17 @@ impl<'a> GenerateTypes<'a> {
             let fieldname = get_field_name(f);
             let name = format_ident!("{}", fieldname);
             let comment = f.description.comment();
+            let aliases = get_field_aliases(f);
             if f.required {
                 quote! {
                     #comment
-                    #[serde(rename = #v)]
+                    #[serde(rename = #v #( , alias = #aliases )*)]
                     pub #name
                 }
             } else if serde_skip {
                 quote! {
                     #comment
-                    #[serde(skip_serializing_if = "Option::is_none", rename = #v, default)]
+                    #[serde(skip_serializing_if = "Option::is_none", rename = #v, default #( , alias = #aliases )*)]
                     pub #name
                 }
             } else {
@@ -2096,6 +2868,24 @@ impl<'a> GenerateTypes<'a> {
                 .collect_vec()
         };
 
+        if serde_skip {
+            if let Some(body) = self.spec.overlay.type_body(&t.name) {
+                return Ok(body);
+            }
+        }
+
+        // Unknown fields are only rejected on the main struct, the companion type is used
+        // for array encodings where field names don't appear
+        let strict = if serde_skip {
+            let overlay = self.spec.overlay.type_attributes(&t.name);
+            quote! {
+                #[cfg_attr(feature = "strict-serde", serde(deny_unknown_fields))]
+                #overlay
+            }
+        } else {
+            quote!()
+        };
+
         let struct_comment = if serde_skip {
             t.description.concat().comment()
         } else {
@@ -2110,6 +2900,7 @@ impl<'a> GenerateTypes<'a> {
         let res = quote! {
             #struct_comment
             #[derive(Serialize, Deserialize, Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+            #strict
             pub struct #typename {
                 #(
                     #fieldnames: #fieldtypes
@@ -2133,4 +2924,32 @@ mod test {
         let types = GenerateTypes::new(Arc::clone(&spec), Arc::new(RwLock::new(HashMap::new())));
         assert!(!types.get_common_methods(t).is_empty());
     }
+
+    #[test]
+    fn reduced_types() {
+        let json = std::fs::read_to_string("../telegram-bot-api-spec/api.json").unwrap();
+        let spec: Spec = serde_json::from_str(&json).unwrap();
+        let types = GenerateTypes::new(Arc::new(spec), Arc::new(RwLock::new(HashMap::new())));
+        let tokens = types.generate_reduced_types().unwrap().to_string();
+        assert!(tokens.contains("From < ChatFullInfo > for Chat"));
+    }
+
+    #[test]
+    fn discriminated_enums() {
+        let json = std::fs::read_to_string("../telegram-bot-api-spec/api.json").unwrap();
+        let spec: Spec = serde_json::from_str(&json).unwrap();
+        for (name, field) in [
+            ("BotCommandScope", "type"),
+            ("MessageOrigin", "type"),
+            ("MenuButton", "type"),
+            ("ChatMember", "status"),
+        ] {
+            let discriminant = spec.get_discriminant(spec.get_type(name).unwrap()).unwrap();
+            assert_eq!(discriminant.field, field, "{}", name);
+        }
+        let types = GenerateTypes::new(Arc::new(spec), Arc::new(RwLock::new(HashMap::new())));
+        let tokens = types.generate_types().unwrap();
+        assert!(tokens.contains("Deserialize < 'de > for BotCommandScope"));
+        assert!(tokens.contains("fn tagged_message_origin"));
+    }
 }
@@ -1,9 +1,17 @@
-use crate::schema::{Field, Spec, Type};
+use crate::schema::{Field, Method, Spec, Type};
 use crate::{naming::get_type_name_str, ARRAY_OF, INPUT_FILE, MULTITYPE_ENUM_PREFIX};
 use anyhow::Result;
+use lazy_static::lazy_static;
 use quote::{format_ident, quote, ToTokens, __private::TokenStream};
+use regex::Regex;
 use std::sync::Arc;
 
+lazy_static! {
+    static ref REGEX_LENGTH: Regex = Regex::new(r"(\d+)-(\d+) (characters|bytes)").unwrap();
+    static ref REGEX_ITEMS: Regex = Regex::new(r"(\d+)-(\d+) [a-z ]*?(items|options)").unwrap();
+    static ref REGEX_RANGE: Regex = Regex::new(r"[Vv]alues between (\d+)-(\d+)").unwrap();
+}
+
 pub(crate) trait ChooserFn {
     fn cb(&self, types: &TypeChooserOpts<'_, '_>) -> String;
 }
@@ -32,9 +40,159 @@ where
     }
 }
 
+/// Method parameters documented as strings that only accept a fixed set of values,
+/// along with the generated enum used in their place
+static TYPED_STR_FIELDS: &[(&str, &str, &str)] = &[
+    ("sendChatAction", "action", "ChatAction"),
+    ("getFile", "file_id", "FileId"),
+    ("deleteStickerFromSet", "sticker", "FileId"),
+    ("setStickerPositionInSet", "sticker", "FileId"),
+    ("setStickerEmojiList", "sticker", "FileId"),
+    ("setStickerKeywords", "sticker", "FileId"),
+    ("setStickerMaskPosition", "sticker", "FileId"),
+    ("replaceStickerInSet", "old_sticker", "FileId"),
+];
+
+/// Get the generated enum type to use for a method parameter if the parameter is a string with
+/// a fixed set of values
+pub(crate) fn typed_str_field(method: &Method, f: &Field) -> Option<TokenStream> {
+    TYPED_STR_FIELDS
+        .iter()
+        .find(|(m, name, _)| *m == method.name && *name == f.name)
+        .map(|(_, _, t)| format_ident!("{}", t).to_token_stream())
+}
+
+/// Newtypes generated for identifier fields of api types. Each entry is the newtype,
+/// the spec type it wraps, and the suffix of the field names using it. Message ids use
+/// MsgId since MessageId is the name of an api type
+pub(crate) static ID_TYPES: &[(&str, &str, &str)] = &[
+    ("FileId", "String", "file_id"),
+    ("FileUniqueId", "String", "file_unique_id"),
+    ("UserId", "Integer", "user_id"),
+    ("MsgId", "Integer", "message_id"),
+    ("MessageThreadId", "Integer", "message_thread_id"),
+    ("UpdateId", "Integer", "update_id"),
+];
+
+/// Enum generated for a string field: the enum, the type or method documenting the
+/// values, the field, and variant names for values that aren't words
+pub(crate) type StrEnum = (
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static [(&'static str, &'static str)],
+);
+
+/// Enums generated for string fields documented as one of a fixed set of quoted values
+pub(crate) static STR_ENUMS: &[StrEnum] = &[
+    ("ChatType", "Chat", "type", &[]),
+    ("MessageEntityType", "MessageEntity", "type", &[]),
+    ("StickerType", "Sticker", "type", &[]),
+    (
+        "DiceEmoji",
+        "sendDice",
+        "emoji",
+        &[
+            ("🎲", "Dice"),
+            ("🎯", "Darts"),
+            ("🏀", "Basketball"),
+            ("⚽", "Football"),
+            ("🎳", "Bowling"),
+            ("🎰", "SlotMachine"),
+        ],
+    ),
+];
+
+/// Typed getters for string fields using an enum from STR_ENUMS, as the type, the field,
+/// the getter, and the enum
+pub(crate) static STR_ENUM_GETTERS: &[(&str, &str, &str, &str)] = &[
+    ("Chat", "type", "get_chat_type", "ChatType"),
+    ("ChatFullInfo", "type", "get_chat_type", "ChatType"),
+    (
+        "MessageEntity",
+        "type",
+        "get_entity_type",
+        "MessageEntityType",
+    ),
+    ("Sticker", "type", "get_sticker_type", "StickerType"),
+    ("Dice", "emoji", "get_dice_emoji", "DiceEmoji"),
+];
+
+/// Types that are a reduced view of a larger type, as the full type and the reduced
+/// type. Every field of the reduced type must also be in the full type
+pub(crate) static REDUCED_TYPES: &[(&str, &str)] = &[("ChatFullInfo", "Chat")];
+
+/// Enums over a full type and reduced versions of it, such as a message that may no
+/// longer be accessible
+pub(crate) static REDUCED_ENUMS: &[&str] = &["MaybeInaccessibleMessage"];
+
+/// Identifier fields not matching the suffix of their newtype, as the parent type, the
+/// field, and the newtype
+static ID_FIELDS: &[(&str, &str, &str)] = &[("User", "id", "UserId")];
+
+/// Get the newtype used for an identifier field, if any
+pub(crate) fn id_type(parent: Option<&str>, name: &str, types: &[String]) -> Option<&'static str> {
+    if types.len() != 1 {
+        return None;
+    }
+    let special = ID_FIELDS
+        .iter()
+        .find(|(p, field, _)| parent == Some(*p) && name == *field)
+        .map(|(_, _, t)| *t);
+    special.or_else(|| {
+        ID_TYPES
+            .iter()
+            .find(|(_, wrapped, suffix)| {
+                types[0] == *wrapped && (name == *suffix || name.ends_with(&format!("_{}", suffix)))
+            })
+            .map(|(t, _, _)| *t)
+    })
+}
+
+/// A length or range restriction documented in the description of a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Validation {
+    /// String length in characters
+    Chars(usize, usize),
+    /// String length in bytes
+    Bytes(usize, usize),
+    /// Number of items in an array
+    Items(usize, usize),
+    /// Range of an integer
+    Range(i64, i64),
+}
+
+/// Parse the bounds documented in a field's description, if any
+pub(crate) fn get_validation(f: &Field) -> Option<Validation> {
+    if f.types.len() != 1 || is_inputfile(f) {
+        return None;
+    }
+    let description = f.description.as_deref()?;
+    if is_str_field(f) {
+        let c = REGEX_LENGTH.captures(description)?;
+        let (min, max) = (c[1].parse().ok()?, c[2].parse().ok()?);
+        if &c[3] == "bytes" {
+            Some(Validation::Bytes(min, max))
+        } else {
+            Some(Validation::Chars(min, max))
+        }
+    } else if is_array(&f.types[0]) == 1 {
+        let c = REGEX_ITEMS.captures(description)?;
+        Some(Validation::Items(c[1].parse().ok()?, c[2].parse().ok()?))
+    } else if f.types[0] == "Integer" {
+        let c = REGEX_RANGE.captures(description)?;
+        Some(Validation::Range(c[1].parse().ok()?, c[2].parse().ok()?))
+    } else {
+        None
+    }
+}
+
 /// Check if a field should be represented as a &str=
 pub(crate) fn is_str_field(f: &Field) -> bool {
-    f.types[0] == "String" && !is_inputfile(f) && f.name != "media"
+    f.types[0] == "String"
+        && !is_inputfile(f)
+        && f.name != "media"
+        && id_type(None, &f.name, &f.types).is_none()
 }
 
 /// Check if a field is an "InputFile" for special treatment
@@ -148,6 +306,7 @@ pub(crate) fn is_chatid(types: &[String]) -> bool {
 
 pub(crate) struct TypeChooserOpts<'a, 'b> {
     pub(crate) types: &'a [String],
+    pub(crate) parent: Option<&'a str>,
     pub(crate) is_media: bool,
     pub(crate) nested: usize,
     pub(crate) name: &'b str,
@@ -318,6 +477,7 @@ impl<'a> ChooseType<'a> {
         let json = is_json_types_internal(&[type_without_array(&types[0])]);
         let opts = TypeChooserOpts {
             types,
+            parent: parent.map(|t| t.name.as_str()),
             is_media,
             nested,
             name: name.as_ref(),
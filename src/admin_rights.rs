@@ -0,0 +1,272 @@
+use std::collections::BTreeSet;
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_methods::{
+    GetMyDefaultAdministratorRightsParams, SetMyDefaultAdministratorRightsParams,
+};
+use crate::gen_types::ChatAdministratorRights;
+
+/// A single administrator right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum AdminRight {
+    /// Hide the administrator in the member list
+    Anonymous,
+    /// Access the event log, boost list, hidden members and other admin-only info
+    ManageChat,
+    /// Delete messages of other users
+    DeleteMessages,
+    /// Manage video chats
+    ManageVideoChats,
+    /// Restrict, ban or unban members
+    RestrictMembers,
+    /// Add administrators with a subset of their own rights
+    PromoteMembers,
+    /// Change the chat title, photo and other settings
+    ChangeInfo,
+    /// Invite new users
+    InviteUsers,
+    /// Post stories to the chat
+    PostStories,
+    /// Edit stories posted by other users
+    EditStories,
+    /// Delete stories posted by other users
+    DeleteStories,
+    /// Post messages in channels
+    PostMessages,
+    /// Edit messages of other users and pin messages in channels
+    EditMessages,
+    /// Pin messages in groups and supergroups
+    PinMessages,
+    /// Create, rename, close and reopen forum topics
+    ManageTopics,
+}
+
+impl AdminRight {
+    /// Every right
+    pub const ALL: [AdminRight; 15] = [
+        AdminRight::Anonymous,
+        AdminRight::ManageChat,
+        AdminRight::DeleteMessages,
+        AdminRight::ManageVideoChats,
+        AdminRight::RestrictMembers,
+        AdminRight::PromoteMembers,
+        AdminRight::ChangeInfo,
+        AdminRight::InviteUsers,
+        AdminRight::PostStories,
+        AdminRight::EditStories,
+        AdminRight::DeleteStories,
+        AdminRight::PostMessages,
+        AdminRight::EditMessages,
+        AdminRight::PinMessages,
+        AdminRight::ManageTopics,
+    ];
+
+    /// Get the name of the field of ChatAdministratorRights for this right
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            AdminRight::Anonymous => "is_anonymous",
+            AdminRight::ManageChat => "can_manage_chat",
+            AdminRight::DeleteMessages => "can_delete_messages",
+            AdminRight::ManageVideoChats => "can_manage_video_chats",
+            AdminRight::RestrictMembers => "can_restrict_members",
+            AdminRight::PromoteMembers => "can_promote_members",
+            AdminRight::ChangeInfo => "can_change_info",
+            AdminRight::InviteUsers => "can_invite_users",
+            AdminRight::PostStories => "can_post_stories",
+            AdminRight::EditStories => "can_edit_stories",
+            AdminRight::DeleteStories => "can_delete_stories",
+            AdminRight::PostMessages => "can_post_messages",
+            AdminRight::EditMessages => "can_edit_messages",
+            AdminRight::PinMessages => "can_pin_messages",
+            AdminRight::ManageTopics => "can_manage_topics",
+        }
+    }
+
+    /// Check if rights include this right
+    fn granted_in(&self, rights: &ChatAdministratorRights) -> bool {
+        match self {
+            AdminRight::Anonymous => rights.get_is_anonymous(),
+            AdminRight::ManageChat => rights.get_can_manage_chat(),
+            AdminRight::DeleteMessages => rights.get_can_delete_messages(),
+            AdminRight::ManageVideoChats => rights.get_can_manage_video_chats(),
+            AdminRight::RestrictMembers => rights.get_can_restrict_members(),
+            AdminRight::PromoteMembers => rights.get_can_promote_members(),
+            AdminRight::ChangeInfo => rights.get_can_change_info(),
+            AdminRight::InviteUsers => rights.get_can_invite_users(),
+            AdminRight::PostStories => rights.get_can_post_stories(),
+            AdminRight::EditStories => rights.get_can_edit_stories(),
+            AdminRight::DeleteStories => rights.get_can_delete_stories(),
+            AdminRight::PostMessages => rights.get_can_post_messages().unwrap_or(false),
+            AdminRight::EditMessages => rights.get_can_edit_messages().unwrap_or(false),
+            AdminRight::PinMessages => rights.get_can_pin_messages().unwrap_or(false),
+            AdminRight::ManageTopics => rights.get_can_manage_topics().unwrap_or(false),
+        }
+    }
+}
+
+/// A set of administrator rights, convertible to and from ChatAdministratorRights
+///
+/// ```no_run
+/// # use botapi::{bot::BotBuilder, admin_rights::{AdminRight, AdminRights}};
+/// # tokio_test::block_on(async {
+/// # let bot = BotBuilder::new("sometoken").unwrap().build();
+/// let rights = AdminRights::moderator().allow(AdminRight::PromoteMembers);
+/// bot.sync_default_rights(&rights, false).await.unwrap();
+/// # })
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AdminRights(BTreeSet<AdminRight>);
+
+impl AdminRights {
+    /// Start from no rights
+    pub fn builder() -> Self {
+        Self::default()
+    }
+
+    /// Rights for moderating a group: managing the chat, deleting messages, restricting
+    /// members, inviting users, pinning messages and managing topics
+    pub fn moderator() -> Self {
+        Self::builder()
+            .allow(AdminRight::ManageChat)
+            .allow(AdminRight::DeleteMessages)
+            .allow(AdminRight::RestrictMembers)
+            .allow(AdminRight::InviteUsers)
+            .allow(AdminRight::PinMessages)
+            .allow(AdminRight::ManageTopics)
+    }
+
+    /// Every right except staying anonymous
+    pub fn full() -> Self {
+        AdminRight::ALL
+            .into_iter()
+            .filter(|r| *r != AdminRight::Anonymous)
+            .fold(Self::builder(), |rights, r| rights.allow(r))
+    }
+
+    /// Add a right
+    pub fn allow(mut self, right: AdminRight) -> Self {
+        self.0.insert(right);
+        self
+    }
+
+    /// Remove a right
+    pub fn deny(mut self, right: AdminRight) -> Self {
+        self.0.remove(&right);
+        self
+    }
+
+    /// Check if a right is included
+    pub fn has(&self, right: AdminRight) -> bool {
+        self.0.contains(&right)
+    }
+
+    /// Check if every right in other is included
+    pub fn contains(&self, other: &AdminRights) -> bool {
+        self.0.is_superset(&other.0)
+    }
+
+    /// Iterate over the included rights
+    pub fn iter(&self) -> impl Iterator<Item = AdminRight> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// Convert to the api type, with every right set to true or false
+    pub fn build(&self) -> ChatAdministratorRights {
+        let mut rights = ChatAdministratorRights::new(
+            self.has(AdminRight::Anonymous),
+            self.has(AdminRight::ManageChat),
+            self.has(AdminRight::DeleteMessages),
+            self.has(AdminRight::ManageVideoChats),
+            self.has(AdminRight::RestrictMembers),
+            self.has(AdminRight::PromoteMembers),
+            self.has(AdminRight::ChangeInfo),
+            self.has(AdminRight::InviteUsers),
+            self.has(AdminRight::PostStories),
+            self.has(AdminRight::EditStories),
+            self.has(AdminRight::DeleteStories),
+        );
+        rights.set_can_post_messages(Some(self.has(AdminRight::PostMessages)));
+        rights.set_can_edit_messages(Some(self.has(AdminRight::EditMessages)));
+        rights.set_can_pin_messages(Some(self.has(AdminRight::PinMessages)));
+        rights.set_can_manage_topics(Some(self.has(AdminRight::ManageTopics)));
+        rights
+    }
+}
+
+impl From<&ChatAdministratorRights> for AdminRights {
+    fn from(value: &ChatAdministratorRights) -> Self {
+        AdminRight::ALL
+            .into_iter()
+            .filter(|r| r.granted_in(value))
+            .fold(Self::builder(), |rights, r| rights.allow(r))
+    }
+}
+
+impl From<AdminRights> for ChatAdministratorRights {
+    fn from(value: AdminRights) -> Self {
+        value.build()
+    }
+}
+
+impl Bot {
+    /// Get the rights suggested when the bot is added as an administrator to groups, or
+    /// to channels if for_channels is set
+    pub async fn get_default_rights(&self, for_channels: bool) -> BotResult<AdminRights> {
+        let rights = self
+            .call(GetMyDefaultAdministratorRightsParams {
+                for_channels: Some(for_channels),
+            })
+            .await?;
+        Ok(AdminRights::from(&rights))
+    }
+
+    /// Set the rights suggested when the bot is added as an administrator to groups, or
+    /// to channels if for_channels is set. The current rights are fetched first and
+    /// nothing is set if they already match. Returns true if the rights were changed
+    pub async fn sync_default_rights(
+        &self,
+        rights: &AdminRights,
+        for_channels: bool,
+    ) -> BotResult<bool> {
+        if self.get_default_rights(for_channels).await? == *rights {
+            return Ok(false);
+        }
+        self.call(SetMyDefaultAdministratorRightsParams {
+            rights: Some(rights.build()),
+            for_channels: Some(for_channels),
+        })
+        .await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+
+    #[tokio::test]
+    async fn sync_rights() {
+        let moderator = AdminRights::moderator();
+        assert_eq!(AdminRights::from(&moderator.build()), moderator);
+        assert!(moderator.build().get_can_manage_chat());
+        assert_eq!(moderator.build().get_can_post_messages(), Some(false));
+        assert!(AdminRights::full().contains(&moderator));
+        assert!(!AdminRights::full().has(AdminRight::Anonymous));
+
+        let test = TestBot::builder()
+            .respond("getMyDefaultAdministratorRights", moderator.build())
+            .respond("getMyDefaultAdministratorRights", moderator.build())
+            .build()
+            .await
+            .unwrap();
+        let bot = test.get_bot();
+        assert!(!bot.sync_default_rights(&moderator, false).await.unwrap());
+        assert!(bot
+            .sync_default_rights(&AdminRights::full(), false)
+            .await
+            .unwrap());
+        assert_eq!(test.sent("setMyDefaultAdministratorRights").len(), 1);
+    }
+}
@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{Flow, Layer};
+use crate::ext::now;
+use crate::gen_types::{ChatHandle, ChatMember, ChatMemberUpdated, MsgId, UpdateExt, UserId};
+
+/// Methods reported as outgoing audit events
+const AUDITED_METHODS: &[&str] = &[
+    "banChatMember",
+    "unbanChatMember",
+    "restrictChatMember",
+    "promoteChatMember",
+    "banChatSenderChat",
+    "unbanChatSenderChat",
+    "deleteMessage",
+    "deleteMessages",
+];
+
+/// Kind of moderation action recorded in an AuditEvent
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Ban,
+    Unban,
+    Restrict,
+    Promote,
+    Demote,
+    BanSenderChat,
+    UnbanSenderChat,
+    Delete,
+}
+
+/// Where an AuditEvent was observed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditSource {
+    /// A chat_member update, the action may have been taken by any admin
+    Update,
+    /// A successful call made by this bot
+    Outgoing,
+}
+
+/// A single moderation action. Serializes to json for storage in compliance logs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    action: AuditAction,
+    source: AuditSource,
+    chat: ChatHandle,
+    actor: Option<UserId>,
+    target_user: Option<UserId>,
+    target_chat: Option<i64>,
+    message_ids: Vec<MsgId>,
+    until_date: Option<i64>,
+    date: i64,
+}
+
+impl AuditEvent {
+    fn new(action: AuditAction, source: AuditSource, chat: ChatHandle, date: i64) -> Self {
+        Self {
+            action,
+            source,
+            chat,
+            actor: None,
+            target_user: None,
+            target_chat: None,
+            message_ids: Vec::new(),
+            until_date: None,
+            date,
+        }
+    }
+
+    /// Build an event from a chat_member update, if the change is a moderation action
+    fn from_update(update: &ChatMemberUpdated) -> Option<Self> {
+        let old = update.get_old_chat_member();
+        let new = update.get_new_chat_member();
+        let (action, until_date) = match (old, new) {
+            (ChatMember::ChatMemberBanned(_), ChatMember::ChatMemberBanned(_)) => return None,
+            (_, ChatMember::ChatMemberBanned(b)) => (AuditAction::Ban, Some(b.get_until_date())),
+            (ChatMember::ChatMemberBanned(_), _) => (AuditAction::Unban, None),
+            (_, ChatMember::ChatMemberRestricted(r)) => {
+                (AuditAction::Restrict, Some(r.get_until_date()))
+            }
+            (ChatMember::ChatMemberAdministrator(_), ChatMember::ChatMemberAdministrator(_)) => {
+                (AuditAction::Promote, None)
+            }
+            (ChatMember::ChatMemberAdministrator(_), _) => (AuditAction::Demote, None),
+            (_, ChatMember::ChatMemberAdministrator(_)) => (AuditAction::Promote, None),
+            _ => return None,
+        };
+        let chat = ChatHandle::ChatId(update.get_chat().get_id());
+        let mut event = Self::new(action, AuditSource::Update, chat, update.get_date());
+        event.actor = Some(update.get_from().get_id());
+        event.target_user = Some(new.get_user().get_id());
+        event.until_date = until_date;
+        Some(event)
+    }
+
+    /// Build an event from the parameters of a successful call to an audited method
+    fn from_call(method: &str, params: &serde_json::Value) -> Option<Self> {
+        let action = match method {
+            "banChatMember" => AuditAction::Ban,
+            "unbanChatMember" => AuditAction::Unban,
+            "restrictChatMember" => AuditAction::Restrict,
+            "promoteChatMember" => AuditAction::Promote,
+            "banChatSenderChat" => AuditAction::BanSenderChat,
+            "unbanChatSenderChat" => AuditAction::UnbanSenderChat,
+            "deleteMessage" | "deleteMessages" => AuditAction::Delete,
+            _ => return None,
+        };
+        let field = |name: &str| params.get(name).cloned();
+        let chat = serde_json::from_value(field("chat_id")?).ok()?;
+        let mut event = Self::new(action, AuditSource::Outgoing, chat, now());
+        event.target_user = field("user_id").and_then(|v| serde_json::from_value(v).ok());
+        event.target_chat = field("sender_chat_id").and_then(|v| v.as_i64());
+        event.until_date = field("until_date").and_then(|v| v.as_i64());
+        event.message_ids = match field("message_ids") {
+            Some(ids) => serde_json::from_value(ids).unwrap_or_default(),
+            None => field("message_id")
+                .and_then(|v| serde_json::from_value(v).ok())
+                .into_iter()
+                .collect(),
+        };
+        // a promotion without any rights demotes the admin
+        if action == AuditAction::Promote
+            && !params
+                .as_object()
+                .into_iter()
+                .flatten()
+                .any(|(k, v)| k.starts_with("can_") && v.as_bool() == Some(true))
+        {
+            event.action = AuditAction::Demote;
+        }
+        Some(event)
+    }
+
+    /// Get the kind of action taken
+    pub fn get_action(&self) -> AuditAction {
+        self.action
+    }
+
+    /// Get whether this event was observed in an update or made by this bot
+    pub fn get_source(&self) -> AuditSource {
+        self.source
+    }
+
+    /// Get the chat the action was taken in
+    pub fn get_chat(&self) -> &'_ ChatHandle {
+        &self.chat
+    }
+
+    /// Get the admin who took the action. None for outgoing calls, where the actor is
+    /// this bot
+    pub fn get_actor(&self) -> Option<UserId> {
+        self.actor
+    }
+
+    /// Get the user the action was taken against
+    pub fn get_target_user(&self) -> Option<UserId> {
+        self.target_user
+    }
+
+    /// Get the sender chat the action was taken against
+    pub fn get_target_chat(&self) -> Option<i64> {
+        self.target_chat
+    }
+
+    /// Get the ids of deleted messages
+    pub fn get_message_ids(&self) -> &'_ [MsgId] {
+        &self.message_ids
+    }
+
+    /// Get the unix time a ban or restriction ends, 0 or None if forever
+    pub fn get_until_date(&self) -> Option<i64> {
+        self.until_date
+    }
+
+    /// Get the unix time the action was taken
+    pub fn get_date(&self) -> i64 {
+        self.date
+    }
+}
+
+/// Delivers AuditEvents for moderation actions to a callback. Use as a dispatcher layer
+/// to record chat_member updates and pass to BotBuilder::audit_log to record this bot's
+/// own moderation calls. chat_member updates must be requested in allowed_updates
+#[derive(Clone)]
+pub struct AuditLog(Arc<dyn Fn(AuditEvent) + Send + Sync>);
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AuditLog")
+    }
+}
+
+impl AuditLog {
+    /// Call a function for every event
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(AuditEvent) + Send + Sync + 'static,
+    {
+        Self(Arc::new(callback))
+    }
+
+    /// Send every event to a channel. Events are dropped if the receiver is closed
+    pub fn channel(sender: UnboundedSender<AuditEvent>) -> Self {
+        Self::new(move |event| {
+            if sender.send(event).is_err() {
+                log::debug!("audit log receiver dropped");
+            }
+        })
+    }
+
+    /// Record a successful call if the method is audited
+    pub(crate) fn record_call<T: Serialize>(&self, method: &str, body: &T) {
+        if !AUDITED_METHODS.contains(&method) {
+            return;
+        }
+        let Ok(params) = serde_json::to_value(body) else {
+            return;
+        };
+        if let Some(event) = AuditEvent::from_call(method, &params) {
+            (self.0)(event);
+        }
+    }
+}
+
+impl Layer for AuditLog {
+    fn call(&self, _: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        if let UpdateExt::ChatMember(ref member) = update {
+            if let Some(event) = AuditEvent::from_update(member) {
+                (self.0)(event);
+            }
+        }
+        Box::pin(async { Ok(Flow::Continue) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn outgoing_calls() {
+        let ban = AuditEvent::from_call(
+            "banChatMember",
+            &json!({"chat_id": -100, "user_id": 5, "until_date": 0}),
+        )
+        .unwrap();
+        assert_eq!(ban.get_action(), AuditAction::Ban);
+        assert_eq!(ban.get_chat(), &ChatHandle::ChatId(-100));
+        assert_eq!(ban.get_target_user(), Some(UserId::from(5)));
+        let delete = AuditEvent::from_call(
+            "deleteMessages",
+            &json!({"chat_id": "@chat", "message_ids": [1, 2]}),
+        )
+        .unwrap();
+        assert_eq!(delete.get_message_ids(), &[MsgId::from(1), MsgId::from(2)]);
+        let demote = AuditEvent::from_call(
+            "promoteChatMember",
+            &json!({"chat_id": 1, "user_id": 2, "can_pin_messages": false}),
+        )
+        .unwrap();
+        assert_eq!(demote.get_action(), AuditAction::Demote);
+        assert!(AuditEvent::from_call("sendMessage", &json!({"chat_id": 1})).is_none());
+    }
+}
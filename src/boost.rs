@@ -0,0 +1,113 @@
+use std::future::Future;
+
+use serde::Serialize;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::Dispatcher;
+use crate::ext::now;
+use crate::gen_types::{
+    ChatBoost, ChatBoostRemoved, ChatBoostUpdated, ChatHandle, GiveawayWinners, Message, UpdateExt,
+    UserChatBoosts, UserId,
+};
+
+impl ChatBoost {
+    /// Check if this boost has not expired yet
+    pub fn is_active(&self) -> bool {
+        self.get_expiration_date() > now()
+    }
+}
+
+impl UserChatBoosts {
+    /// Get the number of boosts that have not expired yet
+    pub fn active_count(&self) -> usize {
+        self.get_boosts().iter().filter(|b| b.is_active()).count()
+    }
+}
+
+impl GiveawayWinners {
+    /// Get the ids of the users who won the giveaway
+    pub fn winner_ids(&self) -> Vec<UserId> {
+        self.get_winners().iter().map(|u| u.get_id()).collect()
+    }
+}
+
+impl Message {
+    /// Check if this message is a giveaway or one of the service messages about a
+    /// giveaway being created, completed or having winners
+    pub fn is_giveaway(&self) -> bool {
+        self.get_giveaway().is_some()
+            || self.get_giveaway_created().is_some()
+            || self.get_giveaway_completed().is_some()
+            || self.get_giveaway_winners().is_some()
+    }
+}
+
+impl Bot {
+    /// Get the number of active boosts a user has added to a chat. Requires the bot to be
+    /// an administrator of the chat. Useful to gate features behind boosting
+    pub async fn count_user_boosts<V>(&self, chat_id: V, user_id: UserId) -> BotResult<usize>
+    where
+        V: Into<ChatHandle> + Serialize,
+    {
+        let boosts = self.get_user_chat_boosts(chat_id, user_id).await?;
+        Ok(boosts.active_count())
+    }
+
+    /// Check if a user has at least `min` active boosts in a chat
+    pub async fn has_boosted<V>(&self, chat_id: V, user_id: UserId, min: usize) -> BotResult<bool>
+    where
+        V: Into<ChatHandle> + Serialize,
+    {
+        Ok(self.count_user_boosts(chat_id, user_id).await? >= min)
+    }
+}
+
+impl Dispatcher {
+    /// Add a handler only called for chat_boost updates
+    pub fn on_chat_boost<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Bot, ChatBoostUpdated) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.typed_handler(
+            "on_chat_boost",
+            |update| match update {
+                UpdateExt::ChatBoost(boost) => Some(boost),
+                _ => None,
+            },
+            handler,
+        )
+    }
+
+    /// Add a handler only called for removed_chat_boost updates
+    pub fn on_removed_chat_boost<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Bot, ChatBoostRemoved) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.typed_handler(
+            "on_removed_chat_boost",
+            |update| match update {
+                UpdateExt::RemovedChatBoost(boost) => Some(boost),
+                _ => None,
+            },
+            handler,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_boosts() {
+        let mut active = ChatBoost::default();
+        active.set_expiration_date(now() + 3600);
+        let mut expired = ChatBoost::default();
+        expired.set_expiration_date(now() - 3600);
+        let mut boosts = UserChatBoosts::default();
+        boosts.set_boosts(vec![active, expired]);
+        assert_eq!(boosts.active_count(), 1);
+    }
+}
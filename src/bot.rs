@@ -1,10 +1,36 @@
-use std::{error::Error, sync::Arc, time::Duration};
+use std::{
+    error::Error,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crate::gen_types::ResponseParameters;
+use crate::audit::AuditLog;
+use crate::cache::ChatCache;
+use crate::caption::CaptionOverflow;
+use crate::capture::{Capture, CaptureSink};
+use crate::circuit::CircuitBreaker;
+use crate::classify::ErrorClassifier;
+use crate::ephemeral::EphemeralRegistry;
+use crate::errors::ApiErrorKind;
+use crate::failover::{is_relay_failure, Endpoints};
+use crate::format::ParseMode;
+use crate::gen_types::{FileData, Message, ResponseParameters};
+use crate::i18n::Translator;
+use crate::keyboard::KeyboardCache;
+use crate::options::{RequestOptions, WithOptions};
+#[cfg(feature = "otel")]
+use crate::otel::traced_call;
+use crate::pin::PinRegistry;
+use crate::throttle::{ChatKey, ThrottlePolicy};
+use crate::unreachable::UnreachableRegistry;
+#[cfg(feature = "hash")]
+use crate::upload_cache::UploadCache;
 use anyhow::Result;
 
+use futures_util::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
 use reqwest::multipart::Form;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 pub use reqwest::multipart::Part;
 //static TELEGRAM_API: &str = "https://api.telegram.org";
@@ -22,6 +48,32 @@ pub struct Response {
     pub floods: Option<Vec<ResponseFlood>>,
 }
 
+/// Response with the result deserialized straight into its final type, skipping the
+/// intermediate serde_json::Value
+#[derive(Deserialize)]
+struct TypedResponse<R> {
+    ok: bool,
+    result: Option<R>,
+    error_code: Option<i64>,
+    description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+impl<R> TypedResponse<R> {
+    /// Split into a Response without a result and the result
+    fn split(self) -> (Response, Option<R>) {
+        let resp = Response {
+            ok: self.ok,
+            result: None,
+            error_code: self.error_code,
+            description: self.description,
+            parameters: self.parameters,
+            floods: None,
+        };
+        (resp, self.result)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResponseFlood {
     pub ok: bool,
@@ -58,56 +110,378 @@ impl Response {
 /// Default result type retruned by API calls.
 pub type BotResult<T> = Result<T, ApiError>;
 
+/// Wrapper for secret values like the bot token. The wrapped value is never printed
+/// by Debug or Display
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Wrap a secret value
+    pub fn new<T>(secret: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self(secret.into())
+    }
+
+    /// Get the wrapped secret value. Take care not to log the result
+    pub fn expose(&self) -> &'_ str {
+        &self.0
+    }
+
+    /// Replace any occurrence of this secret in a string
+    pub fn scrub(&self, s: &str) -> String {
+        if self.0.is_empty() {
+            s.to_owned()
+        } else {
+            s.replace(&self.0, "[REDACTED]")
+        }
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+impl std::fmt::Display for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
 /// Nifty telegram bot api wrapper autogenerated from online documentation
 #[derive(Debug)]
 struct BotState {
     client: reqwest::Client,
-    token: String,
+    token: SecretString,
     api: String,
     auto_wait: bool,
+    cache: Option<ChatCache>,
+    throttle: Option<Arc<dyn ThrottlePolicy>>,
+    translator: Option<Arc<dyn Translator>>,
+    caption_overflow: CaptionOverflow,
+    auto_escape: bool,
+    truncate_overflow: bool,
+    keyboard_cache: Option<KeyboardCache>,
+    ignore_not_modified: bool,
+    error_classifier: Option<ErrorClassifier>,
+    default_parse_mode: Option<ParseMode>,
+    default_disable_notification: Option<bool>,
+    default_protect_content: Option<bool>,
+    circuit_breaker: Option<CircuitBreaker>,
+    debug_capture: Option<CaptureSink>,
+    test_environment: bool,
+    ephemeral: EphemeralRegistry,
+    unreachable: UnreachableRegistry,
+    pins: PinRegistry,
+    audit_log: Option<AuditLog>,
+    #[cfg(feature = "hash")]
+    upload_cache: Option<UploadCache>,
+    headers: HeaderMap,
+    endpoints: Option<Endpoints>,
+}
+
+/// User-Agent sent with every request unless changed with BotBuilder::user_agent
+pub const DEFAULT_USER_AGENT: &str = concat!("botapi-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Headers sent with every request by default
+fn default_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
+    headers
+}
+
+/// Get the http client settings every bot starts with
+fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::ClientBuilder::new().https_only(true)
+}
+
+/// Hash of bytes being uploaded along with the method and field uploading them,
+/// remembered with the returned file_id once sent
+#[cfg(feature = "hash")]
+pub(crate) type UploadKey = Option<(crate::hash::ContentHash, &'static str, &'static str)>;
+
+/// Uploads are not cached without the hash feature
+#[cfg(not(feature = "hash"))]
+pub(crate) type UploadKey = ();
+
+/// Api calls are not traced without the otel feature
+#[cfg(not(feature = "otel"))]
+async fn traced_call<F>(_: &str, call: F) -> BotResult<Response>
+where
+    F: std::future::Future<Output = BotResult<Response>>,
+{
+    call.await
+}
+
+/// Run an api call, cancelling it once the deadline of the current RequestOptions
+/// passes. Cancelling drops the reqwest future, which closes the connection instead of
+/// leaving the request running in the background. Timeouts from the client are reported
+/// the same way
+async fn with_deadline<F>(call: F) -> BotResult<Response>
+where
+    F: std::future::Future<Output = BotResult<Response>>,
+{
+    let start = Instant::now();
+    let res = match RequestOptions::current().get_deadline() {
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), call)
+            .await
+            .unwrap_or_else(|_| Err(ApiError::timeout(start.elapsed()))),
+        None => call.await,
+    };
+    res.map_err(|err| {
+        if err.is_client_timeout() {
+            ApiError::timeout(start.elapsed())
+        } else {
+            err
+        }
+    })
+}
+
+/// Maximum length of a single parameter value in a [`RequestContext`] summary
+const CONTEXT_VALUE_MAX: usize = 64;
+
+/// Summary of the api call that produced an error. Parameter values are truncated
+/// and the bot token is never included, so this is safe to log
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    method: String,
+    params: String,
+}
+
+impl RequestContext {
+    /// Summarize the parameters of a method call
+    pub(crate) fn new<T>(method: &str, params: &T) -> Self
+    where
+        T: Serialize,
+    {
+        let params = match serde_json::to_value(params) {
+            Ok(serde_json::Value::Object(map)) => map
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| {
+                    let v = match v {
+                        serde_json::Value::String(s) => s.clone(),
+                        v => v.to_string(),
+                    };
+                    format!("{}={}", k, truncate(&v, CONTEXT_VALUE_MAX))
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+            _ => String::new(),
+        };
+        Self {
+            method: method.to_owned(),
+            params,
+        }
+    }
+
+    /// Get the name of the telegram api method that was called
+    pub fn get_method(&self) -> &'_ str {
+        &self.method
+    }
+
+    /// Get the redacted summary of the parameters passed to the method
+    pub fn get_params(&self) -> &'_ str {
+        &self.params
+    }
+}
+
+impl std::fmt::Display for RequestContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.method, self.params)
+    }
+}
+
+/// Truncate a string to a maximum number of chars, marking truncated strings with "..."
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() > max {
+        let mut s = s.chars().take(max).collect::<String>();
+        s.push_str("...");
+        s
+    } else {
+        s.to_owned()
+    }
 }
 
 #[derive(Debug)]
 enum ErrResponse {
     Response(Response),
     Err(anyhow::Error),
+    Timeout(Duration),
+}
+
+/// Number of bytes of text shown on either side of the offset of a formatting error
+const SNIPPET_RADIUS: usize = 16;
+
+/// Details of a "can't parse entities" error returned when HTML or Markdown text is
+/// malformed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEntitiesError {
+    offset: Option<usize>,
+    snippet: Option<String>,
+}
+
+impl ParseEntitiesError {
+    /// Get the details of a "can't parse entities" error from its description
+    fn new(description: &str, text: Option<&str>) -> Self {
+        let offset = description
+            .split("byte offset ")
+            .nth(1)
+            .and_then(|v| v.split(|c: char| !c.is_ascii_digit()).next())
+            .and_then(|v| v.parse::<usize>().ok());
+        let snippet = match (offset, text) {
+            (Some(offset), Some(text)) if offset <= text.len() => {
+                let mut start = offset.saturating_sub(SNIPPET_RADIUS);
+                while !text.is_char_boundary(start) {
+                    start -= 1;
+                }
+                let mut end = (offset + SNIPPET_RADIUS).min(text.len());
+                while !text.is_char_boundary(end) {
+                    end += 1;
+                }
+                Some(text[start..end].to_owned())
+            }
+            _ => None,
+        };
+        Self { offset, snippet }
+    }
+
+    /// Get the byte offset in the text where telegram failed to parse formatting
+    pub fn get_offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// Get the text surrounding the offset, if the text is known
+    pub fn get_snippet(&self) -> Option<&'_ str> {
+        self.snippet.as_deref()
+    }
 }
 
 /// Error type containing either a Response type from telegram api or a generic error
 #[derive(Debug)]
-pub struct ApiError(ErrResponse);
+pub struct ApiError {
+    err: ErrResponse,
+    context: Option<Box<RequestContext>>,
+    parse_error: Option<Box<ParseEntitiesError>>,
+}
 
 impl ApiError {
+    fn new(err: ErrResponse) -> Self {
+        Self {
+            err,
+            context: None,
+            parse_error: None,
+        }
+    }
+
     pub(crate) fn from_response(resp: Response) -> Self {
-        Self(ErrResponse::Response(resp))
+        Self::new(ErrResponse::Response(resp))
+    }
+
+    /// Create an error for a request cancelled after running for elapsed
+    pub(crate) fn timeout(elapsed: Duration) -> Self {
+        Self::new(ErrResponse::Timeout(elapsed))
+    }
+
+    /// Check if this wraps a timeout error from reqwest
+    fn is_client_timeout(&self) -> bool {
+        match self.err {
+            ErrResponse::Err(ref err) => err
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_timeout()),
+            _ => false,
+        }
+    }
+
+    /// Attach the method call that produced this error
+    pub(crate) fn with_context(mut self, context: RequestContext) -> Self {
+        self.context = Some(Box::new(context));
+        self
+    }
+
+    /// Attach the formatted text sent with the request that produced this error, used to
+    /// explain "can't parse entities" errors
+    pub(crate) fn with_entities_source(mut self, text: Option<&str>) -> Self {
+        if self.get_kind() != Some(ApiErrorKind::ParseEntities) {
+            return self;
+        }
+        if let ErrResponse::Response(Response {
+            description: Some(ref description),
+            ..
+        }) = self.err
+        {
+            self.parse_error = Some(Box::new(ParseEntitiesError::new(description, text)));
+        }
+        self
+    }
+
+    /// Get details of a formatting error if telegram could not parse the entities in
+    /// the text of a request
+    pub fn get_parse_error(&self) -> Option<&'_ ParseEntitiesError> {
+        self.parse_error.as_deref()
     }
 
     /// Get the telegram api response if it exists, None if this error is a
     /// non-telegram error
     pub fn get_response(&self) -> Option<&'_ Response> {
-        if let ErrResponse::Response(ref response) = self.0 {
+        if let ErrResponse::Response(ref response) = self.err {
             Some(response)
         } else {
             None
         }
     }
+
+    /// Get the method call that produced this error, if known
+    pub fn get_context(&self) -> Option<&'_ RequestContext> {
+        self.context.as_deref()
+    }
+
+    /// Check if the request was cancelled for passing its deadline or timeout. The
+    /// request may still have reached telegram
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.err, ErrResponse::Timeout(_))
+    }
+
+    /// Get how long a timed out request ran before it was cancelled
+    pub fn get_elapsed(&self) -> Option<Duration> {
+        match self.err {
+            ErrResponse::Timeout(elapsed) => Some(elapsed),
+            _ => None,
+        }
+    }
 }
 
 impl From<anyhow::Error> for ApiError {
     fn from(value: anyhow::Error) -> Self {
-        Self(ErrResponse::Err(value))
+        Self::new(ErrResponse::Err(value))
     }
 }
 
 impl From<reqwest::Error> for ApiError {
     fn from(value: reqwest::Error) -> Self {
-        Self(ErrResponse::Err(anyhow::anyhow!(value)))
+        // reqwest errors contain the request url, which contains the token
+        Self::new(ErrResponse::Err(anyhow::anyhow!(value.without_url())))
     }
 }
 
 impl From<serde_json::Error> for ApiError {
     fn from(value: serde_json::Error) -> Self {
-        Self(ErrResponse::Err(anyhow::anyhow!(value)))
+        Self::new(ErrResponse::Err(anyhow::anyhow!(value)))
     }
 }
 
@@ -115,7 +489,10 @@ impl Error for ApiError {}
 
 impl std::fmt::Display for ApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.0 {
+        if let Some(ref context) = self.context {
+            write!(f, "{}: ", context)?;
+        }
+        match self.err {
             ErrResponse::Response(Response {
                 description: Some(ref d),
                 ..
@@ -124,15 +501,90 @@ impl std::fmt::Display for ApiError {
                 f.write_str(&error_code.unwrap_or(-1).to_string())?
             }
             ErrResponse::Err(ref err) => f.write_str(&err.to_string())?,
+            ErrResponse::Timeout(elapsed) => write!(f, "request timed out after {:?}", elapsed)?,
         };
+        if let Some(snippet) = self.parse_error.as_ref().and_then(|e| e.get_snippet()) {
+            write!(f, " (near {:?})", snippet)?;
+        }
         Ok(())
     }
 }
 
-/// Type holding an active connection to telegram API
+/// Owned, serializable parameters for a telegram api method. This is implemented by the
+/// generated `...Params` structs for every method
+pub trait TelegramMethod: Serialize + DeserializeOwned + Send {
+    /// Type returned by this method
+    type Response;
+
+    /// Name of this method in the telegram bot api
+    const NAME: &'static str;
+
+    /// Call this method
+    fn call(self, bot: &Bot) -> BoxFuture<'_, BotResult<Self::Response>>;
+
+    /// Override Bot level settings when calling this method
+    fn with_options(self, options: RequestOptions) -> WithOptions<Self>
+    where
+        Self: Sized,
+    {
+        WithOptions::new(self, options)
+    }
+}
+
+/// A method call serialized for storage, for example in a database backed job queue.
+/// Files must be referenced by file_id or path, raw bytes can't be serialized
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SerializableRequest {
+    method: String,
+    params: serde_json::Value,
+}
+
+impl SerializableRequest {
+    /// Serialize the parameters of a method call
+    pub fn new<P>(params: &P) -> BotResult<Self>
+    where
+        P: TelegramMethod,
+    {
+        Ok(Self {
+            method: P::NAME.to_owned(),
+            params: serde_json::to_value(params)?,
+        })
+    }
+
+    /// Load a request from json
+    pub fn from_json(json: &str) -> BotResult<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Serialize this request to json
+    pub fn to_json(&self) -> BotResult<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Get the name of the method this request calls
+    pub fn get_method(&self) -> &'_ str {
+        &self.method
+    }
+
+    /// Get the serialized parameters of this request
+    pub fn get_params(&self) -> &'_ serde_json::Value {
+        &self.params
+    }
+}
+
+/// Type holding an active connection to telegram API. All state, including the http
+/// client, token, throttle policy and caches, is shared behind an Arc, so cloning is cheap
+/// and every clone uses the same connection pool and limits. Bot is Send + Sync and can be
+/// handed to any number of tokio tasks
 #[derive(Debug)]
 pub struct Bot(Arc<BotState>);
 
+// Fail to compile if a new field makes the bot unusable across tasks
+const _: () = {
+    const fn assert_shareable<T: Send + Sync + Clone + 'static>() {}
+    assert_shareable::<Bot>();
+};
+
 impl Default for Response {
     fn default() -> Self {
         Response {
@@ -154,13 +606,36 @@ impl BotBuilder {
     where
         T: Into<String>,
     {
-        let client = reqwest::ClientBuilder::new().https_only(true).build()?;
+        let client = client_builder().build()?;
 
         Ok(Self(BotState {
             client,
-            token: token.into(),
+            token: SecretString::new(token),
             api: "https://api.telegram.org".to_owned(),
             auto_wait: true,
+            cache: None,
+            throttle: None,
+            translator: None,
+            caption_overflow: CaptionOverflow::Ignore,
+            auto_escape: false,
+            truncate_overflow: false,
+            keyboard_cache: None,
+            ignore_not_modified: false,
+            error_classifier: None,
+            default_parse_mode: None,
+            default_disable_notification: None,
+            default_protect_content: None,
+            circuit_breaker: None,
+            debug_capture: None,
+            test_environment: false,
+            ephemeral: EphemeralRegistry::default(),
+            unreachable: UnreachableRegistry::default(),
+            pins: PinRegistry::default(),
+            audit_log: None,
+            #[cfg(feature = "hash")]
+            upload_cache: None,
+            headers: default_headers(),
+            endpoints: None,
         }))
     }
 
@@ -173,6 +648,59 @@ impl BotBuilder {
         self
     }
 
+    /// Send requests to an ordered list of api urls, failing over to the next when one is
+    /// unreachable. Replaces the url set with api, unless the list is empty
+    pub fn endpoints<I, T>(self, urls: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.failover(Endpoints::new(urls))
+    }
+
+    /// Like endpoints, with a failover list configured with a custom probe interval. An
+    /// empty list is ignored and the current api url is kept
+    pub fn failover(mut self, endpoints: Endpoints) -> Self {
+        match endpoints.get_urls().first() {
+            Some(first) => {
+                self.0.api = first.clone();
+                self.0.endpoints = Some(endpoints);
+            }
+            None => {
+                log::warn!("ignoring empty list of bot api endpoints");
+                self.0.endpoints = None;
+            }
+        }
+        self
+    }
+
+    /// Change the User-Agent sent with every request from the default of
+    /// botapi-rs/version. Values that aren't valid in a header are ignored
+    pub fn user_agent<T: AsRef<str>>(mut self, user_agent: T) -> Self {
+        match HeaderValue::from_str(user_agent.as_ref()) {
+            Ok(value) => {
+                self.0.headers.insert(USER_AGENT, value);
+            }
+            Err(err) => log::warn!("invalid user agent: {}", err),
+        }
+        self
+    }
+
+    /// Send a header with every request, for example credentials for an auth proxy or
+    /// api gateway in front of a local bot api server. The value is hidden from Debug
+    pub fn default_header(mut self, name: HeaderName, mut value: HeaderValue) -> Self {
+        value.set_sensitive(true);
+        self.0.headers.insert(name, value);
+        self
+    }
+
+    /// Allow plain http api urls, for pointing the bot at a local server in tests
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn allow_http(mut self) -> Result<Self> {
+        self.0.client = client_builder().https_only(false).build()?;
+        Ok(self)
+    }
+
     /// If true, set the bot to automatically retry ratelimited api calls
     /// when the retry_after parameter is detected
     pub fn auto_wait(mut self, auto_wait: bool) -> Self {
@@ -180,6 +708,132 @@ impl BotBuilder {
         self
     }
 
+    /// Cache the results of get_chat, get_chat_member, and get_chat_administrators
+    /// when called via their `_cached` variants. Entries expire after `ttl` and at most
+    /// `max_entries` are stored per cache
+    pub fn cache(mut self, ttl: Duration, max_entries: usize) -> Self {
+        self.0.cache = Some(ChatCache::new(ttl, max_entries));
+        self
+    }
+
+    /// Delay outgoing requests according to a ThrottlePolicy, for example
+    /// [`AimdThrottle`](crate::throttle::AimdThrottle) to back off chats that hit
+    /// ratelimits
+    pub fn throttle<T>(mut self, throttle: T) -> Self
+    where
+        T: ThrottlePolicy + 'static,
+    {
+        self.0.throttle = Some(Arc::new(throttle));
+        self
+    }
+
+    /// Resolve localized strings for send_message_localized using a Translator, for
+    /// example [`JsonTranslator`](crate::i18n::JsonTranslator)
+    pub fn translator<T>(mut self, translator: T) -> Self
+    where
+        T: Translator + 'static,
+    {
+        self.0.translator = Some(Arc::new(translator));
+        self
+    }
+
+    /// Set how send methods handle captions longer than telegram's limit
+    pub fn caption_overflow(mut self, caption_overflow: CaptionOverflow) -> Self {
+        self.0.caption_overflow = caption_overflow;
+        self
+    }
+
+    /// Truncate string parameters longer than the limit documented in the api spec,
+    /// like the 200 character text of answer_callback_query, instead of letting
+    /// telegram reject them. Text and captions are not affected, see caption_overflow
+    pub fn truncate_overflow(mut self, truncate_overflow: bool) -> Self {
+        self.0.truncate_overflow = truncate_overflow;
+        self
+    }
+
+    /// If true, text send helpers like send_formatted retry with the markup escaped
+    /// when telegram fails to parse it
+    pub fn auto_escape(mut self, auto_escape: bool) -> Self {
+        self.0.auto_escape = auto_escape;
+        self
+    }
+
+    /// Remember the inline keyboards of up to `max_entries` messages edited with
+    /// update_keyboard, skipping edits that would leave the keyboard unchanged
+    pub fn keyboard_cache(mut self, max_entries: usize) -> Self {
+        self.0.keyboard_cache = Some(KeyboardCache::new(max_entries));
+        self
+    }
+
+    /// Remember the file_ids of up to `max_entries` uploaded files by content hash, and
+    /// send the file_id instead of uploading identical bytes again
+    #[cfg(feature = "hash")]
+    pub fn upload_cache(mut self, max_entries: usize) -> Self {
+        self.0.upload_cache = Some(UploadCache::new(max_entries));
+        self
+    }
+
+    /// If true, update_keyboard treats telegram's "message is not modified" error as
+    /// success
+    pub fn ignore_not_modified(mut self, ignore_not_modified: bool) -> Self {
+        self.0.ignore_not_modified = ignore_not_modified;
+        self
+    }
+
+    /// Treat errors matched by an ErrorClassifier as benign. Benign errors from
+    /// dispatcher handlers are not logged as failures and BotResultExt::non_fatal maps
+    /// them to Outcome::Benign
+    pub fn error_classifier(mut self, error_classifier: ErrorClassifier) -> Self {
+        self.0.error_classifier = Some(error_classifier);
+        self
+    }
+
+    /// Set the parse_mode used by methods called without parse_mode or entities
+    pub fn default_parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.0.default_parse_mode = Some(parse_mode);
+        self
+    }
+
+    /// Set disable_notification for methods called without it
+    pub fn default_disable_notification(mut self, disable_notification: bool) -> Self {
+        self.0.default_disable_notification = Some(disable_notification);
+        self
+    }
+
+    /// Set protect_content for methods called without it
+    pub fn default_protect_content(mut self, protect_content: bool) -> Self {
+        self.0.default_protect_content = Some(protect_content);
+        self
+    }
+
+    /// Pause requests with a CircuitBreaker when telegram returns repeated bad gateway
+    /// errors
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.0.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Send every method call and raw response to a channel for debugging. The bot token
+    /// is redacted from everything captured
+    pub fn debug_capture(mut self, sink: CaptureSink) -> Self {
+        self.0.debug_capture = Some(sink);
+        self
+    }
+
+    /// Report every successful ban, restrict, promote or delete made by this bot to an
+    /// audit log
+    pub fn audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.0.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Send requests to telegram's test environment instead of production. Test
+    /// environment bots are created with a separate account and token
+    pub fn test_environment(mut self, test_environment: bool) -> Self {
+        self.0.test_environment = test_environment;
+        self
+    }
+
     /// Create a new bot instance from this builder
     pub fn build(self) -> Bot {
         Bot(Arc::new(self.0))
@@ -193,12 +847,35 @@ impl Bot {
     where
         T: Into<String>,
     {
-        let client = reqwest::ClientBuilder::new().https_only(true).build()?;
+        let client = client_builder().build()?;
         Ok(Self(Arc::new(BotState {
             client,
-            token: token.into(),
+            token: SecretString::new(token),
             api: "https://api.telegram.org".to_owned(),
             auto_wait,
+            cache: None,
+            throttle: None,
+            translator: None,
+            caption_overflow: CaptionOverflow::Ignore,
+            auto_escape: false,
+            truncate_overflow: false,
+            keyboard_cache: None,
+            ignore_not_modified: false,
+            error_classifier: None,
+            default_parse_mode: None,
+            default_disable_notification: None,
+            default_protect_content: None,
+            circuit_breaker: None,
+            debug_capture: None,
+            test_environment: false,
+            ephemeral: EphemeralRegistry::default(),
+            unreachable: UnreachableRegistry::default(),
+            pins: PinRegistry::default(),
+            audit_log: None,
+            #[cfg(feature = "hash")]
+            upload_cache: None,
+            headers: default_headers(),
+            endpoints: None,
         })))
     }
 
@@ -212,100 +889,503 @@ impl Bot {
         Self::new_auto_wait(token, false)
     }
 
-    /// generate an api endpoint from bot token
-    fn get_endpoint(&self, endpoint: &str) -> String {
-        format!("{}/bot{}/{}", self.0.api, self.0.token, endpoint)
+    /// Call a telegram api method using its owned parameters
+    pub async fn call<P>(&self, params: P) -> BotResult<P::Response>
+    where
+        P: TelegramMethod,
+    {
+        params.call(self).await
     }
 
-    /// HTTP post helper with x-www-form-urlencoded body
-    pub async fn post<T>(&self, endpoint: &str, body: T) -> BotResult<Response>
+    /// Get the user id of the bot, the part of the token before the colon. None if the
+    /// token isn't in the usual format
+    pub fn get_bot_id(&self) -> Option<i64> {
+        let (id, _) = self.0.token.expose().split_once(':')?;
+        id.parse().ok()
+    }
+
+    /// Get the chat info cache if enabled
+    pub(crate) fn get_cache(&self) -> Option<&'_ ChatCache> {
+        self.0.cache.as_ref()
+    }
+
+    /// Get the configured handling of long captions
+    pub(crate) fn get_caption_overflow(&self) -> CaptionOverflow {
+        self.0.caption_overflow
+    }
+
+    /// Check if text send helpers should escape markup telegram fails to parse
+    pub(crate) fn get_auto_escape(&self) -> bool {
+        self.0.auto_escape
+    }
+
+    /// Check if overlong string parameters are truncated
+    pub(crate) fn get_truncate_overflow(&self) -> bool {
+        self.0.truncate_overflow
+    }
+
+    /// Get the inline keyboard cache if enabled
+    pub(crate) fn get_keyboard_cache(&self) -> Option<&'_ KeyboardCache> {
+        self.0.keyboard_cache.as_ref()
+    }
+
+    /// Get the upload cache if enabled
+    #[cfg(feature = "hash")]
+    pub fn get_upload_cache(&self) -> Option<&'_ UploadCache> {
+        self.0.upload_cache.as_ref()
+    }
+
+    /// Swap a file for its cached file_id if its contents were uploaded before
+    #[cfg(feature = "hash")]
+    pub(crate) fn prepare_upload(
+        &self,
+        method: &'static str,
+        field: &'static str,
+        file: FileData,
+    ) -> (FileData, UploadKey) {
+        match self.0.upload_cache {
+            Some(ref cache) => cache.prepare(method, field, file),
+            None => (file, None),
+        }
+    }
+
+    /// Swap a file for its cached file_id if its contents were uploaded before
+    #[cfg(not(feature = "hash"))]
+    pub(crate) fn prepare_upload(
+        &self,
+        _: &'static str,
+        _: &'static str,
+        file: FileData,
+    ) -> (FileData, UploadKey) {
+        (file, ())
+    }
+
+    /// Remember the file_id assigned to a newly uploaded file
+    #[cfg(feature = "hash")]
+    pub(crate) fn finish_upload(&self, key: UploadKey, message: &Message) {
+        if let Some(ref cache) = self.0.upload_cache {
+            cache.finish(key, message);
+        }
+    }
+
+    /// Remember the file_id assigned to a newly uploaded file
+    #[cfg(not(feature = "hash"))]
+    pub(crate) fn finish_upload(&self, _: UploadKey, _: &Message) {}
+
+    /// Check if "message is not modified" errors should be treated as success
+    pub(crate) fn get_ignore_not_modified(&self) -> bool {
+        self.0.ignore_not_modified
+    }
+
+    /// Get the classifier for benign errors if configured
+    pub(crate) fn get_error_classifier(&self) -> Option<&'_ ErrorClassifier> {
+        self.0.error_classifier.as_ref()
+    }
+
+    /// Get the parse_mode used by methods called without parse_mode or entities, from
+    /// the current RequestOptions or the bot's default
+    pub(crate) fn default_parse_mode(&self) -> Option<&'static str> {
+        self.get_parse_mode().map(|p| p.as_str())
+    }
+
+    /// Get the ParseMode behind default_parse_mode, for escaping text filled into
+    /// templates
+    pub(crate) fn get_parse_mode(&self) -> Option<ParseMode> {
+        RequestOptions::current()
+            .get_parse_mode()
+            .or(self.0.default_parse_mode)
+    }
+
+    /// Get the disable_notification used by methods called without it
+    pub(crate) fn default_disable_notification(&self) -> Option<bool> {
+        self.0.default_disable_notification
+    }
+
+    /// Get the protect_content used by methods called without it
+    pub(crate) fn default_protect_content(&self) -> Option<bool> {
+        self.0.default_protect_content
+    }
+
+    /// Get the translator if configured
+    pub(crate) fn get_translator(&self) -> Option<&'_ dyn Translator> {
+        self.0.translator.as_deref()
+    }
+
+    /// Get the chat key used for throttling a request, if throttling is enabled
+    fn get_throttle_key<T>(&self, body: &T) -> Option<String>
     where
+        T: ChatKey,
+    {
+        self.0.throttle.as_ref().and_then(|_| body.chat_key())
+    }
+
+    /// Wait until the throttle policy allows sending a request to a chat
+    async fn throttle_wait(&self, chat: Option<&str>, options: &RequestOptions) {
+        if options.get_skip_throttle() {
+            return;
+        }
+        if let Some(ref throttle) = self.0.throttle {
+            let delay = throttle.delay(chat);
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    /// Wait while the circuit breaker is open
+    async fn circuit_wait(&self) {
+        if let Some(ref breaker) = self.0.circuit_breaker {
+            breaker.wait().await;
+        }
+    }
+
+    /// Record the outcome of a request with the circuit breaker, requests that never got
+    /// a response count as failures
+    fn circuit_record(&self, resp: &BotResult<reqwest::Response>) {
+        if let Some(ref breaker) = self.0.circuit_breaker {
+            breaker.record(resp.as_ref().ok().map(|resp| resp.status().as_u16()));
+        }
+    }
+
+    /// Send a call to the debug capture sink if one is set
+    pub(crate) fn capture<T>(
+        &self,
+        endpoint: &str,
+        body: Option<&T>,
+        multipart: bool,
+        status: u16,
+        bytes: &[u8],
+    ) where
         T: Serialize,
     {
-        let endpoint = self.get_endpoint(endpoint);
-        let mut floods = if self.0.auto_wait {
-            Some(Vec::<ResponseFlood>::new())
-        } else {
-            None
-        };
-        loop {
-            let resp = self
-                .0
-                .client
-                .post(&endpoint)
-                .query(&body)
-                .send()
-                .await
-                .map_err(|e| e.without_url())?;
-            let bytes = resp.bytes().await?;
-            let mut resp: Response = serde_json::from_slice(&bytes)?;
-            if self.0.auto_wait && resp.wait().await {
-                floods.as_mut().unwrap().push(resp.get_flood());
-                continue;
-            } else {
-                if let Some(floods) = floods {
-                    resp.floods = Some(floods);
-                }
-                return Ok(resp);
+        if let Some(ref sink) = self.0.debug_capture {
+            let request = body
+                .and_then(|body| serde_json::to_string(body).ok())
+                .map(|body| self.0.token.scrub(&body))
+                .and_then(|body| serde_json::from_str(&body).ok())
+                .unwrap_or(serde_json::Value::Null);
+            let response = self.0.token.scrub(&String::from_utf8_lossy(bytes));
+            let capture = Capture::new(endpoint, request, multipart, status, response);
+            if sink.send(capture).is_err() {
+                log::debug!("debug capture receiver dropped");
             }
         }
     }
 
-    /// HTTP post helper with empty body
-    pub async fn post_empty(&self, endpoint: &str) -> BotResult<Response> {
-        let endpoint = self.get_endpoint(endpoint);
-        let mut floods = if self.0.auto_wait {
-            Some(Vec::<ResponseFlood>::new())
+    /// Notify the throttle policy of a response
+    fn throttle_response(&self, chat: Option<&str>, resp: &Response) {
+        if let Some(ref throttle) = self.0.throttle {
+            throttle.on_response(chat, resp);
+        }
+    }
+
+    /// Get the registry of scheduled message deletions
+    pub(crate) fn ephemeral(&self) -> &EphemeralRegistry {
+        &self.0.ephemeral
+    }
+
+    /// Get the callbacks run for unreachable chats
+    pub(crate) fn unreachable(&self) -> &UnreachableRegistry {
+        &self.0.unreachable
+    }
+
+    /// Get the messages pinned with replace_pinned
+    pub(crate) fn pins(&self) -> &PinRegistry {
+        &self.0.pins
+    }
+
+    /// Get the path segment selecting the test environment, if enabled
+    fn environment(&self) -> &'static str {
+        if self.0.test_environment {
+            "/test"
         } else {
-            None
+            ""
+        }
+    }
+
+    /// Start a post request to an api endpoint on a given api url, applying the timeout of
+    /// the current RequestOptions
+    fn post_request(
+        &self,
+        api: &str,
+        endpoint: &str,
+        options: &RequestOptions,
+    ) -> reqwest::RequestBuilder {
+        let req = self
+            .0
+            .client
+            .post(format!(
+                "{}/bot{}{}/{}",
+                api,
+                self.0.token.expose(),
+                self.environment(),
+                endpoint
+            ))
+            .headers(self.0.headers.clone());
+        match options.get_timeout() {
+            Some(timeout) => req.timeout(timeout),
+            None => req,
+        }
+    }
+
+    /// Send a post request, moving on to the next failover endpoint if one can't be
+    /// reached. The request is built for each endpoint until build returns None, for
+    /// bodies that can only be sent once. A url set in RequestOptions disables failover
+    async fn send_request<F>(
+        &self,
+        endpoint: &str,
+        options: &RequestOptions,
+        mut build: F,
+    ) -> BotResult<reqwest::Response>
+    where
+        F: FnMut(reqwest::RequestBuilder) -> Option<reqwest::RequestBuilder>,
+    {
+        #[cfg(feature = "test-util")]
+        if crate::snapshot::is_capturing() {
+            let req = build(self.post_request(&self.0.api, endpoint, options))
+                .ok_or_else(|| anyhow::anyhow!("request already sent"))?;
+            return Err(crate::snapshot::record(endpoint, req).await);
+        }
+        let endpoints = match (options.get_api(), self.0.endpoints.as_ref()) {
+            (None, Some(endpoints)) => endpoints,
+            (api, _) => {
+                let api = api.unwrap_or(&self.0.api);
+                let req = build(self.post_request(api, endpoint, options))
+                    .ok_or_else(|| anyhow::anyhow!("request already sent"))?;
+                return Ok(req.send().await.map_err(|e| e.without_url())?);
+            }
         };
-        loop {
-            let resp = self
-                .0
-                .client
-                .post(&endpoint)
-                .send()
-                .await
-                .map_err(|e| e.without_url())?;
-            let bytes = resp.bytes().await?;
-            let mut resp: Response = serde_json::from_slice(&bytes)?;
-
-            if self.0.auto_wait && resp.wait().await {
-                floods.as_mut().unwrap().push(resp.get_flood());
-                continue;
-            } else {
-                if let Some(floods) = floods {
-                    resp.floods = Some(floods);
+        let mut last = None;
+        for index in endpoints.order() {
+            let Some(req) = build(self.post_request(endpoints.get_url(index), endpoint, options))
+            else {
+                break;
+            };
+            match req.send().await {
+                Ok(resp) if is_relay_failure(resp.status()) => {
+                    endpoints.mark_down(index);
+                    last = Some(Ok(resp));
                 }
-                return Ok(resp);
+                Ok(resp) => {
+                    endpoints.mark_up(index);
+                    return Ok(resp);
+                }
+                Err(err) if err.is_connect() => {
+                    endpoints.mark_down(index);
+                    last = Some(Err(err));
+                }
+                Err(err) => return Err(err.without_url().into()),
             }
         }
+        match last {
+            Some(Ok(resp)) => Ok(resp),
+            Some(Err(err)) => Err(err.without_url().into()),
+            None => Err(anyhow::anyhow!("no bot api endpoints configured").into()),
+        }
     }
 
-    /// HTTP post helper with x-www-form-urlencode body and multipart/form-data
-    pub async fn post_data<T>(&self, endpoint: &str, body: T, data: Form) -> BotResult<Response>
-    where
-        T: Serialize,
-    {
-        let endpoint = self.get_endpoint(endpoint);
+    /// Get the api url files are downloaded from, the first healthy failover endpoint
+    /// if there are any
+    fn file_api(&self) -> &'_ str {
+        match self.0.endpoints.as_ref() {
+            Some(endpoints) => match endpoints.order().first() {
+                Some(&index) => endpoints.get_url(index),
+                None => &self.0.api,
+            },
+            None => &self.0.api,
+        }
+    }
 
+    /// Start downloading a file using the file_path returned by get_file
+    pub(crate) async fn get_file_response(&self, file_path: &str) -> BotResult<reqwest::Response> {
+        let url = format!(
+            "{}/file/bot{}{}/{}",
+            self.file_api(),
+            self.0.token.expose(),
+            self.environment(),
+            file_path
+        );
         let resp = self
             .0
             .client
-            .post(&endpoint)
-            .query(&body)
-            .multipart(data)
+            .get(&url)
+            .headers(self.0.headers.clone())
             .send()
             .await
+            .map_err(|e| e.without_url())?
+            .error_for_status()
             .map_err(|e| e.without_url())?;
-        let bytes = resp.bytes().await?;
-        let mut resp: Response = serde_json::from_slice(&bytes)?;
-        if self.0.auto_wait {
-            resp.wait().await;
-            resp.floods = Some(vec![resp.get_flood()]);
-        }
         Ok(resp)
     }
+
+    /// Download a file using the file_path returned by get_file
+    pub async fn download_file(&self, file_path: &str) -> BotResult<Vec<u8>> {
+        let resp = self.get_file_response(file_path).await?;
+        let bytes = resp.bytes().await.map_err(|e| e.without_url())?;
+        Ok(bytes.to_vec())
+    }
+
+    /// HTTP post helper with x-www-form-urlencoded body
+    pub async fn post<T>(&self, endpoint: &str, body: T) -> BotResult<Response>
+    where
+        T: Serialize + ChatKey,
+    {
+        self.post_parsed(endpoint, body, |bytes| {
+            Ok((serde_json::from_slice(bytes)?, None::<()>))
+        })
+        .await
+        .map(|(resp, _)| resp)
+    }
+
+    /// HTTP post helper with x-www-form-urlencoded body, deserializing the result
+    /// straight from the response body instead of building a serde_json::Value first.
+    /// The generated get_updates uses this, since a batch of 100 large updates makes an
+    /// allocation for every object, string and array in the Value. See the
+    /// update_allocations example for a comparison
+    pub async fn post_typed<T, R>(
+        &self,
+        endpoint: &str,
+        body: T,
+    ) -> BotResult<(Response, Option<R>)>
+    where
+        T: Serialize + ChatKey,
+        R: DeserializeOwned,
+    {
+        self.post_parsed(endpoint, body, |bytes| {
+            Ok(crate::json::from_slice::<TypedResponse<R>>(bytes)?.split())
+        })
+        .await
+    }
+
+    /// Post with x-www-form-urlencoded body, parsing the response body with parse.
+    /// The result returned by parse is kept from the final response after flood waits
+    async fn post_parsed<T, R, F>(
+        &self,
+        endpoint: &str,
+        body: T,
+        parse: F,
+    ) -> BotResult<(Response, Option<R>)>
+    where
+        T: Serialize + ChatKey,
+        F: Fn(&[u8]) -> BotResult<(Response, Option<R>)>,
+    {
+        let mut result = None;
+        let resp = traced_call(
+            endpoint,
+            with_deadline(async {
+                let options = RequestOptions::current();
+                let chat = self.get_throttle_key(&body);
+                let mut floods = if self.0.auto_wait {
+                    Some(Vec::<ResponseFlood>::new())
+                } else {
+                    None
+                };
+                loop {
+                    self.circuit_wait().await;
+                    self.throttle_wait(chat.as_deref(), &options).await;
+                    let resp = self
+                        .send_request(endpoint, &options, |req| Some(req.query(&body)))
+                        .await;
+                    self.circuit_record(&resp);
+                    let resp = resp?;
+                    let status = resp.status().as_u16();
+                    let bytes = resp.bytes().await.map_err(|e| e.without_url())?;
+                    self.capture(endpoint, Some(&body), false, status, &bytes);
+                    let (mut resp, parsed) = parse(&bytes)?;
+                    self.throttle_response(chat.as_deref(), &resp);
+                    self.notify_unreachable(&body, &resp);
+                    if self.0.auto_wait && resp.wait().await {
+                        floods.as_mut().unwrap().push(resp.get_flood());
+                        continue;
+                    } else {
+                        if let Some(floods) = floods {
+                            resp.floods = Some(floods);
+                        }
+                        if let (true, Some(audit_log)) = (resp.ok, self.0.audit_log.as_ref()) {
+                            audit_log.record_call(endpoint, &body);
+                        }
+                        result = parsed;
+                        return Ok(resp);
+                    }
+                }
+            }),
+        )
+        .await?;
+        Ok((resp, result))
+    }
+
+    /// HTTP post helper with empty body
+    pub async fn post_empty(&self, endpoint: &str) -> BotResult<Response> {
+        traced_call(
+            endpoint,
+            with_deadline(async {
+                let options = RequestOptions::current();
+                let mut floods = if self.0.auto_wait {
+                    Some(Vec::<ResponseFlood>::new())
+                } else {
+                    None
+                };
+                loop {
+                    self.circuit_wait().await;
+                    let resp = self.send_request(endpoint, &options, Some).await;
+                    self.circuit_record(&resp);
+                    let resp = resp?;
+                    let status = resp.status().as_u16();
+                    let bytes = resp.bytes().await.map_err(|e| e.without_url())?;
+                    self.capture::<()>(endpoint, None, false, status, &bytes);
+                    let mut resp: Response = serde_json::from_slice(&bytes)?;
+
+                    if self.0.auto_wait && resp.wait().await {
+                        floods.as_mut().unwrap().push(resp.get_flood());
+                        continue;
+                    } else {
+                        if let Some(floods) = floods {
+                            resp.floods = Some(floods);
+                        }
+                        return Ok(resp);
+                    }
+                }
+            }),
+        )
+        .await
+    }
+
+    /// HTTP post helper with x-www-form-urlencode body and multipart/form-data
+    pub async fn post_data<T>(&self, endpoint: &str, body: T, data: Form) -> BotResult<Response>
+    where
+        T: Serialize + ChatKey,
+    {
+        traced_call(
+            endpoint,
+            with_deadline(async {
+                let options = RequestOptions::current();
+                let chat = self.get_throttle_key(&body);
+                self.circuit_wait().await;
+                self.throttle_wait(chat.as_deref(), &options).await;
+
+                let mut data = Some(data);
+                let resp = self
+                    .send_request(endpoint, &options, |req| {
+                        Some(req.query(&body).multipart(data.take()?))
+                    })
+                    .await;
+                self.circuit_record(&resp);
+                let resp = resp?;
+                let status = resp.status().as_u16();
+                let bytes = resp.bytes().await.map_err(|e| e.without_url())?;
+                self.capture(endpoint, Some(&body), true, status, &bytes);
+                let mut resp: Response = serde_json::from_slice(&bytes)?;
+                self.throttle_response(chat.as_deref(), &resp);
+                self.notify_unreachable(&body, &resp);
+                if self.0.auto_wait {
+                    resp.wait().await;
+                    resp.floods = Some(vec![resp.get_flood()]);
+                }
+                Ok(resp)
+            }),
+        )
+        .await
+    }
 }
 
 impl Clone for Bot {
@@ -313,3 +1393,160 @@ impl Clone for Bot {
         Self(Arc::clone(&self.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TOKEN: &str = "1234:supersecrettoken";
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn auto_traits() {
+        assert_send_sync::<Bot>();
+        assert_send_sync::<BotBuilder>();
+        assert_send_sync::<ApiError>();
+        assert_send_sync::<crate::dispatch::Dispatcher>();
+        let bot = BotBuilder::new(TOKEN).unwrap().build();
+        let clone = bot.clone();
+        assert!(Arc::ptr_eq(&bot.0, &clone.0));
+    }
+
+    #[test]
+    fn debug_redacts_token() {
+        let bot = BotBuilder::new(TOKEN).unwrap().build();
+        assert!(!format!("{:?}", bot).contains("supersecrettoken"));
+        assert!(!format!("{:?}", bot.0.token).contains("supersecrettoken"));
+        assert!(!bot.0.token.to_string().contains("supersecrettoken"));
+    }
+
+    #[test]
+    fn user_agent_and_headers() {
+        let bot = BotBuilder::new(TOKEN).unwrap().build();
+        assert_eq!(bot.0.headers[USER_AGENT], DEFAULT_USER_AGENT);
+        let bot = BotBuilder::new(TOKEN)
+            .unwrap()
+            .user_agent("mybot/1.0")
+            .default_header(
+                HeaderName::from_static("x-api-key"),
+                HeaderValue::from_static("secretkey"),
+            )
+            .build();
+        assert_eq!(bot.0.headers[USER_AGENT], "mybot/1.0");
+        assert!(!format!("{:?}", bot).contains("secretkey"));
+    }
+
+    #[test]
+    fn scrub_token() {
+        let secret = SecretString::new(TOKEN);
+        let url = format!("https://api.telegram.org/bot{}/getMe", TOKEN);
+        assert!(!secret.scrub(&url).contains("supersecrettoken"));
+    }
+
+    #[test]
+    fn context_truncates_params() {
+        #[derive(Serialize)]
+        struct Params<'a> {
+            chat_id: i64,
+            text: &'a str,
+            parse_mode: Option<&'a str>,
+        }
+        let text = "a".repeat(CONTEXT_VALUE_MAX * 2);
+        let context = RequestContext::new(
+            "sendMessage",
+            &Params {
+                chat_id: 1,
+                text: &text,
+                parse_mode: None,
+            },
+        );
+        assert_eq!(context.get_method(), "sendMessage");
+        assert!(context.get_params().contains("chat_id=1"));
+        assert!(!context.get_params().contains(&text));
+        assert!(!context.get_params().contains("parse_mode"));
+    }
+
+    #[test]
+    fn parse_error_snippet() {
+        let text = "hello <b>world and everyone else here";
+        let err = ApiError::from_response(Response {
+            ok: false,
+            error_code: Some(400),
+            description: Some(
+                "Bad Request: can't parse entities: Can't find end of the entity starting at byte offset 6"
+                    .to_owned(),
+            ),
+            ..Default::default()
+        })
+        .with_entities_source(Some(text));
+        let parse_error = err.get_parse_error().unwrap();
+        assert_eq!(parse_error.get_offset(), Some(6));
+        assert_eq!(parse_error.get_snippet(), Some("hello <b>world and eve"));
+        assert!(err.to_string().contains("near"));
+    }
+
+    #[tokio::test]
+    async fn fake_api_defaults_to_error() {
+        use crate::gen_methods::{LogOutParams, TelegramApi};
+        struct Fake;
+        impl TelegramApi for Fake {}
+        let api: &dyn TelegramApi = &Fake;
+        assert!(api.log_out(LogOutParams::default()).await.is_err());
+        let bot: Box<dyn TelegramApi> = Box::new(BotBuilder::new(TOKEN).unwrap().build());
+        drop(bot);
+    }
+
+    #[tokio::test]
+    async fn request_error_redacts_token() {
+        // the client is https only so this fails before connecting, with the url in the error
+        let bot = BotBuilder::new(TOKEN)
+            .unwrap()
+            .api("http://127.0.0.1:1")
+            .build();
+        let err = bot.get_me().await.unwrap_err();
+        assert!(!err.to_string().contains("supersecrettoken"));
+        assert!(!format!("{:?}", err).contains("supersecrettoken"));
+    }
+
+    #[tokio::test]
+    async fn deadline_cancels_request() {
+        // accepts connections without ever answering
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let bot = BotBuilder::new(TOKEN)
+            .unwrap()
+            .allow_http()
+            .unwrap()
+            .api(format!("http://{}", listener.local_addr().unwrap()))
+            .build();
+        let options = RequestOptions::new().deadline(Instant::now() + Duration::from_millis(50));
+        let err = options.scope(bot.get_me()).await.unwrap_err();
+        assert!(err.is_timeout());
+        assert!(err.get_elapsed().unwrap() >= Duration::from_millis(40));
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn get_updates_typed() {
+        let update = serde_json::json!({
+            "update_id": 7,
+            "message": {"message_id": 1, "date": 0, "chat": {"id": 5, "type": "private"}},
+        });
+        let test = crate::testing::TestBot::builder()
+            .respond("getUpdates", [update])
+            .respond_error(
+                "getUpdates",
+                409,
+                "Conflict: terminated by other getUpdates",
+            )
+            .build()
+            .await
+            .unwrap();
+        let bot = test.get_bot();
+        let updates = bot.get_updates(Some(3), None, None, None).await.unwrap();
+        assert_eq!(updates[0].get_update_id().get(), 7);
+        let err = bot.get_updates(None, None, None, None).await.unwrap_err();
+        assert_eq!(err.get_response().and_then(|r| r.error_code), Some(409));
+        assert_eq!(test.sent("getUpdates")[0].get("offset"), Some("3"));
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{ChatFullInfo, ChatMember, UpdateExt, UserId};
+
+/// Map with a fixed maximum size where entries expire after a ttl. When full the oldest
+/// entry is evicted
+struct TtlMap<K, V> {
+    ttl: Duration,
+    max_entries: usize,
+    entries: HashMap<K, (Instant, V)>,
+}
+
+impl<K, V> TtlMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        match self.entries.get(key) {
+            Some((inserted, value)) if inserted.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.max_entries == 0 {
+            return;
+        }
+        if self.entries.len() >= self.max_entries && !self.entries.contains_key(&key) {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (inserted, _)| inserted.elapsed() < ttl);
+        }
+        if self.entries.len() >= self.max_entries && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (inserted, _))| *inserted)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (Instant::now(), value));
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.entries.retain(|k, _| f(k));
+    }
+}
+
+/// Cache for chat info, chat members, and chat administrators. Entries expire after a ttl
+/// and are invalidated when chat member updates are received by LongPoller or Webhook or
+/// dispatched by a Dispatcher. Telegram doesn't send chat_member updates unless they are
+/// listed in allowed_updates, without them changes to other members are only picked up
+/// once their entries expire
+pub struct ChatCache {
+    chats: Mutex<TtlMap<i64, ChatFullInfo>>,
+    members: Mutex<TtlMap<(i64, UserId), ChatMember>>,
+    admins: Mutex<TtlMap<i64, Vec<ChatMember>>>,
+    counts: Mutex<TtlMap<i64, i64>>,
+}
+
+impl std::fmt::Debug for ChatCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatCache").finish_non_exhaustive()
+    }
+}
+
+impl ChatCache {
+    /// Create a new cache where entries expire after `ttl` and each of the chat, member,
+    /// and administrator caches holds at most `max_entries`
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            chats: Mutex::new(TtlMap::new(ttl, max_entries)),
+            members: Mutex::new(TtlMap::new(ttl, max_entries)),
+            admins: Mutex::new(TtlMap::new(ttl, max_entries)),
+            counts: Mutex::new(TtlMap::new(ttl, max_entries)),
+        }
+    }
+
+    /// Remove all cached info for a chat
+    pub fn invalidate_chat(&self, chat_id: i64) {
+        self.chats.lock().unwrap().remove(&chat_id);
+        self.admins.lock().unwrap().remove(&chat_id);
+        self.counts.lock().unwrap().remove(&chat_id);
+        self.members
+            .lock()
+            .unwrap()
+            .retain(|(chat, _)| *chat != chat_id);
+    }
+
+    /// Remove cached info for a single member of a chat
+    pub fn invalidate_member(&self, chat_id: i64, user_id: UserId) {
+        self.members.lock().unwrap().remove(&(chat_id, user_id));
+        self.admins.lock().unwrap().remove(&chat_id);
+        self.counts.lock().unwrap().remove(&chat_id);
+    }
+
+    /// Invalidate any entries made stale by an update
+    pub fn handle_update(&self, update: &UpdateExt) {
+        match update {
+            UpdateExt::ChatMember(member) | UpdateExt::MyChatMember(member) => {
+                let chat_id = member.get_chat().get_id();
+                let user_id = member.get_new_chat_member().get_user().get_id();
+                self.chats.lock().unwrap().remove(&chat_id);
+                self.invalidate_member(chat_id, user_id);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Bot {
+    /// Invalidate cached chat info made stale by an update. This is called automatically
+    /// for updates received via LongPoller or Webhook and for updates dispatched by a
+    /// Dispatcher
+    pub fn invalidate_cache(&self, update: &UpdateExt) {
+        if let Some(cache) = self.get_cache() {
+            cache.handle_update(update);
+        }
+    }
+
+    /// Like get_chat, but uses the cache if configured
+    pub async fn get_chat_cached(&self, chat_id: i64) -> BotResult<ChatFullInfo> {
+        let Some(cache) = self.get_cache() else {
+            return self.build_get_chat(chat_id).build().await;
+        };
+        if let Some(chat) = cache.chats.lock().unwrap().get(&chat_id) {
+            return Ok(chat);
+        }
+        let chat = self.build_get_chat(chat_id).build().await?;
+        cache.chats.lock().unwrap().insert(chat_id, chat.clone());
+        Ok(chat)
+    }
+
+    /// Like get_chat_member, but uses the cache if configured
+    pub async fn get_chat_member_cached(
+        &self,
+        chat_id: i64,
+        user_id: UserId,
+    ) -> BotResult<ChatMember> {
+        let Some(cache) = self.get_cache() else {
+            return self.build_get_chat_member(chat_id, user_id).build().await;
+        };
+        if let Some(member) = cache.members.lock().unwrap().get(&(chat_id, user_id)) {
+            return Ok(member);
+        }
+        let member = self.build_get_chat_member(chat_id, user_id).build().await?;
+        cache
+            .members
+            .lock()
+            .unwrap()
+            .insert((chat_id, user_id), member.clone());
+        Ok(member)
+    }
+
+    /// Like get_chat_administrators, but uses the cache if configured
+    pub async fn get_chat_administrators_cached(&self, chat_id: i64) -> BotResult<Vec<ChatMember>> {
+        let Some(cache) = self.get_cache() else {
+            return self.build_get_chat_administrators(chat_id).build().await;
+        };
+        if let Some(admins) = cache.admins.lock().unwrap().get(&chat_id) {
+            return Ok(admins);
+        }
+        let admins = self.build_get_chat_administrators(chat_id).build().await?;
+        cache.admins.lock().unwrap().insert(chat_id, admins.clone());
+        Ok(admins)
+    }
+
+    /// Like get_chat_member_count, but uses the cache if configured
+    pub async fn get_chat_member_count_cached(&self, chat_id: i64) -> BotResult<i64> {
+        let Some(cache) = self.get_cache() else {
+            return self.build_get_chat_member_count(chat_id).build().await;
+        };
+        if let Some(count) = cache.counts.lock().unwrap().get(&chat_id) {
+            return Ok(count);
+        }
+        let count = self.build_get_chat_member_count(chat_id).build().await?;
+        cache.counts.lock().unwrap().insert(chat_id, count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_entries() {
+        let mut map = TtlMap::new(Duration::ZERO, 10);
+        map.insert(1, "a");
+        assert_eq!(map.get(&1), None);
+        assert!(map.entries.is_empty());
+
+        let mut map = TtlMap::new(Duration::from_secs(60), 0);
+        map.insert(1, "a");
+        assert_eq!(map.get(&1), None);
+    }
+
+    #[test]
+    fn evicts_oldest() {
+        let mut map = TtlMap::new(Duration::from_secs(60), 2);
+        map.insert(1, "a");
+        std::thread::sleep(Duration::from_millis(1));
+        map.insert(2, "b");
+        std::thread::sleep(Duration::from_millis(1));
+        map.insert(3, "c");
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&2), Some("b"));
+        assert_eq!(map.get(&3), Some("c"));
+    }
+
+    #[test]
+    fn member_updates_invalidate() {
+        let cache = ChatCache::new(Duration::from_secs(60), 10);
+        let user = serde_json::json!({"id": 2, "is_bot": false, "first_name": "Test"});
+        let member: ChatMember =
+            serde_json::from_value(serde_json::json!({"status": "member", "user": user})).unwrap();
+        cache.counts.lock().unwrap().insert(-100, 5);
+        cache.counts.lock().unwrap().insert(-200, 3);
+        cache
+            .members
+            .lock()
+            .unwrap()
+            .insert((-100, UserId::from(2)), member);
+
+        cache.handle_update(&UpdateExt::Invalid);
+        assert_eq!(cache.counts.lock().unwrap().get(&-100), Some(5));
+
+        let update = serde_json::json!({
+            "chat": {"id": -100, "type": "supergroup"},
+            "from": user,
+            "date": 0,
+            "old_chat_member": {"status": "left", "user": user},
+            "new_chat_member": {"status": "member", "user": user},
+        });
+        cache.handle_update(&UpdateExt::ChatMember(
+            serde_json::from_value(update).unwrap(),
+        ));
+        assert_eq!(cache.counts.lock().unwrap().get(&-100), None);
+        assert_eq!(cache.counts.lock().unwrap().get(&-200), Some(3));
+        let cached = cache.members.lock().unwrap().get(&(-100, UserId::from(2)));
+        assert!(cached.is_none());
+    }
+}
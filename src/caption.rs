@@ -0,0 +1,207 @@
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{Message, MessageEntity, ReplyParametersBuilder};
+
+/// Maximum length of a media caption in UTF-16 code units
+pub const MAX_CAPTION_LENGTH: usize = 1024;
+
+/// Maximum length of a text message in UTF-16 code units
+pub const MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Text along with its formatting entities
+pub type TextEntities = (String, Vec<MessageEntity>);
+
+/// How send methods handle captions longer than MAX_CAPTION_LENGTH. Captions using
+/// parse_mode are never modified since their length is only known after parsing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CaptionOverflow {
+    /// Send the caption unchanged, telegram will reject it if too long
+    #[default]
+    Ignore,
+    /// Truncate the caption, discarding overflow text
+    Truncate,
+    /// Truncate the caption and send the overflow text as replies to the sent media
+    Spill,
+}
+
+/// Length of a string as counted by telegram
+pub fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// Byte index of the last char boundary at or before a UTF-16 offset
+pub(crate) fn byte_index(text: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in text.char_indices() {
+        if units + c.len_utf16() > utf16 {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Choose where to split text, preferring whitespace and the start of entities over
+/// cutting words or entities as long as at least half of `max` is kept
+fn split_point(text: &str, entities: &[MessageEntity], max: usize) -> usize {
+    let head = &text[..byte_index(text, max)];
+    let mut split = match head.rfind(char::is_whitespace) {
+        Some(i) if utf16_len(&head[..i]) >= max / 2 => utf16_len(&head[..i]),
+        _ => utf16_len(head),
+    };
+    for entity in entities {
+        let start = entity.get_offset() as usize;
+        let end = start + entity.get_length() as usize;
+        if start < split && split < end && start >= max / 2 {
+            split = start;
+        }
+    }
+    split
+}
+
+/// Split text with entities so that the first part is at most `max` UTF-16 code units.
+/// Entities crossing the split are divided between both parts. Returns the overflow if
+/// any text did not fit
+pub fn split_text(
+    text: &str,
+    entities: &[MessageEntity],
+    max: usize,
+) -> (TextEntities, Option<TextEntities>) {
+    if utf16_len(text) <= max {
+        return ((text.to_owned(), entities.to_vec()), None);
+    }
+    let split = split_point(text, entities, max);
+    let index = byte_index(text, split);
+    let head = text[..index].to_owned();
+    let tail = text[index..].trim_start();
+    let tail_start = split + utf16_len(&text[index..]) - utf16_len(tail);
+
+    let head_entities = entities
+        .iter()
+        .filter(|e| (e.get_offset() as usize) < split)
+        .map(|e| {
+            let mut e = e.clone();
+            let end = (e.get_offset() + e.get_length()).min(split as i64);
+            e.set_length(end - e.get_offset());
+            e
+        })
+        .collect();
+    let tail_entities = entities
+        .iter()
+        .filter(|e| (e.get_offset() + e.get_length()) as usize > tail_start)
+        .map(|e| {
+            let mut e = e.clone();
+            let start = e.get_offset().max(tail_start as i64);
+            e.set_length(e.get_offset() + e.get_length() - start);
+            e.set_offset(start - tail_start as i64);
+            e
+        })
+        .collect();
+
+    let tail = if tail.is_empty() {
+        None
+    } else {
+        Some((tail.to_owned(), tail_entities))
+    };
+    ((head, head_entities), tail)
+}
+
+/// A caption shortened to fit MAX_CAPTION_LENGTH
+pub(crate) struct FittedCaption {
+    pub(crate) caption: String,
+    pub(crate) entities: Vec<MessageEntity>,
+    pub(crate) overflow: Option<TextEntities>,
+}
+
+impl Bot {
+    /// Shorten a caption according to the configured CaptionOverflow, returning None if
+    /// the caption should be sent unchanged
+    pub(crate) fn fit_caption(
+        &self,
+        caption: Option<&str>,
+        entities: Option<&Vec<MessageEntity>>,
+        parse_mode: bool,
+    ) -> Option<FittedCaption> {
+        let mode = self.get_caption_overflow();
+        let caption = caption?;
+        if mode == CaptionOverflow::Ignore || parse_mode || utf16_len(caption) <= MAX_CAPTION_LENGTH
+        {
+            return None;
+        }
+        let ((caption, entities), overflow) = split_text(
+            caption,
+            entities.map(|v| v.as_slice()).unwrap_or_default(),
+            MAX_CAPTION_LENGTH,
+        );
+        Some(FittedCaption {
+            caption,
+            entities,
+            overflow: overflow.filter(|_| mode == CaptionOverflow::Spill),
+        })
+    }
+
+    /// Send caption overflow text as replies to a message, splitting it into as many
+    /// messages as needed
+    pub(crate) async fn spill_caption(
+        &self,
+        message: &Message,
+        overflow: TextEntities,
+    ) -> BotResult<()> {
+        let reply = ReplyParametersBuilder::new(message.get_message_id()).build();
+        let mut rest = Some(overflow);
+        while let Some((text, entities)) = rest.take() {
+            let ((text, entities), tail) = split_text(&text, &entities, MAX_MESSAGE_LENGTH);
+            self.build_send_message(message.get_chat().get_id(), &text)
+                .entities(&entities)
+                .reply_parameters(&reply)
+                .build()
+                .await?;
+            rest = tail;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(offset: i64, length: i64) -> MessageEntity {
+        let mut entity = MessageEntity::default();
+        entity.set_offset(offset);
+        entity.set_length(length);
+        entity
+    }
+
+    #[test]
+    fn split_on_whitespace() {
+        let ((head, _), tail) = split_text("hello world", &[], 8);
+        assert_eq!(head, "hello");
+        assert_eq!(tail.unwrap().0, "world");
+    }
+
+    #[test]
+    fn split_entity() {
+        let ((head, head_entities), tail) = split_text("aaaaaaaaaa", &[entity(1, 6)], 5);
+        let (tail, tail_entities) = tail.unwrap();
+        assert_eq!(head, "aaaaa");
+        assert_eq!(head_entities[0].get_length(), 4);
+        assert_eq!(tail, "aaaaa");
+        assert_eq!(tail_entities[0].get_offset(), 0);
+        assert_eq!(tail_entities[0].get_length(), 2);
+    }
+
+    #[test]
+    fn count_utf16() {
+        assert_eq!(utf16_len("🙂a"), 3);
+        let ((head, _), _) = split_text("🙂🙂", &[], 3);
+        assert_eq!(head, "🙂");
+    }
+
+    #[test]
+    fn keep_entity_whole() {
+        let ((head, head_entities), tail) = split_text("aaaaaaaaaa", &[entity(3, 4)], 5);
+        assert_eq!(head, "aaa");
+        assert!(head_entities.is_empty());
+        assert_eq!(tail.unwrap().1[0].get_offset(), 0);
+    }
+}
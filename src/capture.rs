@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Sink receiving a Capture for every call when debug capture is enabled
+pub type CaptureSink = UnboundedSender<Capture>;
+
+/// A single method call and the raw response received from telegram, with the bot token
+/// redacted. Serializes to json so it can be attached to bug reports as-is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capture {
+    method: String,
+    request: serde_json::Value,
+    multipart: bool,
+    status: u16,
+    response: String,
+}
+
+impl Capture {
+    pub(crate) fn new(
+        method: &str,
+        request: serde_json::Value,
+        multipart: bool,
+        status: u16,
+        response: String,
+    ) -> Self {
+        Self {
+            method: method.to_owned(),
+            request,
+            multipart,
+            status,
+            response,
+        }
+    }
+
+    /// Get the name of the method called
+    pub fn get_method(&self) -> &str {
+        &self.method
+    }
+
+    /// Get the parameters sent with the call. Files uploaded as multipart/form-data are
+    /// not included
+    pub fn get_request(&self) -> &serde_json::Value {
+        &self.request
+    }
+
+    /// Check if the call uploaded multipart/form-data
+    pub fn get_multipart(&self) -> bool {
+        self.multipart
+    }
+
+    /// Get the http status of the response
+    pub fn get_status(&self) -> u16 {
+        self.status
+    }
+
+    /// Get the raw response body
+    pub fn get_response(&self) -> &str {
+        &self.response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::BotBuilder;
+
+    #[test]
+    fn response_is_redacted() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let bot = BotBuilder::new("1234:secret")
+            .unwrap()
+            .debug_capture(tx)
+            .build();
+        let raw = br#"{"ok":true,"result":"https://api.telegram.org/file/bot1234:secret/a.jpg"}"#;
+        bot.capture(
+            "getFile",
+            Some(&serde_json::json!({"file_id": "a"})),
+            false,
+            200,
+            raw,
+        );
+        let capture = rx.try_recv().unwrap();
+        assert_eq!(capture.get_method(), "getFile");
+        assert_eq!(capture.get_request()["file_id"], "a");
+        assert!(!capture.get_response().contains("secret"));
+        assert!(capture.get_response().contains("[REDACTED]"));
+    }
+}
@@ -0,0 +1,295 @@
+use std::sync::{Arc, Mutex};
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_methods::SendMessageParams;
+use crate::gen_types::{ChatHandle, FileData, Message, MsgId};
+use crate::scheduler::Scheduler;
+
+/// Content of a channel post
+#[derive(Debug)]
+pub struct Post {
+    media: Option<PostMedia>,
+    text: Option<String>,
+}
+
+#[derive(Debug)]
+enum PostMedia {
+    Photo(FileData),
+    Video(FileData),
+    Animation(FileData),
+    Document(FileData),
+}
+
+impl Post {
+    /// A text post
+    pub fn text<T: Into<String>>(text: T) -> Self {
+        Self {
+            media: None,
+            text: Some(text.into()),
+        }
+    }
+
+    /// A photo post
+    pub fn photo(photo: FileData) -> Self {
+        Self::media(PostMedia::Photo(photo))
+    }
+
+    /// A video post
+    pub fn video(video: FileData) -> Self {
+        Self::media(PostMedia::Video(video))
+    }
+
+    /// An animation post
+    pub fn animation(animation: FileData) -> Self {
+        Self::media(PostMedia::Animation(animation))
+    }
+
+    /// A document post
+    pub fn document(document: FileData) -> Self {
+        Self::media(PostMedia::Document(document))
+    }
+
+    fn media(media: PostMedia) -> Self {
+        Self {
+            media: Some(media),
+            text: None,
+        }
+    }
+
+    /// Set the caption of a media post, or replace the text of a text post
+    pub fn caption<T: Into<String>>(mut self, caption: T) -> Self {
+        self.text = Some(caption.into());
+        self
+    }
+}
+
+impl From<&str> for Post {
+    fn from(value: &str) -> Self {
+        Self::text(value)
+    }
+}
+
+impl From<String> for Post {
+    fn from(value: String) -> Self {
+        Self::text(value)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChannelState {
+    /// The latest post and whether it was text, since media is edited through its caption
+    last: Option<(MsgId, bool)>,
+}
+
+/// Posts to a channel with the same options every time, remembering the latest post.
+/// Pins are tracked by the bot like Bot::replace_pinned, so both see the same current
+/// pin. Options left unset fall back to the bot defaults. Cloning is cheap and clones
+/// share the latest post
+#[derive(Debug, Clone)]
+pub struct Channel {
+    chat: ChatHandle,
+    silent: Option<bool>,
+    protect_content: Option<bool>,
+    effect: Option<String>,
+    state: Arc<Mutex<ChannelState>>,
+}
+
+/// Send a post builder with the options of a channel and an optional caption
+macro_rules! send_post {
+    ($channel:expr, $call:expr, $caption:expr) => {{
+        let mut call = $call;
+        if let Some(silent) = $channel.silent {
+            call = call.disable_notification(silent);
+        }
+        if let Some(protect_content) = $channel.protect_content {
+            call = call.protect_content(protect_content);
+        }
+        if let Some(ref effect) = $channel.effect {
+            call = call.message_effect_id(effect);
+        }
+        if let Some(caption) = $caption {
+            call = call.caption(caption);
+        }
+        call.build().await
+    }};
+}
+
+impl Channel {
+    /// Post to a channel by id or @username
+    pub fn new<V: Into<ChatHandle>>(chat: V) -> Self {
+        Self {
+            chat: chat.into(),
+            silent: None,
+            protect_content: None,
+            effect: None,
+            state: Arc::new(Mutex::new(ChannelState::default())),
+        }
+    }
+
+    /// Post without notifying subscribers, and pin without notifying
+    pub fn silent(mut self, silent: bool) -> Self {
+        self.silent = Some(silent);
+        self
+    }
+
+    /// Prevent posts from being forwarded or saved
+    pub fn protect_content(mut self, protect_content: bool) -> Self {
+        self.protect_content = Some(protect_content);
+        self
+    }
+
+    /// Show a message effect on every post. Effects only show in private chats, so this
+    /// is for channels mirrored to users
+    pub fn message_effect<T: Into<String>>(mut self, effect_id: T) -> Self {
+        self.effect = Some(effect_id.into());
+        self
+    }
+
+    /// Get the channel
+    pub fn get_chat(&self) -> &'_ ChatHandle {
+        &self.chat
+    }
+
+    /// Get the latest post made through this channel
+    pub fn get_last_post(&self) -> Option<MsgId> {
+        self.state.lock().unwrap().last.map(|(id, _)| id)
+    }
+
+    /// Get the message pinned through this channel or Bot::replace_pinned
+    pub fn get_pinned(&self, bot: &Bot) -> Option<MsgId> {
+        bot.pins().get(&self.chat)
+    }
+
+    /// Publish a post, remembering it as the latest one
+    pub async fn post<P: Into<Post>>(&self, bot: &Bot, post: P) -> BotResult<Message> {
+        let post = post.into();
+        let chat = self.chat.clone();
+        let caption = post.text.as_deref();
+        let is_text = post.media.is_none();
+        let message = match post.media {
+            None => send_post!(
+                self,
+                bot.build_send_message(chat, caption.unwrap_or_default()),
+                None::<&str>
+            ),
+            Some(PostMedia::Photo(file)) => {
+                send_post!(self, bot.build_send_photo(chat, file), caption)
+            }
+            Some(PostMedia::Video(file)) => {
+                send_post!(self, bot.build_send_video(chat, file), caption)
+            }
+            Some(PostMedia::Animation(file)) => {
+                send_post!(self, bot.build_send_animation(chat, file), caption)
+            }
+            Some(PostMedia::Document(file)) => {
+                send_post!(self, bot.build_send_document(chat, file), caption)
+            }
+        }?;
+        self.state.lock().unwrap().last = Some((message.get_message_id(), is_text));
+        Ok(message)
+    }
+
+    /// Replace the text of the latest post, or the caption if it has media. Returns
+    /// false if nothing was posted yet
+    pub async fn edit_last(&self, bot: &Bot, text: &str) -> BotResult<bool> {
+        let Some((id, is_text)) = self.state.lock().unwrap().last else {
+            return Ok(false);
+        };
+        if is_text {
+            bot.build_edit_message_text(text)
+                .chat_id(self.chat.clone())
+                .message_id(id)
+                .build()
+                .await?;
+        } else {
+            bot.build_edit_message_caption()
+                .chat_id(self.chat.clone())
+                .message_id(id)
+                .caption(text)
+                .build()
+                .await?;
+        }
+        Ok(true)
+    }
+
+    /// Pin a message, first unpinning the message previously pinned through this
+    /// channel or Bot::replace_pinned if unpin_old is set. Unlike unpinning everything
+    /// this leaves messages pinned by admins alone
+    pub async fn pin(&self, bot: &Bot, message_id: MsgId, unpin_old: bool) -> BotResult<()> {
+        let old = self
+            .get_pinned(bot)
+            .filter(|old| unpin_old && *old != message_id);
+        bot.pin_tracked(self.chat.clone(), message_id, old, self.silent, false)
+            .await
+    }
+
+    /// Pin the latest post, returning false if nothing was posted yet
+    pub async fn pin_last(&self, bot: &Bot, unpin_old: bool) -> BotResult<bool> {
+        match self.get_last_post() {
+            Some(id) => self.pin(bot, id, unpin_old).await.map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Schedule a text post for a unix time with the options of this channel, returning
+    /// the job id. Scheduled posts are not remembered as the latest post
+    pub async fn schedule_text<T: Into<String>>(
+        &self,
+        scheduler: &Scheduler,
+        text: T,
+        time: i64,
+    ) -> BotResult<u64> {
+        let params = SendMessageParams {
+            chat_id: self.chat.clone(),
+            text: text.into(),
+            disable_notification: self.silent,
+            protect_content: self.protect_content,
+            message_effect_id: self.effect.clone(),
+            ..Default::default()
+        };
+        scheduler.schedule_at(&params, time).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+
+    #[tokio::test]
+    async fn posts_and_pins() {
+        let test = TestBot::new().await.unwrap();
+        let bot = test.get_bot();
+        let channel = Channel::new(-100).silent(true);
+        assert!(!channel.edit_last(bot, "nothing").await.unwrap());
+
+        let first = channel.post(bot, "first").await.unwrap();
+        channel.pin_last(bot, true).await.unwrap();
+        channel
+            .post(
+                bot,
+                Post::photo(FileData::String("file".to_owned())).caption("second"),
+            )
+            .await
+            .unwrap();
+        channel.pin_last(bot, true).await.unwrap();
+        assert!(channel.edit_last(bot, "edited").await.unwrap());
+        assert_eq!(channel.get_pinned(bot), channel.get_last_post());
+        assert_eq!(
+            bot.replace_pinned(-100, first.get_message_id(), false)
+                .await
+                .unwrap(),
+            channel.get_last_post()
+        );
+
+        crate::assert_sent!(test, method = "sendMessage", disable_notification = true);
+        crate::assert_sent!(test, method = "sendPhoto", caption = "second");
+        crate::assert_sent!(
+            test,
+            method = "unpinChatMessage",
+            message_id = first.get_message_id()
+        );
+        crate::assert_sent!(test, method = "editMessageCaption", caption = "edited");
+        assert_eq!(test.sent("pinChatMessage").len(), 2);
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+/// State of a CircuitBreaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    /// Requests are sent normally
+    Closed,
+    /// Telegram is failing, requests wait for the cooldown to pass
+    Open,
+    /// The cooldown passed, a single trial request decides whether to close or reopen
+    HalfOpen,
+}
+
+type StateListener = Arc<dyn Fn(CircuitState) + Send + Sync>;
+
+#[derive(Debug)]
+struct BreakerState {
+    state: CircuitState,
+    failures: usize,
+    opened: Option<Instant>,
+    trial: Option<Instant>,
+}
+
+/// Pauses outgoing requests while telegram is down for maintenance. After a number of
+/// consecutive failures, bad gateway or gateway timeout responses and requests that
+/// couldn't reach telegram at all, the breaker opens and requests wait for a cooldown
+/// instead of failing immediately, so polling loops don't spin during outages. Once the
+/// cooldown passes one trial request is let through while the rest keep waiting for
+/// its result. A trial that never reports back, like one cancelled by a deadline, is
+/// replaced by another after the cooldown
+pub struct CircuitBreaker {
+    threshold: usize,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+    changed: Notify,
+    listener: Option<StateListener>,
+}
+
+impl Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("threshold", &self.threshold)
+            .field("cooldown", &self.cooldown)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(30))
+    }
+}
+
+/// Check if an http status means telegram's servers are unavailable
+fn is_outage(status: u16) -> bool {
+    matches!(status, 502 | 503 | 504)
+}
+
+impl CircuitBreaker {
+    /// Open after `threshold` consecutive failures, pausing requests for `cooldown`
+    pub fn new(threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                failures: 0,
+                opened: None,
+                trial: None,
+            }),
+            changed: Notify::new(),
+            listener: None,
+        }
+    }
+
+    /// Call a function every time the breaker changes state
+    pub fn on_state_change<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(CircuitState) + Send + Sync + 'static,
+    {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Get the current state
+    pub fn get_state(&self) -> CircuitState {
+        self.state.lock().unwrap().state
+    }
+
+    fn transition(&self, state: &mut BreakerState, next: CircuitState) {
+        if state.state == next {
+            return;
+        }
+        state.state = next;
+        self.changed.notify_waiters();
+        log::info!("circuit breaker {:?}", next);
+        if let Some(ref listener) = self.listener {
+            listener(next);
+        }
+    }
+
+    /// Wait until the breaker is closed or this request is picked as the trial request
+    /// after the cooldown
+    pub(crate) async fn wait(&self) {
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            let remaining = {
+                let mut state = self.state.lock().unwrap();
+                let since = match state.state {
+                    CircuitState::Closed => return,
+                    CircuitState::Open => state.opened,
+                    CircuitState::HalfOpen => state.trial,
+                };
+                match since.and_then(|since| self.cooldown.checked_sub(since.elapsed())) {
+                    Some(remaining) if !remaining.is_zero() => remaining,
+                    _ => {
+                        state.trial = Some(Instant::now());
+                        self.transition(&mut state, CircuitState::HalfOpen);
+                        return;
+                    }
+                }
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(remaining) => (),
+                _ = changed => (),
+            }
+        }
+    }
+
+    /// Record the http status of a response, or None if the request failed before
+    /// getting one, like on connection errors and timeouts
+    pub(crate) fn record(&self, status: Option<u16>) {
+        let mut state = self.state.lock().unwrap();
+        state.trial = None;
+        let failed = match status {
+            Some(status) => is_outage(status),
+            None => true,
+        };
+        if failed {
+            state.failures += 1;
+            if state.state == CircuitState::HalfOpen || state.failures >= self.threshold {
+                state.opened = Some(Instant::now());
+                self.transition(&mut state, CircuitState::Open);
+            }
+        } else {
+            state.failures = 0;
+            state.opened = None;
+            self.transition(&mut state, CircuitState::Closed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn opens_and_recovers() {
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&changes);
+        let breaker =
+            CircuitBreaker::new(2, Duration::from_millis(10)).on_state_change(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        breaker.record(Some(502));
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+        breaker.record(None);
+        assert_eq!(breaker.get_state(), CircuitState::Open);
+        breaker.wait().await;
+        assert_eq!(breaker.get_state(), CircuitState::HalfOpen);
+        breaker.record(Some(502));
+        assert_eq!(breaker.get_state(), CircuitState::Open);
+        breaker.wait().await;
+        breaker.record(Some(200));
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+        assert_eq!(changes.load(Ordering::Relaxed), 5);
+    }
+
+    #[tokio::test]
+    async fn single_trial_request() {
+        let breaker = Arc::new(CircuitBreaker::new(1, Duration::from_millis(10)));
+        breaker.record(Some(503));
+        breaker.wait().await;
+        assert_eq!(breaker.get_state(), CircuitState::HalfOpen);
+
+        let waiting = Arc::clone(&breaker);
+        let waiter = tokio::spawn(async move { waiting.wait().await });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(!waiter.is_finished());
+        breaker.record(Some(200));
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+    }
+}
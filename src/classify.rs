@@ -0,0 +1,184 @@
+use crate::bot::{ApiError, Bot, BotResult};
+use crate::errors::ApiErrorKind;
+
+/// Common telegram errors that usually mean there is nothing left to do rather than
+/// that a request failed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BenignError {
+    /// An edit would not change the message
+    NotModified,
+    /// A callback query was answered too late or was already answered
+    QueryTooOld,
+    /// The message to delete was already deleted
+    MessageToDeleteNotFound,
+    /// The message to edit was deleted
+    MessageToEditNotFound,
+    /// An error matching a pattern added with ErrorClassifier::pattern
+    Custom(String),
+}
+
+impl BenignError {
+    const BUILTIN: &'static [BenignError] = &[
+        BenignError::NotModified,
+        BenignError::QueryTooOld,
+        BenignError::MessageToDeleteNotFound,
+        BenignError::MessageToEditNotFound,
+    ];
+
+    /// Get the builtin benign error for a kind of api error, if it has one
+    pub fn from_kind(kind: ApiErrorKind) -> Option<Self> {
+        match kind {
+            ApiErrorKind::NotModified => Some(Self::NotModified),
+            ApiErrorKind::QueryTooOld => Some(Self::QueryTooOld),
+            ApiErrorKind::MessageToDeleteNotFound => Some(Self::MessageToDeleteNotFound),
+            ApiErrorKind::MessageToEditNotFound => Some(Self::MessageToEditNotFound),
+            _ => None,
+        }
+    }
+}
+
+/// Decides which errors returned by telegram are benign, see BotBuilder::error_classifier
+#[derive(Debug, Clone)]
+pub struct ErrorClassifier {
+    kinds: Vec<BenignError>,
+    patterns: Vec<String>,
+}
+
+impl Default for ErrorClassifier {
+    fn default() -> Self {
+        Self {
+            kinds: BenignError::BUILTIN.to_vec(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl ErrorClassifier {
+    /// Treat all builtin BenignError kinds as benign
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat no errors as benign, for adding kinds one at a time
+    pub fn empty() -> Self {
+        Self {
+            kinds: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Treat a builtin kind as benign
+    pub fn kind(mut self, kind: BenignError) -> Self {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+        self
+    }
+
+    /// Treat errors with a description containing `pattern`, ignoring case, as
+    /// BenignError::Custom
+    pub fn pattern<T: Into<String>>(mut self, pattern: T) -> Self {
+        self.patterns.push(pattern.into().to_lowercase());
+        self
+    }
+
+    /// Classify an error, returning None if it should be treated as a failure
+    pub fn classify(&self, err: &ApiError) -> Option<BenignError> {
+        let builtin = err
+            .get_kind()
+            .and_then(BenignError::from_kind)
+            .filter(|kind| self.kinds.contains(kind));
+        if builtin.is_some() {
+            return builtin;
+        }
+        let description = err.get_response()?.description.as_deref()?.to_lowercase();
+        self.patterns
+            .iter()
+            .find(|p| description.contains(p.as_str()))
+            .map(|p| BenignError::Custom(p.clone()))
+    }
+}
+
+/// Result of a request where benign errors are not failures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome<T> {
+    /// The request succeeded
+    Done(T),
+    /// Telegram returned a benign error
+    Benign(BenignError),
+}
+
+impl<T> Outcome<T> {
+    /// Get the result of the request, None if it returned a benign error
+    pub fn ok(self) -> Option<T> {
+        match self {
+            Outcome::Done(v) => Some(v),
+            Outcome::Benign(_) => None,
+        }
+    }
+}
+
+/// Extension for BotResult separating benign errors from failures
+pub trait BotResultExt<T> {
+    /// Map errors the bot's ErrorClassifier considers benign to Outcome::Benign
+    fn non_fatal(self, bot: &Bot) -> BotResult<Outcome<T>>;
+}
+
+impl<T> BotResultExt<T> for BotResult<T> {
+    fn non_fatal(self, bot: &Bot) -> BotResult<Outcome<T>> {
+        match self {
+            Ok(v) => Ok(Outcome::Done(v)),
+            Err(err) => match bot.classify_error(&err) {
+                Some(kind) => Ok(Outcome::Benign(kind)),
+                None => Err(err),
+            },
+        }
+    }
+}
+
+impl Bot {
+    /// Classify an error using the configured ErrorClassifier. Without one no errors are
+    /// benign
+    pub fn classify_error(&self, err: &ApiError) -> Option<BenignError> {
+        self.get_error_classifier()?.classify(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Response;
+
+    fn error(description: &str) -> ApiError {
+        ApiError::from_response(Response {
+            ok: false,
+            error_code: Some(400),
+            description: Some(description.to_owned()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn classify_descriptions() {
+        let classifier = ErrorClassifier::empty()
+            .kind(BenignError::QueryTooOld)
+            .pattern("Chat Not Found");
+        let too_old = error("Bad Request: query is too old and response timeout expired");
+        assert_eq!(
+            classifier.classify(&too_old),
+            Some(BenignError::QueryTooOld)
+        );
+        assert_eq!(
+            classifier.classify(&error("Bad Request: message is not modified")),
+            None
+        );
+        assert_eq!(
+            classifier.classify(&error("Bad Request: chat not found")),
+            Some(BenignError::Custom("chat not found".to_owned()))
+        );
+        assert_eq!(
+            ErrorClassifier::new().classify(&error("Bad Request: message is not modified")),
+            Some(BenignError::NotModified)
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::bot::{Bot, BotResult};
+use crate::gen_methods::{DeleteMyCommandsParams, GetMyCommandsParams, SetMyCommandsParams};
+use crate::gen_types::{BotCommand, BotCommandScope};
+
+#[derive(Debug, Clone)]
+struct ScopedCommands {
+    scope: BotCommandScope,
+    language_code: Option<String>,
+    commands: Vec<BotCommand>,
+}
+
+/// Declarative management of the bot's command lists. The desired commands for each
+/// scope and language are compared against get_my_commands and only scopes that differ
+/// are updated
+#[derive(Debug, Clone, Default)]
+pub struct CommandScopeManager {
+    scopes: Vec<ScopedCommands>,
+}
+
+impl CommandScopeManager {
+    /// Create a manager with no scopes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the desired commands for a scope and optional language, replacing any commands
+    /// previously set for it. An empty list deletes the commands for the scope
+    pub fn commands(
+        mut self,
+        scope: BotCommandScope,
+        language_code: Option<&str>,
+        commands: Vec<BotCommand>,
+    ) -> Self {
+        let language_code = language_code.map(|v| v.to_owned());
+        self.scopes
+            .retain(|s| s.scope != scope || s.language_code != language_code);
+        self.scopes.push(ScopedCommands {
+            scope,
+            language_code,
+            commands,
+        });
+        self
+    }
+
+    /// Delete the commands for a scope and optional language
+    pub fn remove(self, scope: BotCommandScope, language_code: Option<&str>) -> Self {
+        self.commands(scope, language_code, Vec::new())
+    }
+
+    /// Sync all scopes with telegram, returning the number of scopes that were changed
+    pub async fn sync(&self, bot: &Bot) -> BotResult<usize> {
+        let mut changed = 0;
+        for scoped in self.scopes.iter() {
+            let current = bot
+                .call(GetMyCommandsParams {
+                    scope: Some(scoped.scope.clone()),
+                    language_code: scoped.language_code.clone(),
+                })
+                .await?;
+            if current == scoped.commands {
+                continue;
+            }
+
+            if scoped.commands.is_empty() {
+                bot.call(DeleteMyCommandsParams {
+                    scope: Some(scoped.scope.clone()),
+                    language_code: scoped.language_code.clone(),
+                })
+                .await?;
+            } else {
+                bot.call(SetMyCommandsParams {
+                    commands: scoped.commands.clone(),
+                    scope: Some(scoped.scope.clone()),
+                    language_code: scoped.language_code.clone(),
+                })
+                .await?;
+            }
+            changed += 1;
+        }
+        Ok(changed)
+    }
+}
@@ -0,0 +1,74 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::bot::{ApiError, Bot, BotResult};
+use crate::gen_types::{User, WebhookInfo, TELEGRAM_BOT_API_VERSION};
+
+/// Find the keys of a json object that are lost when deserializing it as T, meaning T
+/// was generated from an older version of the api
+fn unknown_fields<T>(raw: &serde_json::Value) -> BotResult<Vec<String>>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let parsed = serde_json::to_value(serde_json::from_value::<T>(raw.clone())?)?;
+    let Some(raw) = raw.as_object() else {
+        return Ok(Vec::new());
+    };
+    Ok(raw
+        .keys()
+        .filter(|k| parsed.get(k.as_str()).is_none())
+        .cloned()
+        .collect())
+}
+
+impl Bot {
+    async fn probe_fields<T>(&self, method: &str) -> BotResult<Vec<String>>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let resp = self.post_empty(method).await?;
+        if !resp.ok {
+            return Err(ApiError::from_response(resp));
+        }
+        let fields = unknown_fields::<T>(&resp.result.unwrap_or_default())?;
+        Ok(fields
+            .into_iter()
+            .map(|f| format!("{}.{}", method, f))
+            .collect())
+    }
+
+    /// Check if the server returns fields from a newer version of the api than
+    /// TELEGRAM_BOT_API_VERSION by inspecting the results of getMe and getWebhookInfo.
+    /// A warning is logged and the unknown fields are returned if any are found
+    pub async fn assert_api_compat(&self) -> BotResult<Vec<String>> {
+        let mut unknown = self.probe_fields::<User>("getMe").await?;
+        unknown.extend(self.probe_fields::<WebhookInfo>("getWebhookInfo").await?);
+        if !unknown.is_empty() {
+            log::warn!(
+                "server supports a newer bot api than {}, unknown fields: {}",
+                TELEGRAM_BOT_API_VERSION,
+                unknown.join(", ")
+            );
+        }
+        Ok(unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_unknown_fields() {
+        let raw = serde_json::json!({
+            "id": 1,
+            "is_bot": true,
+            "first_name": "bot",
+            "has_future_feature": true
+        });
+        assert_eq!(
+            unknown_fields::<User>(&raw).unwrap(),
+            vec!["has_future_feature".to_owned()]
+        );
+    }
+}
@@ -0,0 +1,1095 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures_core::Stream;
+use futures_util::future::BoxFuture;
+use futures_util::{FutureExt, StreamExt};
+use serde::Serialize;
+
+use crate::bot::{ApiError, Bot, BotResult};
+use crate::gen_types::{ChatJoinRequest, ChosenInlineResult, Message, Update, UpdateExt, UpdateId};
+
+tokio::task_local! {
+    static UPDATE_ID: Option<UpdateId>;
+}
+
+/// Get the update_id of the update being dispatched, if it is known. Only set inside
+/// layers and handlers awaited by a Dispatcher, not in tasks they spawn
+pub fn current_update_id() -> Option<UpdateId> {
+    UPDATE_ID.try_with(|update_id| *update_id).ok().flatten()
+}
+
+/// A handler for incoming updates. This is implemented for any async function or closure
+/// taking a Bot and an UpdateExt
+pub trait Handler: Send + Sync {
+    /// Handle a single update
+    fn handle(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<()>>;
+}
+
+impl<F, Fut> Handler for F
+where
+    F: Fn(Bot, UpdateExt) -> Fut + Send + Sync,
+    Fut: Future<Output = BotResult<()>> + Send + 'static,
+{
+    fn handle(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<()>> {
+        Box::pin(self(bot, update))
+    }
+}
+
+/// Whether an update should continue to the next layer and the handlers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flow {
+    /// Pass the update on
+    Continue,
+    /// The update was consumed by this layer
+    Stop,
+}
+
+/// Middleware run before handlers. Layers run in the order they were added and can stop
+/// an update from reaching later layers and handlers
+pub trait Layer: Send + Sync {
+    /// Inspect or act on an update before it reaches the handlers
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>>;
+}
+
+impl<F, Fut> Layer for F
+where
+    F: Fn(Bot, UpdateExt) -> Fut + Send + Sync,
+    Fut: Future<Output = BotResult<Flow>> + Send + 'static,
+{
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        Box::pin(self(bot, update))
+    }
+}
+
+/// How a Dispatcher treats edited messages and channel posts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EditPolicy {
+    /// Pass edits on as EditedMessage and EditedChannelPost updates, for handlers added
+    /// with on_edited
+    #[default]
+    Separate,
+    /// Pass edits on as Message and ChannelPost updates, so handlers treat an edited
+    /// message like a new one
+    AsNew,
+    /// Drop edits before they reach any layer or handler
+    Ignore,
+}
+
+/// A layer or handler was cancelled for running longer than the dispatcher's timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandlerTimeout {
+    limit: Duration,
+}
+
+impl HandlerTimeout {
+    /// Get the timeout that was exceeded
+    pub fn get_limit(&self) -> Duration {
+        self.limit
+    }
+}
+
+impl std::fmt::Display for HandlerTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out after {:?}", self.limit)
+    }
+}
+
+/// The way a layer or handler failed
+#[derive(Debug)]
+pub enum HandlerFailure {
+    /// The handler returned an error
+    Error(ApiError),
+    /// The handler panicked, with the panic message if it was a string
+    Panic(String),
+    /// The handler was cancelled by the dispatcher's handler_timeout
+    Timeout(HandlerTimeout),
+}
+
+impl std::fmt::Display for HandlerFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error(err) => write!(f, "{}", err),
+            Self::Panic(msg) => write!(f, "panicked: {}", msg),
+            Self::Timeout(timeout) => write!(f, "{}", timeout),
+        }
+    }
+}
+
+/// A failed layer or handler along with the update it was handling
+#[derive(Debug)]
+pub struct HandlerError {
+    update_id: Option<UpdateId>,
+    update: UpdateExt,
+    failure: HandlerFailure,
+}
+
+impl HandlerError {
+    /// Get the id of the update, if it was dispatched with dispatch_update
+    pub fn get_update_id(&self) -> Option<UpdateId> {
+        self.update_id
+    }
+
+    /// Get the update being handled
+    pub fn get_update(&self) -> &UpdateExt {
+        &self.update
+    }
+
+    /// Get the error or panic
+    pub fn get_failure(&self) -> &HandlerFailure {
+        &self.failure
+    }
+
+    /// Get the name of the kind of update, like "Message" or "CallbackQuery"
+    pub fn update_kind(&self) -> &'static str {
+        self.update.get_kind()
+    }
+}
+
+impl std::fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.update_id {
+            Some(id) => write!(f, "{} update {}: {}", self.update_kind(), id, self.failure),
+            None => write!(f, "{} update: {}", self.update_kind(), self.failure),
+        }
+    }
+}
+
+/// Updates a Dispatcher can run. Update and (UpdateId, UpdateExt) keep the update_id
+/// for error reports and tracing, a bare UpdateExt doesn't
+pub trait IntoDispatch {
+    /// Split into the update_id, if known, and the update
+    fn into_dispatch(self) -> (Option<UpdateId>, UpdateExt);
+}
+
+impl IntoDispatch for Update {
+    fn into_dispatch(self) -> (Option<UpdateId>, UpdateExt) {
+        (Some(self.get_update_id()), self.into())
+    }
+}
+
+impl IntoDispatch for (UpdateId, UpdateExt) {
+    fn into_dispatch(self) -> (Option<UpdateId>, UpdateExt) {
+        (Some(self.0), self.1)
+    }
+}
+
+impl IntoDispatch for UpdateExt {
+    fn into_dispatch(self) -> (Option<UpdateId>, UpdateExt) {
+        (None, self)
+    }
+}
+
+/// Called with every layer or handler error not considered benign
+pub trait ErrorHandler: Send + Sync {
+    /// Report a failed layer or handler
+    fn handle_error(&self, bot: Bot, error: HandlerError) -> BoxFuture<'static, ()>;
+}
+
+impl<F, Fut> ErrorHandler for F
+where
+    F: Fn(Bot, HandlerError) -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn handle_error(&self, bot: Bot, error: HandlerError) -> BoxFuture<'static, ()> {
+        Box::pin(self(bot, error))
+    }
+}
+
+/// Get the message from a panic payload
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|msg| (*msg).to_owned())
+            .unwrap_or_else(|| "unknown panic".to_owned()),
+    }
+}
+
+/// Counters updated by a Dispatcher and all its clones
+#[derive(Debug, Default)]
+struct Stats {
+    last_update: Mutex<Option<SystemTime>>,
+    in_flight: AtomicUsize,
+    updates: AtomicU64,
+    errors: AtomicU64,
+    probed: Mutex<Option<(Instant, Option<bool>)>>,
+}
+
+/// Outcome counters of one handler, shared by the clones of a Dispatcher and by
+/// handlers with the same name
+#[derive(Debug, Default)]
+struct HandlerCounters {
+    handled: AtomicU64,
+    ignored: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl HandlerCounters {
+    fn snapshot(&self) -> HandlerStats {
+        HandlerStats {
+            handled: self.handled.load(Ordering::Relaxed),
+            ignored: self.ignored.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A handler as run by a Dispatcher, telling if it took the update so skipped updates
+/// are counted as ignored
+trait Route: Send + Sync {
+    /// Handle an update, returning false if the update was not for this handler
+    fn route(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<bool>>;
+}
+
+/// Route of a Handler, which takes every update
+struct Every<H>(H);
+
+impl<H: Handler> Route for Every<H> {
+    fn route(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<bool>> {
+        let fut = self.0.handle(bot, update);
+        Box::pin(async move { fut.await.map(|()| true) })
+    }
+}
+
+/// Route of a closure returning if it took the update
+struct Matching<F>(F);
+
+impl<F, Fut> Route for Matching<F>
+where
+    F: Fn(Bot, UpdateExt) -> Fut + Send + Sync,
+    Fut: Future<Output = BotResult<bool>> + Send + 'static,
+{
+    fn route(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<bool>> {
+        Box::pin((self.0)(bot, update))
+    }
+}
+
+/// A handler added to a Dispatcher with the name and counters of its stats
+#[derive(Clone)]
+struct Registered {
+    name: String,
+    counters: Arc<HandlerCounters>,
+    route: Arc<dyn Route>,
+}
+
+/// Decrements the in flight counter when an update finishes dispatching, even if the
+/// future was dropped
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Snapshot of the state of a Dispatcher for health checks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Health {
+    last_update: Option<u64>,
+    in_flight: usize,
+    updates: u64,
+    errors: u64,
+    reachable: Option<bool>,
+}
+
+impl Health {
+    /// Get the unix time in seconds the last update was received at, None if no updates
+    /// were received yet
+    pub fn get_last_update(&self) -> Option<u64> {
+        self.last_update
+    }
+
+    /// Get the number of updates currently being processed
+    pub fn get_in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Get the number of updates received
+    pub fn get_updates(&self) -> u64 {
+        self.updates
+    }
+
+    /// Get the number of failed layer and handler calls
+    pub fn get_errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Get the number of failed layer and handler calls per update received
+    pub fn get_error_rate(&self) -> f64 {
+        if self.updates == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.updates as f64
+        }
+    }
+
+    /// Check if telegram answered the last probe, None if the dispatcher was never probed
+    pub fn get_reachable(&self) -> Option<bool> {
+        self.reachable
+    }
+}
+
+/// Outcomes of the updates dispatched to one handler
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct HandlerStats {
+    handled: u64,
+    ignored: u64,
+    errors: u64,
+}
+
+impl HandlerStats {
+    /// Get the number of updates the handler returned Ok for
+    pub fn get_handled(&self) -> u64 {
+        self.handled
+    }
+
+    /// Get the number of updates that never reached the handler because the edit policy
+    /// dropped them or a layer stopped them, plus the updates it returned a benign error
+    /// for
+    pub fn get_ignored(&self) -> u64 {
+        self.ignored
+    }
+
+    /// Get the number of updates the handler failed, panicked or timed out on
+    pub fn get_errors(&self) -> u64 {
+        self.errors
+    }
+}
+
+impl std::fmt::Display for HandlerStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} handled, {} ignored, {} errors",
+            self.handled, self.ignored, self.errors
+        )
+    }
+}
+
+/// Routes updates through a list of layers and then to a list of handlers. Every handler
+/// receives every update not stopped by a layer in the order they were added, errors and
+/// panics are passed to the error handler and do not stop later handlers from running.
+/// Errors the bot's ErrorClassifier considers benign are only logged at debug level.
+/// Cloning a Dispatcher is cheap
+#[derive(Clone, Default)]
+pub struct Dispatcher {
+    layers: Vec<Arc<dyn Layer>>,
+    handlers: Vec<Registered>,
+    auto_names: BTreeMap<&'static str, usize>,
+    error_handler: Option<Arc<dyn ErrorHandler>>,
+    timeout: Option<Duration>,
+    slow_threshold: Option<Duration>,
+    edit_policy: EditPolicy,
+    edit_max_age: Option<Duration>,
+    summary_interval: Option<Duration>,
+    stats: Arc<Stats>,
+}
+
+impl std::fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("layers", &self.layers.len())
+            .field("handlers", &self.handlers.len())
+            .field("error_handler", &self.error_handler.is_some())
+            .field("timeout", &self.timeout)
+            .field("slow_threshold", &self.slow_threshold)
+            .field("edit_policy", &self.edit_policy)
+            .field("edit_max_age", &self.edit_max_age)
+            .field("summary_interval", &self.summary_interval)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl Dispatcher {
+    /// Create a dispatcher with no handlers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a handler to the end of the list. Its stats are kept under the name
+    /// "handler N", N counting the handlers added with this method from 0. Handlers
+    /// added by helpers like on_edited are named after the helper instead, so they
+    /// don't change the numbers
+    pub fn handler<H>(self, handler: H) -> Self
+    where
+        H: Handler + 'static,
+    {
+        self.auto_named("handler", handler)
+    }
+
+    /// Add a handler named "kind N", N counting the handlers added with the same kind
+    pub(crate) fn auto_named<H>(self, kind: &'static str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+    {
+        self.auto_routed(kind, Every(handler))
+    }
+
+    /// Add a handler named like auto_named which resolves to false for the updates it
+    /// skips, so they are counted as ignored instead of handled
+    pub(crate) fn auto_matching<F, Fut>(self, kind: &'static str, handler: F) -> Self
+    where
+        F: Fn(Bot, UpdateExt) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<bool>> + Send + 'static,
+    {
+        self.auto_routed(kind, Matching(handler))
+    }
+
+    fn auto_routed<R>(mut self, kind: &'static str, route: R) -> Self
+    where
+        R: Route + 'static,
+    {
+        let count = self.auto_names.entry(kind).or_default();
+        let name = format!("{} {}", kind, count);
+        *count += 1;
+        self.routed(name, Arc::new(route))
+    }
+
+    /// Add a handler to the end of the list, keeping its stats under name. Handlers
+    /// sharing a name share their stats
+    pub fn named_handler<T, H>(self, name: T, handler: H) -> Self
+    where
+        T: Into<String>,
+        H: Handler + 'static,
+    {
+        self.routed(name.into(), Arc::new(Every(handler)))
+    }
+
+    fn routed(mut self, name: String, route: Arc<dyn Route>) -> Self {
+        let counters = self
+            .handlers
+            .iter()
+            .find(|h| h.name == name)
+            .map(|h| Arc::clone(&h.counters))
+            .unwrap_or_default();
+        self.handlers.push(Registered {
+            name,
+            counters,
+            route,
+        });
+        self
+    }
+
+    /// Add a layer to the end of the list
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer + 'static,
+    {
+        self.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Replace the default error handler, which logs errors with the kind and id of the
+    /// update
+    pub fn error_handler<E>(mut self, error_handler: E) -> Self
+    where
+        E: ErrorHandler + 'static,
+    {
+        self.error_handler = Some(Arc::new(error_handler));
+        self
+    }
+
+    /// Cancel layers and handlers running longer than `timeout`, reporting a
+    /// HandlerFailure::Timeout. Keep this below the webhook response deadline when
+    /// handlers run before answering telegram
+    pub fn handler_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Log a warning for layers and handlers still running after `threshold`, without
+    /// cancelling them
+    pub fn slow_handler_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_threshold = Some(threshold);
+        self
+    }
+
+    /// Set how edited messages and channel posts are passed to layers and handlers
+    pub fn edit_policy(mut self, edit_policy: EditPolicy) -> Self {
+        self.edit_policy = edit_policy;
+        self
+    }
+
+    /// Drop edits made more than max_age after the message was sent, whatever the edit
+    /// policy
+    pub fn edit_max_age(mut self, max_age: Duration) -> Self {
+        self.edit_max_age = Some(max_age);
+        self
+    }
+
+    /// Log a summary of the handler stats every interval while running with
+    /// Dispatcher::run. Dispatchers driven by a WebhookService or by calling dispatch
+    /// directly don't log summaries, call log_summary on a timer instead
+    pub fn summary_interval(mut self, interval: Duration) -> Self {
+        self.summary_interval = Some(interval);
+        self
+    }
+
+    /// Apply the edit policy to an update, None if it should be dropped
+    fn apply_edit_policy(&self, update: UpdateExt) -> Option<UpdateExt> {
+        let too_old = |m: &Message| match (self.edit_max_age, m.get_edit_date()) {
+            (Some(max_age), Some(edit_date)) => {
+                edit_date.saturating_sub(m.get_date()) > max_age.as_secs() as i64
+            }
+            _ => false,
+        };
+        match (update, self.edit_policy) {
+            (UpdateExt::EditedMessage(_) | UpdateExt::EditedChannelPost(_), EditPolicy::Ignore) => {
+                None
+            }
+            (UpdateExt::EditedMessage(m) | UpdateExt::EditedChannelPost(m), _) if too_old(&m) => {
+                None
+            }
+            (UpdateExt::EditedMessage(m), EditPolicy::AsNew) => Some(UpdateExt::Message(m)),
+            (UpdateExt::EditedChannelPost(m), EditPolicy::AsNew) => Some(UpdateExt::ChannelPost(m)),
+            (update, _) => Some(update),
+        }
+    }
+
+    /// Run a layer or handler, catching panics and enforcing the timeout and slow
+    /// handler threshold
+    async fn guard<F, T>(&self, kind: &str, index: usize, fut: F) -> Result<T, HandlerFailure>
+    where
+        F: Future<Output = BotResult<T>>,
+    {
+        let start = Instant::now();
+        let mut fut = std::pin::pin!(AssertUnwindSafe(fut).catch_unwind());
+        let watched = async {
+            if let Some(threshold) = self.slow_threshold {
+                tokio::select! {
+                    res = &mut fut => return res,
+                    _ = tokio::time::sleep(threshold) => {
+                        log::warn!("{} {} still running after {:?}", kind, index, threshold)
+                    }
+                }
+            }
+            let res = (&mut fut).await;
+            if self.slow_threshold.is_some() {
+                log::warn!(
+                    "slow {} {} finished after {:?}",
+                    kind,
+                    index,
+                    start.elapsed()
+                );
+            }
+            res
+        };
+        let res = match self.timeout {
+            Some(limit) => match tokio::time::timeout(limit, watched).await {
+                Ok(res) => res,
+                Err(_) => return Err(HandlerFailure::Timeout(HandlerTimeout { limit })),
+            },
+            None => watched.await,
+        };
+        match res {
+            Ok(Ok(res)) => Ok(res),
+            Ok(Err(err)) => Err(HandlerFailure::Error(err)),
+            Err(panic) => Err(HandlerFailure::Panic(panic_message(panic))),
+        }
+    }
+
+    /// Count and report a failure to the error handler, returning false if it was a
+    /// benign error that was only logged
+    async fn report(
+        &self,
+        bot: &Bot,
+        update_id: Option<UpdateId>,
+        update: &UpdateExt,
+        failure: HandlerFailure,
+    ) -> bool {
+        if let HandlerFailure::Error(ref err) = failure {
+            if let Some(kind) = bot.classify_error(err) {
+                log::debug!("handler returned benign error {:?}: {}", kind, err);
+                return false;
+            }
+        }
+        self.stats.errors.fetch_add(1, Ordering::Relaxed);
+        let error = HandlerError {
+            update_id,
+            update: update.clone(),
+            failure,
+        };
+        match self.error_handler {
+            Some(ref handler) => handler.handle_error(bot.clone(), error).await,
+            None => log::warn!("handler failed for {}", error),
+        }
+        true
+    }
+
+    /// Count an update that did not reach any handler
+    fn ignore(&self) {
+        for registered in self.handlers.iter() {
+            registered.counters.ignored.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Add a handler only called for edited messages and channel posts. Edits only reach
+    /// it with the default EditPolicy::Separate
+    pub fn on_edited<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Bot, Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.typed_handler(
+            "on_edited",
+            |update| match update {
+                UpdateExt::EditedMessage(message) | UpdateExt::EditedChannelPost(message) => {
+                    Some(message)
+                }
+                _ => None,
+            },
+            handler,
+        )
+    }
+
+    /// Add a handler only called for chat_join_request updates
+    pub fn on_chat_join_request<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Bot, ChatJoinRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.typed_handler(
+            "on_chat_join_request",
+            |update| match update {
+                UpdateExt::ChatJoinRequest(request) => Some(request),
+                _ => None,
+            },
+            handler,
+        )
+    }
+
+    /// Add a handler only called for chosen_inline_result updates
+    pub fn on_chosen_inline_result<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(Bot, ChosenInlineResult) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.typed_handler(
+            "on_chosen_inline_result",
+            |update| match update {
+                UpdateExt::ChosenInlineResult(result) => Some(result),
+                _ => None,
+            },
+            handler,
+        )
+    }
+
+    /// Add a handler called with the value extract gets from an update, skipping
+    /// updates it returns None for. Its stats are kept under "kind N", see auto_named
+    pub(crate) fn typed_handler<T, X, F, Fut>(
+        self,
+        kind: &'static str,
+        extract: X,
+        handler: F,
+    ) -> Self
+    where
+        X: Fn(UpdateExt) -> Option<T> + Send + Sync + 'static,
+        F: Fn(Bot, T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.auto_matching(kind, move |bot: Bot, update: UpdateExt| {
+            let fut = extract(update).map(|value| handler(bot, value));
+            async move {
+                match fut {
+                    Some(fut) => fut.await.map(|()| true),
+                    None => Ok(false),
+                }
+            }
+        })
+    }
+
+    /// Run all layers and then all handlers for an update. A layer failing is reported and
+    /// treated as Flow::Continue
+    pub async fn dispatch(&self, bot: &Bot, update: UpdateExt) {
+        self.dispatch_with_id(bot, None, update).await
+    }
+
+    /// Like dispatch, but keeps the update_id for error reports
+    pub async fn dispatch_update(&self, bot: &Bot, update: Update) {
+        let update_id = update.get_update_id();
+        self.dispatch_with_id(bot, Some(update_id), update.into())
+            .await
+    }
+
+    pub(crate) async fn dispatch_with_id(
+        &self,
+        bot: &Bot,
+        update_id: Option<UpdateId>,
+        update: UpdateExt,
+    ) {
+        bot.invalidate_cache(&update);
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::FutureExt;
+            let cx = crate::otel::update_context(update_id, &update);
+            UPDATE_ID
+                .scope(update_id, self.run(bot, update_id, update).with_context(cx))
+                .await
+        }
+        #[cfg(not(feature = "otel"))]
+        UPDATE_ID
+            .scope(update_id, self.run(bot, update_id, update))
+            .await
+    }
+
+    async fn run(&self, bot: &Bot, update_id: Option<UpdateId>, update: UpdateExt) {
+        *self.stats.last_update.lock().unwrap() = Some(SystemTime::now());
+        self.stats.updates.fetch_add(1, Ordering::Relaxed);
+        self.stats.in_flight.fetch_add(1, Ordering::Relaxed);
+        let _in_flight = InFlight(&self.stats.in_flight);
+        let Some(update) = self.apply_edit_policy(update) else {
+            self.ignore();
+            return;
+        };
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            let res = self
+                .guard("layer", index, async {
+                    layer.call(bot.clone(), update.clone()).await
+                })
+                .await;
+            let failure = match res {
+                Ok(Flow::Continue) => continue,
+                Ok(Flow::Stop) => {
+                    self.ignore();
+                    return;
+                }
+                Err(failure) => failure,
+            };
+            self.report(bot, update_id, &update, failure).await;
+        }
+        for (index, registered) in self.handlers.iter().enumerate() {
+            let res = self
+                .guard("handler", index, async {
+                    registered.route.route(bot.clone(), update.clone()).await
+                })
+                .await;
+            let counters = &registered.counters;
+            let counter = match res {
+                Ok(true) => &counters.handled,
+                Ok(false) => &counters.ignored,
+                Err(failure) => {
+                    if self.report(bot, update_id, &update, failure).await {
+                        &counters.errors
+                    } else {
+                        &counters.ignored
+                    }
+                }
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Get a snapshot of the updates handled by this dispatcher and its clones
+    pub fn health(&self) -> Health {
+        let last_update = self
+            .stats
+            .last_update
+            .lock()
+            .unwrap()
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|t| t.as_secs());
+        Health {
+            last_update,
+            in_flight: self.stats.in_flight.load(Ordering::Relaxed),
+            updates: self.stats.updates.load(Ordering::Relaxed),
+            errors: self.stats.errors.load(Ordering::Relaxed),
+            reachable: self
+                .stats
+                .probed
+                .lock()
+                .unwrap()
+                .and_then(|(_, reachable)| reachable),
+        }
+    }
+
+    /// Get the outcomes of the updates dispatched to each handler by this dispatcher and
+    /// its clones, by handler name. Handlers that were never dispatched to are included
+    /// with all counts at zero
+    pub fn stats(&self) -> BTreeMap<String, HandlerStats> {
+        self.handlers
+            .iter()
+            .map(|h| (h.name.clone(), h.counters.snapshot()))
+            .collect()
+    }
+
+    /// Log the stats of every handler at info level
+    pub fn log_summary(&self) {
+        for (name, stats) in self.stats() {
+            log::info!("{}: {}", name, stats);
+        }
+    }
+
+    /// Get a snapshot of the dispatcher's health and check if telegram answers a get_me
+    /// call within `timeout`. The result is reported by health until the next probe
+    pub async fn probe(&self, bot: &Bot, timeout: Duration) -> Health {
+        let reachable = matches!(tokio::time::timeout(timeout, bot.get_me()).await, Ok(Ok(_)));
+        *self.stats.probed.lock().unwrap() = Some((Instant::now(), Some(reachable)));
+        self.health()
+    }
+
+    /// Like probe, but only calls telegram if the last probe started more than max_age
+    /// ago, otherwise the last result is reported. Concurrent callers don't probe twice
+    pub async fn probe_cached(&self, bot: &Bot, timeout: Duration, max_age: Duration) -> Health {
+        let fresh = {
+            let mut probed = self.stats.probed.lock().unwrap();
+            match *probed {
+                Some((at, _)) if at.elapsed() < max_age => true,
+                Some((ref mut at, _)) => {
+                    *at = Instant::now();
+                    false
+                }
+                None => {
+                    *probed = Some((Instant::now(), None));
+                    false
+                }
+            }
+        };
+        if fresh {
+            self.health()
+        } else {
+            self.probe(bot, timeout).await
+        }
+    }
+
+    /// Dispatch every update from a stream such as LongPoller::get_updates_with_ids or
+    /// Webhook::get_updates, handling updates concurrently. Streams of UpdateExt lose the
+    /// update_id in error reports, see IntoDispatch. Returns when the stream ends
+    pub async fn run<S, U>(self, bot: &Bot, updates: S)
+    where
+        S: Stream<Item = Result<U, ApiError>>,
+        U: IntoDispatch,
+    {
+        let me = &self;
+        let dispatch = updates.for_each_concurrent(None, |update| async move {
+            match update.map(IntoDispatch::into_dispatch) {
+                Ok((update_id, update)) => me.dispatch_with_id(bot, update_id, update).await,
+                Err(err) => log::warn!("failed to receive update: {}", err),
+            }
+        });
+        let summaries = async {
+            let Some(period) = self.summary_interval else {
+                return std::future::pending().await;
+            };
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                self.log_summary();
+            }
+        };
+        tokio::select! {
+            _ = dispatch => (),
+            _ = summaries => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotBuilder;
+
+    #[tokio::test]
+    async fn health_counts_errors() {
+        let bot = BotBuilder::new("123:abc").unwrap().build();
+        let dispatcher = Dispatcher::new()
+            .handler(|_: Bot, _: UpdateExt| async {
+                Err::<(), ApiError>(anyhow::anyhow!("fail").into())
+            })
+            .handler(|_: Bot, _: UpdateExt| async { Ok::<(), ApiError>(()) });
+        assert_eq!(dispatcher.health().get_last_update(), None);
+        dispatcher.dispatch(&bot, UpdateExt::Invalid).await;
+        dispatcher.clone().dispatch(&bot, UpdateExt::Invalid).await;
+        let health = dispatcher.health();
+        assert_eq!(health.get_updates(), 2);
+        assert_eq!(health.get_errors(), 2);
+        assert_eq!(health.get_in_flight(), 0);
+        assert_eq!(health.get_error_rate(), 1.0);
+        assert!(health.get_last_update().is_some());
+    }
+
+    #[tokio::test]
+    async fn stats_per_handler() {
+        let bot = BotBuilder::new("123:abc").unwrap().build();
+        let dispatcher = Dispatcher::new()
+            .layer(|_: Bot, update: UpdateExt| async move {
+                match update {
+                    UpdateExt::EditedMessage(_) => Ok::<Flow, ApiError>(Flow::Stop),
+                    _ => Ok(Flow::Continue),
+                }
+            })
+            .named_handler("fails", |_: Bot, _: UpdateExt| async {
+                Err::<(), ApiError>(anyhow::anyhow!("fail").into())
+            })
+            .on_edited(|_: Bot, _: Message| async { Ok(()) })
+            .filtered(crate::filter::text(), |_: Bot, _: UpdateExt| async {
+                Ok(())
+            })
+            .handler(|_: Bot, _: UpdateExt| async { Ok::<(), ApiError>(()) });
+        let edit = serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": 1, "type": "private"},
+        });
+        dispatcher.dispatch(&bot, UpdateExt::Invalid).await;
+        dispatcher
+            .dispatch(
+                &bot,
+                UpdateExt::EditedMessage(serde_json::from_value(edit).unwrap()),
+            )
+            .await;
+        let query = serde_json::json!({
+            "id": "1",
+            "from": {"id": 2, "is_bot": false, "first_name": "a"},
+            "chat_instance": "1",
+            "data": "x",
+        });
+        dispatcher
+            .dispatch(
+                &bot,
+                UpdateExt::CallbackQuery(serde_json::from_value(query).unwrap()),
+            )
+            .await;
+        let stats = dispatcher.stats();
+        let names = stats.keys().map(|k| k.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["fails", "filtered 0", "handler 0", "on_edited 0"]);
+        assert_eq!(stats["fails"].get_errors(), 2);
+        assert_eq!(stats["fails"].get_ignored(), 1);
+        assert_eq!(stats["handler 0"].get_handled(), 2);
+        assert_eq!(
+            stats["handler 0"].to_string(),
+            "2 handled, 1 ignored, 0 errors"
+        );
+        assert_eq!(stats["filtered 0"].get_handled(), 0);
+        assert_eq!(stats["filtered 0"].get_ignored(), 3);
+        assert_eq!(stats["on_edited 0"].get_handled(), 0);
+        assert_eq!(stats["on_edited 0"].get_ignored(), 3);
+
+        let shared = dispatcher.named_handler("fails", |_: Bot, _: UpdateExt| async {
+            Ok::<(), ApiError>(())
+        });
+        shared.dispatch(&bot, UpdateExt::Invalid).await;
+        assert_eq!(shared.stats()["fails"].get_errors(), 3);
+        assert_eq!(dispatcher.stats()["handler 0"].get_handled(), 3);
+    }
+
+    #[tokio::test]
+    async fn probes_are_cached() {
+        let test = crate::testing::TestBot::new().await.unwrap();
+        let dispatcher = Dispatcher::new();
+        assert_eq!(dispatcher.health().get_reachable(), None);
+        for _ in 0..3 {
+            let health = dispatcher
+                .probe_cached(
+                    test.get_bot(),
+                    Duration::from_secs(5),
+                    Duration::from_secs(60),
+                )
+                .await;
+            assert_eq!(health.get_reachable(), Some(true));
+        }
+        assert_eq!(test.sent("getMe").len(), 1);
+        assert_eq!(dispatcher.health().get_reachable(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn panics_are_reported() {
+        let bot = BotBuilder::new("123:abc").unwrap().build();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let dispatcher = Dispatcher::new()
+            .handler(|_: Bot, _: UpdateExt| async {
+                if true {
+                    panic!("boom");
+                }
+                Ok::<(), ApiError>(())
+            })
+            .handler(|_: Bot, _: UpdateExt| async { Ok::<(), ApiError>(()) })
+            .error_handler(move |_: Bot, err: HandlerError| {
+                sink.lock().unwrap().push(err.to_string());
+                async {}
+            });
+        dispatcher.dispatch(&bot, UpdateExt::Invalid).await;
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.as_slice(), ["Invalid update: panicked: boom"]);
+        assert_eq!(dispatcher.health().get_errors(), 1);
+    }
+
+    #[tokio::test]
+    async fn run_keeps_update_ids() {
+        let bot = BotBuilder::new("123:abc").unwrap().build();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let dispatcher = Dispatcher::new()
+            .handler(|_: Bot, _: UpdateExt| async {
+                Err::<(), ApiError>(anyhow::anyhow!("fail").into())
+            })
+            .error_handler(move |_: Bot, err: HandlerError| {
+                sink.lock().unwrap().push(err.to_string());
+                async {}
+            });
+        let updates = futures_util::stream::iter([Ok((UpdateId::from(7), UpdateExt::Invalid))]);
+        dispatcher.run(&bot, updates).await;
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.as_slice(), ["Invalid update 7: fail"]);
+    }
+
+    #[tokio::test]
+    async fn slow_handlers_time_out() {
+        let bot = BotBuilder::new("123:abc").unwrap().build();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        let dispatcher = Dispatcher::new()
+            .handler_timeout(Duration::from_millis(20))
+            .slow_handler_threshold(Duration::from_millis(5))
+            .handler(|_: Bot, _: UpdateExt| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok::<(), ApiError>(())
+            })
+            .error_handler(move |_: Bot, err: HandlerError| {
+                let timeout = match err.get_failure() {
+                    HandlerFailure::Timeout(timeout) => Some(timeout.get_limit()),
+                    _ => None,
+                };
+                sink.lock().unwrap().push(timeout);
+                async {}
+            });
+        dispatcher.dispatch(&bot, UpdateExt::Invalid).await;
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.as_slice(), [Some(Duration::from_millis(20))]);
+    }
+
+    #[test]
+    fn edit_policies() {
+        let edit = |date: i64, edit_date: i64| {
+            let message = serde_json::json!({
+                "message_id": 1,
+                "date": date,
+                "edit_date": edit_date,
+                "chat": {"id": 1, "type": "private"},
+            });
+            UpdateExt::EditedMessage(serde_json::from_value(message).unwrap())
+        };
+        let dispatcher = Dispatcher::new();
+        let update = dispatcher.apply_edit_policy(edit(0, 10));
+        assert!(matches!(update, Some(UpdateExt::EditedMessage(_))));
+        let dispatcher = dispatcher.edit_policy(EditPolicy::AsNew);
+        let update = dispatcher.apply_edit_policy(edit(0, 10));
+        assert!(matches!(update, Some(UpdateExt::Message(_))));
+        let dispatcher = dispatcher.edit_max_age(Duration::from_secs(300));
+        assert!(dispatcher.apply_edit_policy(edit(0, 301)).is_none());
+        assert!(dispatcher.apply_edit_policy(edit(0, 300)).is_some());
+        let dispatcher = dispatcher.edit_policy(EditPolicy::Ignore);
+        assert!(dispatcher.apply_edit_policy(edit(0, 10)).is_none());
+        assert!(dispatcher.apply_edit_policy(UpdateExt::Invalid).is_some());
+    }
+}
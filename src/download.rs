@@ -0,0 +1,96 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use hyper::body::Bytes;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::bot::{Bot, BotResult};
+
+type ByteStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+
+/// A file being downloaded from telegram, read as it arrives instead of buffering the
+/// whole file in memory. Errors from the connection surface as io::Error
+pub struct FileReader {
+    stream: ByteStream,
+    chunk: Bytes,
+    content_length: Option<u64>,
+}
+
+impl std::fmt::Debug for FileReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileReader")
+            .field("content_length", &self.content_length)
+            .finish()
+    }
+}
+
+impl FileReader {
+    /// Get the size of the file if telegram reported it
+    pub fn get_content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+}
+
+impl AsyncRead for FileReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.chunk.is_empty() {
+            match self.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => self.chunk = chunk,
+                Poll::Ready(Some(Err(err))) => {
+                    return Poll::Ready(Err(io::Error::other(err.without_url())))
+                }
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let len = self.chunk.len().min(buf.remaining());
+        let chunk = self.chunk.split_to(len);
+        buf.put_slice(&chunk);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Bot {
+    /// Open a file using the file_path returned by get_file for reading with
+    /// tokio::io, for example to copy it straight to disk with tokio::io::copy
+    pub async fn open_file(&self, file_path: &str) -> BotResult<FileReader> {
+        let resp = self.get_file_response(file_path).await?;
+        Ok(FileReader {
+            content_length: resp.content_length(),
+            stream: Box::pin(resp.bytes_stream()),
+            chunk: Bytes::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn reads_across_chunks() {
+        let chunks = vec![
+            Ok(Bytes::from_static(b"hello ")),
+            Ok(Bytes::new()),
+            Ok(Bytes::from_static(b"world")),
+        ];
+        let mut reader = FileReader {
+            stream: Box::pin(futures_util::stream::iter(chunks)),
+            chunk: Bytes::new(),
+            content_length: None,
+        };
+        let mut small = [0u8; 3];
+        reader.read_exact(&mut small).await.unwrap();
+        assert_eq!(&small, b"hel");
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "lo world");
+    }
+}
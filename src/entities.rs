@@ -0,0 +1,153 @@
+use crate::caption::{utf16_len, TextEntities};
+use crate::gen_types::{MessageEntity, User};
+
+/// Builds text along with its formatting entities, so formatted messages can be sent
+/// without escaping HTML or Markdown. Offsets are counted in UTF-16 code units like
+/// telegram expects
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextBuilder {
+    text: String,
+    entities: Vec<MessageEntity>,
+}
+
+impl TextBuilder {
+    /// Start with empty text
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append text with an entity covering it, returning the entity to fill in
+    fn push(&mut self, kind: &str, text: &str) -> &mut MessageEntity {
+        let mut entity = MessageEntity::default();
+        entity.set_tg_type(kind.to_owned());
+        entity.set_offset(utf16_len(&self.text) as i64);
+        entity.set_length(utf16_len(text) as i64);
+        self.text.push_str(text);
+        self.entities.push(entity);
+        self.entities.last_mut().unwrap()
+    }
+
+    fn entity(mut self, kind: &str, text: &str) -> Self {
+        self.push(kind, text);
+        self
+    }
+
+    /// Append unformatted text
+    pub fn text(mut self, text: &str) -> Self {
+        self.text.push_str(text);
+        self
+    }
+
+    /// Append bold text
+    pub fn bold(self, text: &str) -> Self {
+        self.entity("bold", text)
+    }
+
+    /// Append italic text
+    pub fn italic(self, text: &str) -> Self {
+        self.entity("italic", text)
+    }
+
+    /// Append underlined text
+    pub fn underline(self, text: &str) -> Self {
+        self.entity("underline", text)
+    }
+
+    /// Append strikethrough text
+    pub fn strikethrough(self, text: &str) -> Self {
+        self.entity("strikethrough", text)
+    }
+
+    /// Append text hidden behind a spoiler
+    pub fn spoiler(self, text: &str) -> Self {
+        self.entity("spoiler", text)
+    }
+
+    /// Append inline monospace text
+    pub fn code(self, text: &str) -> Self {
+        self.entity("code", text)
+    }
+
+    /// Append a monospace code block, optionally highlighted for a language
+    pub fn pre(mut self, text: &str, language: Option<&str>) -> Self {
+        let entity = self.push("pre", text);
+        entity.set_language(language.map(|l| l.to_owned()));
+        self
+    }
+
+    /// Append a quote
+    pub fn blockquote(self, text: &str) -> Self {
+        self.entity("blockquote", text)
+    }
+
+    /// Append text linking to a url
+    pub fn link(mut self, text: &str, url: &str) -> Self {
+        let entity = self.push("text_link", text);
+        entity.set_url(Some(url.to_owned()));
+        self
+    }
+
+    /// Append a mention of a user showing their first name. Works for users without a
+    /// username
+    pub fn mention(self, user: &User) -> Self {
+        let name = user.get_first_name().to_owned();
+        self.mention_as(user, &name)
+    }
+
+    /// Append a mention of a user showing custom text
+    pub fn mention_as(mut self, user: &User, text: &str) -> Self {
+        let entity = self.push("text_mention", text);
+        entity.set_user(Some(user.clone()));
+        self
+    }
+
+    /// Append a custom emoji, using `fallback` as the emoji shown where custom emoji are
+    /// not available
+    pub fn custom_emoji(mut self, fallback: &str, custom_emoji_id: &str) -> Self {
+        let entity = self.push("custom_emoji", fallback);
+        entity.set_custom_emoji_id(Some(custom_emoji_id.to_owned()));
+        self
+    }
+
+    /// Get the text built so far
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the entities built so far
+    pub fn get_entities(&self) -> &[MessageEntity] {
+        &self.entities
+    }
+
+    /// Finish building, returning the text and entities
+    pub fn build(self) -> TextEntities {
+        (self.text, self.entities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_offsets() {
+        let mut user = User::default();
+        user.set_first_name("Ann".to_owned());
+        let (text, entities) = TextBuilder::new()
+            .bold("Hi 👋 ")
+            .mention(&user)
+            .text(" ")
+            .code("x=1")
+            .build();
+        assert_eq!(text, "Hi 👋 Ann x=1");
+        let spans = entities
+            .iter()
+            .map(|e| (e.get_tg_type(), e.get_offset(), e.get_length()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [("bold", 0, 6), ("text_mention", 6, 3), ("code", 10, 3)]
+        );
+        assert_eq!(entities[1].get_user(), Some(&user));
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{ChatHandle, Message, MsgId};
+
+type Tasks = Arc<Mutex<HashMap<u64, JoinHandle<()>>>>;
+
+/// Registry of pending deletions, shared by all clones of a bot. Deletions run as
+/// background tasks for as long as the process lives
+#[derive(Debug, Default)]
+pub(crate) struct EphemeralRegistry {
+    next: AtomicU64,
+    tasks: Tasks,
+}
+
+/// Handle to a scheduled deletion of a message
+#[derive(Debug, Clone)]
+pub struct EphemeralHandle {
+    id: u64,
+    chat: i64,
+    message_id: MsgId,
+    tasks: Tasks,
+}
+
+impl EphemeralHandle {
+    /// Get the chat of the message to be deleted
+    pub fn get_chat(&self) -> i64 {
+        self.chat
+    }
+
+    /// Get the id of the message to be deleted
+    pub fn get_message_id(&self) -> MsgId {
+        self.message_id
+    }
+
+    /// Check if the deletion has not run or been cancelled yet
+    pub fn is_pending(&self) -> bool {
+        self.tasks.lock().unwrap().contains_key(&self.id)
+    }
+
+    /// Cancel the deletion, returning false if it already ran or was cancelled
+    pub fn cancel(&self) -> bool {
+        match self.tasks.lock().unwrap().remove(&self.id) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Bot {
+    /// Delete a message after ttl has passed. Failures to delete are logged
+    pub fn schedule_delete(&self, chat: i64, message_id: MsgId, ttl: Duration) -> EphemeralHandle {
+        let registry = self.ephemeral();
+        let id = registry.next.fetch_add(1, Ordering::Relaxed);
+        let tasks = Arc::clone(&registry.tasks);
+        // hold the lock while spawning so the task can't remove itself before it is inserted
+        let mut pending = tasks.lock().unwrap();
+        let bot = self.clone();
+        let task_tasks = Arc::clone(&tasks);
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(ttl).await;
+            if task_tasks.lock().unwrap().remove(&id).is_none() {
+                return;
+            }
+            if let Err(err) = bot.build_delete_message(chat, message_id).build().await {
+                log::warn!("failed to delete ephemeral message {}", err);
+            }
+        });
+        pending.insert(id, task);
+        drop(pending);
+        EphemeralHandle {
+            id,
+            chat,
+            message_id,
+            tasks,
+        }
+    }
+
+    /// Send a text message and delete it after ttl has passed, unless cancelled using the
+    /// returned handle
+    pub async fn send_message_ephemeral<V>(
+        &self,
+        chat: V,
+        text: &str,
+        ttl: Duration,
+    ) -> BotResult<(Message, EphemeralHandle)>
+    where
+        V: Into<ChatHandle> + Serialize,
+    {
+        let message = self.build_send_message(chat, text).build().await?;
+        let handle =
+            self.schedule_delete(message.get_chat().get_id(), message.get_message_id(), ttl);
+        Ok((message, handle))
+    }
+
+    /// Get the number of scheduled deletions that have not run yet
+    pub fn pending_ephemeral(&self) -> usize {
+        self.ephemeral().tasks.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotBuilder;
+
+    #[tokio::test]
+    async fn cancel_deletion() {
+        let bot = BotBuilder::new("123:abc").unwrap().build();
+        let handle = bot.schedule_delete(1, MsgId::from(2), Duration::from_secs(3600));
+        assert!(handle.is_pending());
+        assert_eq!(bot.clone().pending_ephemeral(), 1);
+        assert!(handle.cancel());
+        assert!(!handle.cancel());
+        assert!(!handle.is_pending());
+        assert_eq!(bot.pending_ephemeral(), 0);
+    }
+}
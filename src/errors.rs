@@ -0,0 +1,275 @@
+use std::time::Duration;
+
+use crate::bot::{ApiError, Response};
+use crate::unreachable::Unreachable;
+
+/// Known kinds of errors returned by telegram. Telegram only documents error codes, so
+/// kinds are matched on the description, ignoring case. This is the one catalog of
+/// descriptions, Unreachable, BenignError and ApiError's predicates are all derived
+/// from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiErrorKind {
+    /// Too many requests, retry after the given time
+    Flood(Option<Duration>),
+    /// The bot can't send messages in the chat
+    ChatWriteForbidden,
+    /// The bot lacks the administrator rights for the action
+    NotEnoughRights,
+    /// The target user is an administrator and can't be restricted
+    UserIsAdministrator,
+    /// The chat can't be reached anymore, see Unreachable
+    Unreachable(Unreachable),
+    /// The message text is longer than 4096 characters
+    MessageTooLong,
+    /// The caption is longer than 1024 characters
+    CaptionTooLong,
+    /// The message text is empty
+    MessageEmpty,
+    /// Formatted text could not be parsed, see ApiError::get_parse_error
+    ParseEntities,
+    /// An edit would not change the message
+    NotModified,
+    /// The message to edit doesn't exist
+    MessageToEditNotFound,
+    /// The message to delete doesn't exist, usually because it was already deleted
+    MessageToDeleteNotFound,
+    /// The message to reply to or forward doesn't exist
+    MessageNotFound,
+    /// The user doesn't exist or isn't in the chat
+    UserNotFound,
+    /// The file_id is invalid or belongs to another bot
+    WrongFileId,
+    /// A callback query was answered too late or was already answered
+    QueryTooOld,
+    /// Another getUpdates call or a webhook is receiving updates
+    Conflict,
+    /// The bot token is invalid or was revoked
+    Unauthorized,
+    /// The request was cancelled before telegram answered
+    Timeout,
+    /// Any other error
+    Other,
+}
+
+impl ApiErrorKind {
+    /// Description fragments matched in order, so more specific fragments come first
+    const DESCRIPTIONS: &'static [(&'static str, ApiErrorKind)] = &[
+        ("chat_write_forbidden", ApiErrorKind::ChatWriteForbidden),
+        (
+            "not enough rights to send",
+            ApiErrorKind::ChatWriteForbidden,
+        ),
+        ("have no rights to send", ApiErrorKind::ChatWriteForbidden),
+        ("not enough rights", ApiErrorKind::NotEnoughRights),
+        ("chat_admin_required", ApiErrorKind::NotEnoughRights),
+        (
+            "user is an administrator",
+            ApiErrorKind::UserIsAdministrator,
+        ),
+        ("message is too long", ApiErrorKind::MessageTooLong),
+        ("message_too_long", ApiErrorKind::MessageTooLong),
+        ("caption is too long", ApiErrorKind::CaptionTooLong),
+        ("message text is empty", ApiErrorKind::MessageEmpty),
+        ("can't parse entities", ApiErrorKind::ParseEntities),
+        ("message is not modified", ApiErrorKind::NotModified),
+        (
+            "message to edit not found",
+            ApiErrorKind::MessageToEditNotFound,
+        ),
+        (
+            "message to delete not found",
+            ApiErrorKind::MessageToDeleteNotFound,
+        ),
+        ("message to reply not found", ApiErrorKind::MessageNotFound),
+        (
+            "message to forward not found",
+            ApiErrorKind::MessageNotFound,
+        ),
+        ("message_id_invalid", ApiErrorKind::MessageNotFound),
+        ("user not found", ApiErrorKind::UserNotFound),
+        ("participant_id_invalid", ApiErrorKind::UserNotFound),
+        ("wrong file identifier", ApiErrorKind::WrongFileId),
+        ("wrong remote file identifier", ApiErrorKind::WrongFileId),
+        ("wrong_file_id", ApiErrorKind::WrongFileId),
+        ("query is too old", ApiErrorKind::QueryTooOld),
+    ];
+
+    /// Description fragments meaning the chat is unreachable, only matched for 400 and
+    /// 403 errors
+    const UNREACHABLE: &'static [(&'static str, Unreachable)] = &[
+        ("bot was blocked by the user", Unreachable::BlockedByUser),
+        ("user is deactivated", Unreachable::UserDeactivated),
+        (
+            "bot can't initiate conversation with a user",
+            Unreachable::CantInitiateConversation,
+        ),
+        ("bot was kicked from the", Unreachable::KickedFromChat),
+        ("bot is not a member of the", Unreachable::NotMember),
+        ("chat not found", Unreachable::ChatNotFound),
+    ];
+
+    /// Classify an error response from telegram, None for successful responses
+    pub fn from_response(response: &Response) -> Option<Self> {
+        if response.ok {
+            return None;
+        }
+        let description = response
+            .description
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase();
+        if matches!(response.error_code, Some(400) | Some(403)) {
+            let unreachable = Self::UNREACHABLE
+                .iter()
+                .find(|(d, _)| description.contains(d));
+            if let Some((_, unreachable)) = unreachable {
+                return Some(Self::Unreachable(*unreachable));
+            }
+        }
+        let retry_after = response
+            .parameters
+            .as_ref()
+            .and_then(|p| p.get_retry_after())
+            .map(|s| Duration::from_secs(s as u64));
+        let kind = match response.error_code {
+            Some(429) => Self::Flood(retry_after),
+            _ if description.contains("flood_wait")
+                || description.contains("too many requests") =>
+            {
+                Self::Flood(retry_after)
+            }
+            Some(401) => Self::Unauthorized,
+            Some(409) => Self::Conflict,
+            _ => Self::DESCRIPTIONS
+                .iter()
+                .find(|(d, _)| description.contains(d))
+                .map(|(_, kind)| *kind)
+                .unwrap_or(Self::Other),
+        };
+        Some(kind)
+    }
+
+    /// Check if this is a flood wait
+    pub fn is_flood(&self) -> bool {
+        matches!(self, Self::Flood(_))
+    }
+
+    /// Check if the bot is not allowed to do this in the chat, including chats it was
+    /// blocked or kicked from
+    pub fn is_permission(&self) -> bool {
+        matches!(
+            self,
+            Self::ChatWriteForbidden
+                | Self::NotEnoughRights
+                | Self::UserIsAdministrator
+                | Self::Unreachable(
+                    Unreachable::BlockedByUser
+                        | Unreachable::KickedFromChat
+                        | Unreachable::NotMember
+                        | Unreachable::CantInitiateConversation
+                )
+        )
+    }
+
+    /// Check if the chat, user, message or file of the request doesn't exist
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            Self::MessageNotFound
+                | Self::MessageToEditNotFound
+                | Self::MessageToDeleteNotFound
+                | Self::UserNotFound
+                | Self::WrongFileId
+                | Self::Unreachable(Unreachable::ChatNotFound | Unreachable::UserDeactivated)
+        )
+    }
+
+    /// Check if the request was rejected for its content, like text that is too long,
+    /// and will fail again if sent unchanged
+    pub fn is_bad_content(&self) -> bool {
+        matches!(
+            self,
+            Self::MessageTooLong | Self::CaptionTooLong | Self::MessageEmpty | Self::ParseEntities
+        )
+    }
+}
+
+impl ApiError {
+    /// Get the kind of this error, None for errors that didn't come from telegram other
+    /// than timeouts
+    pub fn get_kind(&self) -> Option<ApiErrorKind> {
+        if self.is_timeout() {
+            return Some(ApiErrorKind::Timeout);
+        }
+        self.get_response().and_then(ApiErrorKind::from_response)
+    }
+
+    /// Check if telegram asked to retry the request later
+    pub fn is_flood(&self) -> bool {
+        self.get_kind().is_some_and(|k| k.is_flood())
+    }
+
+    /// Check if the bot is not allowed to do this in the chat, see
+    /// ApiErrorKind::is_permission
+    pub fn is_permission(&self) -> bool {
+        self.get_kind().is_some_and(|k| k.is_permission())
+    }
+
+    /// Check if something the request refers to doesn't exist, see
+    /// ApiErrorKind::is_not_found
+    pub fn is_not_found(&self) -> bool {
+        self.get_kind().is_some_and(|k| k.is_not_found())
+    }
+
+    /// Check if this is telegram's "message is not modified" error, returned when an
+    /// edit would not change the message
+    pub fn is_not_modified(&self) -> bool {
+        self.get_kind() == Some(ApiErrorKind::NotModified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(error_code: i64, description: &str) -> ApiErrorKind {
+        let response: Response = serde_json::from_value(serde_json::json!({
+            "ok": false,
+            "error_code": error_code,
+            "description": description,
+            "parameters": {"retry_after": 5},
+        }))
+        .unwrap();
+        ApiErrorKind::from_response(&response).unwrap()
+    }
+
+    #[test]
+    fn catalog() {
+        assert_eq!(
+            error(429, "Too Many Requests: retry after 5"),
+            ApiErrorKind::Flood(Some(Duration::from_secs(5)))
+        );
+        assert!(error(400, "Bad Request: CHAT_WRITE_FORBIDDEN").is_permission());
+        assert_eq!(
+            error(400, "Bad Request: message is too long"),
+            ApiErrorKind::MessageTooLong
+        );
+        assert!(error(400, "Bad Request: wrong file identifier/HTTP URL specified").is_not_found());
+        assert!(error(403, "Forbidden: bot was blocked by the user").is_permission());
+        assert!(error(400, "Bad Request: chat not found").is_not_found());
+        assert_eq!(
+            error(400, "Bad Request: message to delete not found"),
+            ApiErrorKind::MessageToDeleteNotFound
+        );
+        assert_eq!(
+            error(403, "Forbidden: bot was blocked by the user"),
+            ApiErrorKind::Unreachable(Unreachable::BlockedByUser)
+        );
+        assert_eq!(error(500, "chat not found"), ApiErrorKind::Other);
+        assert_eq!(
+            error(400, "Bad Request: something new"),
+            ApiErrorKind::Other
+        );
+        assert_eq!(error(401, "Unauthorized"), ApiErrorKind::Unauthorized);
+    }
+}
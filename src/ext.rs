@@ -1,30 +1,126 @@
 use hyper_util::rt::TokioIo;
+use rand::distributions::Alphanumeric;
 use rand::rngs::OsRng;
-use rand::RngCore;
+use rand::{Rng, RngCore};
+use std::collections::{HashSet, VecDeque};
 use std::net::SocketAddr;
-use std::{net::IpAddr, pin::Pin};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    pin::Pin,
+};
 use tokio::net::TcpListener;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::error::SendError;
 use uuid::Uuid;
 
-use crate::bot::ApiError;
-use crate::gen_types::UpdateExt;
+use crate::bot::{ApiError, BotResult, SecretString};
+use crate::gen_types::{Chat, ChatAction, UpdateExt, UpdateId};
 use crate::{bot::Bot, gen_types::Update};
 use anyhow::anyhow;
 use anyhow::Result;
 use async_stream::stream;
 use futures_core::Stream;
+use futures_util::StreamExt;
 use http_body_util::{BodyExt, Limited};
-use hyper::body::{Buf, Incoming};
+use hyper::body::Incoming;
 use hyper::service::service_fn;
 use hyper::{Request, Response, StatusCode};
 
+/// Number of recent update_ids webhook receivers remember by default
+pub const DEFAULT_DEDUP_WINDOW: usize = 1024;
+
+/// Bounded set of recently seen update_ids, used to drop updates telegram delivers more
+/// than once. Once full the oldest id is forgotten. Clones share the same window
+#[derive(Debug, Clone)]
+pub struct UpdateDedup {
+    window: usize,
+    seen: Arc<Mutex<(HashSet<UpdateId>, VecDeque<UpdateId>)>>,
+}
+
+impl UpdateDedup {
+    /// Remember the last `window` update_ids. A window of 0 lets every update through
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            seen: Arc::new(Mutex::new((HashSet::new(), VecDeque::new()))),
+        }
+    }
+
+    /// Record an update_id, returning false if it was seen recently
+    pub fn check(&self, update_id: UpdateId) -> bool {
+        if self.window == 0 {
+            return true;
+        }
+        let mut seen = self.seen.lock().unwrap();
+        let (ids, order) = &mut *seen;
+        if !ids.insert(update_id) {
+            return false;
+        }
+        order.push_back(update_id);
+        while order.len() > self.window {
+            if let Some(old) = order.pop_front() {
+                ids.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+impl Default for UpdateDedup {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEDUP_WINDOW)
+    }
+}
+
+/// Get the unix time an update happened at, the edit time for edits. None for updates
+/// without a date, like callback and inline queries
+pub fn update_date(update: &UpdateExt) -> Option<i64> {
+    match update {
+        UpdateExt::Message(m) | UpdateExt::ChannelPost(m) => Some(m.get_date()),
+        UpdateExt::EditedMessage(m) | UpdateExt::EditedChannelPost(m) => {
+            Some(m.get_edit_date().unwrap_or(m.get_date()))
+        }
+        UpdateExt::ChatMember(m) | UpdateExt::MyChatMember(m) => Some(m.get_date()),
+        UpdateExt::ChatJoinRequest(r) => Some(r.get_date()),
+        _ => None,
+    }
+}
+
+/// Current unix time in seconds
+pub(crate) fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Check if an update happened more than max_age ago. Updates without a date are never
+/// stale
+pub fn is_stale(update: &UpdateExt, max_age: Duration) -> bool {
+    is_before(update, now().saturating_sub(max_age.as_secs() as i64))
+}
+
+/// Check if an update happened before a unix time. Updates without a date never did
+fn is_before(update: &UpdateExt, cutoff: i64) -> bool {
+    update_date(update).is_some_and(|date| date < cutoff)
+}
+
+/// Get the unix time updates have to be from to not count as an old backlog, max_age
+/// before now
+fn backlog_cutoff(max_age: Option<Duration>) -> Option<i64> {
+    max_age.map(|age| now().saturating_sub(age.as_secs() as i64))
+}
+
 /// Helper for fetching updates via long polling.
 pub struct LongPoller {
     bot: Bot,
     offset: i64,
     allowed_updates: Option<Vec<String>>,
+    dedup: Option<UpdateDedup>,
+    drop_pending: bool,
+    max_age: Option<Duration>,
 }
 
 impl LongPoller {
@@ -33,24 +129,95 @@ impl LongPoller {
             bot: bot.clone(),
             offset: 0,
             allowed_updates,
+            dedup: None,
+            drop_pending: false,
+            max_age: None,
         }
     }
 
+    /// Skip every update queued before polling starts, so a bot coming back after
+    /// downtime doesn't answer the backlog
+    pub fn drop_pending(mut self, drop_pending: bool) -> Self {
+        self.drop_pending = drop_pending;
+        self
+    }
+
+    /// Skip updates that happened more than max_age before polling started, judged by
+    /// their date. Unlike drop_pending this keeps recent updates from the backlog. Only
+    /// the backlog is checked, updates sent while polling are never skipped even if
+    /// handling falls behind
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Confirm every pending update by fetching only the last one, returning the offset
+    /// after it
+    async fn skip_pending(&self) -> BotResult<i64> {
+        let last = self
+            .bot
+            .get_updates(Some(-1), Some(1), None, self.allowed_updates.as_ref())
+            .await?;
+        Ok(last
+            .iter()
+            .map(|u| u.get_update_id().get() + 1)
+            .max()
+            .unwrap_or(self.offset))
+    }
+
+    /// Drop updates with an update_id among the last `window` received. Polling only
+    /// redelivers updates when the offset is reset, such as after a restart sharing a
+    /// dedup window with another poller
+    pub fn dedup(mut self, dedup: UpdateDedup) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
     /// Return an async stream of updates, terminating with error
-    pub async fn get_updates(
+    pub async fn get_updates(self) -> Pin<Box<impl Stream<Item = Result<UpdateExt, ApiError>>>> {
+        let updates = self.get_updates_with_ids().await;
+        Box::pin(updates.map(|update| update.map(|(_, update)| update)))
+    }
+
+    /// Like get_updates, but keeps the update_id of every update. Pass this to
+    /// Dispatcher::run so error reports and traces carry the update_id
+    pub async fn get_updates_with_ids(
         mut self,
-    ) -> Pin<Box<impl Stream<Item = Result<UpdateExt, ApiError>>>> {
+    ) -> Pin<Box<impl Stream<Item = Result<(UpdateId, UpdateExt), ApiError>>>> {
         let s = stream! {
+            let cutoff = backlog_cutoff(self.max_age);
+            if self.drop_pending {
+                match self.skip_pending().await {
+                    Ok(offset) => {
+                        log::info!("dropped pending updates before {}", offset);
+                        self.offset = offset;
+                    }
+                    Err(err) => log::warn!("failed to drop pending updates {}", err),
+                }
+            }
             loop {
                 match self.bot.get_updates(Some(self.offset), None, None, self.allowed_updates.as_ref()).await {
                     Ok(update) => {
                         let mut max = 0;
                         for update in update {
-                            let id = update.get_update_id();
+                            let update_id = update.get_update_id();
+                            let id = update_id.get();
                             if id > max {
                                 max = id;
                             }
-                            yield Ok(update.into());
+                            if let Some(ref dedup) = self.dedup {
+                                if !dedup.check(update_id) {
+                                    log::debug!("dropping duplicate update {}", id);
+                                    continue;
+                                }
+                            }
+                            let update: UpdateExt = update.into();
+                            if cutoff.is_some_and(|cutoff| is_before(&update, cutoff)) {
+                                log::debug!("dropping stale update {}", id);
+                                continue;
+                            }
+                            self.bot.invalidate_cache(&update);
+                            yield Ok((update_id, update));
                         }
 
                         self.offset = max + 1;
@@ -84,6 +251,91 @@ pub enum BotUrl {
     Host(String),
 }
 
+/// Subnets telegram sends webhook requests from
+pub const TELEGRAM_SUBNETS: &[(IpAddr, u8)] = &[
+    (IpAddr::V4(Ipv4Addr::new(149, 154, 160, 0)), 20),
+    (IpAddr::V4(Ipv4Addr::new(91, 108, 4, 0)), 22),
+];
+
+fn in_subnet(ip: IpAddr, (net, prefix): (IpAddr, u8)) -> bool {
+    match (ip.to_canonical(), net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX
+                .checked_shl(32u32.saturating_sub(prefix as u32))
+                .unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX
+                .checked_shl(128u32.saturating_sub(prefix as u32))
+                .unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Restricts webhook requests to telegram's published subnets. When running behind a
+/// reverse proxy the client address is taken from the X-Forwarded-For header, but only
+/// for requests coming from a trusted proxy
+#[derive(Debug, Clone)]
+pub struct IpAllowlist {
+    subnets: Vec<(IpAddr, u8)>,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for IpAllowlist {
+    fn default() -> Self {
+        Self {
+            subnets: TELEGRAM_SUBNETS.to_vec(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+impl IpAllowlist {
+    /// Allow only telegram's subnets
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also allow a subnet, for example for testing
+    pub fn subnet(mut self, net: IpAddr, prefix: u8) -> Self {
+        self.subnets.push((net, prefix));
+        self
+    }
+
+    /// Trust the X-Forwarded-For header of requests from this proxy
+    pub fn trusted_proxy(mut self, proxy: IpAddr) -> Self {
+        self.trusted_proxies.push(proxy);
+        self
+    }
+
+    /// Find the address of the client making a request, skipping trusted proxies from
+    /// the right of the X-Forwarded-For header
+    fn client(&self, peer: IpAddr, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        if !self.trusted_proxies.contains(&peer) {
+            return Some(peer);
+        }
+        let mut client = peer;
+        for hop in forwarded_for.unwrap_or("").rsplit(',') {
+            if !self.trusted_proxies.contains(&client) {
+                break;
+            }
+            client = hop.trim().parse().ok()?;
+        }
+        Some(client)
+    }
+
+    /// Check if a request from `peer` with an optional X-Forwarded-For value came
+    /// from an allowed subnet
+    pub fn allows(&self, peer: IpAddr, forwarded_for: Option<&str>) -> bool {
+        self.client(peer, forwarded_for)
+            .map(|ip| self.subnets.iter().any(|net| in_subnet(ip, *net)))
+            .unwrap_or(false)
+    }
+}
+
 /// Helper for fetching updates via webhook. This currently requires a reverse proxy as
 /// tls is not supported.
 pub struct Webhook {
@@ -93,6 +345,9 @@ pub struct Webhook {
     addr: SocketAddr,
     cookie: Uuid,
     allowed_updates: Option<Vec<String>>,
+    allowlist: Option<IpAllowlist>,
+    dedup: UpdateDedup,
+    max_age: Option<Duration>,
 }
 
 impl Webhook {
@@ -113,9 +368,33 @@ impl Webhook {
             addr,
             cookie,
             allowed_updates,
+            allowlist: None,
+            dedup: UpdateDedup::default(),
+            max_age: None,
         }
     }
 
+    /// Reject requests not coming from telegram's subnets
+    pub fn ip_allowlist(mut self, allowlist: IpAllowlist) -> Self {
+        self.allowlist = Some(allowlist);
+        self
+    }
+
+    /// Change how many recent update_ids are remembered to drop updates telegram
+    /// redelivers after a timeout. Defaults to DEFAULT_DEDUP_WINDOW, 0 disables this
+    pub fn dedup_window(mut self, window: usize) -> Self {
+        self.dedup = UpdateDedup::new(window);
+        self
+    }
+
+    /// Drop updates that happened more than max_age before the webhook started, judged
+    /// by their date, for keeping recent updates while skipping an old backlog. Updates
+    /// sent after startup are never dropped. Use drop_pending_updates to skip all of it
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
     async fn setup(&self) -> Result<bool, ApiError> {
         match self.url {
             BotUrl::Address(ref addr, ip) => {
@@ -163,15 +442,43 @@ impl Webhook {
 
         let listener = TcpListener::bind(self.addr).await.map_err(|e| anyhow!(e))?;
 
-        let svc = service_fn(move |body: Request<Incoming>| {
+        let allowlist = self.allowlist.clone();
+        let dedup = self.dedup.clone();
+        let cutoff = backlog_cutoff(self.max_age);
+        let svc = move |peer: SocketAddr, body: Request<Incoming>| {
             let tx = tx.clone();
+            let dedup = dedup.clone();
+            let allowed = allowlist
+                .as_ref()
+                .map(|a| {
+                    let forwarded_for = body
+                        .headers()
+                        .get("X-Forwarded-For")
+                        .and_then(|v| v.to_str().ok());
+                    a.allows(peer.ip(), forwarded_for)
+                })
+                .unwrap_or(true);
             async move {
+                if !allowed {
+                    return Ok::<_, ApiError>(
+                        Response::builder()
+                            .status(StatusCode::FORBIDDEN)
+                            .body("".to_owned())
+                            .map_err(|e| anyhow!(e))?,
+                    );
+                }
                 if let Some(token) = body.headers().get("X-Telegram-Bot-Api-Secret-Token") {
-                    if token.to_str().unwrap_or("") == cookie.to_string().as_str() {
+                    if tokens_equal(token.to_str().unwrap_or(""), &cookie.to_string()) {
                         let body = Limited::new(body, 1024 * 1024 * 10);
-                        let body = body.collect().await.map_err(|e| anyhow!(e))?.aggregate();
-                        if let Ok(update) = serde_json::from_reader::<_, Update>(body.reader()) {
-                            tx.send(update.into())
+                        let body = body.collect().await.map_err(|e| anyhow!(e))?.to_bytes();
+                        let update = crate::json::from_slice::<Update>(&body)
+                            .ok()
+                            .filter(|u| dedup.check(u.get_update_id()));
+                        let update = update
+                            .map(UpdateExt::from)
+                            .filter(|u| !cutoff.is_some_and(|cutoff| is_before(u, cutoff)));
+                        if let Some(update) = update {
+                            tx.send(update)
                                 .await
                                 .map_err(|e: SendError<UpdateExt>| anyhow!(e))?;
                         }
@@ -184,11 +491,12 @@ impl Webhook {
                         .map_err(|e| anyhow!(e))?,
                 )
             }
-        });
+        };
         let fut = tokio::spawn(async move {
             loop {
-                let svc = svc.clone();
-                if let Ok((stream, _)) = listener.accept().await {
+                if let Ok((stream, peer)) = listener.accept().await {
+                    let svc = svc.clone();
+                    let svc = service_fn(move |body| svc(peer, body));
                     let io = TokioIo::new(stream);
 
                     tokio::task::spawn(async move {
@@ -210,6 +518,7 @@ impl Webhook {
 
         let s = stream! {
             while let Some(update) = rx.recv().await {
+                self.bot.invalidate_cache(&update);
                 yield Ok(update);
             }
 
@@ -222,3 +531,299 @@ impl Webhook {
         Ok(Box::pin(s))
     }
 }
+
+/// Length of secret tokens generated by WebhookManager, telegram allows up to 256
+const SECRET_TOKEN_LENGTH: usize = 64;
+
+fn generate_secret_token() -> SecretString {
+    OsRng
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_TOKEN_LENGTH)
+        .map(char::from)
+        .collect::<String>()
+        .into()
+}
+
+/// Compare tokens without returning early on the first differing byte
+pub(crate) fn tokens_equal(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[derive(Debug)]
+struct SecretTokens {
+    current: SecretString,
+    previous: Option<SecretString>,
+    pending: Option<SecretString>,
+}
+
+/// Registers a webhook with a randomly generated secret token and rotates the token on
+/// demand. While rotating, requests carrying either the new or the previous token are
+/// accepted so updates sent by telegram before the webhook was re-registered are not
+/// rejected. Cloning a WebhookManager shares the tokens between clones
+#[derive(Debug, Clone)]
+pub struct WebhookManager {
+    bot: Bot,
+    url: String,
+    ip_address: Option<String>,
+    allowed_updates: Option<Vec<String>>,
+    tokens: Arc<RwLock<SecretTokens>>,
+}
+
+impl WebhookManager {
+    /// Create a manager for a webhook at `url` with a new secret token. The webhook is
+    /// not registered until calling register
+    pub fn new<T: Into<String>>(bot: &Bot, url: T) -> Self {
+        Self {
+            bot: bot.clone(),
+            url: url.into(),
+            ip_address: None,
+            allowed_updates: None,
+            tokens: Arc::new(RwLock::new(SecretTokens {
+                current: generate_secret_token(),
+                previous: None,
+                pending: None,
+            })),
+        }
+    }
+
+    /// Set the ip address telegram sends updates to instead of resolving the url
+    pub fn ip_address(mut self, ip_address: IpAddr) -> Self {
+        self.ip_address = Some(ip_address.to_string());
+        self
+    }
+
+    /// Set the update types the webhook receives
+    pub fn allowed_updates(mut self, allowed_updates: Vec<String>) -> Self {
+        self.allowed_updates = Some(allowed_updates);
+        self
+    }
+
+    /// Get the secret token telegram currently sends with updates
+    pub fn get_secret_token(&self) -> String {
+        self.tokens.read().unwrap().current.expose().to_owned()
+    }
+
+    /// Check the secret token of an incoming request against the current token, the
+    /// previous token of an unfinished rotation and the token of a rotation in progress
+    pub fn validate(&self, token: Option<&str>) -> bool {
+        let Some(token) = token else {
+            return false;
+        };
+        let tokens = self.tokens.read().unwrap();
+        [
+            Some(tokens.current.expose()),
+            tokens.previous.as_ref().map(SecretString::expose),
+            tokens.pending.as_ref().map(SecretString::expose),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(false, |valid, t| tokens_equal(t, token) | valid)
+    }
+
+    async fn set_webhook(&self, secret_token: &str) -> BotResult<bool> {
+        let mut builder = self
+            .bot
+            .build_set_webhook(&self.url)
+            .secret_token(secret_token);
+        if let Some(ref ip_address) = self.ip_address {
+            builder = builder.ip_address(ip_address);
+        }
+        if let Some(ref allowed_updates) = self.allowed_updates {
+            builder = builder.allowed_updates(allowed_updates);
+        }
+        builder.build().await
+    }
+
+    /// Register the webhook with the current secret token
+    pub async fn register(&self) -> BotResult<bool> {
+        let token = self.get_secret_token();
+        self.set_webhook(&token).await
+    }
+
+    /// Generate a new secret token and re-register the webhook with it. The new token is
+    /// accepted while registering, and once registered the previous token is still
+    /// accepted until finish_rotation or the next rotation. If registering fails the
+    /// tokens are left unchanged
+    pub async fn rotate(&self) -> BotResult<bool> {
+        let token = generate_secret_token();
+        self.tokens.write().unwrap().pending = Some(token.clone());
+        let res = self.set_webhook(token.expose()).await;
+        let mut tokens = self.tokens.write().unwrap();
+        tokens.pending = None;
+        if res.is_ok() {
+            let current = std::mem::replace(&mut tokens.current, token);
+            tokens.previous = Some(current);
+        }
+        res
+    }
+
+    /// Stop accepting the token replaced by the last rotation
+    pub fn finish_rotation(&self) {
+        self.tokens.write().unwrap().previous = None;
+    }
+
+    /// Remove the webhook
+    pub async fn delete(&self) -> BotResult<bool> {
+        self.bot.build_delete_webhook().build().await
+    }
+}
+
+impl Bot {
+    /// Make sure get_updates will work by removing the webhook if one is set. Telegram
+    /// rejects get_updates while a webhook is registered. Returns true if a webhook was
+    /// removed
+    pub async fn ensure_polling_ready(&self, drop_pending_updates: bool) -> BotResult<bool> {
+        let info = self.get_webhook_info().await?;
+        if info.get_url().is_empty() {
+            return Ok(false);
+        }
+        log::info!(
+            "removing webhook with {} pending updates before polling",
+            info.get_pending_update_count()
+        );
+        self.build_delete_webhook()
+            .drop_pending_updates(drop_pending_updates)
+            .build()
+            .await
+    }
+
+    /// Register a webhook at `url` unless it is already set. Returns true if the webhook
+    /// was registered
+    pub async fn ensure_webhook(&self, url: &str) -> BotResult<bool> {
+        let info = self.get_webhook_info().await?;
+        if info.get_url() == url {
+            return Ok(false);
+        }
+        if let Some(error) = info.get_last_error_message() {
+            log::warn!(
+                "replacing webhook {} after error: {}",
+                info.get_url(),
+                error
+            );
+        }
+        self.build_set_webhook(url).build().await
+    }
+}
+
+impl Chat {
+    /// Broadcast a chat action like "typing" or "upload_photo" to this chat
+    pub async fn send_action(&self, bot: &Bot, action: ChatAction) -> BotResult<bool> {
+        bot.build_send_chat_action(self.get_id(), action)
+            .build()
+            .await
+    }
+
+    /// Show the "typing" status in this chat
+    pub async fn typing(&self, bot: &Bot) -> BotResult<bool> {
+        self.send_action(bot, ChatAction::Typing).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotBuilder;
+
+    #[test]
+    fn allowlist_subnets() {
+        let allowlist = IpAllowlist::new().trusted_proxy("127.0.0.1".parse().unwrap());
+        let local = "127.0.0.1".parse().unwrap();
+        assert!(allowlist.allows("149.154.167.220".parse().unwrap(), None));
+        assert!(!allowlist.allows("149.154.176.1".parse().unwrap(), None));
+        assert!(allowlist.allows("91.108.6.1".parse().unwrap(), None));
+        assert!(allowlist.allows(local, Some("10.0.0.1, 91.108.4.10")));
+        assert!(!allowlist.allows(local, Some("91.108.4.10, 10.0.0.1")));
+        assert!(!allowlist.allows(local, None));
+        assert!(!allowlist.allows("8.8.8.8".parse().unwrap(), Some("91.108.4.10")));
+    }
+
+    #[test]
+    fn dedup_window() {
+        let dedup = UpdateDedup::new(2);
+        assert!(dedup.check(1.into()));
+        assert!(!dedup.clone().check(1.into()));
+        assert!(dedup.check(2.into()));
+        assert!(dedup.check(3.into()));
+        assert!(dedup.check(1.into()));
+        assert!(UpdateDedup::new(0).check(1.into()));
+    }
+
+    #[test]
+    fn stale_updates() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let message = |date: i64| {
+            let message = serde_json::json!({
+                "message_id": 1,
+                "date": date,
+                "chat": {"id": 1, "type": "private"},
+            });
+            UpdateExt::Message(serde_json::from_value(message).unwrap())
+        };
+        let age = Duration::from_secs(60);
+        assert!(is_stale(&message(now - 120), age));
+        assert!(!is_stale(&message(now - 10), age));
+        assert!(!is_stale(&UpdateExt::Invalid, age));
+        assert_eq!(update_date(&message(5)), Some(5));
+        let cutoff = backlog_cutoff(Some(age)).unwrap();
+        assert!(is_before(&message(now - 120), cutoff));
+        assert!(!is_before(&message(now), cutoff));
+        assert_eq!(backlog_cutoff(None), None);
+    }
+
+    #[test]
+    fn previous_token_accepted_until_finished() {
+        let bot = BotBuilder::new("123:abc").unwrap().build();
+        let manager = WebhookManager::new(&bot, "https://example.com/hook");
+        let old = manager.get_secret_token();
+        assert_eq!(old.len(), SECRET_TOKEN_LENGTH);
+        assert!(manager.validate(Some(&old)));
+        assert!(!manager.validate(None));
+
+        {
+            let mut tokens = manager.tokens.write().unwrap();
+            tokens.previous = Some(std::mem::replace(
+                &mut tokens.current,
+                generate_secret_token(),
+            ));
+        }
+        assert!(manager.validate(Some(&old)));
+        assert!(manager.validate(Some(&manager.get_secret_token())));
+        manager.finish_rotation();
+        assert!(!manager.validate(Some(&old)));
+    }
+
+    #[test]
+    fn debug_hides_tokens() {
+        let bot = BotBuilder::new("123:abc").unwrap().build();
+        let manager = WebhookManager::new(&bot, "https://example.com/hook");
+        manager.tokens.write().unwrap().pending = Some(generate_secret_token());
+        let debug = format!("{:?}", manager);
+        assert!(!debug.contains(&manager.get_secret_token()));
+        let pending = manager.tokens.read().unwrap().pending.clone().unwrap();
+        assert!(!debug.contains(pending.expose()));
+    }
+
+    #[tokio::test]
+    async fn failed_rotation_keeps_tokens() {
+        let test = crate::testing::TestBot::builder()
+            .respond_error("setWebhook", 400, "Bad Request: bad webhook")
+            .build()
+            .await
+            .unwrap();
+        let manager = WebhookManager::new(test.get_bot(), "https://example.com/hook");
+        let old = manager.get_secret_token();
+        let previous = generate_secret_token();
+        manager.tokens.write().unwrap().previous = Some(previous.clone());
+        assert!(manager.rotate().await.is_err());
+        assert_eq!(manager.get_secret_token(), old);
+        assert!(manager.validate(Some(previous.expose())));
+        assert_eq!(test.sent("setWebhook").len(), 1);
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default time an endpoint is skipped after failing before it is tried again
+pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Ordered list of bot api urls, for example self-hosted relays in different regions.
+/// Requests go to the first healthy endpoint. An endpoint is marked unhealthy when it
+/// can't be connected to or answers with a bad gateway or service unavailable error
+/// (502 or 503), and the request moves on to the next one. Unhealthy endpoints are
+/// probed again by the next request once the probe interval passes. Requests that may
+/// have reached telegram, like timeouts and gateway timeouts, are never retried on
+/// another endpoint. Multipart uploads are only sent to the first endpoint tried, since
+/// their body can't be sent twice, but still mark it unhealthy when it fails
+#[derive(Debug)]
+pub struct Endpoints {
+    urls: Vec<String>,
+    probe_interval: Duration,
+    down: Mutex<Vec<Option<Instant>>>,
+}
+
+impl Endpoints {
+    /// Create a failover list trying urls in order
+    pub fn new<I, T>(urls: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let urls = urls.into_iter().map(|u| u.into()).collect::<Vec<_>>();
+        Self {
+            down: Mutex::new(vec![None; urls.len()]),
+            urls,
+            probe_interval: DEFAULT_PROBE_INTERVAL,
+        }
+    }
+
+    /// Change how long a failed endpoint is skipped before it is tried again
+    pub fn probe_interval(mut self, probe_interval: Duration) -> Self {
+        self.probe_interval = probe_interval;
+        self
+    }
+
+    /// Get the urls in the order they are tried
+    pub fn get_urls(&self) -> &'_ [String] {
+        &self.urls
+    }
+
+    /// Check if an endpoint is currently considered healthy
+    pub fn is_healthy(&self, url: &str) -> bool {
+        let down = self.down.lock().unwrap();
+        self.urls
+            .iter()
+            .zip(down.iter())
+            .any(|(u, d)| u == url && d.is_none())
+    }
+
+    /// Get the url an endpoint index refers to
+    pub(crate) fn get_url(&self, index: usize) -> &'_ str {
+        &self.urls[index]
+    }
+
+    /// Get the order to try endpoints in for a request. Healthy endpoints and failed
+    /// endpoints due for a probe come first in list order, the rest follow as a last
+    /// resort starting with the one that failed longest ago
+    pub(crate) fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let down = self.down.lock().unwrap();
+        let (mut available, mut failed): (Vec<_>, Vec<_>) = (0..self.urls.len())
+            .partition(|&i| !matches!(down[i], Some(d) if d + self.probe_interval > now));
+        failed.sort_by_key(|&i| down[i]);
+        available.extend(failed);
+        available
+    }
+
+    /// Record a failed request to an endpoint
+    pub(crate) fn mark_down(&self, index: usize) {
+        let url = &self.urls[index];
+        let mut down = self.down.lock().unwrap();
+        if down[index].is_none() {
+            log::warn!("bot api endpoint {} marked unhealthy", url);
+        }
+        down[index] = Some(Instant::now());
+    }
+
+    /// Record a successful request to an endpoint
+    pub(crate) fn mark_up(&self, index: usize) {
+        let mut down = self.down.lock().unwrap();
+        if down[index].take().is_some() {
+            log::info!("bot api endpoint {} recovered", self.urls[index]);
+        }
+    }
+}
+
+/// Check if a response came from a proxy or relay unable to reach telegram, meaning the
+/// request can safely be sent somewhere else. A gateway timeout is not included since
+/// the request may have been forwarded and handled by telegram without an answer in time
+pub(crate) fn is_relay_failure(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 502 | 503)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_endpoints_go_last() {
+        let endpoints = Endpoints::new(["http://a", "http://b", "http://c"]);
+        assert_eq!(endpoints.order(), vec![0, 1, 2]);
+        endpoints.mark_down(0);
+        endpoints.mark_down(1);
+        assert_eq!(endpoints.order(), vec![2, 0, 1]);
+        assert!(!endpoints.is_healthy("http://a"));
+        endpoints.mark_up(0);
+        assert!(endpoints.is_healthy("http://a"));
+        assert_eq!(endpoints.order(), vec![0, 2, 1]);
+        let endpoints = endpoints.probe_interval(Duration::ZERO);
+        assert_eq!(endpoints.order(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn gateway_timeouts_do_not_fail_over() {
+        assert!(is_relay_failure(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(is_relay_failure(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_relay_failure(reqwest::StatusCode::GATEWAY_TIMEOUT));
+    }
+}
@@ -0,0 +1,401 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::Dispatcher;
+use crate::gen_types::{
+    ChatMember, ChatType, DiceEmoji, Message, MessageEntityType, StickerType, UpdateExt,
+};
+use crate::service::ServiceKind;
+
+type Predicate = Arc<dyn Fn(&UpdateExt) -> bool + Send + Sync>;
+
+type AsyncPredicate =
+    Arc<dyn Fn(Bot, UpdateExt) -> BoxFuture<'static, BotResult<bool>> + Send + Sync>;
+
+/// Get the message carried by message-like updates
+fn message(update: &UpdateExt) -> Option<&Message> {
+    match update {
+        UpdateExt::Message(m)
+        | UpdateExt::EditedMessage(m)
+        | UpdateExt::ChannelPost(m)
+        | UpdateExt::EditedChannelPost(m) => Some(m),
+        _ => None,
+    }
+}
+
+/// Predicate over updates, combinable with and, or and not. Filters built from a
+/// message property never match updates without a message. Async filters may call the
+/// api, combining them with other filters makes the result async too. Combinators check
+/// their left side first and short circuit, so put cheap filters first
+///
+/// ```no_run
+/// # use botapi::{bot::Bot, dispatch::Dispatcher, filter, gen_types::ChatType};
+/// let dispatcher = Dispatcher::new().filtered(
+///     filter::text()
+///         .and(filter::chat_type(ChatType::Supergroup))
+///         .and(filter::from_admin()),
+///     |bot: Bot, update| async move { Ok(()) },
+/// );
+/// ```
+#[derive(Clone)]
+pub struct Filter(Inner);
+
+#[derive(Clone)]
+enum Inner {
+    Sync(Predicate),
+    Async(AsyncPredicate),
+}
+
+impl std::fmt::Debug for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Filter")
+    }
+}
+
+impl Filter {
+    /// Create a filter from an arbitrary predicate
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&UpdateExt) -> bool + Send + Sync + 'static,
+    {
+        Self(Inner::Sync(Arc::new(predicate)))
+    }
+
+    /// Create a filter from a predicate that may call the api. Cache results where
+    /// possible, the predicate runs for every update the filter is checked against
+    pub fn new_async<F, Fut>(predicate: F) -> Self
+    where
+        F: Fn(Bot, UpdateExt) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<bool>> + Send + 'static,
+    {
+        Self(Inner::Async(Arc::new(move |bot, update| {
+            Box::pin(predicate(bot, update))
+        })))
+    }
+
+    /// Combine two filters with a function of their results
+    fn combine<F>(self, other: Filter, op: F) -> Self
+    where
+        F: Fn(bool) -> Option<bool> + Copy + Send + Sync + 'static,
+    {
+        match (self.0, other.0) {
+            (Inner::Sync(a), Inner::Sync(b)) => {
+                Self::new(move |update| op(a(update)).unwrap_or_else(|| b(update)))
+            }
+            (a, b) => {
+                let (a, b) = (Filter(a), Filter(b));
+                Self::new_async(move |bot, update| {
+                    let (a, b) = (a.clone(), b.clone());
+                    async move {
+                        match op(a.check(&bot, &update).await?) {
+                            Some(result) => Ok(result),
+                            None => b.check(&bot, &update).await,
+                        }
+                    }
+                })
+            }
+        }
+    }
+
+    /// Create a filter matching updates carrying a message the predicate accepts
+    pub fn message<F>(predicate: F) -> Self
+    where
+        F: Fn(&Message) -> bool + Send + Sync + 'static,
+    {
+        Self::new(move |update| message(update).map(&predicate).unwrap_or(false))
+    }
+
+    /// Check if an update passes this filter without calling the api. Returns None for
+    /// filters containing an async filter, use check for those
+    pub fn matches(&self, update: &UpdateExt) -> Option<bool> {
+        match self.0 {
+            Inner::Sync(ref predicate) => Some(predicate(update)),
+            Inner::Async(_) => None,
+        }
+    }
+
+    /// Check if an update passes this filter, running async filters
+    pub async fn check(&self, bot: &Bot, update: &UpdateExt) -> BotResult<bool> {
+        match self.0 {
+            Inner::Sync(ref predicate) => Ok(predicate(update)),
+            Inner::Async(ref predicate) => predicate(bot.clone(), update.clone()).await,
+        }
+    }
+
+    /// Check if this filter needs check instead of matches
+    pub fn is_async(&self) -> bool {
+        matches!(self.0, Inner::Async(_))
+    }
+
+    /// Match only updates passing both filters
+    pub fn and(self, other: Filter) -> Self {
+        self.combine(other, |a| if a { None } else { Some(false) })
+    }
+
+    /// Match updates passing either filter
+    pub fn or(self, other: Filter) -> Self {
+        self.combine(other, |a| if a { Some(true) } else { None })
+    }
+
+    /// Match updates not passing this filter
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        match self.0 {
+            Inner::Sync(predicate) => Self::new(move |update| !predicate(update)),
+            inner => {
+                let filter = Filter(inner);
+                Self::new_async(move |bot, update| {
+                    let filter = filter.clone();
+                    async move { Ok(!filter.check(&bot, &update).await?) }
+                })
+            }
+        }
+    }
+}
+
+/// Match messages with text, including commands
+pub fn text() -> Filter {
+    Filter::message(|m| m.get_text().is_some())
+}
+
+/// Match messages sent by an owner or administrator of a group or supergroup. Calls
+/// get_chat_member, enable BotBuilder::cache to avoid calling it for every message
+pub fn from_admin() -> Filter {
+    Filter::new_async(|bot: Bot, update: UpdateExt| async move {
+        let Some(m) = message(&update) else {
+            return Ok(false);
+        };
+        let (Some(ChatType::Group | ChatType::Supergroup), Some(from)) =
+            (m.get_chat().get_chat_type(), m.get_from())
+        else {
+            return Ok(false);
+        };
+        let member = bot
+            .get_chat_member_cached(m.get_chat().get_id(), from.get_id())
+            .await?;
+        Ok(matches!(
+            member,
+            ChatMember::ChatMemberOwner(_) | ChatMember::ChatMemberAdministrator(_)
+        ))
+    })
+}
+
+/// Match messages sent in a chat of the given type
+pub fn chat_type(chat_type: ChatType) -> Filter {
+    Filter::message(move |m| m.get_chat().get_chat_type() == Some(chat_type))
+}
+
+/// Match messages with an entity of the given type in the text or caption
+pub fn has_entity(entity_type: MessageEntityType) -> Filter {
+    Filter::message(move |m| {
+        m.get_entities()
+            .into_iter()
+            .chain(m.get_caption_entities())
+            .flatten()
+            .any(|e| e.get_entity_type() == Some(entity_type))
+    })
+}
+
+/// Match messages containing a sticker of the given type
+pub fn sticker_type(sticker_type: StickerType) -> Filter {
+    Filter::message(move |m| {
+        m.get_sticker()
+            .map(|s| s.get_sticker_type() == Some(sticker_type))
+            .unwrap_or(false)
+    })
+}
+
+/// Match messages containing a dice thrown with the given emoji
+pub fn dice(emoji: DiceEmoji) -> Filter {
+    Filter::message(move |m| {
+        m.get_dice()
+            .map(|d| d.get_dice_emoji() == Some(emoji))
+            .unwrap_or(false)
+    })
+}
+
+/// Match service messages of the given kind, like users joining a group
+pub fn service(kind: ServiceKind) -> Filter {
+    Filter::message(move |m| m.service_kind() == Some(kind))
+}
+
+/// Match any service message
+pub fn any_service() -> Filter {
+    Filter::message(|m| m.is_service())
+}
+
+/// Get the text or caption of a message
+#[cfg(feature = "regex")]
+fn message_text(m: &Message) -> Option<&str> {
+    m.get_text().or(m.get_caption())
+}
+
+/// Match messages whose text or caption matches a regex
+#[cfg(feature = "regex")]
+pub fn regex(regex: regex::Regex) -> Filter {
+    Filter::message(move |m| message_text(m).is_some_and(|t| regex.is_match(t)))
+}
+
+/// Capture groups from matching a message against a regex, passed to handlers added with
+/// Dispatcher::on_regex
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures {
+    groups: Vec<Option<String>>,
+    names: Vec<(String, usize)>,
+}
+
+#[cfg(feature = "regex")]
+impl Captures {
+    /// Match a message's text or caption, returning the captures of the first match
+    pub fn extract(regex: &regex::Regex, message: &Message) -> Option<Self> {
+        let captures = regex.captures(message_text(message)?)?;
+        Some(Self {
+            groups: captures
+                .iter()
+                .map(|g| g.map(|g| g.as_str().to_owned()))
+                .collect(),
+            names: regex
+                .capture_names()
+                .enumerate()
+                .filter_map(|(i, n)| n.map(|n| (n.to_owned(), i)))
+                .collect(),
+        })
+    }
+
+    /// Get a group by index, 0 being the whole match
+    pub fn get(&self, index: usize) -> Option<&'_ str> {
+        self.groups.get(index)?.as_deref()
+    }
+
+    /// Get a named group
+    pub fn name(&self, name: &str) -> Option<&'_ str> {
+        let (_, index) = self.names.iter().find(|(n, _)| n == name)?;
+        self.get(*index)
+    }
+
+    /// Get the number of groups, including the whole match
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Check if there are no groups, never true for captures from a match
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+impl Dispatcher {
+    /// Add a handler called for messages whose text or caption matches a regex, with the
+    /// capture groups of the first match
+    #[cfg(feature = "regex")]
+    pub fn on_regex<F, Fut>(self, regex: regex::Regex, handler: F) -> Self
+    where
+        F: Fn(Bot, Message, Captures) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.typed_handler(
+            "on_regex",
+            move |update| {
+                let m = message(&update)?;
+                let captures = Captures::extract(&regex, m)?;
+                Some((m.clone(), captures))
+            },
+            move |bot, (message, captures)| handler(bot, message, captures),
+        )
+    }
+
+    /// Add a handler only called for updates passing a filter
+    pub fn filtered<F, Fut>(self, filter: Filter, handler: F) -> Self
+    where
+        F: Fn(Bot, UpdateExt) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.auto_matching("filtered", move |bot: Bot, update: UpdateExt| {
+            let filter = filter.clone();
+            let handler = Arc::clone(&handler);
+            async move {
+                if filter.check(&bot, &update).await? {
+                    handler(bot, update).await.map(|()| true)
+                } else {
+                    Ok(false)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combinators() {
+        let yes = Filter::new(|_| true);
+        let no = Filter::new(|_| false);
+        let update = UpdateExt::Invalid;
+        assert_eq!(yes.clone().or(no.clone()).matches(&update), Some(true));
+        assert_eq!(yes.clone().and(no.clone()).matches(&update), Some(false));
+        assert_eq!(no.not().matches(&update), Some(true));
+        assert_eq!(
+            chat_type(ChatType::Supergroup).matches(&update),
+            Some(false)
+        );
+        assert_eq!(
+            service(ServiceKind::NewChatMembers).matches(&update),
+            Some(false)
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_captures() {
+        let message: Message = serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": 1, "type": "private"},
+            "text": "ban 1234 for spam",
+        }))
+        .unwrap();
+        let re = regex::Regex::new(r"^ban (?<user>\d+)(?: for (.+))?").unwrap();
+        let captures = Captures::extract(&re, &message).unwrap();
+        assert_eq!(captures.name("user"), Some("1234"));
+        assert_eq!(captures.get(2), Some("spam"));
+        assert_eq!(captures.len(), 3);
+        assert_eq!(regex(re).matches(&UpdateExt::Message(message)), Some(true));
+    }
+
+    #[tokio::test]
+    async fn async_combinators() {
+        let bot = crate::bot::BotBuilder::new("1234:token").unwrap().build();
+        let update = UpdateExt::Invalid;
+        let yes = Filter::new_async(|_, _| async { Ok(true) });
+        let no = Filter::new(|_| false);
+        let filter = no.clone().or(yes.clone());
+        assert!(filter.is_async());
+        assert_eq!(filter.matches(&update), None);
+        assert!(filter.check(&bot, &update).await.unwrap());
+        assert!(!no
+            .clone()
+            .and(yes.clone())
+            .check(&bot, &update)
+            .await
+            .unwrap());
+        assert!(!yes.not().check(&bot, &update).await.unwrap());
+        assert!(!from_admin().check(&bot, &update).await.unwrap());
+    }
+
+    #[test]
+    fn str_enums() {
+        assert_eq!(
+            "supergroup".parse::<ChatType>().ok(),
+            Some(ChatType::Supergroup)
+        );
+        assert_eq!(DiceEmoji::Darts.as_str(), "🎯");
+        assert!("nonsense".parse::<StickerType>().is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{ChatHandle, Message};
+
+/// Formatting modes supported by telegram's parse_mode parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseMode {
+    Html,
+    MarkdownV2,
+    /// Legacy markdown, kept for backwards compatibility by telegram
+    Markdown,
+}
+
+impl ParseMode {
+    /// Get the value of the parse_mode parameter for this mode
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParseMode::Html => "HTML",
+            ParseMode::MarkdownV2 => "MarkdownV2",
+            ParseMode::Markdown => "Markdown",
+        }
+    }
+
+    /// Escape text so it is sent literally in this mode
+    pub fn escape(&self, text: &str) -> String {
+        match self {
+            ParseMode::Html => escape_html(text),
+            ParseMode::MarkdownV2 => escape_markdown_v2(text),
+            ParseMode::Markdown => escape_markdown(text),
+        }
+    }
+}
+
+fn escape_with(text: &str, special: &[char]) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(&c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Escape the characters with special meaning in HTML mode
+pub fn escape_html(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Escape the characters with special meaning in MarkdownV2 mode
+pub fn escape_markdown_v2(text: &str) -> String {
+    escape_with(
+        text,
+        &[
+            '\\', '_', '*', '[', ']', '(', ')', '~', '`', '>', '#', '+', '-', '=', '|', '{', '}',
+            '.', '!',
+        ],
+    )
+}
+
+/// Escape the characters with special meaning in legacy Markdown mode
+pub fn escape_markdown(text: &str) -> String {
+    escape_with(text, &['_', '*', '`', '['])
+}
+
+impl Bot {
+    /// Send a message formatted using `mode`. If telegram fails to parse the formatting
+    /// and auto_escape is enabled on the bot the message is sent again with all markup
+    /// escaped, otherwise the error is returned with details from ApiError::get_parse_error
+    pub async fn send_formatted<V>(
+        &self,
+        chat_id: V,
+        text: &str,
+        mode: ParseMode,
+    ) -> BotResult<Message>
+    where
+        V: Into<ChatHandle>,
+    {
+        let chat: ChatHandle = chat_id.into();
+        let res = self
+            .build_send_message(chat.clone(), text)
+            .parse_mode(mode.as_str())
+            .build()
+            .await;
+        match res {
+            Err(err) if self.get_auto_escape() && err.get_parse_error().is_some() => {
+                log::debug!("resending message with escaped markup: {}", err);
+                self.build_send_message(chat, &mode.escape(text))
+                    .parse_mode(mode.as_str())
+                    .build()
+                    .await
+            }
+            res => res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_modes() {
+        assert_eq!(escape_html("<b>a & b</b>"), "&lt;b&gt;a &amp; b&lt;/b&gt;");
+        assert_eq!(escape_markdown_v2("1.5 *x*"), "1\\.5 \\*x\\*");
+        assert_eq!(escape_markdown("snake_case"), "snake\\_case");
+        assert_eq!(ParseMode::MarkdownV2.as_str(), "MarkdownV2");
+    }
+}
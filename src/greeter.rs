@@ -0,0 +1,333 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{Flow, Layer};
+use crate::format::ParseMode;
+use crate::gen_types::{UpdateExt, User};
+use crate::moderation::CaptchaLayer;
+use crate::service::ServiceKind;
+use crate::settings::ChatSettings;
+
+/// Greeter settings of one chat, overriding the templates of the GreeterLayer. Chats
+/// without saved settings are greeted with the layer's templates
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct GreetSettings {
+    /// Don't greet anyone in this chat
+    pub disabled: bool,
+    /// Welcome template replacing the layer's
+    pub welcome: Option<String>,
+    /// Goodbye template replacing the layer's
+    pub goodbye: Option<String>,
+}
+
+/// Fill in the placeholders of a template, escaping names and the title for the parse
+/// mode the greeting is sent with
+fn render(template: &str, users: &[&User], chat: &str, parse_mode: Option<ParseMode>) -> String {
+    let escape = |text: &str| match parse_mode {
+        Some(parse_mode) => parse_mode.escape(text),
+        None => text.to_owned(),
+    };
+    let names = users
+        .iter()
+        .map(|u| u.get_first_name())
+        .collect::<Vec<_>>()
+        .join(", ");
+    template
+        .replace("{name}", &escape(&names))
+        .replace("{chat}", &escape(chat))
+}
+
+/// Templates and settings of a GreeterLayer
+#[derive(Clone, Debug)]
+struct Greetings {
+    welcome: Option<String>,
+    goodbye: Option<String>,
+    ttl: Option<Duration>,
+    settings: Option<ChatSettings<GreetSettings>>,
+}
+
+impl Greetings {
+    /// Welcome users who joined or say goodbye to a user who left, depending on kind
+    async fn greet(
+        self,
+        bot: &Bot,
+        chat: i64,
+        title: &str,
+        kind: ServiceKind,
+        users: Vec<User>,
+    ) -> BotResult<()> {
+        let (welcome, goodbye) = match self.settings {
+            Some(settings) => {
+                let settings = settings.get(chat).await?;
+                if settings.disabled {
+                    return Ok(());
+                }
+                (
+                    settings.welcome.or(self.welcome),
+                    settings.goodbye.or(self.goodbye),
+                )
+            }
+            None => (self.welcome, self.goodbye),
+        };
+        let template = match kind {
+            ServiceKind::NewChatMembers => welcome,
+            ServiceKind::LeftChatMember => goodbye,
+            _ => None,
+        };
+        let users = users.iter().filter(|u| !u.get_is_bot()).collect::<Vec<_>>();
+        let Some(template) = template.filter(|_| !users.is_empty()) else {
+            return Ok(());
+        };
+        let text = render(&template, &users, title, bot.get_parse_mode());
+        let greeting = bot.build_send_message(chat, &text).build().await?;
+        if let Some(ttl) = self.ttl {
+            bot.schedule_delete(chat, greeting.get_message_id(), ttl);
+        }
+        Ok(())
+    }
+}
+
+/// Layer sending a welcome message when users join a group and a goodbye message when
+/// they leave. Templates may contain `{name}`, replaced with the first names of the
+/// users, and `{chat}`, replaced with the title of the chat. Both are escaped for the
+/// parse mode of the bot. Bots joining or leaving are not greeted. Service messages
+/// still reach the handlers afterwards
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use botapi::{dispatch::Dispatcher, greeter::{GreeterLayer, GreetSettings}};
+/// # use botapi::settings::ChatSettings;
+/// # tokio_test::block_on(async {
+/// let settings = ChatSettings::<GreetSettings>::in_memory();
+/// let dispatcher = Dispatcher::new().layer(
+///     GreeterLayer::new()
+///         .goodbye("Bye {name}")
+///         .delete_after(Duration::from_secs(60))
+///         .settings(settings.clone()),
+/// );
+/// // turn greetings off for one chat
+/// settings.update(-1001234, |s| s.disabled = true).await.unwrap();
+/// # })
+/// ```
+pub struct GreeterLayer {
+    greetings: Greetings,
+    captcha: Option<Arc<CaptchaLayer>>,
+}
+
+impl std::fmt::Debug for GreeterLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GreeterLayer")
+            .field("welcome", &self.greetings.welcome)
+            .field("goodbye", &self.greetings.goodbye)
+            .field("ttl", &self.greetings.ttl)
+            .field("settings", &self.greetings.settings)
+            .field("captcha", &self.captcha.is_some())
+            .finish()
+    }
+}
+
+impl Default for GreeterLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GreeterLayer {
+    /// Create a greeter welcoming users with "Welcome to {chat}, {name}!" and not saying
+    /// goodbye
+    pub fn new() -> Self {
+        Self {
+            greetings: Greetings {
+                welcome: Some("Welcome to {chat}, {name}!".to_owned()),
+                goodbye: None,
+                ttl: None,
+                settings: None,
+            },
+            captcha: None,
+        }
+    }
+
+    /// Set the welcome template
+    pub fn welcome<T: Into<String>>(mut self, template: T) -> Self {
+        self.greetings.welcome = Some(template.into());
+        self
+    }
+
+    /// Set the goodbye template
+    pub fn goodbye<T: Into<String>>(mut self, template: T) -> Self {
+        self.greetings.goodbye = Some(template.into());
+        self
+    }
+
+    /// Don't send welcome messages, only goodbyes
+    pub fn no_welcome(mut self) -> Self {
+        self.greetings.welcome = None;
+        self
+    }
+
+    /// Delete greetings after ttl so they don't clutter the chat
+    pub fn delete_after(mut self, ttl: Duration) -> Self {
+        self.greetings.ttl = Some(ttl);
+        self
+    }
+
+    /// Read per-chat templates and whether greeting is enabled from settings
+    pub fn settings(mut self, settings: ChatSettings<GreetSettings>) -> Self {
+        self.greetings.settings = Some(settings);
+        self
+    }
+
+    /// Challenge new members with a captcha, welcoming them once they solve it. The
+    /// captcha layer handles its own button presses, so don't add it to the dispatcher
+    /// separately
+    pub fn captcha(mut self, captcha: CaptchaLayer) -> Self {
+        self.captcha = Some(Arc::new(captcha));
+        self
+    }
+}
+
+impl Layer for GreeterLayer {
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let captcha = self
+            .captcha
+            .as_ref()
+            .map(|c| c.check(bot.clone(), update.clone()));
+        let greet = match update {
+            UpdateExt::Message(message) => {
+                let users = match message.service_kind() {
+                    // Members are welcomed once they solve the captcha instead
+                    Some(ServiceKind::NewChatMembers) if self.captcha.is_some() => None,
+                    Some(kind @ ServiceKind::NewChatMembers) => message
+                        .get_new_chat_members()
+                        .map(|members| (kind, members.to_vec())),
+                    Some(kind @ ServiceKind::LeftChatMember) => message
+                        .get_left_chat_member()
+                        .map(|member| (kind, vec![member.clone()])),
+                    _ => None,
+                };
+                users.map(|(kind, users)| {
+                    let chat = message.get_chat();
+                    let title = chat.get_title().unwrap_or_default().to_owned();
+                    (chat.get_id(), title, kind, users)
+                })
+            }
+            _ => None,
+        };
+        let greetings = self.greetings.clone();
+        Box::pin(async move {
+            if let Some(captcha) = captcha {
+                let (flow, solved) = captcha.await?;
+                if let Some(solved) = solved {
+                    greetings
+                        .clone()
+                        .greet(
+                            &bot,
+                            solved.chat,
+                            &solved.title,
+                            ServiceKind::NewChatMembers,
+                            vec![solved.user],
+                        )
+                        .await?;
+                }
+                if flow == Flow::Stop {
+                    return Ok(Flow::Stop);
+                }
+            }
+            if let Some((chat, title, kind, users)) = greet {
+                greetings.greet(&bot, chat, &title, kind, users).await?;
+            }
+            Ok(Flow::Continue)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+    use serde_json::json;
+
+    fn joined(chat: i64) -> UpdateExt {
+        UpdateExt::Message(
+            serde_json::from_value(json!({
+                "message_id": 1,
+                "date": 0,
+                "chat": {"id": chat, "type": "supergroup", "title": "Rust"},
+                "new_chat_members": [
+                    {"id": 2, "is_bot": false, "first_name": "Ana"},
+                    {"id": 3, "is_bot": true, "first_name": "Spam"},
+                ],
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn welcomes_members() {
+        let test = TestBot::new().await.unwrap();
+        let settings = ChatSettings::<GreetSettings>::in_memory();
+        settings.update(-200, |s| s.disabled = true).await.unwrap();
+        let layer = GreeterLayer::new().settings(settings);
+
+        let flow = layer.call(test.get_bot().clone(), joined(-100)).await;
+        assert_eq!(flow.unwrap(), Flow::Continue);
+        layer
+            .call(test.get_bot().clone(), joined(-200))
+            .await
+            .unwrap();
+
+        crate::assert_sent!(
+            test,
+            method = "sendMessage",
+            chat_id = -100,
+            text = "Welcome to Rust, Ana!"
+        );
+        assert_eq!(test.sent("sendMessage").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn welcomes_after_captcha() {
+        let test = TestBot::builder()
+            .respond(
+                "getChat",
+                json!({"id": -100, "type": "supergroup", "title": "<Rust>"}),
+            )
+            .build()
+            .await
+            .unwrap();
+        let layer = GreeterLayer::new().captcha(CaptchaLayer::new(Duration::from_secs(60)));
+        layer
+            .call(test.get_bot().clone(), joined(-100))
+            .await
+            .unwrap();
+        assert!(!test
+            .sent("sendMessage")
+            .iter()
+            .any(|m| m.get("text").unwrap().starts_with("Welcome")));
+
+        let query = json!({
+            "id": "1",
+            "from": {"id": 2, "is_bot": false, "first_name": "<b>Ana</b>"},
+            "chat_instance": "1",
+            "data": "captcha:-100:2",
+        });
+        let options = crate::options::RequestOptions::new().parse_mode(ParseMode::Html);
+        let flow = options
+            .scope(layer.call(
+                test.get_bot().clone(),
+                UpdateExt::CallbackQuery(serde_json::from_value(query).unwrap()),
+            ))
+            .await;
+        assert_eq!(flow.unwrap(), Flow::Stop);
+        crate::assert_sent!(
+            test,
+            method = "sendMessage",
+            text = "Welcome to &lt;Rust&gt;, &lt;b&gt;Ana&lt;/b&gt;!"
+        );
+    }
+}
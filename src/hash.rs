@@ -0,0 +1,154 @@
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::bot::{Bot, BotResult};
+use crate::download::FileReader;
+use crate::gen_types::FileData;
+
+/// SHA-256 digest of the contents of a file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    /// Hash a file already in memory
+    pub fn of(bytes: &[u8]) -> Self {
+        Self(Sha256::digest(bytes).into())
+    }
+
+    /// Get the raw digest
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Get the digest as lowercase hex
+    pub fn to_hex(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl FileData {
+    /// Hash the contents of a file to be uploaded. None for file_ids, urls and parts,
+    /// which are not hashed
+    pub fn content_hash(&self) -> Option<ContentHash> {
+        match self {
+            Self::Bytes(bytes) => Some(ContentHash::of(bytes)),
+            _ => None,
+        }
+    }
+}
+
+/// Upload a file with any send method, returning the result along with the hash of the
+/// uploaded contents
+///
+/// ```no_run
+/// # use botapi::{bot::BotBuilder, gen_types::FileData, hash::hash_upload};
+/// # tokio_test::block_on(async {
+/// # let bot = BotBuilder::new("sometoken").unwrap().build();
+/// let (message, hash) = hash_upload(FileData::Bytes(vec![1, 2, 3]), |photo| {
+///     bot.build_send_photo(1, photo).build()
+/// })
+/// .await
+/// .unwrap();
+/// # })
+/// ```
+pub async fn hash_upload<F, Fut, T>(file: FileData, send: F) -> BotResult<(T, Option<ContentHash>)>
+where
+    F: FnOnce(FileData) -> Fut,
+    Fut: Future<Output = BotResult<T>>,
+{
+    let hash = file.content_hash();
+    let result = send(file).await?;
+    Ok((result, hash))
+}
+
+/// Reader that hashes everything read through it
+#[derive(Debug)]
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R> HashingReader<R> {
+    /// Wrap a reader
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Get the wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get the hash of everything read so far
+    pub fn finish(self) -> ContentHash {
+        ContentHash(self.hasher.finalize().into())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let start = buf.filled().len();
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = res {
+            self.hasher.update(&buf.filled()[start..]);
+        }
+        res
+    }
+}
+
+impl Bot {
+    /// Download a file using the file_path returned by get_file along with its hash
+    pub async fn download_file_hashed(&self, file_path: &str) -> BotResult<(Vec<u8>, ContentHash)> {
+        let bytes = self.download_file(file_path).await?;
+        let hash = ContentHash::of(&bytes);
+        Ok((bytes, hash))
+    }
+
+    /// Open a file for reading like open_file, hashing it as it is read. Call finish
+    /// once the file is read to get the hash
+    pub async fn open_file_hashed(&self, file_path: &str) -> BotResult<HashingReader<FileReader>> {
+        Ok(HashingReader::new(self.open_file(file_path).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn reader_matches_digest() {
+        let data = b"some file contents".to_vec();
+        let mut reader = HashingReader::new(&data[..]);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, data);
+        let hash = reader.finish();
+        assert_eq!(hash, ContentHash::of(&data));
+        assert_eq!(FileData::Bytes(data).content_hash(), Some(hash));
+        assert_eq!(
+            ContentHash::of(b"").to_hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
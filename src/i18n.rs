@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{ChatHandle, Message, User};
+
+/// Backend for resolving localized strings. Implement this to plug in a custom
+/// localization system
+pub trait Translator: Send + Sync + Debug {
+    /// Resolve a string by key for a language, substituting args. Returns None if the key
+    /// is not known in this language or the fallback language
+    fn translate(&self, language: Option<&str>, key: &str, args: &[(&str, &str)])
+        -> Option<String>;
+}
+
+/// Candidate bundle names for a language code, for example "pt-br" then "pt"
+fn candidates(language: Option<&str>) -> impl Iterator<Item = String> + '_ {
+    let language = language.map(|v| v.to_lowercase());
+    let primary = language
+        .as_ref()
+        .and_then(|v| v.split(['-', '_']).next())
+        .map(|v| v.to_owned());
+    language.into_iter().chain(primary)
+}
+
+/// Translator using simple json bundles mapping keys to strings. Args are substituted
+/// into the string where `{name}` appears
+#[derive(Debug, Clone)]
+pub struct JsonTranslator {
+    default_language: String,
+    bundles: HashMap<String, HashMap<String, String>>,
+}
+
+impl JsonTranslator {
+    /// Create a translator with no bundles, using `default_language` when a user's
+    /// language has no bundle or is missing a key
+    pub fn new<T: Into<String>>(default_language: T) -> Self {
+        Self {
+            default_language: default_language.into().to_lowercase(),
+            bundles: HashMap::new(),
+        }
+    }
+
+    /// Add or replace the bundle for a language
+    pub fn bundle<T: Into<String>>(mut self, language: T, bundle: HashMap<String, String>) -> Self {
+        self.bundles.insert(language.into().to_lowercase(), bundle);
+        self
+    }
+
+    /// Load bundles from a json object mapping language codes to objects of strings,
+    /// for example `{"en": {"hello": "Hello {name}"}}`
+    pub fn from_json<T: Into<String>>(default_language: T, json: &str) -> Result<Self> {
+        let bundles: HashMap<String, HashMap<String, String>> = serde_json::from_str(json)?;
+        Ok(bundles
+            .into_iter()
+            .fold(Self::new(default_language), |t, (language, bundle)| {
+                t.bundle(language, bundle)
+            }))
+    }
+
+    /// Load every `<language>.json` file in a directory as a bundle
+    pub fn from_dir<T: Into<String>, P: AsRef<Path>>(default_language: T, path: P) -> Result<Self> {
+        let mut res = Self::new(default_language);
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().and_then(|v| v.to_str()) != Some("json") {
+                continue;
+            }
+            let language = path
+                .file_stem()
+                .and_then(|v| v.to_str())
+                .ok_or_else(|| anyhow!("invalid bundle name {}", path.display()))?
+                .to_owned();
+            let bundle = serde_json::from_slice(&std::fs::read(&path)?)?;
+            res = res.bundle(language, bundle);
+        }
+        Ok(res)
+    }
+}
+
+impl Translator for JsonTranslator {
+    fn translate(
+        &self,
+        language: Option<&str>,
+        key: &str,
+        args: &[(&str, &str)],
+    ) -> Option<String> {
+        let template = candidates(language)
+            .chain(std::iter::once(self.default_language.clone()))
+            .find_map(|language| self.bundles.get(&language)?.get(key))?;
+        Some(args.iter().fold(template.clone(), |s, (name, value)| {
+            s.replace(&format!("{{{}}}", name), value)
+        }))
+    }
+}
+
+/// Translator using Fluent resources
+#[cfg(feature = "fluent")]
+pub struct FluentTranslator {
+    default_language: String,
+    bundles:
+        HashMap<String, fluent_bundle::concurrent::FluentBundle<fluent_bundle::FluentResource>>,
+}
+
+#[cfg(feature = "fluent")]
+impl Debug for FluentTranslator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FluentTranslator")
+            .field("default_language", &self.default_language)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "fluent")]
+impl FluentTranslator {
+    /// Create a translator with no resources, using `default_language` when a user's
+    /// language has no resource or is missing a message
+    pub fn new<T: Into<String>>(default_language: T) -> Self {
+        Self {
+            default_language: default_language.into().to_lowercase(),
+            bundles: HashMap::new(),
+        }
+    }
+
+    /// Add a Fluent resource in ftl syntax for a language
+    pub fn resource(mut self, language: &str, ftl: &str) -> Result<Self> {
+        let resource = fluent_bundle::FluentResource::try_new(ftl.to_owned())
+            .map_err(|(_, errors)| anyhow!("invalid fluent resource: {:?}", errors))?;
+        let language = language.to_lowercase();
+        let bundle = match self.bundles.entry(language) {
+            std::collections::hash_map::Entry::Occupied(bundle) => bundle.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let id: unic_langid::LanguageIdentifier = entry.key().parse()?;
+                let mut bundle = fluent_bundle::concurrent::FluentBundle::new_concurrent(vec![id]);
+                bundle.set_use_isolating(false);
+                entry.insert(bundle)
+            }
+        };
+        bundle
+            .add_resource(resource)
+            .map_err(|errors| anyhow!("duplicate fluent messages: {:?}", errors))?;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "fluent")]
+impl Translator for FluentTranslator {
+    fn translate(
+        &self,
+        language: Option<&str>,
+        key: &str,
+        args: &[(&str, &str)],
+    ) -> Option<String> {
+        let (bundle, pattern) = candidates(language)
+            .chain(std::iter::once(self.default_language.clone()))
+            .find_map(|language| {
+                let bundle = self.bundles.get(&language)?;
+                let pattern = bundle.get_message(key)?.value()?;
+                Some((bundle, pattern))
+            })?;
+        let mut fluent_args = fluent_bundle::FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, *value);
+        }
+        let mut errors = Vec::new();
+        let res = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+        if !errors.is_empty() {
+            log::warn!("failed to format {}: {:?}", key, errors);
+        }
+        Some(res.into_owned())
+    }
+}
+
+impl Bot {
+    /// Resolve a localized string using the configured translator. If there is no
+    /// translator or the key is missing the key itself is returned
+    pub fn translate(&self, language: Option<&str>, key: &str, args: &[(&str, &str)]) -> String {
+        self.get_translator()
+            .and_then(|t| t.translate(language, key, args))
+            .unwrap_or_else(|| key.to_owned())
+    }
+
+    /// Send a message with text resolved from a localization key in the user's language
+    pub async fn send_message_localized<V>(
+        &self,
+        chat: V,
+        user: Option<&User>,
+        key: &str,
+        args: &[(&str, &str)],
+    ) -> BotResult<Message>
+    where
+        V: Into<ChatHandle> + Serialize,
+    {
+        let text = self.translate(user.and_then(|u| u.get_language_code()), key, args);
+        self.build_send_message(chat, &text).build().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_fallback() {
+        let t = JsonTranslator::from_json(
+            "en",
+            r#"{"en": {"hi": "Hello {name}", "bye": "Bye"}, "pt": {"hi": "Olá {name}"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            t.translate(Some("pt-BR"), "hi", &[("name", "Ana")])
+                .as_deref(),
+            Some("Olá Ana")
+        );
+        assert_eq!(t.translate(Some("pt"), "bye", &[]).as_deref(), Some("Bye"));
+        assert_eq!(
+            t.translate(None, "hi", &[("name", "Bob")]).as_deref(),
+            Some("Hello Bob")
+        );
+        assert_eq!(t.translate(Some("en"), "missing", &[]), None);
+    }
+}
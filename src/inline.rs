@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{Flow, Layer};
+use crate::gen_types::{ChosenInlineResult, UpdateExt};
+
+type SelectionCallback = Arc<dyn Fn(&SelectionStats) + Send + Sync>;
+
+/// How often an inline result was shown to users and how often it was picked
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SelectionStats {
+    result_id: String,
+    shown: u64,
+    chosen: u64,
+}
+
+impl SelectionStats {
+    /// Get the id of the inline result
+    pub fn get_result_id(&self) -> &str {
+        &self.result_id
+    }
+
+    /// Get the number of answers to inline queries containing this result
+    pub fn get_shown(&self) -> u64 {
+        self.shown
+    }
+
+    /// Get the number of times a user picked this result
+    pub fn get_chosen(&self) -> u64 {
+        self.chosen
+    }
+
+    /// Get the fraction of answers containing this result that led to it being picked
+    pub fn get_selection_rate(&self) -> f64 {
+        if self.shown == 0 {
+            0.0
+        } else {
+            self.chosen as f64 / self.shown as f64
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    shown: u64,
+    chosen: u64,
+}
+
+/// Correlates chosen_inline_result updates with the results returned from earlier
+/// answer_inline_query calls. Record answers with `record_answer` and add this as a layer
+/// to a Dispatcher to count selections. Telegram only sends chosen_inline_result when
+/// inline feedback is enabled for the bot with @BotFather. Cloning is cheap and clones
+/// share their statistics
+#[derive(Clone, Default)]
+pub struct InlineAnalytics {
+    counts: Arc<Mutex<HashMap<String, Counts>>>,
+    on_selection: Option<SelectionCallback>,
+}
+
+impl std::fmt::Debug for InlineAnalytics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InlineAnalytics")
+            .field("counts", &self.counts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl InlineAnalytics {
+    /// Create an empty analytics helper
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call a function with the updated statistics every time a result is chosen
+    pub fn on_selection<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&SelectionStats) + Send + Sync + 'static,
+    {
+        self.on_selection = Some(Arc::new(callback));
+        self
+    }
+
+    /// Record the ids of the results sent in answer to an inline query
+    pub fn record_answer<I, S>(&self, result_ids: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut counts = self.counts.lock().unwrap();
+        for id in result_ids {
+            counts.entry(id.into()).or_default().shown += 1;
+        }
+    }
+
+    /// Record a result picked by a user, returning the updated statistics for it
+    pub fn record_chosen(&self, chosen: &ChosenInlineResult) -> SelectionStats {
+        let result_id = chosen.get_result_id();
+        let counts = {
+            let mut counts = self.counts.lock().unwrap();
+            let entry = counts.entry(result_id.to_owned()).or_default();
+            entry.chosen += 1;
+            *entry
+        };
+        let stats = SelectionStats {
+            result_id: result_id.to_owned(),
+            shown: counts.shown,
+            chosen: counts.chosen,
+        };
+        if let Some(ref callback) = self.on_selection {
+            callback(&stats);
+        }
+        stats
+    }
+
+    /// Get the statistics of a single result
+    pub fn get_stats(&self, result_id: &str) -> Option<SelectionStats> {
+        self.counts
+            .lock()
+            .unwrap()
+            .get(result_id)
+            .map(|counts| SelectionStats {
+                result_id: result_id.to_owned(),
+                shown: counts.shown,
+                chosen: counts.chosen,
+            })
+    }
+
+    /// Get the statistics of every result recorded, sorted by selection rate
+    pub fn get_all_stats(&self) -> Vec<SelectionStats> {
+        let mut stats = self
+            .counts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(result_id, counts)| SelectionStats {
+                result_id: result_id.clone(),
+                shown: counts.shown,
+                chosen: counts.chosen,
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| b.get_selection_rate().total_cmp(&a.get_selection_rate()));
+        stats
+    }
+}
+
+impl Layer for InlineAnalytics {
+    fn call(&self, _: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        if let UpdateExt::ChosenInlineResult(ref chosen) = update {
+            self.record_chosen(chosen);
+        }
+        Box::pin(async { Ok(Flow::Continue) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn selection_rate() {
+        let picked = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&picked);
+        let analytics = InlineAnalytics::new().on_selection(move |stats| {
+            counter.store(stats.get_chosen(), Ordering::Relaxed);
+        });
+        analytics.record_answer(["a", "b"]);
+        analytics.record_answer(["a"]);
+        let mut chosen = ChosenInlineResult::default();
+        chosen.set_result_id("a".to_owned());
+        let stats = analytics.record_chosen(&chosen);
+        assert_eq!(stats.get_shown(), 2);
+        assert_eq!(stats.get_selection_rate(), 0.5);
+        assert_eq!(picked.load(Ordering::Relaxed), 1);
+        let all = analytics.get_all_stats();
+        assert_eq!(all[0].get_result_id(), "a");
+        assert_eq!(analytics.get_stats("b").unwrap().get_chosen(), 0);
+    }
+}
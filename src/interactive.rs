@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use tokio::time::Instant;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{Flow, Layer};
+use crate::gen_types::{CallbackQuery, MaybeInaccessibleMessage, Message, MsgId, UpdateExt};
+
+/// How long expired messages are remembered to answer late callbacks
+const EXPIRED_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+type CallbackHandler =
+    Arc<dyn Fn(Bot, CallbackQuery) -> BoxFuture<'static, BotResult<()>> + Send + Sync>;
+
+/// A registered message, live until its deadline passes
+struct Slot {
+    handler: Option<CallbackHandler>,
+    deadline: Instant,
+}
+
+/// Registry of messages with inline keyboards that stop working after a ttl. Callbacks
+/// on live messages go to the handler registered for the message. When the ttl expires
+/// the keyboard is removed, and late callbacks are answered with an expired notice.
+/// Add as a dispatcher layer to route callbacks
+#[derive(Clone)]
+pub struct InteractiveMessages {
+    slots: Arc<Mutex<HashMap<(i64, MsgId), Slot>>>,
+    expired_text: Arc<str>,
+}
+
+impl std::fmt::Debug for InteractiveMessages {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InteractiveMessages")
+            .field("messages", &self.slots.lock().unwrap().len())
+            .field("expired_text", &self.expired_text)
+            .finish()
+    }
+}
+
+impl Default for InteractiveMessages {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get the key of the message a callback was sent from
+fn callback_key(query: &CallbackQuery) -> Option<(i64, MsgId)> {
+    match query.get_message()? {
+        MaybeInaccessibleMessage::Message(m) => Some((m.get_chat().get_id(), m.get_message_id())),
+        MaybeInaccessibleMessage::InaccessibleMessage(m) => {
+            Some((m.get_chat().get_id(), m.get_message_id()))
+        }
+    }
+}
+
+impl InteractiveMessages {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            slots: Arc::new(Mutex::new(HashMap::new())),
+            expired_text: Arc::from("This message has expired"),
+        }
+    }
+
+    /// Set the notice shown for callbacks on expired messages
+    pub fn expired_text<T: AsRef<str>>(mut self, text: T) -> Self {
+        self.expired_text = Arc::from(text.as_ref());
+        self
+    }
+
+    /// Route callbacks on a message to a handler until ttl passes, then remove the
+    /// message's keyboard
+    pub fn register<F, Fut>(&self, bot: &Bot, message: &Message, ttl: Duration, handler: F)
+    where
+        F: Fn(Bot, CallbackQuery) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        let key = (message.get_chat().get_id(), message.get_message_id());
+        let handler: CallbackHandler = Arc::new(move |bot, query| Box::pin(handler(bot, query)));
+        let now = Instant::now();
+        {
+            let mut slots = self.slots.lock().unwrap();
+            slots.retain(|_, s| s.handler.is_some() || s.deadline + EXPIRED_RETENTION > now);
+            slots.insert(
+                key,
+                Slot {
+                    handler: Some(handler),
+                    deadline: now + ttl,
+                },
+            );
+        }
+
+        let bot = bot.clone();
+        let slots = Arc::clone(&self.slots);
+        tokio::spawn(async move {
+            loop {
+                let deadline = match slots.lock().unwrap().get(&key) {
+                    Some(Slot {
+                        handler: Some(_),
+                        deadline,
+                    }) => *deadline,
+                    _ => return,
+                };
+                tokio::time::sleep_until(deadline).await;
+                let mut lock = slots.lock().unwrap();
+                match lock.get_mut(&key) {
+                    Some(slot) if slot.handler.is_some() && slot.deadline <= Instant::now() => {
+                        slot.handler = None;
+                        break;
+                    }
+                    Some(_) => continue,
+                    None => return,
+                }
+            }
+            let remove = bot
+                .build_edit_message_reply_markup()
+                .chat_id(key.0)
+                .message_id(key.1)
+                .build()
+                .await;
+            if let Err(err) = remove {
+                log::warn!("failed to remove keyboard of expired message {}", err);
+            }
+        });
+    }
+
+    /// Push back the expiry of a live message, for example after showing another page.
+    /// Returns false if the message is not registered or already expired
+    pub fn extend(&self, chat: i64, message_id: MsgId, ttl: Duration) -> bool {
+        match self.slots.lock().unwrap().get_mut(&(chat, message_id)) {
+            Some(slot) if slot.handler.is_some() => {
+                slot.deadline = Instant::now() + ttl;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stop routing callbacks for a message without editing it
+    pub fn remove(&self, chat: i64, message_id: MsgId) -> bool {
+        self.slots
+            .lock()
+            .unwrap()
+            .remove(&(chat, message_id))
+            .is_some()
+    }
+
+    /// Check if callbacks on a message are still routed to its handler
+    pub fn is_live(&self, chat: i64, message_id: MsgId) -> bool {
+        self.slots
+            .lock()
+            .unwrap()
+            .get(&(chat, message_id))
+            .map(|s| s.handler.is_some())
+            .unwrap_or(false)
+    }
+}
+
+impl Layer for InteractiveMessages {
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let UpdateExt::CallbackQuery(query) = update else {
+            return Box::pin(async { Ok(Flow::Continue) });
+        };
+        let slot = callback_key(&query).and_then(|key| {
+            self.slots
+                .lock()
+                .unwrap()
+                .get(&key)
+                .map(|s| s.handler.clone())
+        });
+        let expired_text = Arc::clone(&self.expired_text);
+        Box::pin(async move {
+            match slot {
+                None => return Ok(Flow::Continue),
+                Some(Some(handler)) => handler(bot, query).await?,
+                Some(None) => {
+                    bot.build_answer_callback_query(query.get_id())
+                        .text(&expired_text)
+                        .build()
+                        .await?;
+                }
+            }
+            Ok(Flow::Stop)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_messages_are_not_live() {
+        let messages = InteractiveMessages::new().expired_text("gone");
+        assert!(!messages.is_live(1, MsgId::from(1)));
+        assert!(!messages.extend(1, MsgId::from(1), Duration::from_secs(1)));
+        assert!(!messages.remove(1, MsgId::from(1)));
+    }
+}
@@ -0,0 +1,106 @@
+use std::future::Future;
+
+use futures_util::future::BoxFuture;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{Flow, Layer};
+use crate::gen_types::{ChatJoinRequest, UpdateExt};
+
+impl ChatJoinRequest {
+    /// Approve this request, adding the user to the chat
+    pub async fn approve(&self, bot: &Bot) -> BotResult<bool> {
+        bot.build_approve_chat_join_request(self.get_chat().get_id(), self.get_from().get_id())
+            .build()
+            .await
+    }
+
+    /// Decline this request
+    pub async fn decline(&self, bot: &Bot) -> BotResult<bool> {
+        bot.build_decline_chat_join_request(self.get_chat().get_id(), self.get_from().get_id())
+            .build()
+            .await
+    }
+}
+
+/// Outcome of a JoinRequestPolicy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinDecision {
+    /// Approve the request immediately
+    Approve,
+    /// Decline the request immediately
+    Decline,
+    /// Leave the request pending and pass it on to the dispatcher's handlers, for example
+    /// to start a captcha
+    Defer,
+}
+
+/// Decides what to do with incoming chat join requests. Implemented for any async
+/// function or closure taking a Bot and a ChatJoinRequest
+pub trait JoinRequestPolicy: Send + Sync {
+    /// Decide on a single request
+    fn decide(
+        &self,
+        bot: Bot,
+        request: ChatJoinRequest,
+    ) -> BoxFuture<'static, BotResult<JoinDecision>>;
+}
+
+impl<F, Fut> JoinRequestPolicy for F
+where
+    F: Fn(Bot, ChatJoinRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = BotResult<JoinDecision>> + Send + 'static,
+{
+    fn decide(
+        &self,
+        bot: Bot,
+        request: ChatJoinRequest,
+    ) -> BoxFuture<'static, BotResult<JoinDecision>> {
+        Box::pin(self(bot, request))
+    }
+}
+
+/// Dispatcher layer applying a JoinRequestPolicy to chat_join_request updates. Approved
+/// and declined requests are consumed, deferred requests reach the handlers
+pub struct JoinRequestLayer<P> {
+    policy: P,
+}
+
+impl<P> JoinRequestLayer<P>
+where
+    P: JoinRequestPolicy,
+{
+    /// Create a layer using a policy
+    pub fn new(policy: P) -> Self {
+        Self { policy }
+    }
+}
+
+impl<P> Layer for JoinRequestLayer<P>
+where
+    P: JoinRequestPolicy,
+{
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let UpdateExt::ChatJoinRequest(request) = update else {
+            return Box::pin(async { Ok(Flow::Continue) });
+        };
+        let decision = self.policy.decide(bot.clone(), request.clone());
+        Box::pin(async move {
+            match decision.await? {
+                JoinDecision::Approve => {
+                    request.approve(&bot).await?;
+                    Ok(Flow::Stop)
+                }
+                JoinDecision::Decline => {
+                    request.decline(&bot).await?;
+                    Ok(Flow::Stop)
+                }
+                JoinDecision::Defer => Ok(Flow::Continue),
+            }
+        })
+    }
+}
+
+/// Policy approving every join request
+pub async fn approve_all(_: Bot, _: ChatJoinRequest) -> BotResult<JoinDecision> {
+    Ok(JoinDecision::Approve)
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+
+/// Parse json from an inbound webhook body or getUpdates response. With the simd-json
+/// feature this uses simd-json, which parses in place and so copies the input first
+#[cfg(feature = "simd-json")]
+pub(crate) fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let mut bytes = bytes.to_vec();
+    Ok(simd_json::serde::from_slice(&mut bytes)?)
+}
+
+/// Parse json from an inbound webhook body or getUpdates response with serde_json
+#[cfg(not(feature = "simd-json"))]
+pub(crate) fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen_types::{Update, UpdateExt};
+
+    #[test]
+    fn parses_updates() {
+        let body = br#"{"update_id": 3, "message": {"message_id": 1, "date": 0,
+            "chat": {"id": 5, "type": "private"}, "text": "hi",
+            "entities": [{"type": "bold", "offset": 0, "length": 2}]}}"#;
+        let update: Update = from_slice(body).unwrap();
+        assert_eq!(update.get_update_id().get(), 3);
+        match UpdateExt::from(update) {
+            UpdateExt::Message(message) => assert_eq!(message.get_text(), Some("hi")),
+            update => panic!("unexpected update {:?}", update),
+        }
+        assert!(from_slice::<Update>(b"{").is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{ChatHandle, InlineKeyboardButton, InlineKeyboardMarkup, Message, MsgId};
+
+/// Longest callback data telegram accepts, in bytes
+const MAX_CALLBACK_DATA: usize = 64;
+
+/// Last known inline keyboard of recently edited messages, used to skip edits that would
+/// not change anything. When full the cache is cleared
+#[derive(Debug)]
+pub(crate) struct KeyboardCache {
+    max_entries: usize,
+    markups: Mutex<HashMap<(ChatHandle, MsgId), InlineKeyboardMarkup>>,
+}
+
+impl KeyboardCache {
+    pub(crate) fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            markups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn is_current(&self, key: &(ChatHandle, MsgId), markup: &InlineKeyboardMarkup) -> bool {
+        self.markups.lock().unwrap().get(key) == Some(markup)
+    }
+
+    fn insert(&self, key: (ChatHandle, MsgId), markup: InlineKeyboardMarkup) {
+        let mut markups = self.markups.lock().unwrap();
+        if markups.len() >= self.max_entries && !markups.contains_key(&key) {
+            markups.clear();
+        }
+        if self.max_entries > 0 {
+            markups.insert(key, markup);
+        }
+    }
+}
+
+impl Bot {
+    /// Replace the inline keyboard of a message. If the keyboard cache is enabled and the
+    /// keyboard is unchanged no request is sent. When ignore_not_modified is enabled
+    /// telegram's "message is not modified" error is treated as success. Returns false if
+    /// the message was left unchanged
+    pub async fn update_keyboard<V>(
+        &self,
+        chat_id: V,
+        message_id: MsgId,
+        markup: &InlineKeyboardMarkup,
+    ) -> BotResult<bool>
+    where
+        V: Into<ChatHandle>,
+    {
+        let key = (chat_id.into(), message_id);
+        if let Some(cache) = self.get_keyboard_cache() {
+            if cache.is_current(&key, markup) {
+                return Ok(false);
+            }
+        }
+        let res = self
+            .build_edit_message_reply_markup()
+            .chat_id(key.0.clone())
+            .message_id(message_id)
+            .reply_markup(markup)
+            .build()
+            .await;
+        let edited = match res {
+            Ok(_) => true,
+            Err(err) if self.get_ignore_not_modified() && err.is_not_modified() => false,
+            Err(err) => return Err(err),
+        };
+        if let Some(cache) = self.get_keyboard_cache() {
+            cache.insert(key, markup.clone());
+        }
+        Ok(edited)
+    }
+
+    /// Record the inline keyboard of a sent message in the keyboard cache so the next
+    /// update_keyboard call can be skipped if it is unchanged
+    pub fn cache_keyboard(&self, message: &Message) {
+        if let (Some(cache), Some(markup)) = (self.get_keyboard_cache(), message.get_reply_markup())
+        {
+            cache.insert(
+                (message.get_chat().get_id().into(), message.get_message_id()),
+                markup.clone(),
+            );
+        }
+    }
+}
+
+/// A callback button parsed from a markdown menu
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownButton {
+    id: String,
+    label: String,
+    checked: Option<bool>,
+}
+
+impl MarkdownButton {
+    /// Get the callback data sent when this button is pressed
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the label of this button without any checkbox
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    /// Get the state of a checklist item, None if the button is not a checkbox
+    pub fn get_checked(&self) -> Option<bool> {
+        self.checked
+    }
+}
+
+/// An inline keyboard parsed from a markdown menu, with the callback buttons it contains
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownKeyboard {
+    markup: InlineKeyboardMarkup,
+    buttons: Vec<MarkdownButton>,
+}
+
+impl MarkdownKeyboard {
+    /// Parse a menu written as a markdown list. Each item becomes a row, and cells in a
+    /// row are separated by " | ". A cell is one of
+    ///
+    /// - `label`, a callback button with the id `prefix:n` numbered from 0
+    /// - `label {id}`, a callback button with an explicit id
+    /// - `[label](https://example.com)`, a url button
+    /// - `[ ] label` or `[x] label`, a checklist item shown with a checkbox
+    ///
+    /// Blank lines are skipped, any other line is an error
+    pub fn parse(prefix: &str, source: &str) -> Result<Self> {
+        let mut rows = Vec::new();
+        let mut buttons = Vec::new();
+        for (line, text) in source.lines().enumerate() {
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let item = text
+                .strip_prefix("- ")
+                .or_else(|| text.strip_prefix("* "))
+                .ok_or_else(|| anyhow!("line {}: expected a list item", line + 1))?;
+            let mut row = Vec::new();
+            for cell in item.split(" | ") {
+                let (button, menu) = Self::parse_cell(prefix, cell.trim(), buttons.len())
+                    .map_err(|err| anyhow!("line {}: {}", line + 1, err))?;
+                row.push(button);
+                buttons.extend(menu);
+            }
+            rows.push(row);
+        }
+        Ok(Self {
+            markup: InlineKeyboardMarkup::new(rows),
+            buttons,
+        })
+    }
+
+    fn parse_cell(
+        prefix: &str,
+        cell: &str,
+        index: usize,
+    ) -> Result<(InlineKeyboardButton, Option<MarkdownButton>)> {
+        if let Some((label, url)) = cell
+            .strip_prefix('[')
+            .and_then(|c| c.strip_suffix(')'))
+            .and_then(|c| c.split_once("]("))
+        {
+            if label.is_empty() || url.is_empty() {
+                return Err(anyhow!("empty link in {}", cell));
+            }
+            let mut button = InlineKeyboardButton::new(label.to_owned());
+            button.set_url(Some(url.to_owned()));
+            return Ok((button, None));
+        }
+
+        let (checked, cell) = if let Some(c) = cell.strip_prefix("[ ] ") {
+            (Some(false), c)
+        } else if let Some(c) = cell
+            .strip_prefix("[x] ")
+            .or_else(|| cell.strip_prefix("[X] "))
+        {
+            (Some(true), c)
+        } else {
+            (None, cell)
+        };
+        let (label, id) = match cell.strip_suffix('}').and_then(|c| c.rsplit_once(" {")) {
+            Some((label, id)) if !id.is_empty() => (label.trim_end(), id.to_owned()),
+            _ => (cell, format!("{}:{}", prefix, index)),
+        };
+        if label.is_empty() {
+            return Err(anyhow!("empty label in {}", cell));
+        }
+        if id.len() > MAX_CALLBACK_DATA {
+            return Err(anyhow!(
+                "callback id {} is longer than {} bytes",
+                id,
+                MAX_CALLBACK_DATA
+            ));
+        }
+        let text = match checked {
+            Some(true) => format!("☑ {}", label),
+            Some(false) => format!("☐ {}", label),
+            None => label.to_owned(),
+        };
+        let mut button = InlineKeyboardButton::new(text);
+        button.set_callback_data(Some(id.clone()));
+        let menu = MarkdownButton {
+            id,
+            label: label.to_owned(),
+            checked,
+        };
+        Ok((button, Some(menu)))
+    }
+
+    /// Get the keyboard to attach to a message
+    pub fn get_markup(&self) -> &InlineKeyboardMarkup {
+        &self.markup
+    }
+
+    /// Get every callback button in the order they appear
+    pub fn get_buttons(&self) -> &[MarkdownButton] {
+        &self.buttons
+    }
+
+    /// Find the button pressed for a callback query's data
+    pub fn get_button(&self, id: &str) -> Option<&MarkdownButton> {
+        self.buttons.iter().find(|b| b.id == id)
+    }
+
+    /// Consume this menu, returning the keyboard
+    pub fn into_markup(self) -> InlineKeyboardMarkup {
+        self.markup
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_unchanged() {
+        let cache = KeyboardCache::new(1);
+        let markup =
+            InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::new("a".to_owned())]]);
+        let key = (ChatHandle::ChatId(1), MsgId::from(2));
+        assert!(!cache.is_current(&key, &markup));
+        cache.insert(key.clone(), markup.clone());
+        assert!(cache.is_current(&key, &markup));
+        cache.insert((ChatHandle::ChatId(1), MsgId::from(3)), markup.clone());
+        assert!(!cache.is_current(&key, &markup));
+    }
+
+    #[test]
+    fn markdown_menu() {
+        let menu = MarkdownKeyboard::parse(
+            "menu",
+            "- Settings | Help {help}\n\n* [Docs](https://example.com)\n- [x] Notifications\n",
+        )
+        .unwrap();
+        let rows = menu.get_markup().get_inline_keyboard();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].len(), 2);
+        assert_eq!(rows[1][0].get_url(), Some("https://example.com"));
+        assert_eq!(rows[2][0].get_text(), "☑ Notifications");
+        let ids = menu
+            .get_buttons()
+            .iter()
+            .map(|b| b.get_id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["menu:0", "help", "menu:2"]);
+        assert_eq!(menu.get_button("menu:2").unwrap().get_checked(), Some(true));
+        assert!(MarkdownKeyboard::parse("menu", "not a list").is_err());
+    }
+}
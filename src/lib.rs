@@ -56,19 +56,150 @@
 //! ```
 
 #![recursion_limit = "256"]
+pub use gen_types::{TELEGRAM_BOT_API_RELEASE_DATE, TELEGRAM_BOT_API_VERSION};
+
+/// Presets for administrator rights and syncing the bot's default rights
+pub mod admin_rights;
+
+/// Structured records of moderation actions for compliance logging
+pub mod audit;
+/// Helpers for chat boosts and giveaways
+pub mod boost;
 /// Wrapper type for telegram bot api
 pub mod bot;
+/// Optional caching of chat and chat member info to cut redundant api calls
+pub mod cache;
+/// Validation and splitting of long captions
+pub mod caption;
+/// Capture of raw requests and responses for debugging
+pub mod capture;
+/// Posting, editing and pinning in channels with fixed options
+pub mod channel;
+/// Pausing of requests during telegram outages
+pub mod circuit;
+/// Classification of benign telegram errors like "message is not modified"
+pub mod classify;
+/// Declarative syncing of the bot's command lists per scope and language
+pub mod commands;
+/// Detection of servers supporting a newer api than these bindings
+pub mod compat;
+/// Routing of incoming updates to handlers
+pub mod dispatch;
+/// Streaming file downloads readable with tokio::io
+pub mod download;
+/// Building formatted text without escaping markup
+pub mod entities;
+/// Messages deleted automatically after a delay
+pub mod ephemeral;
+/// Typed catalog of errors returned by telegram
+pub mod errors;
 /// Various helpers to manage receiving updates via webhooks or long polling,
 /// or to better map json types onto rust design patterns
 pub mod ext;
+/// Failover between multiple bot api urls
+pub mod failover;
+/// Predicates for routing updates by chat, entity, sticker, dice or service message type
+pub mod filter;
+/// Escaping of text for telegram's HTML and Markdown formatting modes
+pub mod format;
+/// Welcome and goodbye messages for members joining and leaving groups
+pub mod greeter;
+/// Content hashes of uploaded and downloaded files
+#[cfg(feature = "hash")]
+pub mod hash;
+/// Localization of outgoing messages
+pub mod i18n;
+/// Selection statistics for inline query results
+pub mod inline;
+/// Inline keyboards that expire after a timeout
+pub mod interactive;
+/// Helpers for approving or declining chat join requests
+pub mod join_request;
+/// Json parsing of updates, optionally with simd-json
+mod json;
+/// Editing of inline keyboards without redundant requests
+pub mod keyboard;
+/// Uniform access to and downloading of message media
+pub mod media;
+/// Predicates and helpers for chat members
+pub mod members;
+/// Typed menu buttons and helpers to configure a chat's menu
+pub mod menu_button;
+/// Hierarchical inline keyboard menus with navigation and actions
+pub mod menus;
+/// Dispatcher layers for flood detection, captchas, and word filters
+pub mod moderation;
+/// Per request overrides of Bot level settings
+pub mod options;
+/// Typed accessors for the origin of forwarded messages
+pub mod origin;
+/// OpenTelemetry spans for dispatched updates and api calls
+#[cfg(feature = "otel")]
+mod otel;
+/// Decryption of telegram passport data
+#[cfg(feature = "passport")]
+pub mod passport;
+/// Replacing the pinned message of a chat
+pub mod pin;
+/// Declarative syncing of the bot's name and descriptions per language
+pub mod profile;
+/// Quizzes run as a series of quiz polls with scoring
+pub mod quiz;
+/// Role based access control for handlers
+pub mod rbac;
+/// Names for chats and groups of chats, resolved from code, env or config files
+pub mod recipients;
+/// Recording and replaying of updates for reproducing bugs
+pub mod replay;
+/// Requests sent at a later time or on a repeating schedule
+pub mod scheduler;
+/// Detection of service messages like members joining or topics being created
+pub mod service;
+/// Typed settings stored per chat
+pub mod settings;
+/// Handling of users and chats shared through keyboard buttons
+pub mod shared;
+/// Export of updates to message queues and other services
+pub mod sink;
+/// Capturing the exact requests method calls would send, for snapshot tests
+#[cfg(feature = "test-util")]
+pub mod snapshot;
+/// Database backed stores for settings and scheduled jobs
+#[cfg(any(feature = "storage-sqlite", feature = "storage-postgres"))]
+pub mod storage;
+/// Deserializing enums of subtypes by their tag field
+mod tagged;
+/// Local mock server and assertions for testing handlers
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+/// Bot handles bound to a forum topic
+pub mod thread;
+/// Adaptive throttling of outgoing requests based on ratelimit responses
+pub mod throttle;
+/// Typed errors and callbacks for chats the bot can no longer reach
+pub mod unreachable;
+/// Reuse of file_ids for previously uploaded content
+#[cfg(feature = "hash")]
+pub mod upload_cache;
+/// Validation of method parameters against the limits documented in the api spec
+pub mod validate;
+/// Webhook receiver for mounting in an existing web server
+#[cfg(any(feature = "tower", feature = "warp", feature = "actix-web"))]
+pub mod webhook;
 
 #[allow(unused_imports, rustdoc::bare_urls)]
 /// Autogenerated REST api methods
 pub mod gen_methods {
+    #[cfg(not(feature = "codegen-at-build"))]
     include!("gen_methods.rs");
+    #[cfg(feature = "codegen-at-build")]
+    include!(concat!(env!("OUT_DIR"), "/gen_methods.rs"));
 }
 ///Autogenerated REST api types from json
 #[allow(rustdoc::bare_urls)]
 pub mod gen_types {
+    #[cfg(not(feature = "codegen-at-build"))]
     include!("gen_types.rs");
+    #[cfg(feature = "codegen-at-build")]
+    include!(concat!(env!("OUT_DIR"), "/gen_types.rs"));
 }
@@ -0,0 +1,267 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use tokio::io::AsyncWriteExt;
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{
+    Animation, Audio, Document, FileId, Message, PhotoSize, Sticker, Video, VideoNote, Voice,
+};
+
+/// Selection of a single size from the sizes telegram provides for a photo
+pub trait PhotoSizeExt {
+    /// Get the size with the most pixels
+    fn largest(&self) -> Option<&PhotoSize>;
+
+    /// Get the size with the fewest pixels
+    fn smallest(&self) -> Option<&PhotoSize>;
+
+    /// Get the size with dimensions closest to `width` and `height`
+    fn closest_to(&self, width: i64, height: i64) -> Option<&PhotoSize>;
+}
+
+fn area(photo: &PhotoSize) -> (i64, i64) {
+    (
+        photo.get_width() * photo.get_height(),
+        photo.get_file_size().unwrap_or_default(),
+    )
+}
+
+impl PhotoSizeExt for [PhotoSize] {
+    fn largest(&self) -> Option<&PhotoSize> {
+        self.iter().max_by_key(|p| area(p))
+    }
+
+    fn smallest(&self) -> Option<&PhotoSize> {
+        self.iter().min_by_key(|p| area(p))
+    }
+
+    fn closest_to(&self, width: i64, height: i64) -> Option<&PhotoSize> {
+        self.iter()
+            .min_by_key(|p| (p.get_width() - width).abs() + (p.get_height() - height).abs())
+    }
+}
+
+/// Downloadable media attached to a message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageMedia<'a> {
+    /// All available sizes of a photo
+    Photo(&'a Vec<PhotoSize>),
+    Video(&'a Video),
+    Voice(&'a Voice),
+    Audio(&'a Audio),
+    Document(&'a Document),
+    Animation(&'a Animation),
+    Sticker(&'a Sticker),
+    VideoNote(&'a VideoNote),
+}
+
+impl MessageMedia<'_> {
+    /// Get the file_id of this media. For photos this is the largest size
+    pub fn get_file_id(&self) -> Option<&'_ FileId> {
+        let res = match self {
+            MessageMedia::Photo(sizes) => sizes.largest()?.get_file_id(),
+            MessageMedia::Video(v) => v.get_file_id(),
+            MessageMedia::Voice(v) => v.get_file_id(),
+            MessageMedia::Audio(v) => v.get_file_id(),
+            MessageMedia::Document(v) => v.get_file_id(),
+            MessageMedia::Animation(v) => v.get_file_id(),
+            MessageMedia::Sticker(v) => v.get_file_id(),
+            MessageMedia::VideoNote(v) => v.get_file_id(),
+        };
+        Some(res)
+    }
+
+    /// Download the contents of this media
+    pub async fn download(&self, bot: &Bot) -> BotResult<Vec<u8>> {
+        let file_id = self
+            .get_file_id()
+            .ok_or_else(|| anyhow!("photo has no sizes"))?;
+        bot.download_file_id(file_id).await
+    }
+}
+
+impl Message {
+    /// Get the largest size of this message's photo, if it has one
+    pub fn best_photo(&self) -> Option<&'_ PhotoSize> {
+        self.get_photo()?.largest()
+    }
+
+    /// Get the media attached to this message, if any. Animations are returned as
+    /// MessageMedia::Animation even though telegram also sets the document field
+    pub fn media(&self) -> Option<MessageMedia<'_>> {
+        if let Some(v) = self.get_animation() {
+            Some(MessageMedia::Animation(v))
+        } else if let Some(v) = self.get_photo() {
+            Some(MessageMedia::Photo(v))
+        } else if let Some(v) = self.get_video() {
+            Some(MessageMedia::Video(v))
+        } else if let Some(v) = self.get_voice() {
+            Some(MessageMedia::Voice(v))
+        } else if let Some(v) = self.get_audio() {
+            Some(MessageMedia::Audio(v))
+        } else if let Some(v) = self.get_document() {
+            Some(MessageMedia::Document(v))
+        } else if let Some(v) = self.get_sticker() {
+            Some(MessageMedia::Sticker(v))
+        } else {
+            self.get_video_note().map(MessageMedia::VideoNote)
+        }
+    }
+}
+
+/// Add download and download_to to file types with a file_id
+macro_rules! impl_download {
+    ($($t:ident),*) => {
+        $(
+            impl $t {
+                /// Download the contents of this file using get_file
+                pub async fn download(&self, bot: &Bot) -> BotResult<Vec<u8>> {
+                    bot.download_file_id(self.get_file_id()).await
+                }
+
+                /// Download this file to a path without holding it in memory, returning
+                /// the number of bytes written. See Bot::download_file_id_to
+                pub async fn download_to<P>(&self, bot: &Bot, path: P) -> BotResult<u64>
+                where
+                    P: AsRef<Path>,
+                {
+                    bot.download_file_id_to(self.get_file_id(), path).await
+                }
+            }
+        )*
+    };
+}
+
+/// Add download_thumbnail to file types with an optional thumbnail
+macro_rules! impl_download_thumbnail {
+    ($($t:ident),*) => {
+        $(
+            impl $t {
+                /// Download the thumbnail of this file, None if it has no thumbnail
+                pub async fn download_thumbnail(
+                    &self,
+                    bot: &Bot,
+                ) -> BotResult<Option<Vec<u8>>> {
+                    match self.get_thumbnail() {
+                        Some(thumbnail) => thumbnail.download(bot).await.map(Some),
+                        None => Ok(None),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_download!(PhotoSize, Sticker, Document, Voice, VideoNote, Video, Audio, Animation);
+impl_download_thumbnail!(Sticker, Document, VideoNote, Video, Audio, Animation);
+
+impl Bot {
+    /// Download a file by file_id using get_file and the file download api
+    pub async fn download_file_id(&self, file_id: &FileId) -> BotResult<Vec<u8>> {
+        let file = self.build_get_file(file_id.clone()).build().await?;
+        let path = file
+            .get_file_path()
+            .ok_or_else(|| anyhow!("file has no file_path"))?;
+        self.download_file(path).await
+    }
+
+    /// Download a file by file_id to a path, streaming it instead of holding it in
+    /// memory. Returns the number of bytes written. The file is written next to path
+    /// with a .part extension and only renamed to path once complete, so a failed
+    /// download never leaves a truncated file at path
+    pub async fn download_file_id_to<P: AsRef<Path>>(
+        &self,
+        file_id: &FileId,
+        path: P,
+    ) -> BotResult<u64> {
+        let path = path.as_ref();
+        let file = self.build_get_file(file_id.clone()).build().await?;
+        let file_path = file
+            .get_file_path()
+            .ok_or_else(|| anyhow!("file has no file_path"))?;
+        let mut reader = self.open_file(file_path).await?;
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        let copy = async {
+            let mut out = tokio::fs::File::create(&partial).await?;
+            let written = tokio::io::copy(&mut reader, &mut out).await?;
+            out.flush().await?;
+            tokio::fs::rename(&partial, path).await?;
+            Ok::<_, std::io::Error>(written)
+        };
+        match copy.await {
+            Ok(written) => Ok(written),
+            Err(err) => {
+                if let Err(err) = tokio::fs::remove_file(&partial).await {
+                    log::debug!("failed to remove partial download {}", err);
+                }
+                Err(anyhow::Error::from(err).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+    use serde_json::json;
+
+    fn size(width: i64, height: i64) -> PhotoSize {
+        let mut photo = PhotoSize::default();
+        photo.set_width(width);
+        photo.set_height(height);
+        photo
+    }
+
+    #[test]
+    fn select_sizes() {
+        let sizes = vec![size(90, 60), size(800, 600), size(320, 240)];
+        assert_eq!(sizes.largest().unwrap().get_width(), 800);
+        assert_eq!(sizes.smallest().unwrap().get_width(), 90);
+        assert_eq!(sizes.closest_to(300, 300).unwrap().get_width(), 320);
+        assert!(Vec::<PhotoSize>::new().largest().is_none());
+    }
+
+    #[tokio::test]
+    async fn downloads_to_path() {
+        let test = TestBot::builder()
+            .respond(
+                "getFile",
+                json!({"file_id": "a", "file_unique_id": "a", "file_path": "documents/a.txt"}),
+            )
+            .file("documents/a.txt", "contents")
+            .build()
+            .await
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("botapi-download-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("a.txt");
+        let file_id = FileId::from("a".to_owned());
+        let bot = test.get_bot();
+        assert_eq!(bot.download_file_id_to(&file_id, &path).await.unwrap(), 8);
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "contents");
+        assert_eq!(test.sent("getFile")[0].get("file_id"), Some("a"));
+
+        // a missing file fails without leaving anything behind
+        let missing = dir.join("b.txt");
+        let test = TestBot::builder()
+            .respond(
+                "getFile",
+                json!({"file_id": "b", "file_unique_id": "b", "file_path": "documents/b.txt"}),
+            )
+            .build()
+            .await
+            .unwrap();
+        assert!(test
+            .get_bot()
+            .download_file_id_to(&file_id, &missing)
+            .await
+            .is_err());
+        assert!(!missing.exists());
+        assert!(!dir.join("b.txt.part").exists());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use async_stream::stream;
+use futures_core::Stream;
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{ChatMember, UserId};
+
+impl ChatMember {
+    /// Check if the user is currently in the chat. Restricted users may be in the chat
+    /// or not, banned users and users who left are not
+    pub fn is_member(&self) -> bool {
+        match self {
+            Self::ChatMemberOwner(_)
+            | Self::ChatMemberAdministrator(_)
+            | Self::ChatMemberMember(_) => true,
+            Self::ChatMemberRestricted(m) => m.get_is_member(),
+            Self::ChatMemberLeft(_) | Self::ChatMemberBanned(_) => false,
+        }
+    }
+
+    /// Check if the user created the chat
+    pub fn is_owner(&self) -> bool {
+        matches!(self, Self::ChatMemberOwner(_))
+    }
+
+    /// Check if the user is the owner or an administrator of the chat
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            Self::ChatMemberOwner(_) | Self::ChatMemberAdministrator(_)
+        )
+    }
+
+    /// Check if the user is banned from the chat
+    pub fn is_banned(&self) -> bool {
+        matches!(self, Self::ChatMemberBanned(_))
+    }
+
+    /// Check if the user has restrictions applied in the chat
+    pub fn is_restricted(&self) -> bool {
+        matches!(self, Self::ChatMemberRestricted(_))
+    }
+}
+
+impl Bot {
+    /// Stream the administrators of a chat, including the owner, using the cache if
+    /// configured. Telegram returns every administrator at once, so the stream fails
+    /// with a single error if the call fails
+    pub fn iter_chat_administrators(
+        &self,
+        chat_id: i64,
+    ) -> impl Stream<Item = BotResult<ChatMember>> + Send + 'static {
+        let bot = self.clone();
+        stream! {
+            match bot.get_chat_administrators_cached(chat_id).await {
+                Ok(admins) => {
+                    for admin in admins {
+                        yield Ok(admin);
+                    }
+                }
+                Err(err) => yield Err(err),
+            }
+        }
+    }
+
+    /// Check if a user is currently in a chat, using the cache if configured
+    pub async fn is_chat_member(&self, chat_id: i64, user_id: UserId) -> BotResult<bool> {
+        Ok(self
+            .get_chat_member_cached(chat_id, user_id)
+            .await?
+            .is_member())
+    }
+
+    /// Check if a user is the owner or an administrator of a chat, using the cache if
+    /// configured
+    pub async fn is_chat_admin(&self, chat_id: i64, user_id: UserId) -> BotResult<bool> {
+        Ok(self
+            .get_chat_member_cached(chat_id, user_id)
+            .await?
+            .is_admin())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn member(status: &str, extra: serde_json::Value) -> ChatMember {
+        let mut value = json!({
+            "status": status,
+            "user": {"id": 1, "is_bot": false, "first_name": "Test"},
+        });
+        value
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn predicates() {
+        let owner = member("creator", json!({"is_anonymous": false}));
+        assert!(owner.is_owner() && owner.is_admin() && owner.is_member());
+        let left = member("left", json!({}));
+        assert!(!left.is_member() && !left.is_admin());
+        let banned = member("kicked", json!({"until_date": 0}));
+        assert!(banned.is_banned() && !banned.is_member());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{
+    MenuButton, MenuButtonCommands, MenuButtonDefault, MenuButtonWebApp, WebAppInfo,
+};
+
+impl MenuButton {
+    /// A menu button opening the list of bot commands
+    pub fn commands() -> Self {
+        MenuButtonCommands::new().into()
+    }
+
+    /// A menu button launching the web app at url, labeled text
+    pub fn web_app<U, T>(url: U, text: T) -> Self
+    where
+        U: Into<String>,
+        T: Into<String>,
+    {
+        MenuButtonWebApp::new(text.into(), WebAppInfo::new(url.into())).into()
+    }
+
+    /// No specific menu button, telegram decides what to show
+    pub fn default_button() -> Self {
+        MenuButtonDefault::new().into()
+    }
+
+    /// Get the url of the web app launched by this button, if it launches one
+    pub fn get_web_app_url(&self) -> Option<&'_ str> {
+        match self {
+            MenuButton::MenuButtonWebApp(button) => Some(button.get_web_app().get_url()),
+            _ => None,
+        }
+    }
+}
+
+impl Bot {
+    /// Set the menu button shown in a private chat, or the default for all private chats
+    /// if chat_id is None
+    pub async fn set_menu_button(
+        &self,
+        chat_id: Option<i64>,
+        button: &MenuButton,
+    ) -> BotResult<bool> {
+        let mut call = self.build_set_chat_menu_button().menu_button(button);
+        if let Some(chat_id) = chat_id {
+            call = call.chat_id(chat_id);
+        }
+        call.build().await
+    }
+
+    /// Get the menu button shown in a private chat, or the default for all private chats
+    /// if chat_id is None
+    pub async fn get_menu_button(&self, chat_id: Option<i64>) -> BotResult<MenuButton> {
+        let mut call = self.build_get_chat_menu_button();
+        if let Some(chat_id) = chat_id {
+            call = call.chat_id(chat_id);
+        }
+        call.build().await
+    }
+
+    /// Make the menu button of every private chat launch the web app at url, labeled
+    /// text
+    pub async fn set_webapp_menu<U, T>(&self, url: U, text: T) -> BotResult<bool>
+    where
+        U: Into<String>,
+        T: Into<String>,
+    {
+        self.set_menu_button(None, &MenuButton::web_app(url, text))
+            .await
+    }
+
+    /// Go back to telegram's default menu button for a chat, or for all private chats if
+    /// chat_id is None
+    pub async fn reset_menu_button(&self, chat_id: Option<i64>) -> BotResult<bool> {
+        self.set_menu_button(chat_id, &MenuButton::default_button())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn configures_menu() {
+        let test = TestBot::builder()
+            .respond(
+                "getChatMenuButton",
+                json!({"type": "web_app", "text": "Shop", "web_app": {"url": "https://example.com"}}),
+            )
+            .build()
+            .await
+            .unwrap();
+        let bot = test.get_bot();
+        bot.set_webapp_menu("https://example.com", "Shop")
+            .await
+            .unwrap();
+        let sent = &test.sent("setChatMenuButton")[0];
+        assert!(sent.get("chat_id").is_none());
+        let button: serde_json::Value =
+            serde_json::from_str(sent.get("menu_button").unwrap()).unwrap();
+        assert_eq!(button["type"], "web_app");
+        assert_eq!(button["web_app"]["url"], "https://example.com");
+        assert_eq!(button["text"], "Shop");
+
+        let button = bot.get_menu_button(Some(1)).await.unwrap();
+        assert_eq!(button.get_web_app_url(), Some("https://example.com"));
+        assert_eq!(
+            serde_json::to_value(MenuButton::commands()).unwrap()["type"],
+            "commands"
+        );
+    }
+}
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use futures_util::future::BoxFuture;
+use serde::Deserialize;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{Flow, Layer};
+use crate::gen_types::{
+    CallbackQuery, EReplyMarkup, InlineKeyboardButton, InlineKeyboardMarkup,
+    MaybeInaccessibleMessage, Message, MsgId, UpdateExt, UserId,
+};
+use crate::validate::MAX_CALLBACK_DATA;
+
+/// Navigation states kept before the least recently used is forgotten, users on a
+/// forgotten menu go back to the root page
+const MAX_STATES: usize = 10000;
+
+/// Pages kept in the path of a user for back buttons, older pages after the root page are
+/// dropped
+const MAX_DEPTH: usize = 32;
+
+type ActionHandler =
+    Arc<dyn Fn(Bot, CallbackQuery) -> BoxFuture<'static, BotResult<()>> + Send + Sync>;
+
+/// A button on a menu page
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "ButtonDef")]
+pub enum Button {
+    /// Open another page
+    Page { label: String, page: String },
+    /// Run an action registered with Menu::on_action
+    Action { label: String, action: String },
+    /// Open a url
+    Url { label: String, url: String },
+    /// Return to the previous page
+    Back { label: String },
+}
+
+/// A button as written in a menu file, exactly one of page, action, url or back is set
+#[derive(Deserialize)]
+struct ButtonDef {
+    label: String,
+    page: Option<String>,
+    action: Option<String>,
+    url: Option<String>,
+    #[serde(default)]
+    back: bool,
+}
+
+impl TryFrom<ButtonDef> for Button {
+    type Error = String;
+
+    fn try_from(def: ButtonDef) -> std::result::Result<Self, Self::Error> {
+        let label = def.label;
+        match (def.page, def.action, def.url, def.back) {
+            (Some(page), None, None, false) => Ok(Self::Page { label, page }),
+            (None, Some(action), None, false) => Ok(Self::Action { label, action }),
+            (None, None, Some(url), false) => Ok(Self::Url { label, url }),
+            (None, None, None, true) => Ok(Self::Back { label }),
+            _ => Err(format!(
+                "button {} needs exactly one of page, action, url or back",
+                label
+            )),
+        }
+    }
+}
+
+/// One screen of a menu, a message text and rows of buttons
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+pub struct Page {
+    text: String,
+    #[serde(default)]
+    rows: Vec<Vec<Button>>,
+}
+
+impl Page {
+    /// Create a page showing text, without buttons
+    pub fn new<T: Into<String>>(text: T) -> Self {
+        Self {
+            text: text.into(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a button to the last row
+    pub fn button(mut self, button: Button) -> Self {
+        match self.rows.last_mut() {
+            Some(row) => row.push(button),
+            None => self.rows.push(vec![button]),
+        }
+        self
+    }
+
+    /// Start a new row of buttons
+    pub fn row(mut self) -> Self {
+        self.rows.push(Vec::new());
+        self
+    }
+
+    /// Add a button opening another page
+    pub fn page<L: Into<String>, P: Into<String>>(self, label: L, page: P) -> Self {
+        self.button(Button::Page {
+            label: label.into(),
+            page: page.into(),
+        })
+    }
+
+    /// Add a button running an action
+    pub fn action<L: Into<String>, A: Into<String>>(self, label: L, action: A) -> Self {
+        self.button(Button::Action {
+            label: label.into(),
+            action: action.into(),
+        })
+    }
+
+    /// Add a button opening a url
+    pub fn url<L: Into<String>, U: Into<String>>(self, label: L, url: U) -> Self {
+        self.button(Button::Url {
+            label: label.into(),
+            url: url.into(),
+        })
+    }
+
+    /// Add a button returning to the previous page
+    pub fn back<L: Into<String>>(self, label: L) -> Self {
+        self.button(Button::Back {
+            label: label.into(),
+        })
+    }
+
+    /// Get the text of the page
+    pub fn get_text(&self) -> &'_ str {
+        &self.text
+    }
+
+    /// Get the rows of buttons, without empty rows
+    pub fn get_rows(&self) -> impl Iterator<Item = &'_ [Button]> {
+        self.rows
+            .iter()
+            .filter(|r| !r.is_empty())
+            .map(|r| r.as_slice())
+    }
+}
+
+/// Pages of a menu as loaded from a file
+#[derive(Debug, Clone, Deserialize)]
+struct MenuDef {
+    prefix: String,
+    root: String,
+    pages: HashMap<String, Page>,
+}
+
+/// Key of the navigation state of a user on a menu message
+type StateKey = (i64, MsgId, UserId);
+
+struct MenuInner {
+    prefix: String,
+    root: String,
+    pages: HashMap<String, Page>,
+    actions: HashMap<String, ActionHandler>,
+    states: Mutex<HashMap<StateKey, (Instant, Vec<String>)>>,
+}
+
+/// A hierarchical menu of pages shown as one message with an inline keyboard. Page
+/// buttons edit the message to show another page, back buttons return along the path
+/// each user took, and action buttons call handlers registered by name. Every callback
+/// for the menu carries its prefix, so several menus can share a dispatcher. Add as a
+/// dispatcher layer to route callbacks, and open the menu with send. Cloning is cheap
+///
+/// Menus can be declared in code or loaded from TOML with the `toml` feature:
+///
+/// ```toml
+/// prefix = "settings"
+/// root = "main"
+///
+/// [pages.main]
+/// text = "Settings"
+/// rows = [[{ label = "Notifications", page = "notify" }], [{ label = "Reset", action = "reset" }]]
+///
+/// [pages.notify]
+/// text = "Notifications"
+/// rows = [[{ label = "Mute", action = "mute" }, { label = "Back", back = true }]]
+/// ```
+#[derive(Clone)]
+pub struct Menu(Arc<MenuInner>);
+
+impl std::fmt::Debug for Menu {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Menu")
+            .field("prefix", &self.0.prefix)
+            .field("root", &self.0.root)
+            .field("pages", &self.0.pages.len())
+            .field("actions", &self.0.actions.len())
+            .finish()
+    }
+}
+
+/// Builder for a Menu, checking the pages link up when built
+pub struct MenuBuilder {
+    prefix: String,
+    root: String,
+    pages: HashMap<String, Page>,
+    actions: HashMap<String, ActionHandler>,
+}
+
+impl std::fmt::Debug for MenuBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MenuBuilder")
+            .field("prefix", &self.prefix)
+            .field("root", &self.root)
+            .field("pages", &self.pages)
+            .finish()
+    }
+}
+
+impl MenuBuilder {
+    /// Add a page under an id
+    pub fn page<T: Into<String>>(mut self, id: T, page: Page) -> Self {
+        self.pages.insert(id.into(), page);
+        self
+    }
+
+    /// Call a handler when a button with this action is pressed. The handler should
+    /// answer the callback query
+    pub fn on_action<T, F, Fut>(mut self, action: T, handler: F) -> Self
+    where
+        T: Into<String>,
+        F: Fn(Bot, CallbackQuery) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        let handler: ActionHandler = Arc::new(move |bot, query| Box::pin(handler(bot, query)));
+        self.actions.insert(action.into(), handler);
+        self
+    }
+
+    /// Check every page and action exists and all callback data fits in telegram's
+    /// limit, then create the menu
+    pub fn build(self) -> Result<Menu> {
+        if !self.pages.contains_key(&self.root) {
+            return Err(anyhow!("root page {} does not exist", self.root));
+        }
+        for (id, page) in self.pages.iter() {
+            for button in page.get_rows().flatten() {
+                let target = match button {
+                    Button::Page { page, .. } if !self.pages.contains_key(page) => {
+                        return Err(anyhow!("page {} links to missing page {}", id, page));
+                    }
+                    Button::Action { action, .. } if !self.actions.contains_key(action) => {
+                        return Err(anyhow!("page {} uses unregistered action {}", id, action));
+                    }
+                    Button::Page { page, .. } => page,
+                    Button::Action { action, .. } => action,
+                    Button::Url { .. } | Button::Back { .. } => continue,
+                };
+                if callback_data(&self.prefix, 'p', target).len() > MAX_CALLBACK_DATA {
+                    return Err(anyhow!(
+                        "callback data for {} is longer than {} bytes",
+                        target,
+                        MAX_CALLBACK_DATA
+                    ));
+                }
+            }
+        }
+        Ok(Menu(Arc::new(MenuInner {
+            prefix: self.prefix,
+            root: self.root,
+            pages: self.pages,
+            actions: self.actions,
+            states: Mutex::new(HashMap::new()),
+        })))
+    }
+}
+
+/// Encode a button press as callback data, p for pages, a for actions and b for back
+fn callback_data(prefix: &str, kind: char, target: &str) -> String {
+    format!("{}:{}:{}", prefix, kind, target)
+}
+
+impl Menu {
+    /// Start a menu with callback data prefix and the id of the first page shown
+    pub fn builder<P: Into<String>, R: Into<String>>(prefix: P, root: R) -> MenuBuilder {
+        MenuBuilder {
+            prefix: prefix.into(),
+            root: root.into(),
+            pages: HashMap::new(),
+            actions: HashMap::new(),
+        }
+    }
+
+    /// Load the prefix, root and pages of a menu from TOML. Register actions on the
+    /// returned builder
+    #[cfg(feature = "toml")]
+    pub fn from_toml(source: &str) -> Result<MenuBuilder> {
+        let def: MenuDef = toml::from_str(source)?;
+        Ok(Self::from_def(def))
+    }
+
+    /// Load the prefix, root and pages of a menu from json. Register actions on the
+    /// returned builder
+    pub fn from_json(source: &str) -> Result<MenuBuilder> {
+        let def: MenuDef = serde_json::from_str(source)?;
+        Ok(Self::from_def(def))
+    }
+
+    fn from_def(def: MenuDef) -> MenuBuilder {
+        MenuBuilder {
+            prefix: def.prefix,
+            root: def.root,
+            pages: def.pages,
+            actions: HashMap::new(),
+        }
+    }
+
+    /// Get a page by id
+    pub fn get_page(&self, id: &str) -> Option<&'_ Page> {
+        self.0.pages.get(id)
+    }
+
+    /// Build the inline keyboard of a page
+    pub fn render(&self, id: &str) -> Option<InlineKeyboardMarkup> {
+        let page = self.get_page(id)?;
+        let rows = page
+            .get_rows()
+            .map(|row| {
+                row.iter()
+                    .map(|button| match button {
+                        Button::Page { label, page } => {
+                            let mut b = InlineKeyboardButton::new(label.clone());
+                            b.set_callback_data(Some(callback_data(&self.0.prefix, 'p', page)));
+                            b
+                        }
+                        Button::Action { label, action } => {
+                            let mut b = InlineKeyboardButton::new(label.clone());
+                            b.set_callback_data(Some(callback_data(&self.0.prefix, 'a', action)));
+                            b
+                        }
+                        Button::Url { label, url } => {
+                            let mut b = InlineKeyboardButton::new(label.clone());
+                            b.set_url(Some(url.clone()));
+                            b
+                        }
+                        Button::Back { label } => {
+                            let mut b = InlineKeyboardButton::new(label.clone());
+                            b.set_callback_data(Some(callback_data(&self.0.prefix, 'b', "")));
+                            b
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Some(InlineKeyboardMarkup::new(rows))
+    }
+
+    /// Send the root page of the menu to a chat
+    pub async fn send(&self, bot: &Bot, chat: i64) -> BotResult<Message> {
+        let page = &self.0.pages[&self.0.root];
+        let markup = EReplyMarkup::InlineKeyboardMarkup(self.render(&self.0.root).unwrap());
+        bot.build_send_message(chat, page.get_text())
+            .reply_markup(&markup)
+            .build()
+            .await
+    }
+
+    /// Get the page a user is on in a menu message, the root page if unknown
+    pub fn current_page(&self, chat: i64, message_id: MsgId, user: UserId) -> String {
+        self.0
+            .states
+            .lock()
+            .unwrap()
+            .get(&(chat, message_id, user))
+            .and_then(|(_, path)| path.last().cloned())
+            .unwrap_or_else(|| self.0.root.clone())
+    }
+
+    /// Update the navigation state of a user for a button press, returning the page to
+    /// show
+    fn navigate(&self, key: StateKey, kind: &str, target: &str) -> Option<String> {
+        let mut states = self.0.states.lock().unwrap();
+        if states.len() >= MAX_STATES && !states.contains_key(&key) {
+            let oldest = states
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(k, _)| *k);
+            if let Some(oldest) = oldest {
+                states.remove(&oldest);
+            }
+        }
+        let (used, path) = states
+            .entry(key)
+            .or_insert_with(|| (Instant::now(), vec![self.0.root.clone()]));
+        *used = Instant::now();
+        match kind {
+            "p" if self.0.pages.contains_key(target) => {
+                if path.len() >= MAX_DEPTH {
+                    path.remove(1);
+                }
+                path.push(target.to_owned());
+            }
+            "b" if path.len() > 1 => {
+                path.pop();
+            }
+            "b" => (),
+            _ => return None,
+        }
+        path.last().cloned()
+    }
+
+    /// Edit a menu message to show a page. The message already showing the page, like
+    /// after a back button on the root page, is not an error
+    async fn show(&self, bot: &Bot, query: &CallbackQuery, page: &str) -> BotResult<()> {
+        if let (Some(MaybeInaccessibleMessage::Message(message)), Some(markup)) =
+            (query.get_message(), self.render(page))
+        {
+            let res = bot
+                .build_edit_message_text(self.0.pages[page].get_text())
+                .chat_id(message.get_chat().get_id())
+                .message_id(message.get_message_id())
+                .reply_markup(&markup)
+                .build()
+                .await;
+            match res {
+                Ok(_) => (),
+                Err(err) if err.is_not_modified() => (),
+                Err(err) => return Err(err),
+            }
+        }
+        bot.build_answer_callback_query(query.get_id())
+            .build()
+            .await?;
+        Ok(())
+    }
+}
+
+impl Layer for Menu {
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let menu = self.clone();
+        Box::pin(async move {
+            let UpdateExt::CallbackQuery(query) = update else {
+                return Ok(Flow::Continue);
+            };
+            let Some((kind, target)) = query
+                .get_data()
+                .and_then(|d| d.strip_prefix(menu.0.prefix.as_str()))
+                .and_then(|d| d.strip_prefix(':'))
+                .and_then(|d| d.split_once(':'))
+            else {
+                return Ok(Flow::Continue);
+            };
+            if kind == "a" {
+                match menu.0.actions.get(target) {
+                    Some(action) => action(bot, query).await?,
+                    None => return Ok(Flow::Continue),
+                }
+                return Ok(Flow::Stop);
+            }
+            let key = match query.get_message() {
+                Some(MaybeInaccessibleMessage::Message(m)) => {
+                    (m.get_chat().get_id(), m.get_message_id())
+                }
+                Some(MaybeInaccessibleMessage::InaccessibleMessage(m)) => {
+                    (m.get_chat().get_id(), m.get_message_id())
+                }
+                None => return Ok(Flow::Continue),
+            };
+            let key = (key.0, key.1, query.get_from().get_id());
+            let Some(page) = menu.navigate(key, kind, target) else {
+                return Ok(Flow::Continue);
+            };
+            menu.show(&bot, &query, &page).await?;
+            Ok(Flow::Stop)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu() -> Menu {
+        Menu::builder("settings", "main")
+            .page(
+                "main",
+                Page::new("Settings")
+                    .page("Notifications", "notify")
+                    .row()
+                    .url("Docs", "https://example.com"),
+            )
+            .page(
+                "notify",
+                Page::new("Notifications")
+                    .action("Mute", "mute")
+                    .back("Back"),
+            )
+            .on_action("mute", |_, _| async { Ok(()) })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn navigation() {
+        let menu = menu();
+        let rows = menu.render("main").unwrap();
+        let rows = rows.get_inline_keyboard();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0].get_callback_data(), Some("settings:p:notify"));
+        let key = (1, MsgId::from(2), UserId::from(3));
+        assert_eq!(menu.navigate(key, "p", "notify").as_deref(), Some("notify"));
+        assert_eq!(
+            menu.current_page(1, MsgId::from(2), UserId::from(3)),
+            "notify"
+        );
+        assert_eq!(menu.navigate(key, "b", "").as_deref(), Some("main"));
+        assert_eq!(menu.navigate(key, "b", "").as_deref(), Some("main"));
+        assert_eq!(menu.navigate(key, "p", "missing"), None);
+        for _ in 0..MAX_DEPTH * 2 {
+            menu.navigate(key, "p", "notify");
+        }
+        let states = menu.0.states.lock().unwrap();
+        let (_, path) = &states[&key];
+        assert_eq!(path.len(), MAX_DEPTH);
+        assert_eq!(path[0], "main");
+    }
+
+    #[test]
+    fn checks_links() {
+        let err = Menu::builder("m", "main")
+            .page("main", Page::new("Main").page("Next", "next"))
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "page main links to missing page next");
+        let json = r#"{"prefix": "m", "root": "main", "pages": {"main": {"text": "Main",
+            "rows": [[{"label": "Go", "action": "go", "url": "https://example.com"}]]}}}"#;
+        assert!(Menu::from_json(json).is_err());
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures_util::future::BoxFuture;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{Flow, Layer};
+use crate::gen_types::{
+    ChatPermissions, EReplyMarkup, InlineKeyboardButton, InlineKeyboardMarkup, Message, MsgId,
+    UpdateExt, User, UserId,
+};
+
+/// Action taken against a user caught by a moderation layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModAction {
+    /// Only delete the offending message
+    Delete,
+    /// Delete the message and mute the user for a duration
+    Mute(Duration),
+    /// Delete the message and ban the user
+    Ban,
+}
+
+fn unix_time(after: Duration) -> i64 {
+    (SystemTime::now() + after)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn done(flow: Flow) -> BoxFuture<'static, BotResult<Flow>> {
+    Box::pin(async move { Ok(flow) })
+}
+
+/// Permissions letting a member send anything, used to lift a restriction when the
+/// chat's default permissions can't be read
+fn unrestricted() -> ChatPermissions {
+    let mut permissions = ChatPermissions::default();
+    permissions.set_can_send_messages(Some(true));
+    permissions.set_can_send_audios(Some(true));
+    permissions.set_can_send_documents(Some(true));
+    permissions.set_can_send_photos(Some(true));
+    permissions.set_can_send_videos(Some(true));
+    permissions.set_can_send_video_notes(Some(true));
+    permissions.set_can_send_voice_notes(Some(true));
+    permissions.set_can_send_polls(Some(true));
+    permissions.set_can_send_other_messages(Some(true));
+    permissions.set_can_add_web_page_previews(Some(true));
+    permissions
+}
+
+impl ModAction {
+    /// Apply this action to the sender of a message
+    pub async fn apply(&self, bot: &Bot, message: &Message) -> BotResult<()> {
+        let chat = message.get_chat().get_id();
+        bot.build_delete_message(chat, message.get_message_id())
+            .build()
+            .await?;
+        let Some(user) = message.get_from() else {
+            return Ok(());
+        };
+        match self {
+            ModAction::Delete => (),
+            ModAction::Mute(duration) => {
+                bot.build_restrict_chat_member(chat, user.get_id(), &ChatPermissions::default())
+                    .until_date(unix_time(*duration))
+                    .build()
+                    .await?;
+            }
+            ModAction::Ban => {
+                bot.build_ban_chat_member(chat, user.get_id())
+                    .build()
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Layer detecting users sending more than a number of messages in a time window
+pub struct FloodLayer {
+    max_messages: usize,
+    window: Duration,
+    action: ModAction,
+    seen: Mutex<HashMap<(i64, UserId), VecDeque<Instant>>>,
+}
+
+impl FloodLayer {
+    /// Apply `action` to users sending more than `max_messages` per `window` in a chat
+    pub fn new(max_messages: usize, window: Duration, action: ModAction) -> Self {
+        Self {
+            max_messages,
+            window,
+            action,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a message, returning true if the sender is flooding
+    fn record(&self, chat: i64, user: UserId) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, times| {
+            times
+                .back()
+                .map(|t| now.duration_since(*t) < self.window)
+                .unwrap_or(false)
+        });
+        let times = seen.entry((chat, user)).or_default();
+        times.push_back(now);
+        while times
+            .front()
+            .map(|t| now.duration_since(*t) >= self.window)
+            .unwrap_or(false)
+        {
+            times.pop_front();
+        }
+        times.len() > self.max_messages
+    }
+}
+
+impl Layer for FloodLayer {
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let UpdateExt::Message(message) = update else {
+            return done(Flow::Continue);
+        };
+        let Some(user) = message.get_from() else {
+            return done(Flow::Continue);
+        };
+        if !self.record(message.get_chat().get_id(), user.get_id()) {
+            return done(Flow::Continue);
+        }
+        let action = self.action;
+        Box::pin(async move {
+            action.apply(&bot, &message).await?;
+            Ok(Flow::Stop)
+        })
+    }
+}
+
+/// Layer filtering messages containing any word from a list, ignoring case
+pub struct WordFilterLayer {
+    words: Vec<String>,
+    action: ModAction,
+}
+
+impl WordFilterLayer {
+    /// Apply `action` to messages with text or captions containing any of `words`
+    pub fn new<I, T>(words: I, action: ModAction) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        Self {
+            words: words
+                .into_iter()
+                .map(|w| w.as_ref().to_lowercase())
+                .collect(),
+            action,
+        }
+    }
+
+    /// Check if text contains a filtered word
+    pub fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        text.split(|c: char| !c.is_alphanumeric())
+            .any(|word| self.words.iter().any(|w| w == word))
+            || self
+                .words
+                .iter()
+                .filter(|w| w.contains(char::is_whitespace))
+                .any(|w| text.contains(w.as_str()))
+    }
+}
+
+impl Layer for WordFilterLayer {
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let UpdateExt::Message(message) = update else {
+            return done(Flow::Continue);
+        };
+        let text = message.get_text().or_else(|| message.get_caption());
+        if !text.map(|t| self.matches(t)).unwrap_or(false) {
+            return done(Flow::Continue);
+        }
+        let action = self.action;
+        Box::pin(async move {
+            action.apply(&bot, &message).await?;
+            Ok(Flow::Stop)
+        })
+    }
+}
+
+const CAPTCHA_PREFIX: &str = "captcha:";
+
+/// A member who solved the captcha, with the title of the chat they were let into
+#[derive(Debug, Clone)]
+pub(crate) struct Solved {
+    pub(crate) chat: i64,
+    pub(crate) title: String,
+    pub(crate) user: User,
+}
+
+type CaptchaFlow = BoxFuture<'static, BotResult<(Flow, Option<Solved>)>>;
+
+fn unsolved(flow: Flow) -> CaptchaFlow {
+    Box::pin(async move { Ok((flow, None)) })
+}
+
+/// Layer muting new members until they press a button. Members who do not respond
+/// within the timeout are kicked
+pub struct CaptchaLayer {
+    timeout: Duration,
+    text: String,
+    button: String,
+    pending: Arc<Mutex<HashMap<(i64, UserId), MsgId>>>,
+}
+
+impl CaptchaLayer {
+    /// Create a captcha kicking new members who do not respond within `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            text: "Press the button below within the time limit to be allowed to chat".to_owned(),
+            button: "I am not a bot".to_owned(),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Set the text of the challenge message
+    pub fn text<T: Into<String>>(mut self, text: T) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    /// Set the label of the challenge button
+    pub fn button<T: Into<String>>(mut self, button: T) -> Self {
+        self.button = button.into();
+        self
+    }
+
+    async fn challenge(
+        bot: Bot,
+        chat: i64,
+        users: Vec<UserId>,
+        timeout: Duration,
+        text: String,
+        button: String,
+        pending: Arc<Mutex<HashMap<(i64, UserId), MsgId>>>,
+    ) -> BotResult<()> {
+        for user in users {
+            bot.build_restrict_chat_member(chat, user, &ChatPermissions::default())
+                .build()
+                .await?;
+            let mut b = InlineKeyboardButton::new(button.clone());
+            b.set_callback_data(Some(format!("{}{}:{}", CAPTCHA_PREFIX, chat, user)));
+            let markup =
+                EReplyMarkup::InlineKeyboardMarkup(InlineKeyboardMarkup::new(vec![vec![b]]));
+            let message = bot
+                .build_send_message(chat, &text)
+                .reply_markup(&markup)
+                .build()
+                .await?;
+            let challenge = message.get_message_id();
+            pending.lock().unwrap().insert((chat, user), challenge);
+
+            let bot = bot.clone();
+            let pending = Arc::clone(&pending);
+            tokio::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                if pending.lock().unwrap().remove(&(chat, user)).is_none() {
+                    return;
+                }
+                let kick = async {
+                    bot.build_ban_chat_member(chat, user).build().await?;
+                    bot.build_unban_chat_member(chat, user)
+                        .only_if_banned(true)
+                        .build()
+                        .await?;
+                    bot.build_delete_message(chat, challenge).build().await
+                };
+                if let Err(err) = kick.await {
+                    log::warn!("failed to kick user after captcha timeout {}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+impl CaptchaLayer {
+    /// Handle an update like Layer::call, also returning the member who solved the
+    /// captcha if the update was them pressing the button
+    pub(crate) fn check(&self, bot: Bot, update: UpdateExt) -> CaptchaFlow {
+        match update {
+            UpdateExt::Message(message) => {
+                let Some(members) = message.get_new_chat_members() else {
+                    return unsolved(Flow::Continue);
+                };
+                let users = members
+                    .iter()
+                    .filter(|u| !u.get_is_bot())
+                    .map(|u| u.get_id())
+                    .collect();
+                let challenge = Self::challenge(
+                    bot,
+                    message.get_chat().get_id(),
+                    users,
+                    self.timeout,
+                    self.text.clone(),
+                    self.button.clone(),
+                    Arc::clone(&self.pending),
+                );
+                Box::pin(async move {
+                    challenge.await?;
+                    Ok((Flow::Continue, None))
+                })
+            }
+            UpdateExt::CallbackQuery(query) => {
+                let Some((chat, user)) = query
+                    .get_data()
+                    .and_then(|d| d.strip_prefix(CAPTCHA_PREFIX))
+                    .and_then(|d| d.split_once(':'))
+                    .and_then(|(c, u)| {
+                        Some((c.parse::<i64>().ok()?, UserId::from(u.parse::<i64>().ok()?)))
+                    })
+                else {
+                    return unsolved(Flow::Continue);
+                };
+                let from = query.get_from().clone();
+                let challenge = if from.get_id() == user {
+                    self.pending.lock().unwrap().remove(&(chat, user))
+                } else {
+                    None
+                };
+                Box::pin(async move {
+                    bot.build_answer_callback_query(query.get_id())
+                        .build()
+                        .await?;
+                    let Some(challenge) = challenge else {
+                        return Ok((Flow::Stop, None));
+                    };
+                    let (permissions, title) = match bot.build_get_chat(chat).build().await {
+                        Ok(chat_info) => (
+                            chat_info.get_permissions().cloned(),
+                            chat_info.get_title().unwrap_or_default().to_owned(),
+                        ),
+                        Err(err) => {
+                            log::warn!("failed to get chat permissions {}", err);
+                            (None, String::new())
+                        }
+                    };
+                    let permissions = permissions.unwrap_or_else(unrestricted);
+                    bot.build_restrict_chat_member(chat, user, &permissions)
+                        .build()
+                        .await?;
+                    bot.build_delete_message(chat, challenge).build().await?;
+                    let solved = Solved {
+                        chat,
+                        title,
+                        user: from,
+                    };
+                    Ok((Flow::Stop, Some(solved)))
+                })
+            }
+            _ => unsolved(Flow::Continue),
+        }
+    }
+}
+
+impl Layer for CaptchaLayer {
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let check = self.check(bot, update);
+        Box::pin(async move { check.await.map(|(flow, _)| flow) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Dispatcher;
+    use crate::testing::TestBot;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn solved_captcha_lifts_restriction() {
+        let test = TestBot::builder()
+            .respond("getChat", json!({"id": -100, "type": "supergroup"}))
+            .build()
+            .await
+            .unwrap();
+        let dispatcher = Dispatcher::new().layer(CaptchaLayer::new(Duration::from_secs(60)));
+        let joined = json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup"},
+            "new_chat_members": [{"id": 2, "is_bot": false, "first_name": "New"}],
+        });
+        test.dispatch(
+            &dispatcher,
+            UpdateExt::Message(serde_json::from_value(joined).unwrap()),
+        )
+        .await;
+        let query = json!({
+            "id": "1",
+            "from": {"id": 2, "is_bot": false, "first_name": "New"},
+            "chat_instance": "1",
+            "data": "captcha:-100:2",
+        });
+        test.dispatch(
+            &dispatcher,
+            UpdateExt::CallbackQuery(serde_json::from_value(query).unwrap()),
+        )
+        .await;
+
+        let restricted = test.sent("restrictChatMember");
+        assert_eq!(restricted.len(), 2);
+        let permissions: serde_json::Value =
+            serde_json::from_str(restricted[1].get("permissions").unwrap()).unwrap();
+        assert_eq!(permissions["can_send_messages"], true);
+    }
+}
@@ -0,0 +1,144 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{Bot, BotResult, TelegramMethod};
+use crate::format::ParseMode;
+
+tokio::task_local! {
+    static REQUEST_OPTIONS: RequestOptions;
+}
+
+/// Overrides of Bot level settings for a single request, set using `with_options` on a
+/// method builder or TelegramMethod::with_options on a Params struct
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct RequestOptions {
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    skip_throttle: bool,
+    parse_mode: Option<ParseMode>,
+    api: Option<String>,
+}
+
+impl RequestOptions {
+    /// Create options leaving every setting at the Bot's default
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the request if it takes longer than `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Cancel the request if it hasn't finished by `deadline`, including time spent
+    /// waiting for throttling or flood waits. Fails with an ApiError where is_timeout
+    /// is true
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Send the request without waiting for the bot's ThrottlePolicy
+    pub fn skip_throttle(mut self, skip_throttle: bool) -> Self {
+        self.skip_throttle = skip_throttle;
+        self
+    }
+
+    /// Use this parse_mode if the request does not set one
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = Some(parse_mode);
+        self
+    }
+
+    /// Send the request to a different api url than the bot's
+    pub fn api<T: Into<String>>(mut self, api: T) -> Self {
+        self.api = Some(api.into());
+        self
+    }
+
+    pub(crate) fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn get_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub(crate) fn get_skip_throttle(&self) -> bool {
+        self.skip_throttle
+    }
+
+    pub(crate) fn get_parse_mode(&self) -> Option<ParseMode> {
+        self.parse_mode
+    }
+
+    pub(crate) fn get_api(&self) -> Option<&'_ str> {
+        self.api.as_deref()
+    }
+
+    /// Get the options of the request currently being sent, or the defaults outside
+    /// of a request with options
+    pub(crate) fn current() -> Self {
+        REQUEST_OPTIONS
+            .try_with(|options| options.clone())
+            .unwrap_or_default()
+    }
+
+    /// Run a future with these options applied to every request it sends
+    pub async fn scope<F>(self, fut: F) -> F::Output
+    where
+        F: Future,
+    {
+        REQUEST_OPTIONS.scope(self, fut).await
+    }
+}
+
+/// A TelegramMethod sent with RequestOptions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct WithOptions<P> {
+    params: P,
+    #[serde(skip)]
+    options: RequestOptions,
+}
+
+impl<P> WithOptions<P> {
+    pub(crate) fn new(params: P, options: RequestOptions) -> Self {
+        Self { params, options }
+    }
+}
+
+impl<P> TelegramMethod for WithOptions<P>
+where
+    P: TelegramMethod,
+{
+    type Response = P::Response;
+    const NAME: &'static str = P::NAME;
+
+    fn call(self, bot: &Bot) -> BoxFuture<'_, BotResult<Self::Response>> {
+        Box::pin(self.options.scope(self.params.call(bot)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scoped_options() {
+        assert_eq!(RequestOptions::current(), RequestOptions::default());
+        let options = RequestOptions::new()
+            .skip_throttle(true)
+            .parse_mode(ParseMode::Html);
+        let current = options
+            .clone()
+            .scope(async { RequestOptions::current() })
+            .await;
+        assert_eq!(current, options);
+        assert_eq!(RequestOptions::current(), RequestOptions::default());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::gen_types::{Chat, Message, MessageOrigin, MsgId, User};
+
+/// The kind of sender a forwarded message originally came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OriginKind {
+    /// A known user
+    User,
+    /// A user who hides their account in forwards
+    HiddenUser,
+    /// An anonymous group admin or a group itself
+    Chat,
+    /// A channel post
+    Channel,
+}
+
+impl MessageOrigin {
+    /// Get the kind of sender without matching on the enum
+    pub fn kind(&self) -> OriginKind {
+        match self {
+            Self::MessageOriginUser(_) => OriginKind::User,
+            Self::MessageOriginHiddenUser(_) => OriginKind::HiddenUser,
+            Self::MessageOriginChat(_) => OriginKind::Chat,
+            Self::MessageOriginChannel(_) => OriginKind::Channel,
+        }
+    }
+
+    /// Get the original sender if it was a visible user
+    pub fn user(&self) -> Option<&User> {
+        match self {
+            Self::MessageOriginUser(origin) => Some(origin.get_sender_user()),
+            _ => None,
+        }
+    }
+
+    /// Get the original sender chat for anonymous admins and channel posts
+    pub fn chat(&self) -> Option<&Chat> {
+        match self {
+            Self::MessageOriginChat(origin) => Some(origin.get_sender_chat()),
+            Self::MessageOriginChannel(origin) => Some(origin.get_chat()),
+            _ => None,
+        }
+    }
+
+    /// Get the name shown for the original sender if they hide their account
+    pub fn sender_name(&self) -> Option<&str> {
+        match self {
+            Self::MessageOriginHiddenUser(origin) => Some(origin.get_sender_user_name()),
+            _ => None,
+        }
+    }
+
+    /// Get the signature of the original author for anonymous admins and channel posts
+    pub fn author_signature(&self) -> Option<&str> {
+        match self {
+            Self::MessageOriginChat(origin) => origin.get_author_signature(),
+            Self::MessageOriginChannel(origin) => origin.get_author_signature(),
+            _ => None,
+        }
+    }
+
+    /// Get the id of the original message in the channel for channel posts
+    pub fn message_id(&self) -> Option<MsgId> {
+        match self {
+            Self::MessageOriginChannel(origin) => Some(origin.get_message_id()),
+            _ => None,
+        }
+    }
+
+    /// Get the unix time the original message was sent
+    pub fn date(&self) -> i64 {
+        match self {
+            Self::MessageOriginUser(origin) => origin.get_date(),
+            Self::MessageOriginHiddenUser(origin) => origin.get_date(),
+            Self::MessageOriginChat(origin) => origin.get_date(),
+            Self::MessageOriginChannel(origin) => origin.get_date(),
+        }
+    }
+}
+
+impl Message {
+    /// Get the kind of sender of the original message if this message was forwarded
+    pub fn forward_origin_kind(&self) -> Option<OriginKind> {
+        self.get_forward_origin().map(|origin| origin.kind())
+    }
+
+    /// Get the original sender of a forwarded message if it was a visible user
+    pub fn origin_user(&self) -> Option<&User> {
+        self.get_forward_origin().and_then(|origin| origin.user())
+    }
+
+    /// Get the original sender chat of a forwarded message from an anonymous admin or
+    /// channel
+    pub fn origin_chat(&self) -> Option<&Chat> {
+        self.get_forward_origin().and_then(|origin| origin.chat())
+    }
+
+    /// Get the unix time the original message was sent if this message was forwarded
+    pub fn origin_date(&self) -> Option<i64> {
+        self.get_forward_origin().map(|origin| origin.date())
+    }
+
+    /// Replacement for the forward_from field removed in Bot API 7.0
+    #[deprecated(note = "use origin_user or get_forward_origin")]
+    pub fn forward_from(&self) -> Option<&User> {
+        self.origin_user()
+    }
+
+    /// Replacement for the forward_from_chat field removed in Bot API 7.0
+    #[deprecated(note = "use origin_chat or get_forward_origin")]
+    pub fn forward_from_chat(&self) -> Option<&Chat> {
+        self.origin_chat()
+    }
+
+    /// Replacement for the forward_from_message_id field removed in Bot API 7.0
+    #[deprecated(note = "use get_forward_origin")]
+    pub fn forward_from_message_id(&self) -> Option<MsgId> {
+        self.get_forward_origin()
+            .and_then(|origin| origin.message_id())
+    }
+
+    /// Replacement for the forward_signature field removed in Bot API 7.0
+    #[deprecated(note = "use get_forward_origin")]
+    pub fn forward_signature(&self) -> Option<&str> {
+        self.get_forward_origin()
+            .and_then(|origin| origin.author_signature())
+    }
+
+    /// Replacement for the forward_sender_name field removed in Bot API 7.0
+    #[deprecated(note = "use get_forward_origin")]
+    pub fn forward_sender_name(&self) -> Option<&str> {
+        self.get_forward_origin()
+            .and_then(|origin| origin.sender_name())
+    }
+
+    /// Replacement for the forward_date field removed in Bot API 7.0
+    #[deprecated(note = "use origin_date")]
+    pub fn forward_date(&self) -> Option<i64> {
+        self.origin_date()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen_types::MessageOriginChannel;
+
+    #[test]
+    #[allow(deprecated)]
+    fn channel_origin() {
+        let mut chat = Chat::default();
+        chat.set_id(-100);
+        let mut origin = MessageOriginChannel::default();
+        origin.set_chat(chat);
+        origin.set_message_id(7.into());
+        origin.set_date(1234);
+        let mut message = Message::default();
+        message.set_forward_origin(Some(MessageOrigin::MessageOriginChannel(origin)));
+        assert_eq!(message.forward_origin_kind(), Some(OriginKind::Channel));
+        assert_eq!(message.origin_chat().map(|c| c.get_id()), Some(-100));
+        assert_eq!(message.origin_user(), None);
+        assert_eq!(message.forward_from_message_id(), Some(7.into()));
+        assert_eq!(message.forward_date(), Some(1234));
+        assert_eq!(Message::default().forward_origin_kind(), None);
+    }
+}
@@ -0,0 +1,98 @@
+use std::future::Future;
+
+use opentelemetry::trace::{
+    FutureExt, Link, SpanContext, SpanKind, Status, TraceContextExt, Tracer,
+};
+use opentelemetry::{global, Context, KeyValue};
+
+use crate::bot::{BotResult, Response};
+use crate::gen_types::{MaybeInaccessibleMessage, UpdateExt, UpdateId};
+
+/// Name of the tracer spans are recorded with
+const TRACER: &str = "botapi";
+
+/// Span context of the update being dispatched, kept in the otel Context so api calls
+/// can link to it even from inside spans created by handlers
+#[derive(Debug, Clone)]
+struct UpdateSpan(SpanContext);
+
+/// Get the chat an update happened in, if it has one
+fn update_chat(update: &UpdateExt) -> Option<i64> {
+    match update {
+        UpdateExt::Message(m)
+        | UpdateExt::EditedMessage(m)
+        | UpdateExt::ChannelPost(m)
+        | UpdateExt::EditedChannelPost(m) => Some(m.get_chat().get_id()),
+        UpdateExt::CallbackQuery(q) => match q.get_message()? {
+            MaybeInaccessibleMessage::Message(m) => Some(m.get_chat().get_id()),
+            MaybeInaccessibleMessage::InaccessibleMessage(m) => Some(m.get_chat().get_id()),
+        },
+        UpdateExt::ChatMember(m) | UpdateExt::MyChatMember(m) => Some(m.get_chat().get_id()),
+        UpdateExt::ChatJoinRequest(r) => Some(r.get_chat().get_id()),
+        _ => None,
+    }
+}
+
+/// Start the span covering dispatch of one update, returning a Context to run the
+/// layers and handlers in
+pub(crate) fn update_context(update_id: Option<UpdateId>, update: &UpdateExt) -> Context {
+    let tracer = global::tracer(TRACER);
+    let mut attributes = Vec::with_capacity(2);
+    if let Some(update_id) = update_id {
+        attributes.push(KeyValue::new("telegram.update_id", update_id.get()));
+    }
+    if let Some(chat) = update_chat(update) {
+        attributes.push(KeyValue::new("telegram.chat_id", chat));
+    }
+    let span = tracer
+        .span_builder("telegram.update")
+        .with_kind(SpanKind::Consumer)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, &Context::current());
+    let cx = Context::current_with_span(span);
+    let update_span = UpdateSpan(cx.span().span_context().clone());
+    cx.with_value(update_span)
+}
+
+/// Run an api call in a client span, a child of the current span and linked to the
+/// update being handled if there is one
+pub(crate) async fn traced_call<F>(method: &str, call: F) -> BotResult<Response>
+where
+    F: Future<Output = BotResult<Response>>,
+{
+    let tracer = global::tracer(TRACER);
+    let parent = Context::current();
+    let mut builder = tracer
+        .span_builder(method.to_owned())
+        .with_kind(SpanKind::Client)
+        .with_attributes([KeyValue::new("telegram.method", method.to_owned())]);
+    if let Some(UpdateSpan(update)) = parent.get::<UpdateSpan>() {
+        builder = builder.with_links(vec![Link::with_context(update.clone())]);
+    }
+    let cx = parent.with_span(builder.start_with_context(&tracer, &parent));
+    let res = call.with_context(cx.clone()).await;
+    let span = cx.span();
+    match res {
+        Ok(ref resp) if resp.ok => span.set_status(Status::Ok),
+        Ok(ref resp) => {
+            if let Some(code) = resp.error_code {
+                span.set_attribute(KeyValue::new("telegram.error_code", code));
+            }
+            span.set_status(Status::error(resp.description.clone().unwrap_or_default()));
+        }
+        Err(ref err) => span.set_status(Status::error(err.to_string())),
+    }
+    span.end();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_context_carries_span() {
+        let cx = update_context(Some(UpdateId::from(1)), &UpdateExt::Invalid);
+        assert!(cx.get::<UpdateSpan>().is_some());
+    }
+}
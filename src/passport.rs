@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use cbc::cipher::{block_padding::NoPadding, BlockDecryptMut, KeyIvInit};
+use rsa::{pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, Oaep, RsaPrivateKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{EncryptedPassportElement, PassportData, PassportFile};
+
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// The bot's private key used to decrypt telegram passport credentials
+pub struct PassportKey(RsaPrivateKey);
+
+impl PassportKey {
+    /// Load a private key from a PEM encoded PKCS#1 or PKCS#8 string
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        let key = RsaPrivateKey::from_pkcs1_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs8_pem(pem))
+            .map_err(|e| anyhow!("invalid passport key: {}", e))?;
+        Ok(Self(key))
+    }
+}
+
+/// Decrypt data using a secret and hash as described in the telegram passport docs.
+/// The hash is verified and padding is removed
+fn decrypt_data(data: &[u8], secret: &[u8], hash: &[u8]) -> Result<Vec<u8>> {
+    let mut hasher = Sha512::new();
+    hasher.update(secret);
+    hasher.update(hash);
+    let secret_hash = hasher.finalize();
+    let decryptor = Aes256CbcDec::new_from_slices(&secret_hash[0..32], &secret_hash[32..48])
+        .map_err(|_| anyhow!("invalid passport key length"))?;
+    let mut buf = data.to_vec();
+    let decrypted = decryptor
+        .decrypt_padded_mut::<NoPadding>(&mut buf)
+        .map_err(|_| anyhow!("invalid passport data length"))?;
+    if Sha256::digest(decrypted).as_slice() != hash {
+        return Err(anyhow!("passport data hash mismatch"));
+    }
+    let padding = *decrypted
+        .first()
+        .ok_or_else(|| anyhow!("empty passport data"))? as usize;
+    if padding > decrypted.len() {
+        return Err(anyhow!("invalid passport data padding"));
+    }
+    Ok(decrypted[padding..].to_vec())
+}
+
+fn decode(s: &str) -> Result<Vec<u8>> {
+    STANDARD.decode(s).map_err(|e| anyhow!(e))
+}
+
+/// Credentials required to decrypt an element's data field
+#[derive(Deserialize, Debug, Clone)]
+pub struct DataCredentials {
+    pub data_hash: String,
+    pub secret: String,
+}
+
+/// Credentials required to decrypt a passport file
+#[derive(Deserialize, Debug, Clone)]
+pub struct FileCredentials {
+    pub file_hash: String,
+    pub secret: String,
+}
+
+/// Credentials for a single passport element
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SecureValue {
+    pub data: Option<DataCredentials>,
+    pub front_side: Option<FileCredentials>,
+    pub reverse_side: Option<FileCredentials>,
+    pub selfie: Option<FileCredentials>,
+    #[serde(default)]
+    pub translation: Vec<FileCredentials>,
+    #[serde(default)]
+    pub files: Vec<FileCredentials>,
+}
+
+/// Decrypted credentials from EncryptedCredentials
+#[derive(Deserialize, Debug, Clone)]
+pub struct Credentials {
+    pub secure_data: HashMap<String, SecureValue>,
+    pub nonce: String,
+}
+
+/// Decrypted data of a "personal_details" element
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PersonalDetails {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub middle_name: Option<String>,
+    pub birth_date: Option<String>,
+    pub gender: Option<String>,
+    pub country_code: Option<String>,
+    pub residence_country_code: Option<String>,
+    pub first_name_native: Option<String>,
+    pub last_name_native: Option<String>,
+    pub middle_name_native: Option<String>,
+}
+
+/// Decrypted data of an identity document element
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct IdDocumentData {
+    pub document_no: Option<String>,
+    pub expiry_date: Option<String>,
+}
+
+/// Decrypted data of an "address" element
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ResidentialAddress {
+    pub street_line1: Option<String>,
+    pub street_line2: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country_code: Option<String>,
+    pub post_code: Option<String>,
+}
+
+/// Typed decrypted data field of a passport element
+#[derive(Debug, Clone)]
+pub enum DecryptedData {
+    PersonalDetails(PersonalDetails),
+    IdDocument(IdDocumentData),
+    Address(ResidentialAddress),
+    Other(serde_json::Value),
+}
+
+impl DecryptedData {
+    fn parse(element_type: &str, data: &[u8]) -> Result<Self> {
+        let res = match element_type {
+            "personal_details" => Self::PersonalDetails(serde_json::from_slice(data)?),
+            "passport" | "driver_license" | "identity_card" | "internal_passport" => {
+                Self::IdDocument(serde_json::from_slice(data)?)
+            }
+            "address" => Self::Address(serde_json::from_slice(data)?),
+            _ => Self::Other(serde_json::from_slice(data)?),
+        };
+        Ok(res)
+    }
+}
+
+/// An encrypted passport file along with the credentials needed to decrypt it
+#[derive(Debug, Clone)]
+pub struct EncryptedFile {
+    pub file: PassportFile,
+    pub credentials: FileCredentials,
+}
+
+impl EncryptedFile {
+    /// Download and decrypt this file
+    pub async fn download(&self, bot: &Bot) -> BotResult<Vec<u8>> {
+        let bytes = bot.download_file_id(self.file.get_file_id()).await?;
+        let secret = decode(&self.credentials.secret)?;
+        let hash = decode(&self.credentials.file_hash)?;
+        Ok(decrypt_data(&bytes, &secret, &hash)?)
+    }
+}
+
+/// A passport element with its data decrypted and files ready to download
+#[derive(Debug, Clone)]
+pub struct DecryptedElement {
+    pub element_type: String,
+    pub data: Option<DecryptedData>,
+    pub phone_number: Option<String>,
+    pub email: Option<String>,
+    pub front_side: Option<EncryptedFile>,
+    pub reverse_side: Option<EncryptedFile>,
+    pub selfie: Option<EncryptedFile>,
+    pub files: Vec<EncryptedFile>,
+    pub translation: Vec<EncryptedFile>,
+}
+
+fn pair_files(files: Option<&Vec<PassportFile>>, creds: &[FileCredentials]) -> Vec<EncryptedFile> {
+    files
+        .iter()
+        .flat_map(|f| f.iter())
+        .zip(creds.iter())
+        .map(|(file, credentials)| EncryptedFile {
+            file: file.clone(),
+            credentials: credentials.clone(),
+        })
+        .collect()
+}
+
+fn pair_file(
+    file: Option<&PassportFile>,
+    creds: &Option<FileCredentials>,
+) -> Option<EncryptedFile> {
+    match (file, creds) {
+        (Some(file), Some(credentials)) => Some(EncryptedFile {
+            file: file.clone(),
+            credentials: credentials.clone(),
+        }),
+        _ => None,
+    }
+}
+
+impl DecryptedElement {
+    fn new(element: &EncryptedPassportElement, value: Option<&SecureValue>) -> Result<Self> {
+        let element_type = element.get_tg_type().to_owned();
+        let default = SecureValue::default();
+        let value = value.unwrap_or(&default);
+        let data = match (element.get_data(), value.data.as_ref()) {
+            (Some(data), Some(creds)) => {
+                let data = decrypt_data(
+                    &decode(data)?,
+                    &decode(&creds.secret)?,
+                    &decode(&creds.data_hash)?,
+                )?;
+                Some(DecryptedData::parse(&element_type, &data)?)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            data,
+            phone_number: element.get_phone_number().map(|v| v.to_owned()),
+            email: element.get_email().map(|v| v.to_owned()),
+            front_side: pair_file(element.get_front_side(), &value.front_side),
+            reverse_side: pair_file(element.get_reverse_side(), &value.reverse_side),
+            selfie: pair_file(element.get_selfie(), &value.selfie),
+            files: pair_files(element.get_files(), &value.files),
+            translation: pair_files(element.get_translation(), &value.translation),
+            element_type,
+        })
+    }
+}
+
+/// Fully decrypted telegram passport data
+#[derive(Debug, Clone)]
+pub struct DecryptedPassport {
+    pub nonce: String,
+    pub elements: Vec<DecryptedElement>,
+}
+
+impl PassportData {
+    /// Decrypt the credentials for this passport data with the bot's private key
+    pub fn decrypt_credentials(&self, key: &PassportKey) -> Result<Credentials> {
+        let credentials = self.get_credentials();
+        let secret = key
+            .0
+            .decrypt(
+                Oaep::new::<sha1::Sha1>(),
+                &decode(credentials.get_secret())?,
+            )
+            .map_err(|e| anyhow!("failed to decrypt passport secret: {}", e))?;
+        let data = decrypt_data(
+            &decode(credentials.get_data())?,
+            &secret,
+            &decode(credentials.get_hash())?,
+        )?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Decrypt all elements of this passport data with the bot's private key
+    pub fn decrypt(&self, key: &PassportKey) -> Result<DecryptedPassport> {
+        let credentials = self.decrypt_credentials(key)?;
+        let elements = self
+            .get_data()
+            .iter()
+            .map(|element| {
+                DecryptedElement::new(element, credentials.secure_data.get(element.get_tg_type()))
+            })
+            .collect::<Result<Vec<DecryptedElement>>>()?;
+        Ok(DecryptedPassport {
+            nonce: credentials.nonce,
+            elements,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cbc::cipher::BlockEncryptMut;
+
+    type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+
+    #[test]
+    fn decrypt_roundtrip() {
+        let payload = br#"{"document_no":"1234567890abcde"}"#;
+        let mut data = vec![0u8; 32];
+        data[0] = 32;
+        data.extend_from_slice(payload);
+        while data.len() % 16 != 0 {
+            data.insert(1, 0);
+            data[0] += 1;
+        }
+        let hash = Sha256::digest(&data).to_vec();
+        let secret = b"super secret passport secret".to_vec();
+
+        let mut hasher = Sha512::new();
+        hasher.update(&secret);
+        hasher.update(&hash);
+        let secret_hash = hasher.finalize();
+        let len = data.len();
+        let encrypted = Aes256CbcEnc::new_from_slices(&secret_hash[0..32], &secret_hash[32..48])
+            .unwrap()
+            .encrypt_padded_mut::<NoPadding>(&mut data, len)
+            .unwrap()
+            .to_vec();
+
+        let decrypted = decrypt_data(&encrypted, &secret, &hash).unwrap();
+        assert_eq!(decrypted, payload);
+        match DecryptedData::parse("passport", &decrypted).unwrap() {
+            DecryptedData::IdDocument(doc) => {
+                assert_eq!(doc.document_no.as_deref(), Some("1234567890abcde"))
+            }
+            _ => panic!("wrong element type"),
+        }
+    }
+
+    #[test]
+    fn decrypt_bad_hash() {
+        let data = vec![0u8; 32];
+        assert!(decrypt_data(&data, b"secret", &[0u8; 32]).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{ChatHandle, MaybeInaccessibleMessage, Message, MsgId};
+
+#[derive(Debug, Clone, Copy)]
+struct Pinned {
+    message_id: MsgId,
+    delete_service: bool,
+}
+
+/// Messages pinned with Bot::replace_pinned or Channel::pin per chat, shared by all
+/// clones of a bot
+#[derive(Debug, Default)]
+pub(crate) struct PinRegistry {
+    pinned: Mutex<HashMap<ChatHandle, Pinned>>,
+}
+
+impl PinRegistry {
+    /// Get the message last pinned in a chat through the registry
+    pub(crate) fn get(&self, chat: &ChatHandle) -> Option<MsgId> {
+        self.pinned.lock().unwrap().get(chat).map(|p| p.message_id)
+    }
+}
+
+impl Bot {
+    /// Pin a message in place of the message currently pinned, returning the id of the
+    /// unpinned message. The current pin is the last one made with this method, or
+    /// fetched with get_chat the first time for a chat. Other pinned messages are left
+    /// alone. If delete_service is set the "pinned a message" service message is deleted
+    /// once it arrives, which requires passing messages to Bot::delete_pin_service
+    pub async fn replace_pinned(
+        &self,
+        chat_id: i64,
+        message_id: MsgId,
+        delete_service: bool,
+    ) -> BotResult<Option<MsgId>> {
+        let chat = ChatHandle::ChatId(chat_id);
+        let previous = match self.pins().get(&chat) {
+            Some(id) => Some(id),
+            None => self
+                .build_get_chat(chat_id)
+                .build()
+                .await?
+                .get_pinned_message()
+                .map(|m| m.get_message_id()),
+        };
+        let previous = previous.filter(|id| *id != message_id);
+        self.pin_tracked(chat, message_id, previous, None, delete_service)
+            .await?;
+        Ok(previous)
+    }
+
+    /// Unpin previous if set, then pin a message and track it as the current pin of
+    /// the chat
+    pub(crate) async fn pin_tracked(
+        &self,
+        chat: ChatHandle,
+        message_id: MsgId,
+        previous: Option<MsgId>,
+        silent: Option<bool>,
+        delete_service: bool,
+    ) -> BotResult<()> {
+        if let Some(previous) = previous {
+            match self
+                .build_unpin_chat_message(chat.clone())
+                .message_id(previous)
+                .build()
+                .await
+            {
+                // The old pin was deleted or unpinned by someone else
+                Err(err) if err.is_not_found() => (),
+                res => {
+                    res?;
+                }
+            }
+        }
+        let mut pin = self.build_pin_chat_message(chat.clone(), message_id);
+        if let Some(silent) = silent {
+            pin = pin.disable_notification(silent);
+        }
+        pin.build().await?;
+        self.pins().pinned.lock().unwrap().insert(
+            chat,
+            Pinned {
+                message_id,
+                delete_service,
+            },
+        );
+        Ok(())
+    }
+
+    /// Delete a "pinned a message" service message if it is for a pin made with
+    /// replace_pinned and delete_service set. Returns true if the message was deleted.
+    /// Call this with incoming messages, channel posts included
+    pub async fn delete_pin_service(&self, message: &Message) -> BotResult<bool> {
+        let pinned = match message.get_pinned_message() {
+            Some(MaybeInaccessibleMessage::Message(m)) => m.get_message_id(),
+            Some(MaybeInaccessibleMessage::InaccessibleMessage(m)) => m.get_message_id(),
+            None => return Ok(false),
+        };
+        let chat_id = message.get_chat().get_id();
+        let delete = self
+            .pins()
+            .pinned
+            .lock()
+            .unwrap()
+            .get(&ChatHandle::ChatId(chat_id))
+            .is_some_and(|p| p.delete_service && p.message_id == pinned);
+        if !delete {
+            return Ok(false);
+        }
+        self.build_delete_message(chat_id, message.get_message_id())
+            .build()
+            .await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn replaces_pins() {
+        let test = TestBot::builder()
+            .respond(
+                "getChat",
+                json!({
+                    "id": -100,
+                    "type": "channel",
+                    "accent_color_id": 0,
+                    "max_reaction_count": 11,
+                    "pinned_message": {"message_id": 3, "date": 0, "chat": {"id": -100, "type": "channel"}},
+                }),
+            )
+            .build()
+            .await
+            .unwrap();
+        let bot = test.get_bot();
+        assert_eq!(
+            bot.replace_pinned(-100, MsgId::from(5), true)
+                .await
+                .unwrap(),
+            Some(MsgId::from(3))
+        );
+        assert_eq!(
+            bot.replace_pinned(-100, MsgId::from(6), true)
+                .await
+                .unwrap(),
+            Some(MsgId::from(5))
+        );
+        assert_eq!(test.sent("getChat").len(), 1);
+        crate::assert_sent!(test, method = "unpinChatMessage", message_id = 5);
+
+        let service: Message = serde_json::from_value(json!({
+            "message_id": 7,
+            "date": 0,
+            "chat": {"id": -100, "type": "channel"},
+            "pinned_message": {"message_id": 6, "date": 0, "chat": {"id": -100, "type": "channel"}},
+        }))
+        .unwrap();
+        assert!(bot.delete_pin_service(&service).await.unwrap());
+        crate::assert_sent!(test, method = "deleteMessage", message_id = 7);
+    }
+}
@@ -0,0 +1,209 @@
+use std::future::Future;
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_methods::{
+    GetMyDescriptionParams, GetMyNameParams, GetMyShortDescriptionParams, SetMyDescriptionParams,
+    SetMyNameParams, SetMyShortDescriptionParams,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct LocalizedProfile {
+    language_code: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    short_description: Option<String>,
+}
+
+/// Check if a value of a language needs to be set, getting the current value with get.
+/// Telegram returns the value of the default language for languages without one, so an
+/// empty value, which removes the value for a language, is already in place when the
+/// current value is the default language's
+async fn differs<F, Fut>(desired: &str, language_code: &Option<String>, get: F) -> BotResult<bool>
+where
+    F: Fn(Option<String>) -> Fut,
+    Fut: Future<Output = BotResult<String>>,
+{
+    let current = get(language_code.clone()).await?;
+    if current == desired {
+        return Ok(false);
+    }
+    if desired.is_empty() && language_code.is_some() {
+        return Ok(current != get(None).await?);
+    }
+    Ok(true)
+}
+
+/// Declarative management of the bot's name, description and short description. The
+/// desired values for each language are compared against getMyName, getMyDescription and
+/// getMyShortDescription, and only values that differ are set. Values that are never
+/// set here are left alone. An empty string removes the value for a language so the
+/// default is shown
+///
+/// ```no_run
+/// # use botapi::{bot::BotBuilder, profile::BotProfile};
+/// # tokio_test::block_on(async {
+/// # let bot = BotBuilder::new("sometoken").unwrap().build();
+/// BotProfile::new()
+///     .name(None, "Weather Bot")
+///     .short_description(None, "Forecasts for your city")
+///     .name(Some("de"), "Wetter Bot")
+///     .sync(&bot)
+///     .await
+///     .unwrap();
+/// # })
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BotProfile {
+    languages: Vec<LocalizedProfile>,
+}
+
+impl BotProfile {
+    /// Create a profile managing nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn language(&mut self, language_code: Option<&str>) -> &mut LocalizedProfile {
+        let language_code = language_code.map(|v| v.to_owned());
+        match self
+            .languages
+            .iter()
+            .position(|l| l.language_code == language_code)
+        {
+            Some(index) => &mut self.languages[index],
+            None => {
+                self.languages.push(LocalizedProfile {
+                    language_code,
+                    ..Default::default()
+                });
+                self.languages.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Set the desired name for an optional language
+    pub fn name<T: Into<String>>(mut self, language_code: Option<&str>, name: T) -> Self {
+        self.language(language_code).name = Some(name.into());
+        self
+    }
+
+    /// Set the desired description, shown in empty chats with the bot, for an optional
+    /// language
+    pub fn description<T: Into<String>>(
+        mut self,
+        language_code: Option<&str>,
+        description: T,
+    ) -> Self {
+        self.language(language_code).description = Some(description.into());
+        self
+    }
+
+    /// Set the desired short description, shown on the bot's profile page and when
+    /// sharing it, for an optional language
+    pub fn short_description<T: Into<String>>(
+        mut self,
+        language_code: Option<&str>,
+        short_description: T,
+    ) -> Self {
+        self.language(language_code).short_description = Some(short_description.into());
+        self
+    }
+
+    /// Sync all languages with telegram, returning the number of values that were changed
+    pub async fn sync(&self, bot: &Bot) -> BotResult<usize> {
+        let mut changed = 0;
+        for profile in self.languages.iter() {
+            let language_code = profile.language_code.clone();
+            if let Some(ref name) = profile.name {
+                let get = |language_code| async move {
+                    let current = bot.call(GetMyNameParams { language_code }).await?;
+                    Ok(current.get_name().to_owned())
+                };
+                if differs(name, &language_code, get).await? {
+                    bot.call(SetMyNameParams {
+                        name: Some(name.clone()),
+                        language_code: language_code.clone(),
+                    })
+                    .await?;
+                    changed += 1;
+                }
+            }
+            if let Some(ref description) = profile.description {
+                let get = |language_code| async move {
+                    let current = bot.call(GetMyDescriptionParams { language_code }).await?;
+                    Ok(current.get_description().to_owned())
+                };
+                if differs(description, &language_code, get).await? {
+                    bot.call(SetMyDescriptionParams {
+                        description: Some(description.clone()),
+                        language_code: language_code.clone(),
+                    })
+                    .await?;
+                    changed += 1;
+                }
+            }
+            if let Some(ref short_description) = profile.short_description {
+                let get = |language_code| async move {
+                    let current = bot
+                        .call(GetMyShortDescriptionParams { language_code })
+                        .await?;
+                    Ok(current.get_short_description().to_owned())
+                };
+                if differs(short_description, &language_code, get).await? {
+                    bot.call(SetMyShortDescriptionParams {
+                        short_description: Some(short_description.clone()),
+                        language_code: language_code.clone(),
+                    })
+                    .await?;
+                    changed += 1;
+                }
+            }
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn sync_skips_unchanged() {
+        let test = TestBot::builder()
+            .respond("getMyName", json!({"name": "Old"}))
+            .respond("getMyDescription", json!({"description": "Same"}))
+            .build()
+            .await
+            .unwrap();
+        let changed = BotProfile::new()
+            .name(None, "New")
+            .description(None, "Same")
+            .sync(test.get_bot())
+            .await
+            .unwrap();
+        assert_eq!(changed, 1);
+        crate::assert_sent!(test, method = "setMyName", name = "New");
+        assert!(test.sent("setMyDescription").is_empty());
+        assert!(test.sent("getMyShortDescription").is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_matches_default() {
+        let test = TestBot::builder()
+            .respond("getMyDescription", json!({"description": "Weather"}))
+            .build()
+            .await
+            .unwrap();
+        let changed = BotProfile::new()
+            .description(Some("de"), "")
+            .sync(test.get_bot())
+            .await
+            .unwrap();
+        assert_eq!(changed, 0);
+        let sent = test.sent("getMyDescription");
+        assert_eq!(sent[0].get("language_code"), Some("de"));
+        assert!(sent[1].get("language_code").is_none());
+    }
+}
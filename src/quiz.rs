@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures_util::future::BoxFuture;
+use tokio::sync::Notify;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{Dispatcher, Flow, Layer};
+use crate::ext::now;
+use crate::gen_methods::StopPollParams;
+use crate::gen_types::{InputPollOption, PollAnswer, UpdateExt, UserId};
+use crate::scheduler::Scheduler;
+
+/// Default time each question stays open
+pub const DEFAULT_OPEN_PERIOD: Duration = Duration::from_secs(30);
+
+/// Shortest time a question can stay open, the lower bound telegram has for open_period
+pub const MIN_OPEN_PERIOD: Duration = Duration::from_secs(5);
+
+/// Longest time a question can stay open, the upper bound telegram has for open_period
+pub const MAX_OPEN_PERIOD: Duration = Duration::from_secs(600);
+
+/// A quiz question with one correct option
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    text: String,
+    options: Vec<String>,
+    correct: usize,
+    explanation: Option<String>,
+}
+
+impl Question {
+    /// Create a question with options, where correct is the index of the right one
+    pub fn new<T, I, O>(text: T, options: I, correct: usize) -> Self
+    where
+        T: Into<String>,
+        I: IntoIterator<Item = O>,
+        O: Into<String>,
+    {
+        Self {
+            text: text.into(),
+            options: options.into_iter().map(|o| o.into()).collect(),
+            correct,
+            explanation: None,
+        }
+    }
+
+    /// Show an explanation to users who answer wrong
+    pub fn explanation<T: Into<String>>(mut self, explanation: T) -> Self {
+        self.explanation = Some(explanation.into());
+        self
+    }
+
+    /// Get the text of the question
+    pub fn get_text(&self) -> &'_ str {
+        &self.text
+    }
+
+    /// Get the index of the correct option
+    pub fn get_correct(&self) -> usize {
+        self.correct
+    }
+}
+
+/// Result of one user in a quiz
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Score {
+    user: UserId,
+    name: String,
+    correct: usize,
+    answered: usize,
+}
+
+impl Score {
+    /// Get the user
+    pub fn get_user(&self) -> UserId {
+        self.user
+    }
+
+    /// Get the first name of the user
+    pub fn get_name(&self) -> &'_ str {
+        &self.name
+    }
+
+    /// Get the number of questions answered correctly
+    pub fn get_correct(&self) -> usize {
+        self.correct
+    }
+
+    /// Get the number of questions answered
+    pub fn get_answered(&self) -> usize {
+        self.answered
+    }
+}
+
+/// Scores of everyone who answered, best first. Ties are ordered by user id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuizSummary {
+    questions: usize,
+    scores: Vec<Score>,
+}
+
+impl QuizSummary {
+    /// Get the number of questions asked
+    pub fn get_questions(&self) -> usize {
+        self.questions
+    }
+
+    /// Get the scores, best first
+    pub fn get_scores(&self) -> &'_ [Score] {
+        &self.scores
+    }
+
+    /// Format the scores as a leaderboard, one line per user
+    pub fn to_text(&self) -> String {
+        self.scores
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("{}. {}: {}/{}", i + 1, s.name, s.correct, self.questions))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Default)]
+struct QuizState {
+    chat: Option<i64>,
+    scheduler: Option<Scheduler>,
+    current: Option<String>,
+    polls: HashMap<String, usize>,
+    names: HashMap<UserId, String>,
+    answers: HashMap<UserId, HashMap<usize, i64>>,
+}
+
+/// Runs a quiz in a chat as a series of quiz polls, one at a time. Each poll is closed
+/// by a stopPoll job on a Scheduler after the open period, so a restart doesn't leave it
+/// open, and the next question is sent once telegram reports the poll closed. Answers
+/// are collected from poll_answer updates, so register the session with Dispatcher::quiz
+/// before starting it and make sure poll and poll_answer updates are received. Polls are
+/// sent as non anonymous, otherwise telegram sends no answers. Cloning is cheap and
+/// clones share answers
+#[derive(Clone)]
+pub struct QuizSession {
+    questions: Arc<[Question]>,
+    open_period: Duration,
+    state: Arc<Mutex<QuizState>>,
+    registered: Arc<AtomicBool>,
+    finished: Arc<Notify>,
+}
+
+impl std::fmt::Debug for QuizSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuizSession")
+            .field("questions", &self.questions)
+            .field("open_period", &self.open_period)
+            .field("registered", &self.registered)
+            .finish()
+    }
+}
+
+impl QuizSession {
+    /// Create a session asking questions in order
+    pub fn new<I: IntoIterator<Item = Question>>(questions: I) -> Self {
+        Self {
+            questions: questions.into_iter().collect(),
+            open_period: DEFAULT_OPEN_PERIOD,
+            state: Arc::new(Mutex::new(QuizState::default())),
+            registered: Arc::new(AtomicBool::new(false)),
+            finished: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Change how long each question stays open, clamped to the 5 to 600 seconds
+    /// telegram allows
+    pub fn open_period(mut self, open_period: Duration) -> Self {
+        self.open_period = open_period.clamp(MIN_OPEN_PERIOD, MAX_OPEN_PERIOD);
+        self
+    }
+
+    /// Get how long each question stays open
+    pub fn get_open_period(&self) -> Duration {
+        self.open_period
+    }
+
+    /// Check if a quiz is currently running
+    pub fn is_running(&self) -> bool {
+        self.state.lock().unwrap().chat.is_some()
+    }
+
+    /// Start asking the questions in a chat, closing each with a job on scheduler. Fails
+    /// if the session isn't registered with a dispatcher or is already running
+    pub async fn start(&self, bot: &Bot, scheduler: &Scheduler, chat: i64) -> BotResult<()> {
+        if !self.registered.load(Ordering::Relaxed) {
+            return Err(
+                anyhow!("register the quiz with Dispatcher::quiz before starting it").into(),
+            );
+        }
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.chat.is_some() {
+                return Err(anyhow!("quiz is already running").into());
+            }
+            state.chat = Some(chat);
+            state.scheduler = Some(scheduler.clone());
+        }
+        if let Err(err) = self.ask(bot, 0).await {
+            self.finish();
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Wait until the running quiz is finished, returning the scores
+    pub async fn wait(&self) -> QuizSummary {
+        loop {
+            let finished = self.finished.notified();
+            tokio::pin!(finished);
+            finished.as_mut().enable();
+            if !self.is_running() {
+                return self.summary();
+            }
+            finished.await;
+        }
+    }
+
+    /// Send a question and schedule closing it
+    async fn ask(&self, bot: &Bot, index: usize) -> BotResult<()> {
+        let (chat, scheduler) = {
+            let state = self.state.lock().unwrap();
+            match (state.chat, state.scheduler.clone()) {
+                (Some(chat), Some(scheduler)) => (chat, scheduler),
+                _ => return Ok(()),
+            }
+        };
+        let question = &self.questions[index];
+        let options = question
+            .options
+            .iter()
+            .map(|o| InputPollOption::new(o.clone()))
+            .collect::<Vec<_>>();
+        let mut poll = bot
+            .build_send_poll(chat, &question.text, &options)
+            .tg_type("quiz")
+            .is_anonymous(false)
+            .correct_option_id(question.correct as i64);
+        if let Some(ref explanation) = question.explanation {
+            poll = poll.explanation(explanation);
+        }
+        let message = poll.build().await?;
+        if let Some(poll) = message.get_poll() {
+            let mut state = self.state.lock().unwrap();
+            let id = poll.get_id().to_owned();
+            state.polls.insert(id.clone(), index);
+            state.current = Some(id);
+        }
+        let stop = StopPollParams {
+            chat_id: chat.into(),
+            message_id: message.get_message_id(),
+            ..Default::default()
+        };
+        scheduler
+            .schedule_at(&stop, now() + self.open_period.as_secs() as i64)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark the quiz as finished and wake up anyone waiting for it
+    fn finish(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.chat = None;
+            state.scheduler = None;
+            state.current = None;
+        }
+        self.finished.notify_waiters();
+    }
+
+    /// Handle a poll closing, sending the next question or finishing after the last one.
+    /// Returns false if the poll isn't the open question of this session
+    async fn closed(&self, bot: &Bot, poll_id: &str) -> BotResult<bool> {
+        let next = {
+            let mut state = self.state.lock().unwrap();
+            if state.current.as_deref() != Some(poll_id) {
+                return Ok(false);
+            }
+            state.current = None;
+            state.polls.get(poll_id).map(|index| index + 1)
+        };
+        match next {
+            Some(next) if next < self.questions.len() => {
+                if let Err(err) = self.ask(bot, next).await {
+                    self.finish();
+                    return Err(err);
+                }
+            }
+            _ => self.finish(),
+        }
+        Ok(true)
+    }
+
+    /// Record an answer, returning false if it isn't for a poll of this session
+    fn record(&self, answer: &PollAnswer) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(&index) = state.polls.get(answer.get_poll_id()) else {
+            return false;
+        };
+        let (Some(user), Some(&option)) = (answer.get_user(), answer.get_option_ids().first())
+        else {
+            return true;
+        };
+        let id = user.get_id();
+        state.names.insert(id, user.get_first_name().to_owned());
+        state
+            .answers
+            .entry(id)
+            .or_default()
+            .entry(index)
+            .or_insert(option);
+        true
+    }
+
+    /// Get the scores from the answers received so far
+    pub fn summary(&self) -> QuizSummary {
+        let state = self.state.lock().unwrap();
+        let mut scores = state
+            .answers
+            .iter()
+            .map(|(&user, answers)| Score {
+                user,
+                name: state.names.get(&user).cloned().unwrap_or_default(),
+                correct: answers
+                    .iter()
+                    .filter(|(&q, &a)| self.questions[q].correct as i64 == a)
+                    .count(),
+                answered: answers.len(),
+            })
+            .collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.correct.cmp(&a.correct).then(a.user.cmp(&b.user)));
+        QuizSummary {
+            questions: self.questions.len(),
+            scores,
+        }
+    }
+}
+
+/// Layer routing poll updates to a registered QuizSession
+struct QuizLayer(QuizSession);
+
+impl Layer for QuizLayer {
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let session = self.0.clone();
+        Box::pin(async move {
+            let handled = match update {
+                UpdateExt::PollAnswer(ref answer) => session.record(answer),
+                UpdateExt::Poll(ref poll) if poll.get_is_closed() => {
+                    session.closed(&bot, poll.get_id()).await?
+                }
+                _ => false,
+            };
+            Ok(if handled { Flow::Stop } else { Flow::Continue })
+        })
+    }
+}
+
+impl Dispatcher {
+    /// Add a layer collecting answers and advancing a quiz session. Sessions have to be
+    /// registered before they are started
+    pub fn quiz(self, session: &QuizSession) -> Self {
+        session.registered.store(true, Ordering::Relaxed);
+        self.layer(QuizLayer(session.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn answer(poll: &str, user: i64, option: i64) -> PollAnswer {
+        serde_json::from_value(json!({
+            "poll_id": poll,
+            "user": {"id": user, "is_bot": false, "first_name": format!("user{}", user)},
+            "option_ids": [option],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn scores_answers() {
+        let session = QuizSession::new([
+            Question::new("2 + 2", ["3", "4"], 1),
+            Question::new("Capital of France", ["Paris", "Rome"], 0).explanation("Paris"),
+        ]);
+        {
+            let mut state = session.state.lock().unwrap();
+            state.polls.insert("a".to_owned(), 0);
+            state.polls.insert("b".to_owned(), 1);
+        }
+        assert!(session.record(&answer("a", 1, 1)));
+        assert!(session.record(&answer("b", 1, 0)));
+        assert!(session.record(&answer("a", 2, 0)));
+        assert!(session.record(&answer("a", 2, 1)));
+        assert!(!session.record(&answer("other", 2, 0)));
+
+        let summary = session.summary();
+        assert_eq!(summary.get_scores()[0].get_correct(), 2);
+        assert_eq!(summary.get_scores()[1].get_correct(), 0);
+        assert_eq!(summary.get_scores()[1].get_answered(), 1);
+        assert_eq!(summary.to_text(), "1. user1: 2/2\n2. user2: 0/2");
+        let session = session.open_period(Duration::from_secs(3600));
+        assert_eq!(session.get_open_period(), MAX_OPEN_PERIOD);
+    }
+
+    fn poll_message(id: &str) -> serde_json::Value {
+        json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": 5, "type": "private"},
+            "poll": {
+                "id": id,
+                "question": "question",
+                "options": [],
+                "total_voter_count": 0,
+                "is_closed": false,
+                "is_anonymous": false,
+                "type": "quiz",
+                "allows_multiple_answers": false,
+            },
+        })
+    }
+
+    fn closed(id: &str) -> UpdateExt {
+        let mut poll = poll_message(id)["poll"].clone();
+        poll["is_closed"] = json!(true);
+        let update = json!({"update_id": 1, "poll": poll});
+        serde_json::from_value::<crate::gen_types::Update>(update)
+            .unwrap()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn runs_on_scheduler() {
+        let test = crate::testing::TestBot::builder()
+            .respond("sendPoll", poll_message("a"))
+            .respond("sendPoll", poll_message("b"))
+            .build()
+            .await
+            .unwrap();
+        let bot = test.get_bot();
+        let scheduler = Scheduler::in_memory(bot.clone());
+        let session = QuizSession::new([
+            Question::new("2 + 2", ["3", "4"], 1),
+            Question::new("Capital of France", ["Paris", "Rome"], 0),
+        ]);
+        assert!(session.start(bot, &scheduler, 5).await.is_err());
+
+        let dispatcher = Dispatcher::new().quiz(&session);
+        session.start(bot, &scheduler, 5).await.unwrap();
+        assert!(session.start(bot, &scheduler, 5).await.is_err());
+        assert_eq!(
+            scheduler.get_jobs()[0].get_request().get_method(),
+            "stopPoll"
+        );
+        test.dispatch(&dispatcher, closed("a")).await;
+        assert_eq!(test.sent("sendPoll").len(), 2);
+        assert!(session.is_running());
+        test.dispatch(&dispatcher, closed("b")).await;
+        assert!(!session.is_running());
+        assert_eq!(session.wait().await.get_questions(), 2);
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::Dispatcher;
+use crate::filter::Filter;
+use crate::gen_types::{ChatType, MaybeInaccessibleMessage, UpdateExt, UserId};
+
+/// Level of access a user has, ordered from least to most trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    /// Any user
+    Everyone,
+    /// A user assigned the trusted role
+    Trusted,
+    /// An administrator of the chat, or a user assigned the admin role
+    Admin,
+    /// The creator of the chat, or a user assigned the owner role
+    Owner,
+}
+
+/// Get the chat an update happened in and the user who sent it
+fn sender(update: &UpdateExt) -> Option<(Option<(i64, ChatType)>, UserId)> {
+    match update {
+        UpdateExt::Message(m) | UpdateExt::EditedMessage(m) => {
+            let chat = m.get_chat();
+            let chat = chat.get_chat_type().map(|t| (chat.get_id(), t));
+            Some((chat, m.get_from()?.get_id()))
+        }
+        UpdateExt::CallbackQuery(q) => {
+            let chat = match q.get_message() {
+                Some(MaybeInaccessibleMessage::Message(m)) => Some(m.get_chat()),
+                Some(MaybeInaccessibleMessage::InaccessibleMessage(m)) => Some(m.get_chat()),
+                None => None,
+            };
+            let chat = chat.and_then(|c| c.get_chat_type().map(|t| (c.get_id(), t)));
+            Some((chat, q.get_from().get_id()))
+        }
+        UpdateExt::InlineQuery(q) => Some((None, q.get_from().get_id())),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
+struct RolesInner {
+    assigned: HashMap<UserId, Role>,
+    chat_roles: bool,
+    rejection: Option<String>,
+}
+
+/// Role assignments for gating handlers. Users get the highest of their assigned role
+/// and, in groups, the role given by their chat membership, resolved with
+/// get_chat_member. Enable BotBuilder::cache to avoid a call for every update
+#[derive(Clone)]
+pub struct Roles(Arc<RolesInner>);
+
+impl std::fmt::Debug for Roles {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Roles")
+            .field("assigned", &self.0.assigned)
+            .field("chat_roles", &self.0.chat_roles)
+            .field("rejection", &self.0.rejection)
+            .finish()
+    }
+}
+
+impl Default for Roles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Roles {
+    /// Create role assignments resolving chat administrators and without a rejection
+    /// message
+    pub fn new() -> Self {
+        Self(Arc::new(RolesInner {
+            assigned: HashMap::new(),
+            chat_roles: true,
+            rejection: None,
+        }))
+    }
+
+    fn inner(&mut self) -> &mut RolesInner {
+        Arc::make_mut(&mut self.0)
+    }
+
+    /// Give a user a role in every chat
+    pub fn assign(mut self, user: UserId, role: Role) -> Self {
+        self.inner().assigned.insert(user, role);
+        self
+    }
+
+    /// Set if chat owners and administrators get the Owner and Admin roles
+    pub fn chat_roles(mut self, chat_roles: bool) -> Self {
+        self.inner().chat_roles = chat_roles;
+        self
+    }
+
+    /// Reply to unauthorized users with a message, or an alert for button presses.
+    /// Unauthorized updates are ignored silently without one
+    pub fn rejection<T: Into<String>>(mut self, rejection: T) -> Self {
+        self.inner().rejection = Some(rejection.into());
+        self
+    }
+
+    /// Get the role of a user, in a group if one is given
+    pub async fn role_of(&self, bot: &Bot, chat: Option<i64>, user: UserId) -> BotResult<Role> {
+        let assigned = self
+            .0
+            .assigned
+            .get(&user)
+            .copied()
+            .unwrap_or(Role::Everyone);
+        let member = match chat {
+            Some(chat) if self.0.chat_roles && assigned < Role::Owner => {
+                let member = bot.get_chat_member_cached(chat, user).await?;
+                if member.is_owner() {
+                    Role::Owner
+                } else if member.is_admin() {
+                    Role::Admin
+                } else {
+                    Role::Everyone
+                }
+            }
+            _ => Role::Everyone,
+        };
+        Ok(assigned.max(member))
+    }
+
+    /// Get the role of the sender of an update, Everyone for updates without a sender
+    pub async fn role_for(&self, bot: &Bot, update: &UpdateExt) -> BotResult<Role> {
+        let Some((chat, user)) = sender(update) else {
+            return Ok(Role::Everyone);
+        };
+        let chat = chat
+            .filter(|(_, t)| matches!(t, ChatType::Group | ChatType::Supergroup))
+            .map(|(id, _)| id);
+        self.role_of(bot, chat, user).await
+    }
+
+    /// Check if the sender of an update has at least a role. The chat membership is only
+    /// fetched if the role isn't Everyone and the assigned role of the sender is lower
+    pub async fn has_role(&self, bot: &Bot, update: &UpdateExt, role: Role) -> BotResult<bool> {
+        if role == Role::Everyone {
+            return Ok(true);
+        }
+        let assigned = sender(update).and_then(|(_, user)| self.0.assigned.get(&user).copied());
+        if assigned.is_some_and(|assigned| assigned >= role) {
+            return Ok(true);
+        }
+        Ok(self.role_for(bot, update).await? >= role)
+    }
+
+    /// Match updates from users with at least a role
+    pub fn filter(&self, role: Role) -> Filter {
+        if role == Role::Everyone {
+            return Filter::new(|_| true);
+        }
+        let roles = self.clone();
+        Filter::new_async(move |bot: Bot, update: UpdateExt| {
+            let roles = roles.clone();
+            async move { roles.has_role(&bot, &update, role).await }
+        })
+    }
+
+    /// Send the rejection message for an update, if there is one
+    async fn reject(&self, bot: &Bot, update: &UpdateExt) -> BotResult<()> {
+        let Some(ref text) = self.0.rejection else {
+            return Ok(());
+        };
+        match update {
+            UpdateExt::Message(m) => {
+                bot.build_send_message(m.get_chat().get_id(), text)
+                    .build()
+                    .await?;
+            }
+            UpdateExt::CallbackQuery(q) => {
+                bot.build_answer_callback_query(q.get_id())
+                    .text(text)
+                    .show_alert(true)
+                    .build()
+                    .await?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl Dispatcher {
+    /// Add a handler called for updates passing a filter from users with at least a
+    /// role. Updates passing the filter from other users get the rejection message of
+    /// the roles, updates not passing it are ignored
+    pub fn with_role<F, Fut>(self, roles: Roles, role: Role, filter: Filter, handler: F) -> Self
+    where
+        F: Fn(Bot, UpdateExt) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.auto_matching("with_role", move |bot: Bot, update: UpdateExt| {
+            let roles = roles.clone();
+            let filter = filter.clone();
+            let handler = Arc::clone(&handler);
+            async move {
+                if sender(&update).is_none() || !filter.check(&bot, &update).await? {
+                    return Ok(false);
+                }
+                if roles.has_role(&bot, &update, role).await? {
+                    handler(bot, update).await?;
+                } else {
+                    roles.reject(&bot, &update).await?;
+                }
+                Ok(true)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn assigned_roles() {
+        let bot = crate::bot::BotBuilder::new("1234:token").unwrap().build();
+        let roles = Roles::new()
+            .assign(UserId::from(1), Role::Owner)
+            .assign(UserId::from(2), Role::Trusted)
+            .rejection("not allowed");
+        let role = roles.role_of(&bot, None, UserId::from(2)).await.unwrap();
+        assert_eq!(role, Role::Trusted);
+        let role = roles
+            .role_of(&bot, Some(-100), UserId::from(1))
+            .await
+            .unwrap();
+        assert_eq!(role, Role::Owner);
+        let role = roles.role_for(&bot, &UpdateExt::Invalid).await.unwrap();
+        assert_eq!(role, Role::Everyone);
+        assert!(Role::Admin > Role::Trusted);
+    }
+
+    #[tokio::test]
+    async fn skips_member_lookup() {
+        let test = crate::testing::TestBot::new().await.unwrap();
+        let roles = Roles::new().assign(UserId::from(2), Role::Admin);
+        let message = |from: i64| {
+            let update = serde_json::json!({
+                "update_id": from,
+                "message": {
+                    "message_id": 1,
+                    "date": 0,
+                    "chat": {"id": -100, "type": "supergroup"},
+                    "from": {"id": from, "is_bot": false, "first_name": "Test"},
+                    "text": "hi",
+                }
+            });
+            UpdateExt::from(serde_json::from_value::<crate::gen_types::Update>(update).unwrap())
+        };
+        let bot = test.get_bot();
+        assert!(roles
+            .has_role(bot, &message(5), Role::Everyone)
+            .await
+            .unwrap());
+        assert!(roles.has_role(bot, &message(2), Role::Admin).await.unwrap());
+        assert!(!roles.filter(Role::Everyone).is_async());
+        assert!(test.sent("getChatMember").is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_matching_updates() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let test = crate::testing::TestBot::new().await.unwrap();
+        let roles = Roles::new()
+            .assign(UserId::from(1), Role::Trusted)
+            .rejection("not allowed");
+        let called = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&called);
+        let dispatcher = Dispatcher::new().with_role(
+            roles,
+            Role::Trusted,
+            crate::filter::regex(regex::Regex::new("^/admin").unwrap()),
+            move |_: Bot, _: UpdateExt| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                async { Ok(()) }
+            },
+        );
+        test.dispatch(&dispatcher, test.text_message(2, "hello"))
+            .await;
+        assert!(test.sent("sendMessage").is_empty());
+        test.dispatch(&dispatcher, test.text_message(1, "/admin"))
+            .await;
+        assert_eq!(called.load(Ordering::Relaxed), 1);
+        assert!(test.sent("sendMessage").is_empty());
+        test.dispatch(&dispatcher, test.text_message(2, "/admin"))
+            .await;
+        assert_eq!(called.load(Ordering::Relaxed), 1);
+        crate::assert_sent!(
+            test,
+            method = "sendMessage",
+            chat_id = 2,
+            text = "not allowed"
+        );
+
+        let stats = dispatcher.stats();
+        assert_eq!(stats["with_role 0"].get_handled(), 2);
+        assert_eq!(stats["with_role 0"].get_ignored(), 1);
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::bot::{Bot, BotResult};
+use crate::gen_types::{ChatHandle, Message};
+
+/// Resolve a recipient alias from the global Recipients registry into a ChatHandle,
+/// for passing to any method taking a chat. Evaluates to an error if the alias is not
+/// registered or names a group, use Recipients::send_message to send to every chat of
+/// a group
+///
+/// ```no_run
+/// # use botapi::{alias, bot::BotBuilder, recipients::Recipients};
+/// # tokio_test::block_on(async {
+/// # let bot = BotBuilder::new("sometoken").unwrap().build();
+/// Recipients::global().alias("logs", -1001234);
+/// bot.build_send_message(alias!("logs").unwrap(), "deployed").build().await.unwrap();
+/// # })
+/// ```
+#[macro_export]
+macro_rules! alias {
+    ($name:expr) => {
+        $crate::recipients::Recipients::global().resolve($name)
+    };
+}
+
+/// A chat or list of chats in a config file, either an id or a @username
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChatDef {
+    One(ChatHandle),
+    Many(Vec<ChatHandle>),
+}
+
+impl From<ChatDef> for Vec<ChatHandle> {
+    fn from(value: ChatDef) -> Self {
+        match value {
+            ChatDef::One(chat) => vec![chat],
+            ChatDef::Many(chats) => chats,
+        }
+    }
+}
+
+/// Parse a chat from text, numbers are chat ids and anything else a username
+fn parse_chat(chat: &str) -> ChatHandle {
+    match chat.trim().parse::<i64>() {
+        Ok(id) => ChatHandle::ChatId(id),
+        Err(_) => ChatHandle::Username(chat.trim().to_owned()),
+    }
+}
+
+/// Registry of names for chats, like "logs" or "admins", so ops bots don't pass chat
+/// ids around. A name can stand for a group of chats, sending to it sends to each.
+/// Names are case insensitive. Cloning is cheap and clones share names
+#[derive(Clone, Debug, Default)]
+pub struct Recipients(Arc<RwLock<HashMap<String, Vec<ChatHandle>>>>);
+
+impl Recipients {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the registry used by the alias! macro
+    pub fn global() -> &'static Recipients {
+        static GLOBAL: OnceLock<Recipients> = OnceLock::new();
+        GLOBAL.get_or_init(Recipients::new)
+    }
+
+    /// Name a single chat, replacing anything the name stood for
+    pub fn alias<T, V>(&self, name: T, chat: V) -> &Self
+    where
+        T: AsRef<str>,
+        V: Into<ChatHandle>,
+    {
+        self.group(name, [chat])
+    }
+
+    /// Name a group of chats, replacing anything the name stood for
+    pub fn group<T, I, V>(&self, name: T, chats: I) -> &Self
+    where
+        T: AsRef<str>,
+        I: IntoIterator<Item = V>,
+        V: Into<ChatHandle>,
+    {
+        let chats = chats.into_iter().map(|c| c.into()).collect();
+        self.0
+            .write()
+            .unwrap()
+            .insert(name.as_ref().to_lowercase(), chats);
+        self
+    }
+
+    /// Remove a name, returning the chats it stood for
+    pub fn remove(&self, name: &str) -> Option<Vec<ChatHandle>> {
+        self.0.write().unwrap().remove(&name.to_lowercase())
+    }
+
+    /// Load names from environment variables starting with prefix, so that
+    /// `BOT_CHAT_LOGS=-1001234` and `BOT_CHAT_ADMINS=1,2,@ops` with prefix
+    /// `BOT_CHAT_` define "logs" and "admins". Returns the number of names loaded
+    pub fn load_env(&self, prefix: &str) -> usize {
+        let mut loaded = 0;
+        for (key, value) in std::env::vars() {
+            if let Some(name) = key.strip_prefix(prefix).filter(|n| !n.is_empty()) {
+                self.group(
+                    name,
+                    value
+                        .split(',')
+                        .filter(|c| !c.trim().is_empty())
+                        .map(parse_chat),
+                );
+                loaded += 1;
+            }
+        }
+        loaded
+    }
+
+    /// Load names from a json object mapping names to a chat or a list of chats
+    pub fn load_json(&self, json: &str) -> Result<usize> {
+        let defs: HashMap<String, ChatDef> = serde_json::from_str(json)?;
+        Ok(self.load(defs))
+    }
+
+    /// Load names from a toml table mapping names to a chat or a list of chats
+    ///
+    /// ```toml
+    /// logs = -1001234
+    /// admins = [1, 2, "@ops"]
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn load_toml(&self, source: &str) -> Result<usize> {
+        let defs: HashMap<String, ChatDef> = toml::from_str(source)?;
+        Ok(self.load(defs))
+    }
+
+    fn load(&self, defs: HashMap<String, ChatDef>) -> usize {
+        let loaded = defs.len();
+        for (name, chats) in defs {
+            self.group(name, Vec::from(chats));
+        }
+        loaded
+    }
+
+    /// Get the chats a name stands for
+    pub fn get(&self, name: &str) -> Option<Vec<ChatHandle>> {
+        self.0.read().unwrap().get(&name.to_lowercase()).cloned()
+    }
+
+    /// Get the chat a name stands for. None if the name is unknown or stands for a
+    /// group of chats
+    pub fn get_chat(&self, name: &str) -> Option<ChatHandle> {
+        self.resolve(name).ok()
+    }
+
+    /// Get the chat a name stands for, failing if the name is unknown or stands for a
+    /// group of chats rather than silently picking one of them
+    pub fn resolve(&self, name: &str) -> Result<ChatHandle> {
+        let mut chats = self
+            .get(name)
+            .ok_or_else(|| anyhow!("unknown recipient alias {:?}", name))?;
+        if chats.len() != 1 {
+            return Err(anyhow!(
+                "recipient alias {:?} stands for {} chats, not one",
+                name,
+                chats.len()
+            ));
+        }
+        Ok(chats.remove(0))
+    }
+
+    /// Send a message to every chat a name stands for, in order. Failures for one chat
+    /// don't stop sending to the rest, the result for each chat is returned
+    pub async fn send_message(
+        &self,
+        bot: &Bot,
+        name: &str,
+        text: &str,
+    ) -> Result<Vec<BotResult<Message>>> {
+        let chats = self
+            .get(name)
+            .ok_or_else(|| anyhow!("unknown recipient alias {:?}", name))?;
+        let mut results = Vec::with_capacity(chats.len());
+        for chat in chats {
+            results.push(bot.build_send_message(chat, text).build().await);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_names() {
+        let recipients = Recipients::new();
+        recipients
+            .alias("Logs", -100)
+            .load_json(r#"{"admins": [1, "@ops"], "alerts": "@alerts"}"#)
+            .unwrap();
+        assert_eq!(recipients.get_chat("logs"), Some(ChatHandle::ChatId(-100)));
+        assert_eq!(
+            recipients.get("admins"),
+            Some(vec![
+                ChatHandle::ChatId(1),
+                ChatHandle::Username("@ops".to_owned())
+            ])
+        );
+        assert_eq!(parse_chat(" 42"), ChatHandle::ChatId(42));
+
+        std::env::set_var("RECIPIENTS_TEST_OPS", "5,@ops");
+        assert_eq!(recipients.load_env("RECIPIENTS_TEST_"), 1);
+        assert_eq!(recipients.get("ops").unwrap().len(), 2);
+
+        assert_eq!(recipients.get_chat("admins"), None);
+        assert!(recipients.resolve("admins").is_err());
+
+        Recipients::global().alias("test_logs", 7);
+        assert_eq!(alias!("test_logs").unwrap(), ChatHandle::ChatId(7));
+        assert!(alias!("test_missing").is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_to_groups() {
+        let test = crate::testing::TestBot::new().await.unwrap();
+        let recipients = Recipients::new();
+        recipients.group("admins", [1, 2]);
+        let results = recipients
+            .send_message(test.get_bot(), "admins", "hi")
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(test.sent("sendMessage").len(), 2);
+    }
+}
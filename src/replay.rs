@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use async_stream::stream;
+use futures_core::Stream;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+
+use crate::bot::ApiError;
+use crate::gen_types::{Update, UpdateExt, UpdateId};
+
+/// A single line of a recording, an update along with the time it was received relative
+/// to the start of the recording
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedUpdate {
+    pub elapsed_ms: u64,
+    pub update: Update,
+}
+
+/// Records updates to a newline delimited json file for later replay with UpdateReplayer
+#[derive(Debug)]
+pub struct UpdateRecorder {
+    file: Mutex<File>,
+    start: Instant,
+}
+
+impl UpdateRecorder {
+    /// Open a recording file, appending to it if it already exists
+    pub async fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Self {
+            file: Mutex::new(file),
+            start: Instant::now(),
+        })
+    }
+
+    /// Write a single update to the recording
+    pub async fn record(&self, update_id: UpdateId, update: &UpdateExt) -> Result<()> {
+        let record = RecordedUpdate {
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+            update: (update_id, update.clone()).into(),
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().await;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Record every update from a stream such as LongPoller::get_updates_with_ids while
+    /// passing it through unchanged. Failing to record an update is logged and does not
+    /// end the stream
+    pub fn tee<S>(
+        self,
+        updates: S,
+    ) -> Pin<Box<impl Stream<Item = Result<(UpdateId, UpdateExt), ApiError>>>>
+    where
+        S: Stream<Item = Result<(UpdateId, UpdateExt), ApiError>>,
+    {
+        let s = stream! {
+            let mut updates = Box::pin(updates);
+            while let Some(update) = updates.next().await {
+                if let Ok((update_id, ref update)) = update {
+                    if let Err(err) = self.record(update_id, update).await {
+                        log::warn!("failed to record update {}", err);
+                    }
+                }
+                yield update;
+            }
+        };
+
+        Box::pin(s)
+    }
+}
+
+/// Source of updates read from a recording made by UpdateRecorder. Updates are yielded at
+/// the pace they were originally received, scaled by a speed multiplier
+#[derive(Debug, Clone)]
+pub struct UpdateReplayer {
+    path: PathBuf,
+    speed: f64,
+}
+
+impl UpdateReplayer {
+    /// Replay a recording at its original pace
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            speed: 1.0,
+        }
+    }
+
+    /// Multiply the replay speed, 2.0 replays twice as fast. A speed of zero or less
+    /// replays all updates without waiting
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Return an async stream of recorded updates with their update_ids, suitable for
+    /// Dispatcher::run. The stream ends with an error if a line of the recording is
+    /// invalid
+    pub async fn get_updates(
+        self,
+    ) -> Result<Pin<Box<impl Stream<Item = Result<(UpdateId, UpdateExt), ApiError>>>>, ApiError>
+    {
+        let file = File::open(&self.path).await.map_err(|e| anyhow!(e))?;
+        let mut lines = BufReader::new(file).lines();
+        let s = stream! {
+            let mut last = 0;
+            loop {
+                let line = match lines.next_line().await {
+                    Ok(Some(line)) => line,
+                    Ok(None) => break,
+                    Err(err) => {
+                        yield Err(anyhow!(err).into());
+                        break;
+                    }
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let record = match serde_json::from_str::<RecordedUpdate>(&line) {
+                    Ok(record) => record,
+                    Err(err) => {
+                        yield Err(err.into());
+                        break;
+                    }
+                };
+                if self.speed > 0.0 {
+                    let wait = record.elapsed_ms.saturating_sub(last) as f64 / self.speed;
+                    tokio::time::sleep(Duration::from_secs_f64(wait / 1000.0)).await;
+                }
+                last = record.elapsed_ms;
+                yield Ok((record.update.get_update_id(), record.update.into()));
+            }
+        };
+
+        Ok(Box::pin(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen_types::Message;
+
+    #[tokio::test]
+    async fn record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("botapi-replay-{}.ndjson", std::process::id()));
+        let mut message = Message::default();
+        message.set_message_id(42.into());
+        let update = UpdateExt::Message(message);
+
+        let recorder = UpdateRecorder::create(&path).await.unwrap();
+        recorder.record(1.into(), &update).await.unwrap();
+        recorder.record(2.into(), &update).await.unwrap();
+
+        let replayed = UpdateReplayer::new(&path)
+            .speed(0.0)
+            .get_updates()
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replayed.len(), 2);
+        for (id, u) in replayed.into_iter().enumerate() {
+            assert_eq!(u.unwrap(), (UpdateId::from(id as i64 + 1), update.clone()));
+        }
+    }
+}
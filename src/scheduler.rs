@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::bot::{Bot, BotResult, SerializableRequest, TelegramMethod};
+use crate::ext::now;
+
+/// Seconds in a day, used for daily triggers
+const DAY: i64 = 86400;
+
+/// Longest time to sleep before checking the jobs again, in case a store was edited or
+/// the clock jumped
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// When a scheduled request runs. All times are unix timestamps in seconds, UTC
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Run once at a specific time
+    At(i64),
+    /// Run at start and then every interval seconds
+    Every { start: i64, interval: u64 },
+    /// Run every day at hour:minute
+    Daily { hour: u8, minute: u8 },
+}
+
+impl Trigger {
+    /// Get the first time this trigger fires after the given time, or None if it never
+    /// fires again
+    pub fn next_after(&self, time: i64) -> Option<i64> {
+        match *self {
+            Self::At(at) => (at > time).then_some(at),
+            Self::Every { start, interval } => {
+                if start > time {
+                    Some(start)
+                } else {
+                    let interval = interval.max(1) as i64;
+                    Some(start + ((time - start) / interval + 1) * interval)
+                }
+            }
+            Self::Daily { hour, minute } => {
+                let offset = (hour as i64 % 24) * 3600 + (minute as i64 % 60) * 60;
+                let next = time - time.rem_euclid(DAY) + offset;
+                Some(if next > time { next } else { next + DAY })
+            }
+        }
+    }
+
+    /// Get the first time this trigger fires, counting from now. A one shot trigger in
+    /// the past fires immediately
+    fn first(&self, time: i64) -> i64 {
+        match *self {
+            Self::At(at) => at,
+            Self::Every { start, .. } if start >= time => start,
+            _ => self.next_after(time).unwrap_or(time),
+        }
+    }
+}
+
+/// A request along with when to run it next
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScheduledJob {
+    id: u64,
+    request: SerializableRequest,
+    trigger: Trigger,
+    next_run: i64,
+}
+
+impl ScheduledJob {
+    /// Get the id used to cancel this job
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get the request this job runs
+    pub fn get_request(&self) -> &'_ SerializableRequest {
+        &self.request
+    }
+
+    /// Get the trigger of this job
+    pub fn get_trigger(&self) -> Trigger {
+        self.trigger
+    }
+
+    /// Get the unix time this job runs next
+    pub fn get_next_run(&self) -> i64 {
+        self.next_run
+    }
+}
+
+/// Persistence for scheduled jobs. save is called with every pending job whenever the
+/// schedule changes, and load once when restoring a scheduler
+pub trait SchedulerStore: Send + Sync {
+    /// Load all saved jobs
+    fn load(&self) -> BoxFuture<'_, Result<Vec<ScheduledJob>>>;
+
+    /// Replace all saved jobs
+    fn save<'a>(&'a self, jobs: &'a [ScheduledJob]) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Store that keeps nothing, jobs are lost on restart
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryStore;
+
+impl SchedulerStore for MemoryStore {
+    fn load(&self) -> BoxFuture<'_, Result<Vec<ScheduledJob>>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    fn save<'a>(&'a self, _: &'a [ScheduledJob]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Store keeping jobs in a json file, replaced atomically on every save
+#[derive(Debug, Clone)]
+pub struct JsonFileStore {
+    path: PathBuf,
+}
+
+impl JsonFileStore {
+    /// Store jobs at path. A missing file loads as no jobs
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SchedulerStore for JsonFileStore {
+    fn load(&self) -> BoxFuture<'_, Result<Vec<ScheduledJob>>> {
+        Box::pin(async move {
+            match tokio::fs::read(&self.path).await {
+                Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    fn save<'a>(&'a self, jobs: &'a [ScheduledJob]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let bytes = serde_json::to_vec(jobs)?;
+            let tmp = self.path.with_extension("tmp");
+            tokio::fs::write(&tmp, bytes).await?;
+            tokio::fs::rename(&tmp, &self.path).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Runs serialized requests at specific times or on an interval. Cloning is cheap and
+/// every clone shares the same schedule, so one clone can be spawned with run while
+/// others add jobs
+#[derive(Clone)]
+pub struct Scheduler {
+    bot: Bot,
+    store: Arc<dyn SchedulerStore>,
+    jobs: Arc<Mutex<BTreeMap<u64, ScheduledJob>>>,
+    next_id: Arc<AtomicU64>,
+    notify: Arc<Notify>,
+    saving: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl std::fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("jobs", &self.jobs)
+            .finish()
+    }
+}
+
+impl Scheduler {
+    /// Create a scheduler restoring any jobs saved in store
+    pub async fn new<S>(bot: Bot, store: S) -> BotResult<Self>
+    where
+        S: SchedulerStore + 'static,
+    {
+        let saved = store.load().await?;
+        let next_id = saved.iter().map(|j| j.id + 1).max().unwrap_or_default();
+        let jobs = saved.into_iter().map(|j| (j.id, j)).collect();
+        Ok(Self {
+            bot,
+            store: Arc::new(store),
+            jobs: Arc::new(Mutex::new(jobs)),
+            next_id: Arc::new(AtomicU64::new(next_id)),
+            notify: Arc::new(Notify::new()),
+            saving: Arc::new(tokio::sync::Mutex::new(())),
+        })
+    }
+
+    /// Create a scheduler that does not persist jobs
+    pub fn in_memory(bot: Bot) -> Self {
+        Self {
+            bot,
+            store: Arc::new(MemoryStore),
+            jobs: Arc::new(Mutex::new(BTreeMap::new())),
+            next_id: Arc::new(AtomicU64::new(0)),
+            notify: Arc::new(Notify::new()),
+            saving: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Schedule a serialized request, returning the id of the job
+    pub async fn schedule(&self, request: SerializableRequest, trigger: Trigger) -> BotResult<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let job = ScheduledJob {
+            id,
+            request,
+            trigger,
+            next_run: trigger.first(now()),
+        };
+        self.jobs.lock().unwrap().insert(id, job);
+        self.persist().await?;
+        self.notify.notify_one();
+        Ok(id)
+    }
+
+    /// Schedule a method call to run once at a unix time
+    pub async fn schedule_at<P>(&self, params: &P, time: i64) -> BotResult<u64>
+    where
+        P: TelegramMethod,
+    {
+        self.schedule(SerializableRequest::new(params)?, Trigger::At(time))
+            .await
+    }
+
+    /// Schedule a method call to run at start and then repeatedly
+    pub async fn schedule_every<P>(
+        &self,
+        params: &P,
+        start: i64,
+        interval: Duration,
+    ) -> BotResult<u64>
+    where
+        P: TelegramMethod,
+    {
+        let trigger = Trigger::Every {
+            start,
+            interval: interval.as_secs(),
+        };
+        self.schedule(SerializableRequest::new(params)?, trigger)
+            .await
+    }
+
+    /// Remove a job, returning false if no job had this id
+    pub async fn cancel(&self, id: u64) -> BotResult<bool> {
+        let removed = self.jobs.lock().unwrap().remove(&id).is_some();
+        if removed {
+            self.persist().await?;
+        }
+        Ok(removed)
+    }
+
+    /// Get all pending jobs ordered by id
+    pub fn get_jobs(&self) -> Vec<ScheduledJob> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Save all pending jobs to the store. Saves from every clone run one at a time and
+    /// the jobs are read once it's this save's turn, so the last save has the latest jobs
+    async fn persist(&self) -> BotResult<()> {
+        let _saving = self.saving.lock().await;
+        let jobs = self.get_jobs();
+        self.store.save(&jobs).await?;
+        Ok(())
+    }
+
+    /// Take all jobs that are due, rescheduling repeating jobs
+    fn take_due(&self, time: i64) -> Vec<ScheduledJob> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let due = jobs
+            .values()
+            .filter(|j| j.next_run <= time)
+            .cloned()
+            .collect::<Vec<_>>();
+        for job in due.iter() {
+            match job.trigger.next_after(time) {
+                Some(next) => {
+                    if let Some(j) = jobs.get_mut(&job.id) {
+                        j.next_run = next;
+                    }
+                }
+                None => {
+                    jobs.remove(&job.id);
+                }
+            }
+        }
+        due
+    }
+
+    /// Get how long to wait until the next job is due
+    fn until_next(&self, time: i64) -> Duration {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|j| Duration::from_secs((j.next_run - time).max(0) as u64))
+            .min()
+            .unwrap_or(MAX_SLEEP)
+            .min(MAX_SLEEP)
+    }
+
+    /// Run due jobs forever, spawned on one clone of the scheduler. The schedule is saved
+    /// before due requests are sent, so a one shot job interrupted by a restart is not
+    /// run again and a repeating job skips to its next run. Failing requests are logged,
+    /// repeating jobs stay scheduled and one shot jobs are not retried
+    pub async fn run(self) {
+        loop {
+            let due = self.take_due(now());
+            if !due.is_empty() {
+                if let Err(err) = self.persist().await {
+                    log::warn!("failed to persist schedule {}", err);
+                }
+            }
+            for job in due {
+                if let Err(err) = self.bot.execute_serialized(&job.request).await {
+                    log::warn!(
+                        "scheduled {} job {} failed {}",
+                        job.request.get_method(),
+                        job.id,
+                        err
+                    );
+                }
+            }
+            let wait = self.until_next(now());
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => (),
+                _ = self.notify.notified() => ()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers() {
+        assert_eq!(Trigger::At(10).next_after(5), Some(10));
+        assert_eq!(Trigger::At(10).next_after(10), None);
+        let every = Trigger::Every {
+            start: 100,
+            interval: 30,
+        };
+        assert_eq!(every.next_after(50), Some(100));
+        assert_eq!(every.next_after(100), Some(130));
+        assert_eq!(every.next_after(145), Some(160));
+        let daily = Trigger::Daily {
+            hour: 1,
+            minute: 30,
+        };
+        assert_eq!(daily.next_after(DAY), Some(DAY + 5400));
+        assert_eq!(daily.next_after(DAY + 5400), Some(2 * DAY + 5400));
+    }
+
+    #[tokio::test]
+    async fn file_store_roundtrip() {
+        let path = std::env::temp_dir().join(format!("botapi-schedule-{}.json", now()));
+        let store = JsonFileStore::new(&path);
+        assert!(store.load().await.unwrap().is_empty());
+        let job = ScheduledJob {
+            id: 3,
+            request: SerializableRequest::from_json(r#"{"method":"getMe","params":{}}"#).unwrap(),
+            trigger: Trigger::Daily { hour: 8, minute: 0 },
+            next_run: 0,
+        };
+        store.save(&[job.clone()]).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), vec![job]);
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+use crate::gen_types::Message;
+
+/// The kind of a service message, sent by telegram about something happening in a
+/// chat rather than by a user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ServiceKind {
+    /// Users joined or were added to the group
+    NewChatMembers,
+    /// A user left or was removed from the group
+    LeftChatMember,
+    /// The chat title was changed
+    NewChatTitle,
+    /// The chat photo was changed
+    NewChatPhoto,
+    /// The chat photo was deleted
+    DeleteChatPhoto,
+    /// A group, supergroup or channel was created
+    ChatCreated,
+    /// The auto-delete timer of the chat was changed
+    AutoDeleteTimerChanged,
+    /// The group was migrated to or from a supergroup
+    Migrated,
+    /// A message was pinned
+    PinnedMessage,
+    /// A payment to the bot succeeded
+    SuccessfulPayment,
+    /// A user shared users with the bot
+    UsersShared,
+    /// A user shared a chat with the bot
+    ChatShared,
+    /// A user logged in on a website through the bot
+    ConnectedWebsite,
+    /// A user allowed the bot to write messages
+    WriteAccessAllowed,
+    /// A user triggered a proximity alert of another user
+    ProximityAlertTriggered,
+    /// A user boosted the chat
+    BoostAdded,
+    /// The chat background was changed
+    ChatBackgroundSet,
+    /// A forum topic was created
+    ForumTopicCreated,
+    /// A forum topic was edited
+    ForumTopicEdited,
+    /// A forum topic was closed
+    ForumTopicClosed,
+    /// A forum topic was reopened
+    ForumTopicReopened,
+    /// The general forum topic was hidden
+    GeneralForumTopicHidden,
+    /// The general forum topic was unhidden
+    GeneralForumTopicUnhidden,
+    /// A giveaway was created
+    GiveawayCreated,
+    /// A giveaway without public winners was completed
+    GiveawayCompleted,
+    /// A video chat was scheduled
+    VideoChatScheduled,
+    /// A video chat started
+    VideoChatStarted,
+    /// A video chat ended
+    VideoChatEnded,
+    /// Users were invited to a video chat
+    VideoChatParticipantsInvited,
+    /// Data was sent from a web app
+    WebAppData,
+}
+
+impl ServiceKind {
+    /// Check if this is about the members of the chat changing
+    pub fn is_membership(&self) -> bool {
+        matches!(self, Self::NewChatMembers | Self::LeftChatMember)
+    }
+
+    /// Check if this is about a forum topic
+    pub fn is_forum_topic(&self) -> bool {
+        matches!(
+            self,
+            Self::ForumTopicCreated
+                | Self::ForumTopicEdited
+                | Self::ForumTopicClosed
+                | Self::ForumTopicReopened
+                | Self::GeneralForumTopicHidden
+                | Self::GeneralForumTopicUnhidden
+        )
+    }
+
+    /// Check if this is about a video chat
+    pub fn is_video_chat(&self) -> bool {
+        matches!(
+            self,
+            Self::VideoChatScheduled
+                | Self::VideoChatStarted
+                | Self::VideoChatEnded
+                | Self::VideoChatParticipantsInvited
+        )
+    }
+}
+
+impl Message {
+    /// Get the kind of service message this is, or None for messages sent by users
+    pub fn service_kind(&self) -> Option<ServiceKind> {
+        let kind = if self.get_new_chat_members().is_some() {
+            ServiceKind::NewChatMembers
+        } else if self.get_left_chat_member().is_some() {
+            ServiceKind::LeftChatMember
+        } else if self.get_new_chat_title().is_some() {
+            ServiceKind::NewChatTitle
+        } else if self.get_new_chat_photo().is_some() {
+            ServiceKind::NewChatPhoto
+        } else if self.get_delete_chat_photo().is_some() {
+            ServiceKind::DeleteChatPhoto
+        } else if self.get_group_chat_created().is_some()
+            || self.get_supergroup_chat_created().is_some()
+            || self.get_channel_chat_created().is_some()
+        {
+            ServiceKind::ChatCreated
+        } else if self.get_message_auto_delete_timer_changed().is_some() {
+            ServiceKind::AutoDeleteTimerChanged
+        } else if self.get_migrate_to_chat_id().is_some()
+            || self.get_migrate_from_chat_id().is_some()
+        {
+            ServiceKind::Migrated
+        } else if self.get_pinned_message().is_some() {
+            ServiceKind::PinnedMessage
+        } else if self.get_successful_payment().is_some() {
+            ServiceKind::SuccessfulPayment
+        } else if self.get_users_shared().is_some() {
+            ServiceKind::UsersShared
+        } else if self.get_chat_shared().is_some() {
+            ServiceKind::ChatShared
+        } else if self.get_connected_website().is_some() {
+            ServiceKind::ConnectedWebsite
+        } else if self.get_write_access_allowed().is_some() {
+            ServiceKind::WriteAccessAllowed
+        } else if self.get_proximity_alert_triggered().is_some() {
+            ServiceKind::ProximityAlertTriggered
+        } else if self.get_boost_added().is_some() {
+            ServiceKind::BoostAdded
+        } else if self.get_chat_background_set().is_some() {
+            ServiceKind::ChatBackgroundSet
+        } else if self.get_forum_topic_created().is_some() {
+            ServiceKind::ForumTopicCreated
+        } else if self.get_forum_topic_edited().is_some() {
+            ServiceKind::ForumTopicEdited
+        } else if self.get_forum_topic_closed().is_some() {
+            ServiceKind::ForumTopicClosed
+        } else if self.get_forum_topic_reopened().is_some() {
+            ServiceKind::ForumTopicReopened
+        } else if self.get_general_forum_topic_hidden().is_some() {
+            ServiceKind::GeneralForumTopicHidden
+        } else if self.get_general_forum_topic_unhidden().is_some() {
+            ServiceKind::GeneralForumTopicUnhidden
+        } else if self.get_giveaway_created().is_some() {
+            ServiceKind::GiveawayCreated
+        } else if self.get_giveaway_completed().is_some() {
+            ServiceKind::GiveawayCompleted
+        } else if self.get_video_chat_scheduled().is_some() {
+            ServiceKind::VideoChatScheduled
+        } else if self.get_video_chat_started().is_some() {
+            ServiceKind::VideoChatStarted
+        } else if self.get_video_chat_ended().is_some() {
+            ServiceKind::VideoChatEnded
+        } else if self.get_video_chat_participants_invited().is_some() {
+            ServiceKind::VideoChatParticipantsInvited
+        } else if self.get_web_app_data().is_some() {
+            ServiceKind::WebAppData
+        } else {
+            return None;
+        };
+        Some(kind)
+    }
+
+    /// Check if this is a service message
+    pub fn is_service(&self) -> bool {
+        self.service_kind().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn detects_kind() {
+        let joined: Message = serde_json::from_value(json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup"},
+            "new_chat_members": [{"id": 2, "is_bot": false, "first_name": "New"}],
+        }))
+        .unwrap();
+        assert_eq!(joined.service_kind(), Some(ServiceKind::NewChatMembers));
+        assert!(joined.service_kind().unwrap().is_membership());
+
+        let topic: Message = serde_json::from_value(json!({
+            "message_id": 2,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup"},
+            "forum_topic_created": {"name": "news", "icon_color": 0},
+        }))
+        .unwrap();
+        assert!(topic.service_kind().unwrap().is_forum_topic());
+
+        let text: Message = serde_json::from_value(json!({
+            "message_id": 3,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup"},
+            "text": "hi",
+        }))
+        .unwrap();
+        assert!(!text.is_service());
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Persistence for per-chat settings, stored as json values keyed by chat id
+pub trait SettingsStore: Send + Sync {
+    /// Load the settings of a chat, None if it has none saved
+    fn load(&self, chat: i64) -> BoxFuture<'_, Result<Option<serde_json::Value>>>;
+
+    /// Save the settings of a chat, or remove them if None
+    fn save<'a>(
+        &'a self,
+        chat: i64,
+        settings: Option<&'a serde_json::Value>,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Store keeping settings in memory, they are lost on restart
+#[derive(Debug, Default)]
+pub struct MemorySettingsStore(Mutex<HashMap<i64, serde_json::Value>>);
+
+impl SettingsStore for MemorySettingsStore {
+    fn load(&self, chat: i64) -> BoxFuture<'_, Result<Option<serde_json::Value>>> {
+        let settings = self.0.lock().unwrap().get(&chat).cloned();
+        Box::pin(async move { Ok(settings) })
+    }
+
+    fn save<'a>(
+        &'a self,
+        chat: i64,
+        settings: Option<&'a serde_json::Value>,
+    ) -> BoxFuture<'a, Result<()>> {
+        let mut map = self.0.lock().unwrap();
+        match settings {
+            Some(settings) => map.insert(chat, settings.clone()),
+            None => map.remove(&chat),
+        };
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Store keeping the settings of every chat in one json file, replaced atomically on
+/// every save. Suited to bots in a modest number of chats
+#[derive(Debug)]
+pub struct JsonFileSettingsStore {
+    path: PathBuf,
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl JsonFileSettingsStore {
+    /// Store settings at path. A missing file loads as no settings
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    async fn read(&self) -> Result<BTreeMap<i64, serde_json::Value>> {
+        match tokio::fs::read(&self.path).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl SettingsStore for JsonFileSettingsStore {
+    fn load(&self, chat: i64) -> BoxFuture<'_, Result<Option<serde_json::Value>>> {
+        Box::pin(async move {
+            let _lock = self.lock.lock().await;
+            Ok(self.read().await?.remove(&chat))
+        })
+    }
+
+    fn save<'a>(
+        &'a self,
+        chat: i64,
+        settings: Option<&'a serde_json::Value>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let _lock = self.lock.lock().await;
+            let mut all = self.read().await?;
+            match settings {
+                Some(settings) => all.insert(chat, settings.clone()),
+                None => all.remove(&chat),
+            };
+            let tmp = self.path.with_extension("tmp");
+            tokio::fs::write(&tmp, serde_json::to_vec(&all)?).await?;
+            tokio::fs::rename(&tmp, &self.path).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Typed settings for each chat, persisted through a SettingsStore. Chats without saved
+/// settings get T::default(). Loaded settings are kept in memory, so the store is only
+/// read once per chat. Cloning is cheap and clones share the same settings
+pub struct ChatSettings<T> {
+    store: Arc<dyn SettingsStore>,
+    cache: Arc<Mutex<HashMap<i64, T>>>,
+    updates: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl<T> Clone for ChatSettings<T> {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+            cache: Arc::clone(&self.cache),
+            updates: Arc::clone(&self.updates),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for ChatSettings<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatSettings")
+            .field("cached", &self.cache.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl<T> ChatSettings<T>
+where
+    T: Serialize + DeserializeOwned + Default + Clone + Send + 'static,
+{
+    /// Keep settings in a store
+    pub fn new<S: SettingsStore + 'static>(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            updates: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Keep settings in memory only
+    pub fn in_memory() -> Self {
+        Self::new(MemorySettingsStore::default())
+    }
+
+    /// Get the settings of a chat. If an update finished while the settings were being
+    /// loaded, the updated settings are returned instead of the loaded ones
+    pub async fn get(&self, chat: i64) -> Result<T> {
+        if let Some(settings) = self.cache.lock().unwrap().get(&chat) {
+            return Ok(settings.clone());
+        }
+        let settings = match self.store.load(chat).await? {
+            Some(value) => serde_json::from_value(value)?,
+            None => T::default(),
+        };
+        Ok(self
+            .cache
+            .lock()
+            .unwrap()
+            .entry(chat)
+            .or_insert(settings)
+            .clone())
+    }
+
+    /// Change the settings of a chat and save them, returning the new settings. Updates
+    /// run one at a time so concurrent changes aren't lost
+    pub async fn update<F>(&self, chat: i64, update: F) -> Result<T>
+    where
+        F: FnOnce(&mut T),
+    {
+        let _lock = self.updates.lock().await;
+        let mut settings = self.get(chat).await?;
+        update(&mut settings);
+        let value = serde_json::to_value(&settings)?;
+        self.store.save(chat, Some(&value)).await?;
+        self.cache.lock().unwrap().insert(chat, settings.clone());
+        Ok(settings)
+    }
+
+    /// Remove the saved settings of a chat, for example after the bot leaves it
+    pub async fn reset(&self, chat: i64) -> Result<()> {
+        let _lock = self.updates.lock().await;
+        self.store.save(chat, None).await?;
+        self.cache.lock().unwrap().remove(&chat);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+    struct Group {
+        welcome: Option<String>,
+        max_warns: u32,
+    }
+
+    #[tokio::test]
+    async fn update_persists() {
+        let path = std::env::temp_dir().join(format!("botapi-settings-{}", std::process::id()));
+        let settings = ChatSettings::<Group>::new(JsonFileSettingsStore::new(&path));
+        assert_eq!(settings.get(-100).await.unwrap(), Group::default());
+        settings.update(-100, |s| s.max_warns = 3).await.unwrap();
+
+        let reloaded = ChatSettings::<Group>::new(JsonFileSettingsStore::new(&path));
+        assert_eq!(reloaded.get(-100).await.unwrap().max_warns, 3);
+        reloaded.reset(-100).await.unwrap();
+        assert_eq!(reloaded.get(-100).await.unwrap(), Group::default());
+        let _ = std::fs::remove_file(&path);
+    }
+}
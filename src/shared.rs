@@ -0,0 +1,136 @@
+use std::future::Future;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::Dispatcher;
+use crate::gen_types::{
+    ChatShared, KeyboardButton, KeyboardButtonRequestChat, KeyboardButtonRequestUsers, Message,
+    UpdateExt, UserId, UsersShared,
+};
+
+/// Users or a chat shared with the bot after pressing a request_users or request_chat
+/// keyboard button
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SharedRequest<'a> {
+    /// Users shared from a request_users button
+    Users(&'a UsersShared),
+    /// A chat shared from a request_chat button
+    Chat(&'a ChatShared),
+}
+
+impl SharedRequest<'_> {
+    /// Get the request_id of the button that was pressed
+    pub fn get_request_id(&self) -> i64 {
+        match self {
+            Self::Users(users) => users.get_request_id(),
+            Self::Chat(chat) => chat.get_request_id(),
+        }
+    }
+}
+
+impl UsersShared {
+    /// Get the ids of all users shared
+    pub fn user_ids(&self) -> Vec<UserId> {
+        self.get_users().iter().map(|u| u.get_user_id()).collect()
+    }
+}
+
+impl Message {
+    /// Get the users or chat shared by this message, if any
+    pub fn get_shared_request(&self) -> Option<SharedRequest<'_>> {
+        self.get_users_shared()
+            .map(SharedRequest::Users)
+            .or_else(|| self.get_chat_shared().map(SharedRequest::Chat))
+    }
+}
+
+impl KeyboardButton {
+    /// Create a button asking the user to pick users to share with the bot
+    pub fn request_users<T>(text: T, request: KeyboardButtonRequestUsers) -> Self
+    where
+        T: Into<String>,
+    {
+        let mut button = Self::default();
+        button.set_text(text.into());
+        button.set_request_users(Some(request));
+        button
+    }
+
+    /// Create a button asking the user to pick a chat to share with the bot
+    pub fn request_chat<T>(text: T, request: KeyboardButtonRequestChat) -> Self
+    where
+        T: Into<String>,
+    {
+        let mut button = Self::default();
+        button.set_text(text.into());
+        button.set_request_chat(Some(request));
+        button
+    }
+}
+
+/// Get the message of an update if it shared something for a request_id
+fn shared_message(update: UpdateExt, request_id: i64) -> Option<Message> {
+    match update {
+        UpdateExt::Message(message)
+            if message
+                .get_shared_request()
+                .is_some_and(|shared| shared.get_request_id() == request_id) =>
+        {
+            Some(message)
+        }
+        _ => None,
+    }
+}
+
+impl Dispatcher {
+    /// Add a handler called when users are shared in response to the button with
+    /// `request_id`. Pick a different request_id for each button to tell them apart
+    pub fn on_users_shared<F, Fut>(self, request_id: i64, handler: F) -> Self
+    where
+        F: Fn(Bot, Message, UsersShared) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.typed_handler(
+            "on_users_shared",
+            move |update| {
+                let message = shared_message(update, request_id)?;
+                let users = message.get_users_shared()?.clone();
+                Some((message, users))
+            },
+            move |bot, (message, users)| handler(bot, message, users),
+        )
+    }
+
+    /// Add a handler called when a chat is shared in response to the button with
+    /// `request_id`
+    pub fn on_chat_shared<F, Fut>(self, request_id: i64, handler: F) -> Self
+    where
+        F: Fn(Bot, Message, ChatShared) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = BotResult<()>> + Send + 'static,
+    {
+        self.typed_handler(
+            "on_chat_shared",
+            move |update| {
+                let message = shared_message(update, request_id)?;
+                let chat = message.get_chat_shared()?.clone();
+                Some((message, chat))
+            },
+            move |bot, (message, chat)| handler(bot, message, chat),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_request_id() {
+        let mut chat = ChatShared::default();
+        chat.set_request_id(3);
+        let mut message = Message::default();
+        message.set_chat_shared(Some(chat));
+        let update = UpdateExt::Message(message);
+        assert!(shared_message(update.clone(), 3).is_some());
+        assert!(shared_message(update, 4).is_none());
+    }
+}
@@ -0,0 +1,279 @@
+use std::sync::{Arc, OnceLock};
+
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use tokio::sync::mpsc;
+
+use crate::bot::{Bot, BotResult};
+use crate::dispatch::{current_update_id, Flow, Layer};
+use crate::filter::Filter;
+use crate::gen_types::{Update, UpdateExt, UpdateId};
+
+/// Number of updates an Exporter queues for its sink by default
+pub const DEFAULT_EXPORT_QUEUE: usize = 1024;
+
+/// An update encoded for export, with the json of the Update as telegram sent it. The
+/// update_id is only included if the update was dispatched with one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedUpdate {
+    bot_id: Option<i64>,
+    update_id: Option<UpdateId>,
+    update_type: String,
+    payload: Vec<u8>,
+}
+
+impl ExportedUpdate {
+    /// Encode an update received by a bot
+    pub fn new(bot: &Bot, update_id: Option<UpdateId>, update: &UpdateExt) -> Result<Self> {
+        let update = Update::from((update_id.unwrap_or_default(), update.clone()));
+        let mut value = serde_json::to_value(update)?;
+        if let Some(object) = value.as_object_mut() {
+            if update_id.is_none() {
+                object.remove("update_id");
+            }
+        }
+        let update_type = value
+            .as_object()
+            .and_then(|o| o.keys().find(|k| *k != "update_id"))
+            .cloned()
+            .unwrap_or_else(|| "invalid".to_owned());
+        Ok(Self {
+            bot_id: bot.get_bot_id(),
+            update_id,
+            update_type,
+            payload: serde_json::to_vec(&value)?,
+        })
+    }
+
+    /// Get the id of the update, if it was dispatched with one
+    pub fn get_update_id(&self) -> Option<UpdateId> {
+        self.update_id
+    }
+
+    /// Get the user id of the bot that received the update
+    pub fn get_bot_id(&self) -> Option<i64> {
+        self.bot_id
+    }
+
+    /// Get the kind of update as named by telegram, like "message" or "callback_query"
+    pub fn get_update_type(&self) -> &'_ str {
+        &self.update_type
+    }
+
+    /// Get the json encoded update
+    pub fn get_payload(&self) -> &'_ [u8] {
+        &self.payload
+    }
+
+    /// Get the headers describing the update, Bot-Id, Update-Id and Update-Type
+    pub fn get_headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("Update-Type", self.update_type.clone())];
+        if let Some(bot_id) = self.bot_id {
+            headers.push(("Bot-Id", bot_id.to_string()));
+        }
+        if let Some(update_id) = self.update_id {
+            headers.push(("Update-Id", update_id.to_string()));
+        }
+        headers
+    }
+}
+
+/// Destination for exported updates, like a message queue
+pub trait UpdateSink: Send + Sync {
+    /// Publish one update
+    fn send<'a>(&'a self, update: &'a ExportedUpdate) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Layer forwarding updates to a sink before they reach the handlers. Updates are
+/// queued and published by a background task started on the first update, so a slow
+/// sink only holds up dispatching once the queue is full. Failing to publish is logged
+/// and the update is still handled
+pub struct Exporter {
+    sink: Arc<dyn UpdateSink>,
+    filter: Option<Filter>,
+    capacity: usize,
+    queue: OnceLock<mpsc::Sender<ExportedUpdate>>,
+}
+
+impl std::fmt::Debug for Exporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Exporter")
+            .field("filter", &self.filter.is_some())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl Exporter {
+    /// Forward every update to sink
+    pub fn new<S: UpdateSink + 'static>(sink: S) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            filter: None,
+            capacity: DEFAULT_EXPORT_QUEUE,
+            queue: OnceLock::new(),
+        }
+    }
+
+    /// Only forward updates matching a filter
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Change how many updates are queued for the sink, defaults to DEFAULT_EXPORT_QUEUE
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Get the queue, starting the task publishing to the sink if needed
+    fn queue(&self) -> mpsc::Sender<ExportedUpdate> {
+        self.queue
+            .get_or_init(|| {
+                let (tx, mut rx) = mpsc::channel::<ExportedUpdate>(self.capacity);
+                let sink = Arc::clone(&self.sink);
+                tokio::spawn(async move {
+                    while let Some(update) = rx.recv().await {
+                        if let Err(err) = sink.send(&update).await {
+                            log::warn!("failed to export update: {}", err);
+                        }
+                    }
+                });
+                tx
+            })
+            .clone()
+    }
+}
+
+impl Layer for Exporter {
+    fn call(&self, bot: Bot, update: UpdateExt) -> BoxFuture<'static, BotResult<Flow>> {
+        let queue = self.queue();
+        let filter = self.filter.clone();
+        let update_id = current_update_id();
+        Box::pin(async move {
+            if let Some(filter) = filter {
+                if !filter.check(&bot, &update).await? {
+                    return Ok(Flow::Continue);
+                }
+            }
+            let exported = ExportedUpdate::new(&bot, update_id, &update)?;
+            if queue.send(exported).await.is_err() {
+                log::warn!("export queue closed");
+            }
+            Ok(Flow::Continue)
+        })
+    }
+}
+
+/// Sink publishing updates to NATS. Each update goes to the subject
+/// `<prefix>.<update type>`, so consumers can subscribe to `<prefix>.>` or to single
+/// kinds of updates
+#[cfg(feature = "nats")]
+#[derive(Debug, Clone)]
+pub struct NatsSink {
+    client: async_nats::Client,
+    prefix: String,
+}
+
+#[cfg(feature = "nats")]
+impl NatsSink {
+    /// Publish with a connected client under a subject prefix like "telegram.updates"
+    pub fn new<T: Into<String>>(client: async_nats::Client, prefix: T) -> Self {
+        Self {
+            client,
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Get the subject an update is published to
+    pub fn subject(&self, update: &ExportedUpdate) -> String {
+        format!("{}.{}", self.prefix, update.get_update_type())
+    }
+}
+
+#[cfg(feature = "nats")]
+impl UpdateSink for NatsSink {
+    fn send<'a>(&'a self, update: &'a ExportedUpdate) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut headers = async_nats::HeaderMap::new();
+            headers.insert("Content-Type", "application/json");
+            for (name, value) in update.get_headers() {
+                headers.insert(name, value.as_str());
+            }
+            self.client
+                .publish_with_headers(
+                    self.subject(update),
+                    headers,
+                    update.get_payload().to_vec().into(),
+                )
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Dispatcher;
+    use crate::testing::TestBot;
+    use std::sync::Mutex;
+
+    #[derive(Default, Clone)]
+    struct Collect(Arc<Mutex<Vec<ExportedUpdate>>>);
+
+    impl UpdateSink for Collect {
+        fn send<'a>(&'a self, update: &'a ExportedUpdate) -> BoxFuture<'a, Result<()>> {
+            self.0.lock().unwrap().push(update.clone());
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn exports_updates() {
+        let test = TestBot::new().await.unwrap();
+        let sink = Collect::default();
+        let dispatcher = Dispatcher::new()
+            .layer(Exporter::new(sink.clone()))
+            .layer(Exporter::new(sink.clone()).filter(crate::filter::text()));
+        dispatcher
+            .dispatch(test.get_bot(), UpdateExt::Invalid)
+            .await;
+        test.dispatch(&dispatcher, test.text_message(5, "hi")).await;
+        dispatcher
+            .dispatch_update(
+                test.get_bot(),
+                serde_json::from_value(serde_json::json!({"update_id": 9})).unwrap(),
+            )
+            .await;
+        for _ in 0..100 {
+            if sink.0.lock().unwrap().len() == 4 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let exported = sink.0.lock().unwrap();
+        assert_eq!(exported.len(), 4);
+        let messages = exported
+            .iter()
+            .filter(|e| e.get_update_type() == "message")
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].get_bot_id(), Some(1234));
+        let payload: serde_json::Value = serde_json::from_slice(messages[0].get_payload()).unwrap();
+        assert_eq!(payload["message"]["text"], "hi");
+        assert!(payload.get("update_id").is_none());
+
+        let identified = exported
+            .iter()
+            .find(|e| e.get_update_id().is_some())
+            .unwrap();
+        assert_eq!(identified.get_update_type(), "invalid");
+        assert_eq!(identified.get_update_id(), Some(UpdateId::from(9)));
+        assert!(identified
+            .get_headers()
+            .contains(&("Update-Id", "9".to_owned())));
+    }
+}
@@ -0,0 +1,262 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use http_body_util::BodyExt;
+
+use crate::bot::{ApiError, BotResult};
+
+tokio::task_local! {
+    static SNAPSHOT: Arc<Mutex<Option<RequestSnapshot>>>;
+}
+
+/// One part of a multipart/form-data body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartSnapshot {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+impl PartSnapshot {
+    /// Get the form field name of the part, for uploads this is the name used in
+    /// attach:// references
+    pub fn get_name(&self) -> &'_ str {
+        &self.name
+    }
+
+    /// Get the file name sent with the part, if any. Uploads from bytes send an empty
+    /// file name
+    pub fn get_file_name(&self) -> Option<&'_ str> {
+        self.file_name.as_deref()
+    }
+
+    /// Get the content type of the part, if set
+    pub fn get_content_type(&self) -> Option<&'_ str> {
+        self.content_type.as_deref()
+    }
+
+    /// Get the contents of the part
+    pub fn get_body(&self) -> &'_ [u8] {
+        &self.body
+    }
+
+    /// Get the contents of the part as text, if it is valid utf8
+    pub fn get_text(&self) -> Option<&'_ str> {
+        std::str::from_utf8(&self.body).ok()
+    }
+}
+
+/// The exact request a method call would send to telegram, captured with [`capture`].
+/// The bot token and api url are not included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestSnapshot {
+    method: String,
+    query: Vec<(String, String)>,
+    parts: Vec<PartSnapshot>,
+}
+
+impl RequestSnapshot {
+    /// Get the name of the telegram api method
+    pub fn get_method(&self) -> &'_ str {
+        &self.method
+    }
+
+    /// Get the x-www-form-urlencoded parameters in the order they are sent
+    pub fn get_query(&self) -> &'_ [(String, String)] {
+        &self.query
+    }
+
+    /// Get the value of a urlencoded parameter
+    pub fn get(&self, name: &str) -> Option<&'_ str> {
+        self.query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get the multipart/form-data parts in the order they are sent, empty if the
+    /// method uploads nothing
+    pub fn get_parts(&self) -> &'_ [PartSnapshot] {
+        &self.parts
+    }
+
+    /// Get a multipart part by field name
+    pub fn get_part(&self, name: &str) -> Option<&'_ PartSnapshot> {
+        self.parts.iter().find(|p| p.name == name)
+    }
+}
+
+/// Run a method call without sending it, returning the request it would have made.
+/// Everything up to sending runs as usual, including defaults, validation and upload
+/// conversion, so errors from those are returned as is. Only the first request is
+/// captured for calls that send several
+///
+/// ```no_run
+/// # use botapi::{bot::BotBuilder, gen_types::FileData, snapshot::capture};
+/// # tokio_test::block_on(async {
+/// # let bot = BotBuilder::new("sometoken").unwrap().build();
+/// let snapshot = capture(
+///     bot.build_send_photo(1, FileData::Bytes(vec![1, 2, 3]))
+///         .caption("hi")
+///         .build(),
+/// )
+/// .await
+/// .unwrap();
+/// assert_eq!(snapshot.get("caption"), Some("hi"));
+/// # })
+/// ```
+pub async fn capture<F, T>(call: F) -> BotResult<RequestSnapshot>
+where
+    F: Future<Output = BotResult<T>>,
+{
+    let slot = Arc::new(Mutex::new(None));
+    let res = SNAPSHOT.scope(Arc::clone(&slot), call).await;
+    let snapshot = slot.lock().unwrap().take();
+    match (snapshot, res) {
+        (Some(snapshot), _) => Ok(snapshot),
+        (None, Err(err)) => Err(err),
+        (None, Ok(_)) => Err(anyhow::anyhow!("call finished without sending a request").into()),
+    }
+}
+
+/// Check if requests made by the current task should be captured instead of sent
+pub(crate) fn is_capturing() -> bool {
+    SNAPSHOT.try_with(|_| ()).is_ok()
+}
+
+/// Record a request in the current capture, returning the error that ends the call
+pub(crate) async fn record(endpoint: &str, req: reqwest::RequestBuilder) -> ApiError {
+    match snapshot_request(endpoint, req).await {
+        Ok(snapshot) => {
+            let _ = SNAPSHOT.try_with(|slot| *slot.lock().unwrap() = Some(snapshot));
+            anyhow::anyhow!("request to {} captured without sending", endpoint).into()
+        }
+        Err(err) => err,
+    }
+}
+
+async fn snapshot_request(
+    endpoint: &str,
+    req: reqwest::RequestBuilder,
+) -> BotResult<RequestSnapshot> {
+    let mut req = req.build().map_err(|e| e.without_url())?;
+    let query = req
+        .url()
+        .query()
+        .map(serde_urlencoded::from_str::<Vec<(String, String)>>)
+        .transpose()
+        .map_err(anyhow::Error::from)?
+        .unwrap_or_default();
+    let boundary = req
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split("boundary=").nth(1))
+        .map(|b| b.trim_matches('"').to_owned());
+    let parts = match (boundary, req.body_mut().take()) {
+        (Some(boundary), Some(body)) => {
+            let bytes = body
+                .collect()
+                .await
+                .map_err(|e| e.without_url())?
+                .to_bytes();
+            parse_multipart(&bytes, &boundary)
+        }
+        _ => Vec::new(),
+    };
+    Ok(RequestSnapshot {
+        method: endpoint.to_owned(),
+        query,
+        parts,
+    })
+}
+
+/// Find the first occurrence of needle in haystack
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Get a quoted parameter like name="value" from a Content-Disposition header
+fn disposition_param(header: &str, param: &str) -> Option<String> {
+    let start = header.find(&format!("{}=\"", param))? + param.len() + 2;
+    let len = header[start..].find('"')?;
+    Some(header[start..start + len].to_owned())
+}
+
+/// Split a multipart/form-data body into its parts
+fn parse_multipart(body: &[u8], boundary: &str) -> Vec<PartSnapshot> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+    let mut rest = body;
+    while let Some(start) = find(rest, delimiter.as_bytes()) {
+        rest = &rest[start + delimiter.len()..];
+        if rest.starts_with(b"--") {
+            break;
+        }
+        let rest_part = rest.strip_prefix(b"\r\n").unwrap_or(rest);
+        let Some(end) = find(rest_part, delimiter.as_bytes()) else {
+            break;
+        };
+        let part = &rest_part[..end];
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        if let Some(split) = find(part, b"\r\n\r\n") {
+            let headers = String::from_utf8_lossy(&part[..split]);
+            let mut name = String::new();
+            let mut file_name = None;
+            let mut content_type = None;
+            for line in headers.split("\r\n") {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                if key.eq_ignore_ascii_case("content-disposition") {
+                    name = disposition_param(value, "name").unwrap_or_default();
+                    file_name = disposition_param(value, "filename");
+                } else if key.eq_ignore_ascii_case("content-type") {
+                    content_type = Some(value.trim().to_owned());
+                }
+            }
+            parts.push(PartSnapshot {
+                name,
+                file_name,
+                content_type,
+                body: part[split + 4..].to_vec(),
+            });
+        }
+        rest = &rest_part[end..];
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::BotBuilder;
+    use crate::gen_types::FileData;
+
+    #[tokio::test]
+    async fn captures_multipart() {
+        let bot = BotBuilder::new("sometoken").unwrap().build();
+        let snapshot = capture(
+            bot.build_send_document(5, FileData::Bytes(vec![1, 2, 3]))
+                .caption("report")
+                .build(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(snapshot.get_method(), "sendDocument");
+        assert_eq!(snapshot.get("chat_id"), Some("5"));
+        assert_eq!(snapshot.get("caption"), Some("report"));
+        let document = snapshot.get("document").unwrap();
+        let name = document.strip_prefix("attach://").unwrap();
+        let part = snapshot.get_part(name).unwrap();
+        assert_eq!(part.get_body(), &[1, 2, 3]);
+        assert_eq!(part.get_file_name(), Some(""));
+
+        let snapshot = capture(bot.build_send_message(5, "hi").build())
+            .await
+            .unwrap();
+        assert!(snapshot.get_parts().is_empty());
+        assert_eq!(snapshot.get("text"), Some("hi"));
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+
+use crate::scheduler::{ScheduledJob, SchedulerStore};
+use crate::settings::SettingsStore;
+
+/// Schema changes applied in order by migrate. Each entry runs once and is recorded in
+/// botapi_migrations, so new tables or columns are added as new entries at the end and
+/// existing entries are never edited. Values are stored as json text to keep the same
+/// schema on every database
+static MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS botapi_chat_settings (
+        chat_id BIGINT PRIMARY KEY,
+        settings TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS botapi_scheduled_jobs (
+        id BIGINT PRIMARY KEY,
+        job TEXT NOT NULL
+    )",
+];
+
+const MIGRATIONS_TABLE: &str =
+    "CREATE TABLE IF NOT EXISTS botapi_migrations (version BIGINT PRIMARY KEY)";
+
+/// Implement every store trait for a sqlx pool type. The queries only use syntax shared
+/// by sqlite and postgres
+macro_rules! sql_store {
+    ($(#[$meta:meta])* $name:ident, $db:ty) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        pub struct $name {
+            pool: sqlx::Pool<$db>,
+        }
+
+        impl $name {
+            /// Connect to a database url and apply any pending migrations
+            pub async fn connect(url: &str) -> Result<Self> {
+                Self::from_pool(sqlx::Pool::<$db>::connect(url).await?).await
+            }
+
+            /// Use an existing pool, applying any pending migrations
+            pub async fn from_pool(pool: sqlx::Pool<$db>) -> Result<Self> {
+                let store = Self { pool };
+                store.migrate().await?;
+                Ok(store)
+            }
+
+            /// Get the pool used by this store
+            pub fn get_pool(&self) -> &'_ sqlx::Pool<$db> {
+                &self.pool
+            }
+
+            /// Apply migrations newer than the recorded schema version
+            pub async fn migrate(&self) -> Result<()> {
+                let mut tx = self.pool.begin().await?;
+                sqlx::query(MIGRATIONS_TABLE).execute(&mut *tx).await?;
+                let version: i64 =
+                    sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM botapi_migrations")
+                        .fetch_one(&mut *tx)
+                        .await?;
+                for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+                    log::info!("applying storage migration {}", i + 1);
+                    sqlx::query(migration).execute(&mut *tx).await?;
+                    sqlx::query("INSERT INTO botapi_migrations (version) VALUES ($1)")
+                        .bind(i as i64 + 1)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                tx.commit().await?;
+                Ok(())
+            }
+        }
+
+        impl SettingsStore for $name {
+            fn load(&self, chat: i64) -> BoxFuture<'_, Result<Option<serde_json::Value>>> {
+                Box::pin(async move {
+                    let settings: Option<String> = sqlx::query_scalar(
+                        "SELECT settings FROM botapi_chat_settings WHERE chat_id = $1",
+                    )
+                    .bind(chat)
+                    .fetch_optional(&self.pool)
+                    .await?;
+                    Ok(settings.map(|s| serde_json::from_str(&s)).transpose()?)
+                })
+            }
+
+            fn save<'a>(
+                &'a self,
+                chat: i64,
+                settings: Option<&'a serde_json::Value>,
+            ) -> BoxFuture<'a, Result<()>> {
+                Box::pin(async move {
+                    let query = match settings {
+                        Some(settings) => sqlx::query(
+                            "INSERT INTO botapi_chat_settings (chat_id, settings) VALUES ($1, $2)
+                            ON CONFLICT (chat_id) DO UPDATE SET settings = excluded.settings",
+                        )
+                        .bind(chat)
+                        .bind(settings.to_string()),
+                        None => sqlx::query("DELETE FROM botapi_chat_settings WHERE chat_id = $1")
+                            .bind(chat),
+                    };
+                    query.execute(&self.pool).await?;
+                    Ok(())
+                })
+            }
+        }
+
+        impl SchedulerStore for $name {
+            fn load(&self) -> BoxFuture<'_, Result<Vec<ScheduledJob>>> {
+                Box::pin(async move {
+                    let jobs: Vec<String> =
+                        sqlx::query_scalar("SELECT job FROM botapi_scheduled_jobs ORDER BY id")
+                            .fetch_all(&self.pool)
+                            .await?;
+                    jobs.iter()
+                        .map(|j| Ok(serde_json::from_str(j)?))
+                        .collect()
+                })
+            }
+
+            fn save<'a>(&'a self, jobs: &'a [ScheduledJob]) -> BoxFuture<'a, Result<()>> {
+                Box::pin(async move {
+                    let mut tx = self.pool.begin().await?;
+                    sqlx::query("DELETE FROM botapi_scheduled_jobs")
+                        .execute(&mut *tx)
+                        .await?;
+                    for job in jobs {
+                        sqlx::query("INSERT INTO botapi_scheduled_jobs (id, job) VALUES ($1, $2)")
+                            .bind(job.get_id() as i64)
+                            .bind(serde_json::to_string(job)?)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    tx.commit().await?;
+                    Ok(())
+                })
+            }
+        }
+    };
+}
+
+#[cfg(feature = "storage-sqlite")]
+sql_store!(
+    /// Store keeping chat settings and scheduled jobs in a sqlite database. The same
+    /// store can back both, each uses its own table
+    SqliteStore,
+    sqlx::Sqlite
+);
+
+#[cfg(feature = "storage-postgres")]
+sql_store!(
+    /// Store keeping chat settings and scheduled jobs in a postgres database. The same
+    /// store can back both, each uses its own table
+    PostgresStore,
+    sqlx::Postgres
+);
+
+#[cfg(all(test, feature = "storage-sqlite"))]
+mod tests {
+    use super::*;
+    use crate::settings::ChatSettings;
+
+    #[tokio::test]
+    async fn sqlite_settings() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let store = SqliteStore::from_pool(pool).await.unwrap();
+        store.migrate().await.unwrap();
+        let settings = ChatSettings::<Vec<String>>::new(store.clone());
+        settings
+            .update(-100, |s| s.push("rules".to_owned()))
+            .await
+            .unwrap();
+        let value = SettingsStore::load(&store, -100).await.unwrap();
+        assert_eq!(value, Some(serde_json::json!(["rules"])));
+        assert!(SchedulerStore::load(&store).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use std::marker::PhantomData;
+
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer, StringDeserializer};
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Enums of subtypes told apart by the value of a field, like ChatMember by status.
+/// Implemented by the generated types
+pub(crate) trait Tagged: Sized {
+    /// Name of the enum for errors
+    const NAME: &'static str;
+
+    /// Field holding the tag
+    const TAG: &'static str;
+
+    /// Check if a tag value picks a variant
+    fn is_tag(tag: &str) -> bool;
+
+    /// Deserialize the variant picked by a known tag
+    fn deserialize_tag<'de, D>(tag: &str, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>;
+
+    /// Try each variant in order, for payloads without a known tag
+    fn deserialize_untagged(value: &Value) -> Option<Self>;
+}
+
+/// Deserialize a Tagged enum. Map entries are only buffered until the tag is found,
+/// the rest of the map is streamed to the variant, so payloads starting with the tag
+/// are never buffered. Payloads without a known tag, like arrays from non
+/// self-describing formats, are buffered and matched against each variant in order
+pub(crate) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Tagged,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(TaggedVisitor(PhantomData))
+}
+
+fn untagged<T: Tagged, E: de::Error>(value: Value) -> Result<T, E> {
+    T::deserialize_untagged(&value)
+        .ok_or_else(|| E::custom(format!("data did not match any variant of {}", T::NAME)))
+}
+
+struct TaggedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Tagged> Visitor<'de> for TaggedVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a {}", T::NAME)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<T, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut buffered = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key != T::TAG {
+                buffered.push((key, map.next_value::<Value>()?));
+                continue;
+            }
+            let tag = map.next_value::<Value>()?;
+            let known = tag.as_str().filter(|t| T::is_tag(t)).map(str::to_owned);
+            buffered.push((key, tag));
+            if let Some(tag) = known {
+                let replay = Replay {
+                    buffered: buffered.into_iter(),
+                    value: None,
+                    rest: map,
+                };
+                return T::deserialize_tag(&tag, MapAccessDeserializer::new(replay));
+            }
+            break;
+        }
+        while let Some(entry) = map.next_entry::<String, Value>()? {
+            buffered.push(entry);
+        }
+        untagged(Value::Object(buffered.into_iter().collect()))
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<T, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        untagged(Value::deserialize(SeqAccessDeserializer::new(seq))?)
+    }
+}
+
+/// Map replaying the entries read while looking for the tag before the rest of a map
+struct Replay<A> {
+    buffered: std::vec::IntoIter<(String, Value)>,
+    value: Option<Value>,
+    rest: A,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for Replay<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.buffered.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(StringDeserializer::<A::Error>::new(key))
+                    .map(Some)
+            }
+            None => self.rest.next_key_seed(seed),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, A::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value).map_err(de::Error::custom),
+            None => self.rest.next_value_seed(seed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gen_types::ChatMember;
+
+    #[test]
+    fn tag_after_fields() {
+        let user = r#"{"id": 1, "is_bot": false, "first_name": "a"}"#;
+        let left = format!(r#"{{"user": {}, "status": "left"}}"#, user);
+        let member: ChatMember = serde_json::from_str(&left).unwrap();
+        assert!(matches!(member, ChatMember::ChatMemberLeft(_)));
+
+        let banned = format!(
+            r#"{{"status": "kicked", "user": {}, "until_date": 0}}"#,
+            user
+        );
+        let member: ChatMember = serde_json::from_str(&banned).unwrap();
+        assert!(matches!(member, ChatMember::ChatMemberBanned(_)));
+
+        let unknown = format!(r#"{{"user": {}, "status": "unknown"}}"#, user);
+        assert!(serde_json::from_str::<ChatMember>(&unknown).is_ok());
+        assert!(serde_json::from_str::<ChatMember>(r#"{"status": "left"}"#).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::bot::{Bot, BotBuilder, BotResult};
+use crate::dispatch::Dispatcher;
+use crate::gen_types::{Update, UpdateExt};
+
+const TEST_TOKEN: &str = "1234:testtoken";
+
+/// Check every call with a method and parameters was sent by a TestBot, panicking with
+/// the calls that were sent otherwise. Parameter values are anything serializable
+///
+/// ```no_run
+/// # use botapi::{assert_sent, testing::TestBot};
+/// # tokio_test::block_on(async {
+/// let test = TestBot::new().await.unwrap();
+/// test.get_bot().build_send_message(1, "hi").build().await.unwrap();
+/// assert_sent!(test, method = "sendMessage", chat_id = 1, text = "hi");
+/// # })
+/// ```
+#[macro_export]
+macro_rules! assert_sent {
+    ($bot:expr, method = $method:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $bot.assert_sent(
+            $method,
+            &[$((stringify!($key), $crate::testing::param_value(&$value))),*],
+        )
+    };
+}
+
+/// Convert a value to json for comparing against sent parameters
+#[doc(hidden)]
+pub fn param_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("parameter is not serializable")
+}
+
+/// A call received by the mock server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentRequest {
+    method: String,
+    params: BTreeMap<String, String>,
+}
+
+impl SentRequest {
+    /// Get the name of the method called
+    pub fn get_method(&self) -> &'_ str {
+        &self.method
+    }
+
+    /// Get a parameter as it was sent, json objects and arrays are still encoded
+    pub fn get(&self, name: &str) -> Option<&'_ str> {
+        self.params.get(name).map(|v| v.as_str())
+    }
+
+    /// Get all parameters of the call
+    pub fn get_params(&self) -> &'_ BTreeMap<String, String> {
+        &self.params
+    }
+
+    /// Check if a parameter was sent with a value. Strings compare as sent, anything
+    /// else compares as json
+    pub fn matches(&self, name: &str, expected: &Value) -> bool {
+        match (self.get(name), expected) {
+            (None, _) => false,
+            (Some(sent), Value::String(expected)) => sent == expected,
+            (Some(sent), expected) => {
+                serde_json::from_str::<Value>(sent).ok().as_ref() == Some(expected)
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    responses: Mutex<HashMap<String, VecDeque<Value>>>,
+    files: HashMap<String, String>,
+    sent: Mutex<Vec<SentRequest>>,
+    next_id: AtomicI64,
+}
+
+impl MockState {
+    /// Get the response to a call. Queued responses are used in order with the last one
+    /// repeating, methods without responses succeed with a placeholder result
+    fn respond(&self, request: &SentRequest) -> Value {
+        let mut responses = self.responses.lock().unwrap();
+        if let Some(queue) = responses.get_mut(&request.method) {
+            match queue.len() {
+                0 => (),
+                1 => return queue[0].clone(),
+                _ => return queue.pop_front().unwrap(),
+            }
+        }
+        drop(responses);
+        let chat = request
+            .get("chat_id")
+            .and_then(|c| c.parse::<i64>().ok())
+            .unwrap_or(1);
+        let result = match request.method.as_str() {
+            "getMe" => {
+                json!({"id": 1, "is_bot": true, "first_name": "Test", "username": "test_bot"})
+            }
+            m if m.starts_with("send") || m == "forwardMessage" => json!({
+                "message_id": self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+                "date": 0,
+                "chat": {"id": chat, "type": "private"},
+            }),
+            _ => Value::Bool(true),
+        };
+        json!({"ok": true, "result": result})
+    }
+}
+
+/// Builder for a TestBot with canned responses
+#[derive(Default)]
+pub struct TestBotBuilder {
+    responses: HashMap<String, VecDeque<Value>>,
+    files: HashMap<String, String>,
+}
+
+impl TestBotBuilder {
+    /// Queue a successful result for a method
+    pub fn respond<T: Serialize>(mut self, method: &str, result: T) -> Self {
+        let response = json!({"ok": true, "result": param_value(&result)});
+        self.responses
+            .entry(method.to_owned())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Queue an error for a method, like telegram's 400 responses
+    pub fn respond_error(mut self, method: &str, error_code: i64, description: &str) -> Self {
+        let response = json!({"ok": false, "error_code": error_code, "description": description});
+        self.responses
+            .entry(method.to_owned())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Serve the contents of a file for downloads of a file_path returned by getFile.
+    /// Downloads of other paths fail with 404
+    pub fn file<T: Into<String>>(mut self, file_path: &str, contents: T) -> Self {
+        self.files.insert(file_path.to_owned(), contents.into());
+        self
+    }
+
+    /// Start the mock server on a local port and create a bot using it
+    pub async fn build(self) -> BotResult<TestBot> {
+        let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .map_err(anyhow::Error::from)?;
+        let addr = listener.local_addr().map_err(anyhow::Error::from)?;
+        let state = Arc::new(MockState {
+            responses: Mutex::new(self.responses),
+            files: self.files,
+            ..Default::default()
+        });
+        let server = tokio::spawn(serve(listener, Arc::clone(&state)));
+        let bot = BotBuilder::new(TEST_TOKEN)?
+            .allow_http()?
+            .api(format!("http://{}", addr))
+            .auto_wait(false)
+            .build();
+        Ok(TestBot { bot, state, server })
+    }
+}
+
+/// Record a call and answer it like telegram
+async fn handle(state: Arc<MockState>, request: Request<Incoming>) -> Response<String> {
+    if let Some((_, file_path)) = request
+        .uri()
+        .path()
+        .strip_prefix("/file/bot")
+        .and_then(|p| p.split_once('/'))
+    {
+        return match state.files.get(file_path) {
+            Some(contents) => Response::new(contents.clone()),
+            None => {
+                let mut response = Response::new("Not Found".to_owned());
+                *response.status_mut() = hyper::StatusCode::NOT_FOUND;
+                response
+            }
+        };
+    }
+    let method = request
+        .uri()
+        .path()
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+    let params = request
+        .uri()
+        .query()
+        .and_then(|q| serde_urlencoded::from_str(q).ok())
+        .unwrap_or_default();
+    let sent = SentRequest { method, params };
+    let response = state.respond(&sent);
+    state.sent.lock().unwrap().push(sent);
+    Response::new(response.to_string())
+}
+
+async fn serve(listener: TcpListener, state: Arc<MockState>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let state = Arc::clone(&state);
+        let svc = service_fn(move |request| {
+            let state = Arc::clone(&state);
+            async move { Ok::<_, std::convert::Infallible>(handle(state, request).await) }
+        });
+        tokio::spawn(async move {
+            if let Err(err) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), svc)
+                .await
+            {
+                log::debug!("mock server connection error {}", err);
+            }
+        });
+    }
+}
+
+/// A Bot talking to a local mock server that records every call, for testing handlers
+/// without a real bot token. Unconfigured methods succeed, send methods return a stub
+/// message in the requested chat. The server stops when the TestBot is dropped
+pub struct TestBot {
+    bot: Bot,
+    state: Arc<MockState>,
+    server: JoinHandle<()>,
+}
+
+impl std::fmt::Debug for TestBot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TestBot")
+            .field("sent", &self.state.sent.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl Drop for TestBot {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl TestBot {
+    /// Start a mock server without canned responses
+    pub async fn new() -> BotResult<Self> {
+        Self::builder().build().await
+    }
+
+    /// Configure responses before starting the mock server
+    pub fn builder() -> TestBotBuilder {
+        TestBotBuilder::default()
+    }
+
+    /// Get the bot pointed at the mock server
+    pub fn get_bot(&self) -> &'_ Bot {
+        &self.bot
+    }
+
+    /// Run an update through a dispatcher using this bot
+    pub async fn dispatch(&self, dispatcher: &Dispatcher, update: UpdateExt) {
+        dispatcher.dispatch(&self.bot, update).await
+    }
+
+    /// Build a private chat text message update from a user with the same id as the chat
+    pub fn text_message(&self, chat: i64, text: &str) -> UpdateExt {
+        let id = self.state.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let update = json!({
+            "update_id": id,
+            "message": {
+                "message_id": id,
+                "date": 0,
+                "chat": {"id": chat, "type": "private", "first_name": "Test"},
+                "from": {"id": chat, "is_bot": false, "first_name": "Test"},
+                "text": text,
+            }
+        });
+        serde_json::from_value::<Update>(update)
+            .expect("invalid test update")
+            .into()
+    }
+
+    /// Get every call received so far
+    pub fn get_sent(&self) -> Vec<SentRequest> {
+        self.state.sent.lock().unwrap().clone()
+    }
+
+    /// Get the calls to one method received so far
+    pub fn sent(&self, method: &str) -> Vec<SentRequest> {
+        self.state
+            .sent
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.method == method)
+            .cloned()
+            .collect()
+    }
+
+    /// Forget calls received so far
+    pub fn clear(&self) {
+        self.state.sent.lock().unwrap().clear();
+    }
+
+    /// Panic unless a call to method was sent with all of the given parameters. Usually
+    /// called through assert_sent!
+    pub fn assert_sent(&self, method: &str, params: &[(&str, Value)]) {
+        let sent = self.get_sent();
+        let found = sent.iter().any(|r| {
+            r.method == method && params.iter().all(|(name, value)| r.matches(name, value))
+        });
+        assert!(
+            found,
+            "no {} call with {:?} was sent, sent calls: {:#?}",
+            method, params, sent
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_calls() {
+        let test = TestBot::builder()
+            .respond_error("banChatMember", 400, "Bad Request: not enough rights")
+            .build()
+            .await
+            .unwrap();
+        let bot = test.get_bot();
+        let message = bot.build_send_message(5, "hello").build().await.unwrap();
+        assert_eq!(message.get_chat().get_id(), 5);
+        let user = crate::gen_types::UserId::from(2);
+        assert!(bot.build_ban_chat_member(5, user).build().await.is_err());
+        assert_sent!(test, method = "sendMessage", chat_id = 5, text = "hello");
+        assert_sent!(test, method = "banChatMember", user_id = 2);
+        assert_eq!(test.sent("banChatMember").len(), 1);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::bot::Bot;
+use crate::gen_methods::{
+    CallSendAnimation, CallSendAudio, CallSendChatAction, CallSendDocument, CallSendMessage,
+    CallSendPhoto, CallSendPoll, CallSendSticker, CallSendVideo, CallSendVoice,
+};
+use crate::gen_types::{ChatAction, FileData, InputPollOption, Message, MessageThreadId};
+
+/// A bot bound to a forum topic. Builders created from the scope target the chat and
+/// have message_thread_id already set, every other parameter can still be changed on
+/// the returned builder
+///
+/// ```no_run
+/// # use botapi::bot::BotBuilder;
+/// # tokio_test::block_on(async {
+/// # let bot = BotBuilder::new("sometoken").unwrap().build();
+/// let topic = bot.in_thread(-1001234, 42);
+/// topic.send_message("hello topic").build().await.unwrap();
+/// # })
+/// ```
+#[derive(Clone, Debug)]
+pub struct ThreadScope {
+    bot: Bot,
+    chat_id: i64,
+    thread_id: MessageThreadId,
+}
+
+impl Bot {
+    /// Bind this bot to a forum topic so send helpers target it without passing the
+    /// chat and thread every time
+    pub fn in_thread<T>(&self, chat_id: i64, thread_id: T) -> ThreadScope
+    where
+        T: Into<MessageThreadId>,
+    {
+        ThreadScope {
+            bot: self.clone(),
+            chat_id,
+            thread_id: thread_id.into(),
+        }
+    }
+}
+
+impl ThreadScope {
+    /// Bind to the topic a message was sent in, or None if it wasn't sent in one
+    pub fn from_message(bot: &Bot, message: &Message) -> Option<Self> {
+        if !message.get_is_topic_message().unwrap_or(false) {
+            return None;
+        }
+        message
+            .get_message_thread_id()
+            .map(|thread| bot.in_thread(message.get_chat().get_id(), thread))
+    }
+
+    /// Get the bot
+    pub fn get_bot(&self) -> &'_ Bot {
+        &self.bot
+    }
+
+    /// Get the chat the topic is in
+    pub fn get_chat_id(&self) -> i64 {
+        self.chat_id
+    }
+
+    /// Get the message_thread_id of the topic
+    pub fn get_thread_id(&self) -> MessageThreadId {
+        self.thread_id
+    }
+
+    /// Send a text message to the topic
+    pub fn send_message<'a>(&'a self, text: &'a str) -> CallSendMessage<'a, i64> {
+        self.bot
+            .build_send_message(self.chat_id, text)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Send a photo to the topic
+    pub fn send_photo(&self, photo: FileData) -> CallSendPhoto<'_, i64> {
+        self.bot
+            .build_send_photo(self.chat_id, photo)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Send a document to the topic
+    pub fn send_document(&self, document: FileData) -> CallSendDocument<'_, i64> {
+        self.bot
+            .build_send_document(self.chat_id, document)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Send a video to the topic
+    pub fn send_video(&self, video: FileData) -> CallSendVideo<'_, i64> {
+        self.bot
+            .build_send_video(self.chat_id, video)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Send an animation to the topic
+    pub fn send_animation(&self, animation: FileData) -> CallSendAnimation<'_, i64> {
+        self.bot
+            .build_send_animation(self.chat_id, animation)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Send an audio file to the topic
+    pub fn send_audio(&self, audio: FileData) -> CallSendAudio<'_, i64> {
+        self.bot
+            .build_send_audio(self.chat_id, audio)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Send a voice message to the topic
+    pub fn send_voice(&self, voice: FileData) -> CallSendVoice<'_, i64> {
+        self.bot
+            .build_send_voice(self.chat_id, voice)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Send a sticker to the topic
+    pub fn send_sticker(&self, sticker: FileData) -> CallSendSticker<'_, i64> {
+        self.bot
+            .build_send_sticker(self.chat_id, sticker)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Send a poll to the topic
+    pub fn send_poll<'a>(
+        &'a self,
+        question: &'a str,
+        options: &'a Vec<InputPollOption>,
+    ) -> CallSendPoll<'a, i64> {
+        self.bot
+            .build_send_poll(self.chat_id, question, options)
+            .message_thread_id(self.thread_id)
+    }
+
+    /// Show a chat action such as "typing" in the topic
+    pub fn send_chat_action(&self, action: ChatAction) -> CallSendChatAction<'_, i64> {
+        self.bot
+            .build_send_chat_action(self.chat_id, action)
+            .message_thread_id(self.thread_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn scope_from_topic_message() {
+        let bot = crate::bot::BotBuilder::new("sometoken").unwrap().build();
+        let message: Message = serde_json::from_value(json!({
+            "message_id": 1,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup"},
+            "message_thread_id": 7,
+            "is_topic_message": true,
+        }))
+        .unwrap();
+        let scope = ThreadScope::from_message(&bot, &message).unwrap();
+        assert_eq!(scope.get_chat_id(), -100);
+        assert_eq!(scope.get_thread_id().get(), 7);
+
+        let reply: Message = serde_json::from_value(json!({
+            "message_id": 2,
+            "date": 0,
+            "chat": {"id": -100, "type": "supergroup"},
+            "message_thread_id": 7,
+        }))
+        .unwrap();
+        assert!(ThreadScope::from_message(&bot, &reply).is_none());
+    }
+}
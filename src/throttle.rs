@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::bot::Response;
+
+/// Policy for delaying outgoing api calls to avoid telegram's ratelimiting. Policies are
+/// consulted before every request and notified of every response, keyed by the chat_id
+/// parameter of the request if it has one
+pub trait ThrottlePolicy: Send + Sync + Debug {
+    /// Return how long to wait before sending a request to this chat
+    fn delay(&self, chat: Option<&str>) -> Duration;
+
+    /// Notify the policy of a response from telegram for a request to this chat
+    fn on_response(&self, chat: Option<&str>, response: &Response);
+}
+
+/// Request bodies that may be sent to a chat. The chat_id parameter is used as the key
+/// for throttling, the generated parameters of every api method implement this
+pub trait ChatKey {
+    /// Get the chat_id parameter of the request, if it has one
+    fn chat_key(&self) -> Option<String>;
+}
+
+impl<T: ChatKey + ?Sized> ChatKey for &T {
+    fn chat_key(&self) -> Option<String> {
+        (**self).chat_key()
+    }
+}
+
+impl ChatKey for () {
+    fn chat_key(&self) -> Option<String> {
+        None
+    }
+}
+
+impl ChatKey for serde_json::Value {
+    fn chat_key(&self) -> Option<String> {
+        match self.get("chat_id")? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Format a chat_id parameter, either a chat id or a username, as a throttle key
+pub(crate) fn format_chat_key<T>(chat_id: &T) -> Option<String>
+where
+    T: Serialize,
+{
+    let key = serde_json::to_string(chat_id).ok()?;
+    match key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+        Some(username) => Some(username.to_owned()),
+        None if key == "null" => None,
+        None => Some(key),
+    }
+}
+
+#[derive(Debug)]
+struct ChatRate {
+    interval: Duration,
+    next: Instant,
+}
+
+/// Adaptive throttle using additive increase / multiplicative decrease. When a chat
+/// receives a 429 error the minimum interval between requests to that chat is doubled
+/// (or set to retry_after, whichever is larger). Each successful request shrinks the
+/// interval by a fixed step until the chat is unthrottled again
+#[derive(Debug)]
+pub struct AimdThrottle {
+    step: Duration,
+    max_interval: Duration,
+    chats: Mutex<HashMap<String, ChatRate>>,
+}
+
+impl Default for AimdThrottle {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100), Duration::from_secs(60))
+    }
+}
+
+impl AimdThrottle {
+    /// Create a new throttle that recovers by `step` per successful request and never
+    /// delays requests to a chat longer than `max_interval`
+    pub fn new(step: Duration, max_interval: Duration) -> Self {
+        Self {
+            step,
+            max_interval,
+            chats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the current minimum interval between requests to a chat
+    pub fn get_interval(&self, chat: &str) -> Duration {
+        self.chats
+            .lock()
+            .unwrap()
+            .get(chat)
+            .map(|rate| rate.interval)
+            .unwrap_or_default()
+    }
+}
+
+impl ThrottlePolicy for AimdThrottle {
+    fn delay(&self, chat: Option<&str>) -> Duration {
+        let Some(chat) = chat else {
+            return Duration::ZERO;
+        };
+        let mut chats = self.chats.lock().unwrap();
+        if let Some(rate) = chats.get_mut(chat) {
+            let now = Instant::now();
+            let wait = rate.next.saturating_duration_since(now);
+            rate.next = now + wait + rate.interval;
+            wait
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    fn on_response(&self, chat: Option<&str>, response: &Response) {
+        let Some(chat) = chat else {
+            return;
+        };
+        let mut chats = self.chats.lock().unwrap();
+        if response.error_code == Some(429) {
+            let retry = response
+                .parameters
+                .as_ref()
+                .and_then(|p| p.get_retry_after())
+                .map(|r| Duration::from_secs(r as u64))
+                .unwrap_or_default();
+            let rate = chats.entry(chat.to_owned()).or_insert_with(|| ChatRate {
+                interval: self.step,
+                next: Instant::now(),
+            });
+            rate.interval = (rate.interval * 2).max(retry).min(self.max_interval);
+            rate.next = Instant::now() + retry;
+        } else if response.ok {
+            if let Some(rate) = chats.get_mut(chat) {
+                rate.interval = rate.interval.saturating_sub(self.step);
+                if rate.interval.is_zero() {
+                    chats.remove(chat);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen_types::ResponseParameters;
+
+    fn flood(retry_after: i64) -> Response {
+        let mut parameters = ResponseParameters::default();
+        parameters.set_retry_after(Some(retry_after));
+        Response {
+            ok: false,
+            error_code: Some(429),
+            parameters: Some(parameters),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn aimd_backoff_and_recovery() {
+        let throttle = AimdThrottle::new(Duration::from_secs(1), Duration::from_secs(60));
+        assert_eq!(throttle.delay(Some("1")), Duration::ZERO);
+        throttle.on_response(Some("1"), &flood(0));
+        assert_eq!(throttle.get_interval("1"), Duration::from_secs(2));
+        throttle.on_response(Some("1"), &flood(5));
+        assert_eq!(throttle.get_interval("1"), Duration::from_secs(5));
+        assert_eq!(throttle.get_interval("2"), Duration::ZERO);
+        for _ in 0..5 {
+            throttle.on_response(Some("1"), &Response::default());
+        }
+        assert_eq!(throttle.get_interval("1"), Duration::ZERO);
+    }
+
+    #[test]
+    fn chat_key_from_params() {
+        assert_eq!(format_chat_key(&-100), Some("-100".to_owned()));
+        assert_eq!(format_chat_key(&"@chat"), Some("@chat".to_owned()));
+        assert_eq!(format_chat_key(&None::<i64>), None);
+        assert_eq!(
+            serde_json::json!({"chat_id": -100, "text": "hi"}).chat_key(),
+            Some("-100".to_owned())
+        );
+        assert_eq!(().chat_key(), None);
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+
+use crate::bot::{ApiError, Bot, Response};
+use crate::errors::ApiErrorKind;
+use crate::gen_types::ChatHandle;
+
+/// Reason telegram gave for refusing to deliver to a chat. These errors don't go away
+/// by retrying, the chat stays unreachable until the user or an admin acts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unreachable {
+    /// The user blocked the bot
+    BlockedByUser,
+    /// The user deleted their account
+    UserDeactivated,
+    /// The user never started a conversation with the bot
+    CantInitiateConversation,
+    /// The bot was removed from the group or channel
+    KickedFromChat,
+    /// The bot is not a member of the group or channel
+    NotMember,
+    /// The chat doesn't exist or the bot has never seen it
+    ChatNotFound,
+}
+
+impl Unreachable {
+    /// Classify an error response from telegram, None if the error doesn't mean the
+    /// chat is unreachable
+    pub fn from_response(response: &Response) -> Option<Self> {
+        match ApiErrorKind::from_response(response)? {
+            ApiErrorKind::Unreachable(unreachable) => Some(unreachable),
+            _ => None,
+        }
+    }
+}
+
+impl ApiError {
+    /// Get the reason the chat of the request can't be reached anymore, if this error
+    /// means it can't
+    pub fn get_unreachable(&self) -> Option<Unreachable> {
+        match self.get_kind()? {
+            ApiErrorKind::Unreachable(unreachable) => Some(unreachable),
+            _ => None,
+        }
+    }
+
+    /// Check if this is telegram's "bot was blocked by the user" error
+    pub fn is_blocked(&self) -> bool {
+        self.get_unreachable() == Some(Unreachable::BlockedByUser)
+    }
+}
+
+type UnreachableCallback = Arc<dyn Fn(&ChatHandle, Unreachable) + Send + Sync>;
+
+/// Callbacks run when a request fails because its chat is unreachable
+#[derive(Default)]
+pub(crate) struct UnreachableRegistry {
+    callbacks: RwLock<Vec<UnreachableCallback>>,
+}
+
+impl std::fmt::Debug for UnreachableRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UnreachableRegistry")
+            .field("callbacks", &self.callbacks.read().unwrap().len())
+            .finish()
+    }
+}
+
+impl Bot {
+    /// Run a callback whenever a request fails because its chat can't be reached, for
+    /// example to mark a subscriber inactive after they block the bot. Callbacks run on
+    /// the task making the request, before the error is returned, so they should not
+    /// block. Every clone of the bot shares the callbacks
+    pub fn on_unreachable<F>(&self, callback: F)
+    where
+        F: Fn(&ChatHandle, Unreachable) + Send + Sync + 'static,
+    {
+        self.unreachable()
+            .callbacks
+            .write()
+            .unwrap()
+            .push(Arc::new(callback));
+    }
+
+    /// Run the unreachable callbacks if a response failed because of its chat
+    pub(crate) fn notify_unreachable<T>(&self, body: &T, response: &Response)
+    where
+        T: Serialize,
+    {
+        let Some(kind) = Unreachable::from_response(response) else {
+            return;
+        };
+        let callbacks = self.unreachable().callbacks.read().unwrap().clone();
+        if callbacks.is_empty() {
+            return;
+        }
+        let chat = serde_json::to_value(body)
+            .ok()
+            .and_then(|mut body| body.get_mut("chat_id").map(|c| c.take()))
+            .and_then(|chat| serde_json::from_value::<ChatHandle>(chat).ok());
+        if let Some(chat) = chat {
+            for callback in callbacks {
+                callback(&chat, kind);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn notifies_blocked_chats() {
+        let test = TestBot::builder()
+            .respond_error("sendMessage", 403, "Forbidden: bot was blocked by the user")
+            .respond_error("sendMessage", 400, "Bad Request: message text is empty")
+            .build()
+            .await
+            .unwrap();
+        let bot = test.get_bot();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let record = Arc::clone(&seen);
+        bot.on_unreachable(move |chat, kind| record.lock().unwrap().push((chat.clone(), kind)));
+
+        let err = bot.build_send_message(5, "hi").build().await.unwrap_err();
+        assert!(err.is_blocked());
+        let err = bot.build_send_message(5, "").build().await.unwrap_err();
+        assert_eq!(err.get_unreachable(), None);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![(ChatHandle::ChatId(5), Unreachable::BlockedByUser)]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::bot::UploadKey;
+use crate::gen_types::{FileData, FileId, Message};
+use crate::hash::ContentHash;
+
+/// Remembers the file_id telegram assigned to uploaded content, so sending the same
+/// bytes again sends the file_id instead of uploading them. Only files sent by methods
+/// returning a Message are remembered. Files are remembered per method and field, since
+/// the same bytes sent as a photo and as a document get different file_ids. When full the
+/// cache is cleared
+#[derive(Debug)]
+pub struct UploadCache {
+    max_entries: usize,
+    file_ids: Mutex<HashMap<(ContentHash, String, String), FileId>>,
+}
+
+impl UploadCache {
+    /// Create a cache remembering at most max_entries files
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            file_ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the file_id of content previously uploaded as field of method, like "photo"
+    /// of "sendPhoto"
+    pub fn get(&self, hash: &ContentHash, method: &str, field: &str) -> Option<FileId> {
+        self.file_ids
+            .lock()
+            .unwrap()
+            .get(&(*hash, method.to_owned(), field.to_owned()))
+            .cloned()
+    }
+
+    /// Remember the file_id of content uploaded as field of method
+    pub fn insert(&self, hash: ContentHash, method: &str, field: &str, file_id: FileId) {
+        let key = (hash, method.to_owned(), field.to_owned());
+        let mut file_ids = self.file_ids.lock().unwrap();
+        if file_ids.len() >= self.max_entries && !file_ids.contains_key(&key) {
+            file_ids.clear();
+        }
+        if self.max_entries > 0 {
+            file_ids.insert(key, file_id);
+        }
+    }
+
+    /// Forget all remembered files
+    pub fn clear(&self) {
+        self.file_ids.lock().unwrap().clear();
+    }
+
+    /// Swap bytes for a cached file_id, or hash them to remember after uploading
+    pub(crate) fn prepare(
+        &self,
+        method: &'static str,
+        field: &'static str,
+        file: FileData,
+    ) -> (FileData, UploadKey) {
+        match file.content_hash() {
+            Some(hash) => match self.get(&hash, method, field) {
+                Some(file_id) => (FileData::from(file_id), None),
+                None => (file, Some((hash, method, field))),
+            },
+            None => (file, None),
+        }
+    }
+
+    /// Remember the file_id of the media in a sent message
+    pub(crate) fn finish(&self, key: UploadKey, message: &Message) {
+        let file_id = message.media().and_then(|m| m.get_file_id().cloned());
+        if let (Some((hash, method, field)), Some(file_id)) = (key, file_id) {
+            self.insert(hash, method, field, file_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_file_id() {
+        let cache = UploadCache::new(8);
+        let bytes = vec![1, 2, 3];
+        let (file, key) = cache.prepare("sendPhoto", "photo", FileData::Bytes(bytes.clone()));
+        assert!(matches!(file, FileData::Bytes(_)));
+        assert_eq!(key, Some((ContentHash::of(&bytes), "sendPhoto", "photo")));
+        cache.insert(
+            ContentHash::of(&bytes),
+            "sendPhoto",
+            "photo",
+            FileId::from("abc".to_owned()),
+        );
+        let (file, key) = cache.prepare("sendPhoto", "photo", FileData::Bytes(bytes.clone()));
+        assert!(matches!(file, FileData::String(ref id) if id == "abc"));
+        assert_eq!(key, None);
+        let (file, _) = cache.prepare("sendDocument", "document", FileData::Bytes(bytes));
+        assert!(matches!(file, FileData::Bytes(_)));
+    }
+}
@@ -0,0 +1,262 @@
+use std::fmt::Display;
+
+use crate::bot::{ApiError, Bot};
+use crate::caption::{byte_index, utf16_len};
+use crate::gen_types::{EReplyMarkup, InlineKeyboardMarkup};
+
+/// Maximum number of buttons in an inline keyboard
+pub const MAX_INLINE_BUTTONS: usize = 100;
+
+/// Maximum number of buttons in one row of an inline keyboard
+pub const MAX_INLINE_ROW: usize = 8;
+
+/// Maximum length of callback_data in bytes
+pub const MAX_CALLBACK_DATA: usize = 64;
+
+/// A method parameter violating a length or range documented in the bot api spec
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ValidationError {
+    method: &'static str,
+    field: &'static str,
+    message: String,
+}
+
+impl ValidationError {
+    fn new(method: &'static str, field: &'static str, message: String) -> Self {
+        Self {
+            method,
+            field,
+            message,
+        }
+    }
+
+    /// Get the name of the method with the invalid parameter
+    pub fn get_method(&self) -> &'static str {
+        self.method
+    }
+
+    /// Get the name of the invalid parameter
+    pub fn get_field(&self) -> &'static str {
+        self.field
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid {}.{}: {}",
+            self.method, self.field, self.message
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for ApiError {
+    fn from(value: ValidationError) -> Self {
+        anyhow::Error::from(value).into()
+    }
+}
+
+fn check<T>(
+    method: &'static str,
+    field: &'static str,
+    value: Option<T>,
+    min: T,
+    max: T,
+    unit: &str,
+) -> Result<(), ValidationError>
+where
+    T: PartialOrd + Display,
+{
+    match value {
+        Some(v) if v < min || v > max => Err(ValidationError::new(
+            method,
+            field,
+            format!("{} is not within {}-{}{}", v, min, max, unit),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Check the length of a string in characters as counted by telegram
+pub(crate) fn check_chars(
+    method: &'static str,
+    field: &'static str,
+    value: Option<&str>,
+    min: usize,
+    max: usize,
+) -> Result<(), ValidationError> {
+    check(method, field, value.map(utf16_len), min, max, " characters")
+}
+
+/// Check the length of a string in bytes
+pub(crate) fn check_bytes(
+    method: &'static str,
+    field: &'static str,
+    value: Option<&str>,
+    min: usize,
+    max: usize,
+) -> Result<(), ValidationError> {
+    check(method, field, value.map(|v| v.len()), min, max, " bytes")
+}
+
+/// Check the number of items in an array
+pub(crate) fn check_items(
+    method: &'static str,
+    field: &'static str,
+    value: Option<usize>,
+    min: usize,
+    max: usize,
+) -> Result<(), ValidationError> {
+    check(method, field, value, min, max, " items")
+}
+
+/// Check that an integer is within a range
+pub(crate) fn check_range(
+    method: &'static str,
+    field: &'static str,
+    value: Option<i64>,
+    min: i64,
+    max: i64,
+) -> Result<(), ValidationError> {
+    check(method, field, value, min, max, "")
+}
+
+/// Reply markup that may hold an inline keyboard
+pub(crate) trait InlineButtons {
+    fn get_inline(&self) -> Option<&'_ InlineKeyboardMarkup>;
+}
+
+impl InlineButtons for InlineKeyboardMarkup {
+    fn get_inline(&self) -> Option<&'_ InlineKeyboardMarkup> {
+        Some(self)
+    }
+}
+
+impl InlineButtons for EReplyMarkup {
+    fn get_inline(&self) -> Option<&'_ InlineKeyboardMarkup> {
+        match self {
+            EReplyMarkup::InlineKeyboardMarkup(markup) => Some(markup),
+            _ => None,
+        }
+    }
+}
+
+/// Check an inline keyboard against telegram's button limits, which are not part of
+/// the api spec
+pub(crate) fn check_keyboard<K: InlineButtons>(
+    method: &'static str,
+    field: &'static str,
+    value: Option<&K>,
+) -> Result<(), ValidationError> {
+    let Some(markup) = value.and_then(|v| v.get_inline()) else {
+        return Ok(());
+    };
+    let rows = markup.get_inline_keyboard();
+    let buttons = rows.iter().map(|r| r.len()).sum::<usize>();
+    check(
+        method,
+        field,
+        Some(buttons),
+        0,
+        MAX_INLINE_BUTTONS,
+        " buttons",
+    )?;
+    for (i, row) in rows.iter().enumerate() {
+        if row.len() > MAX_INLINE_ROW {
+            return Err(ValidationError::new(
+                method,
+                field,
+                format!(
+                    "row {} has {} buttons, at most {} fit in a row",
+                    i,
+                    row.len(),
+                    MAX_INLINE_ROW
+                ),
+            ));
+        }
+        for button in row {
+            check(
+                method,
+                field,
+                button.get_callback_data().map(|d| d.len()),
+                1,
+                MAX_CALLBACK_DATA,
+                " bytes of callback_data",
+            )?;
+        }
+    }
+    Ok(())
+}
+
+impl Bot {
+    /// Truncate a string parameter to max characters if truncate_overflow is set,
+    /// returning None if it should be sent unchanged
+    pub(crate) fn fit_chars(&self, value: Option<&str>, max: usize) -> Option<String> {
+        let value = value?;
+        if !self.get_truncate_overflow() || utf16_len(value) <= max {
+            return None;
+        }
+        Some(value[..byte_index(value, max)].to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_bounds() {
+        assert!(check_chars("sendMessage", "text", Some(""), 1, 4096).is_err());
+        assert!(check_chars("sendMessage", "text", Some("hi"), 1, 4096).is_ok());
+        assert!(check_chars("sendMessage", "text", None, 1, 4096).is_ok());
+        assert!(check_range("getUpdates", "limit", Some(101), 1, 100).is_err());
+        let err = check_items("sendPoll", "options", Some(1), 2, 12).unwrap_err();
+        assert_eq!(err.get_field(), "options");
+        assert_eq!(
+            err.to_string(),
+            "invalid sendPoll.options: 1 is not within 2-12 items"
+        );
+    }
+
+    fn button(data: &str) -> crate::gen_types::InlineKeyboardButton {
+        let mut button = crate::gen_types::InlineKeyboardButton::new("b".to_owned());
+        button.set_callback_data(Some(data.to_owned()));
+        button
+    }
+
+    #[test]
+    fn keyboard_limits() {
+        let ok = InlineKeyboardMarkup::new(vec![vec![button("a"); 8]; 12]);
+        assert!(check_keyboard("sendMessage", "reply_markup", Some(&ok)).is_ok());
+        let wide =
+            EReplyMarkup::InlineKeyboardMarkup(InlineKeyboardMarkup::new(vec![vec![
+                button("a");
+                9
+            ]]));
+        assert!(check_keyboard("sendMessage", "reply_markup", Some(&wide)).is_err());
+        let many = InlineKeyboardMarkup::new(vec![vec![button("a"); 5]; 21]);
+        assert!(check_keyboard("sendMessage", "reply_markup", Some(&many)).is_err());
+        let long = InlineKeyboardMarkup::new(vec![vec![button(&"x".repeat(65))]]);
+        let err = check_keyboard("editMessageReplyMarkup", "reply_markup", Some(&long));
+        assert_eq!(err.unwrap_err().get_method(), "editMessageReplyMarkup");
+    }
+
+    #[test]
+    fn truncates_when_configured() {
+        let text = "é".repeat(250);
+        let bot = crate::bot::BotBuilder::new("sometoken").unwrap().build();
+        assert_eq!(bot.fit_chars(Some(&text), 200), None);
+        let bot = crate::bot::BotBuilder::new("sometoken")
+            .unwrap()
+            .truncate_overflow(true)
+            .build();
+        assert_eq!(
+            bot.fit_chars(Some(&text), 200).unwrap().chars().count(),
+            200
+        );
+        assert_eq!(bot.fit_chars(Some("short"), 200), None);
+    }
+}
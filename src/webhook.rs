@@ -0,0 +1,514 @@
+#[cfg(feature = "tower")]
+use std::convert::Infallible;
+#[cfg(feature = "tower")]
+use std::task::{Context, Poll};
+
+#[cfg(feature = "tower")]
+use http_body_util::{BodyExt, Limited};
+#[cfg(feature = "tower")]
+use hyper::body::Body;
+#[cfg(feature = "tower")]
+use hyper::{Request, Response, StatusCode};
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+
+use crate::bot::{Bot, BotResult, SecretString, SerializableRequest};
+use crate::dispatch::Dispatcher;
+use crate::ext::{tokens_equal, IpAllowlist, UpdateDedup, WebhookManager};
+use crate::gen_types::{Update, UpdateExt, UpdateId};
+
+const MAX_BODY: usize = 1024 * 1024 * 10;
+const SECRET_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+const FORWARDED_HEADER: &str = "X-Forwarded-For";
+const HEALTH_PATH: &str = "/healthz";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a reply handler may run before telegram is answered without a reply
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handler run before answering a webhook request, which may return a single method call
+/// for telegram to execute from the http response. The result of a call made this way is
+/// never returned, so use it for fire and forget calls like sendMessage or
+/// answerCallbackQuery
+pub trait ReplyHandler: Send + Sync {
+    /// Choose a request to send in the webhook response, if any
+    fn reply(
+        &self,
+        bot: Bot,
+        update: UpdateExt,
+    ) -> BoxFuture<'static, BotResult<Option<SerializableRequest>>>;
+}
+
+impl<F, Fut> ReplyHandler for F
+where
+    F: Fn(Bot, UpdateExt) -> Fut + Send + Sync,
+    Fut: Future<Output = BotResult<Option<SerializableRequest>>> + Send + 'static,
+{
+    fn reply(
+        &self,
+        bot: Bot,
+        update: UpdateExt,
+    ) -> BoxFuture<'static, BotResult<Option<SerializableRequest>>> {
+        Box::pin(self(bot, update))
+    }
+}
+
+/// Convert a request to the body of a webhook response, with the method name alongside
+/// the parameters
+fn reply_body(request: &SerializableRequest) -> Option<String> {
+    let mut params = request.get_params().as_object()?.clone();
+    params.insert(
+        "method".to_owned(),
+        serde_json::Value::String(request.get_method().to_owned()),
+    );
+    serde_json::to_string(&params).ok()
+}
+
+/// Source of the secret token requests must carry
+#[derive(Clone, Debug)]
+enum Secret {
+    /// Accept all requests
+    None,
+    Static(SecretString),
+    Managed(WebhookManager),
+}
+
+/// Receiver for webhook updates from telegram passing them to a Dispatcher. This can be
+/// mounted in an existing web server instead of using ext::Webhook, either directly as a
+/// tower Service or through the axum, warp, or actix-web adapters. Telegram is answered
+/// before handlers finish running
+#[derive(Clone)]
+pub struct WebhookService {
+    bot: Bot,
+    dispatcher: Dispatcher,
+    secret: Secret,
+    allowlist: Option<IpAllowlist>,
+    dedup: UpdateDedup,
+    reply: Option<Arc<dyn ReplyHandler>>,
+    probe_interval: Option<Duration>,
+}
+
+impl std::fmt::Debug for WebhookService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookService")
+            .field("bot", &self.bot)
+            .field("dispatcher", &self.dispatcher)
+            .field("secret", &self.secret)
+            .field("allowlist", &self.allowlist)
+            .field("dedup", &self.dedup)
+            .field("reply", &self.reply.is_some())
+            .field("probe_interval", &self.probe_interval)
+            .finish()
+    }
+}
+
+impl WebhookService {
+    /// Create a new service dispatching updates received by webhook
+    pub fn new(bot: &Bot, dispatcher: Dispatcher) -> Self {
+        Self {
+            bot: bot.clone(),
+            dispatcher,
+            secret: Secret::None,
+            allowlist: None,
+            dedup: UpdateDedup::default(),
+            reply: None,
+            probe_interval: None,
+        }
+    }
+
+    /// Reject requests missing the secret_token passed to set_webhook
+    pub fn secret_token<T: Into<String>>(mut self, secret_token: T) -> Self {
+        self.secret = Secret::Static(SecretString::new(secret_token));
+        self
+    }
+
+    /// Reject requests missing the current or previous secret token of a WebhookManager,
+    /// following its rotations
+    pub fn secret_manager(mut self, manager: WebhookManager) -> Self {
+        self.secret = Secret::Managed(manager);
+        self
+    }
+
+    /// Reject requests not coming from telegram's subnets. Requests with an unknown peer
+    /// address are rejected, when using axum serve the router with
+    /// into_make_service_with_connect_info::<SocketAddr>
+    pub fn ip_allowlist(mut self, allowlist: IpAllowlist) -> Self {
+        self.allowlist = Some(allowlist);
+        self
+    }
+
+    /// Change how many recent update_ids are remembered to drop updates telegram
+    /// redelivers after a timeout. Defaults to ext::DEFAULT_DEDUP_WINDOW, 0 disables this
+    pub fn dedup_window(mut self, window: usize) -> Self {
+        self.dedup = UpdateDedup::new(window);
+        self
+    }
+
+    /// Use an existing dedup window, for example one shared with another receiver
+    pub fn dedup(mut self, dedup: UpdateDedup) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Check if telegram is reachable when answering health checks, at most once per
+    /// interval. Without this health checks never call telegram, so unauthenticated
+    /// requests to "/healthz" can't use up the bot's rate limit
+    pub fn probe_interval(mut self, interval: Duration) -> Self {
+        self.probe_interval = Some(interval);
+        self
+    }
+
+    /// Run a handler before answering each update, sending the request it returns in the
+    /// webhook response instead of making a separate api call. The dispatcher still
+    /// receives every update
+    pub fn reply_handler<R>(mut self, reply: R) -> Self
+    where
+        R: ReplyHandler + 'static,
+    {
+        self.reply = Some(Arc::new(reply));
+        self
+    }
+
+    /// Handle the body of a webhook request, returning the http status code to respond
+    /// with and a json method call if the reply handler returned one. Handlers are
+    /// spawned in the background
+    async fn receive(
+        &self,
+        peer: Option<IpAddr>,
+        forwarded_for: Option<&str>,
+        token: Option<&str>,
+        body: &[u8],
+    ) -> (u16, Option<String>) {
+        let code = self.accept(peer, forwarded_for, token, body);
+        let (update_id, update) = match code {
+            Ok(update) => update,
+            Err(code) => return (code, None),
+        };
+        let reply = match self.reply {
+            Some(ref reply) => {
+                let request = reply.reply(self.bot.clone(), update.clone());
+                match tokio::time::timeout(REPLY_TIMEOUT, request).await {
+                    Ok(Ok(request)) => request.as_ref().and_then(reply_body),
+                    Ok(Err(err)) => {
+                        log::warn!("webhook reply handler failed: {}", err);
+                        None
+                    }
+                    Err(_) => {
+                        log::warn!("webhook reply handler timed out");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let me = self.clone();
+        tokio::spawn(async move {
+            me.dispatcher
+                .dispatch_with_id(&me.bot, Some(update_id), update)
+                .await
+        });
+        (200, reply)
+    }
+
+    /// Check a webhook request and parse the update, or get the status to reject it with.
+    /// Duplicate updates are answered with 200 without dispatching
+    fn accept(
+        &self,
+        peer: Option<IpAddr>,
+        forwarded_for: Option<&str>,
+        token: Option<&str>,
+        body: &[u8],
+    ) -> Result<(UpdateId, UpdateExt), u16> {
+        if let Some(ref allowlist) = self.allowlist {
+            match peer {
+                Some(peer) if allowlist.allows(peer, forwarded_for) => (),
+                _ => return Err(403),
+            }
+        }
+
+        let authorized = match self.secret {
+            Secret::None => true,
+            Secret::Static(ref secret_token) => {
+                token.is_some_and(|token| tokens_equal(token, secret_token.expose()))
+            }
+            Secret::Managed(ref manager) => manager.validate(token),
+        };
+        if !authorized {
+            return Err(401);
+        }
+
+        let update = match crate::json::from_slice::<Update>(body) {
+            Ok(update) => update,
+            Err(err) => {
+                log::warn!("invalid webhook update: {}", err);
+                return Err(400);
+            }
+        };
+        let update_id = update.get_update_id();
+        if !self.dedup.check(update_id) {
+            log::debug!("dropping duplicate update {}", update_id);
+            return Err(200);
+        }
+        let update: UpdateExt = update.into();
+        self.bot.invalidate_cache(&update);
+        Ok((update_id, update))
+    }
+
+    /// Answer a health check with the dispatcher's health as json, using status 503 if
+    /// the last probe found telegram unreachable
+    async fn health(&self) -> (u16, String) {
+        let health = match self.probe_interval {
+            Some(interval) => {
+                self.dispatcher
+                    .probe_cached(&self.bot, PROBE_TIMEOUT, interval)
+                    .await
+            }
+            None => self.dispatcher.health(),
+        };
+        let code = if health.get_reachable() == Some(false) {
+            503
+        } else {
+            200
+        };
+        (code, serde_json::to_string(&health).unwrap_or_default())
+    }
+
+    /// Convert this service into an axum Router handling updates on "/" and health
+    /// checks on "/healthz"
+    #[cfg(feature = "axum")]
+    pub fn into_router(self) -> axum::Router {
+        axum::Router::new()
+            .route_service(HEALTH_PATH, self.clone())
+            .route_service("/", self)
+    }
+}
+
+#[cfg(feature = "tower")]
+fn status(code: u16) -> Response<String> {
+    with_body(code, String::new())
+}
+
+#[cfg(feature = "tower")]
+fn with_body(code: u16, body: String) -> Response<String> {
+    let mut response = Response::new(body);
+    *response.status_mut() = StatusCode::from_u16(code).unwrap_or(StatusCode::OK);
+    response
+}
+
+/// Build the response to a webhook request, as json if it carries a method call
+#[cfg(feature = "tower")]
+fn reply(code: u16, body: Option<String>) -> Response<String> {
+    match body {
+        Some(body) => {
+            let mut response = with_body(code, body);
+            response.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static("application/json"),
+            );
+            response
+        }
+        None => status(code),
+    }
+}
+
+/// Get the address of the client of a request if the server recorded it
+#[cfg(feature = "tower")]
+fn peer_addr<B>(req: &Request<B>) -> Option<IpAddr> {
+    #[cfg(feature = "axum")]
+    if let Some(info) = req
+        .extensions()
+        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+    {
+        return Some(info.0.ip());
+    }
+    req.extensions()
+        .get::<std::net::SocketAddr>()
+        .map(|addr| addr.ip())
+}
+
+#[cfg(feature = "tower")]
+impl<B> tower::Service<Request<B>> for WebhookService
+where
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Response = Response<String>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let me = self.clone();
+        Box::pin(async move {
+            if req.method() == hyper::Method::GET && req.uri().path().ends_with(HEALTH_PATH) {
+                let (code, body) = me.health().await;
+                return Ok(with_body(code, body));
+            }
+            let header = |name: &str| {
+                req.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_owned())
+            };
+            let token = header(SECRET_HEADER);
+            let forwarded_for = header(FORWARDED_HEADER);
+            let peer = peer_addr(&req);
+            let body = match Limited::new(req.into_body(), MAX_BODY).collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(err) => {
+                    log::warn!("failed to read webhook body: {}", err);
+                    return Ok(status(400));
+                }
+            };
+            let (code, body) = me
+                .receive(peer, forwarded_for.as_deref(), token.as_deref(), &body)
+                .await;
+            Ok(reply(code, body))
+        })
+    }
+}
+
+/// Create a warp Filter passing updates POSTed to it to a Dispatcher and answering health
+/// checks on "healthz"
+#[cfg(feature = "warp")]
+pub fn warp_filter(
+    service: WebhookService,
+) -> impl warp::Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    use warp::Filter;
+    let health_service = service.clone();
+    let health = warp::get()
+        .and(warp::path("healthz"))
+        .and(warp::path::end())
+        .then(move || {
+            let service = health_service.clone();
+            async move {
+                let (code, body) = service.health().await;
+                warp::reply::with_status(
+                    body,
+                    warp::http::StatusCode::from_u16(code).unwrap_or(warp::http::StatusCode::OK),
+                )
+            }
+        });
+    let updates = warp::post()
+        .and(warp::addr::remote())
+        .and(warp::header::optional::<String>(FORWARDED_HEADER))
+        .and(warp::header::optional::<String>(SECRET_HEADER))
+        .and(warp::body::content_length_limit(MAX_BODY as u64))
+        .and(warp::body::bytes())
+        .then(
+            move |peer: Option<std::net::SocketAddr>,
+                  forwarded_for: Option<String>,
+                  token: Option<String>,
+                  body: warp::hyper::body::Bytes| {
+                let service = service.clone();
+                async move {
+                    let (code, body) = service
+                        .receive(
+                            peer.map(|p| p.ip()),
+                            forwarded_for.as_deref(),
+                            token.as_deref(),
+                            &body,
+                        )
+                        .await;
+                    let content_type = if body.is_some() {
+                        "application/json"
+                    } else {
+                        "text/plain"
+                    };
+                    warp::reply::with_status(
+                        warp::reply::with_header(
+                            body.unwrap_or_default(),
+                            "content-type",
+                            content_type,
+                        ),
+                        warp::http::StatusCode::from_u16(code)
+                            .unwrap_or(warp::http::StatusCode::OK),
+                    )
+                }
+            },
+        );
+    health.or(updates).unify()
+}
+
+#[cfg(feature = "actix-web")]
+async fn actix_handler(
+    service: actix_web::web::Data<WebhookService>,
+    req: actix_web::HttpRequest,
+    body: actix_web::web::Bytes,
+) -> actix_web::HttpResponse {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let peer = req.peer_addr().map(|p| p.ip());
+    let (code, body) = service
+        .receive(peer, header(FORWARDED_HEADER), header(SECRET_HEADER), &body)
+        .await;
+    let mut response = actix_web::HttpResponse::build(
+        actix_web::http::StatusCode::from_u16(code).unwrap_or(actix_web::http::StatusCode::OK),
+    );
+    match body {
+        Some(body) => response.content_type("application/json").body(body),
+        None => response.finish(),
+    }
+}
+
+#[cfg(feature = "actix-web")]
+async fn actix_health(service: actix_web::web::Data<WebhookService>) -> actix_web::HttpResponse {
+    let (code, body) = service.health().await;
+    actix_web::HttpResponse::build(
+        actix_web::http::StatusCode::from_u16(code).unwrap_or(actix_web::http::StatusCode::OK),
+    )
+    .content_type("application/json")
+    .body(body)
+}
+
+/// Create an actix-web Scope at `path` passing updates POSTed to it to a Dispatcher and
+/// answering health checks on "/healthz"
+#[cfg(feature = "actix-web")]
+pub fn actix_scope(path: &str, service: WebhookService) -> actix_web::Scope {
+    use actix_web::web;
+    web::scope(path)
+        .app_data(web::Data::new(service))
+        .app_data(web::PayloadConfig::new(MAX_BODY))
+        .route("", web::post().to(actix_handler))
+        .route("/", web::post().to(actix_handler))
+        .route(HEALTH_PATH, web::get().to(actix_health))
+}
+
+/// Create an axum Router passing updates received on "/" to a Dispatcher. Use
+/// Router::nest to mount it at a path in an existing application
+#[cfg(feature = "axum")]
+pub fn axum_router(bot: &Bot, dispatcher: Dispatcher) -> axum::Router {
+    WebhookService::new(bot, dispatcher).into_router()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply_includes_method() {
+        let request = SerializableRequest::from_json(
+            r#"{"method":"sendMessage","params":{"chat_id":1,"text":"hi"}}"#,
+        )
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&reply_body(&request).unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"method": "sendMessage", "chat_id": 1, "text": "hi"})
+        );
+    }
+
+    #[test]
+    fn debug_hides_secret() {
+        let bot = crate::bot::BotBuilder::new("123:abc").unwrap().build();
+        let service = WebhookService::new(&bot, Dispatcher::new()).secret_token("hunter2");
+        assert!(!format!("{:?}", service).contains("hunter2"));
+    }
+}
@@ -0,0 +1,60 @@
+//! Integration tests against telegram's test environment. These only run when TEST_TOKEN
+//! is set to the token of a bot created in the test environment, and TEST_CHAT to a chat
+//! that bot can write to. TEST_PHOTO optionally overrides the url of the photo sent.
+
+use botapi::bot::{Bot, BotBuilder};
+use botapi::gen_types::FileData;
+
+const DEFAULT_PHOTO: &str = "https://telegram.org/img/t_logo.png";
+
+fn test_bot() -> Option<Bot> {
+    let token = std::env::var("TEST_TOKEN").ok()?;
+    let bot = BotBuilder::new(token)
+        .unwrap()
+        .test_environment(true)
+        .build();
+    Some(bot)
+}
+
+fn test_chat() -> Option<i64> {
+    std::env::var("TEST_CHAT").ok()?.parse().ok()
+}
+
+#[tokio::test]
+async fn get_me() {
+    let Some(bot) = test_bot() else {
+        return;
+    };
+    let me = bot.get_me().await.unwrap();
+    assert!(me.get_is_bot());
+}
+
+#[tokio::test]
+async fn send_message() {
+    let (Some(bot), Some(chat)) = (test_bot(), test_chat()) else {
+        return;
+    };
+    let message = bot
+        .build_send_message(chat, "integration test")
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(message.get_text(), Some("integration test"));
+    assert_eq!(message.get_chat().get_id(), chat);
+}
+
+#[tokio::test]
+async fn send_photo() {
+    let (Some(bot), Some(chat)) = (test_bot(), test_chat()) else {
+        return;
+    };
+    let photo = std::env::var("TEST_PHOTO").unwrap_or_else(|_| DEFAULT_PHOTO.to_owned());
+    let message = bot
+        .build_send_photo(chat, FileData::String(photo))
+        .caption("integration test")
+        .build()
+        .await
+        .unwrap();
+    assert!(message.get_photo().is_some_and(|sizes| !sizes.is_empty()));
+    assert_eq!(message.get_caption(), Some("integration test"));
+}